@@ -0,0 +1,24 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+fn main() {
+    tower_grpc_build::Config::new()
+        .enable_server(true)
+        .enable_client(true)
+        .build_mocks(true)
+        .box_services(true)
+        .build(&["proto/boxed.proto"], &["proto"])
+        .unwrap_or_else(|e| panic!("protobuf compilation failed: {}", e));
+
+    // An ergonomic client of the same service.
+    let ergonomic = PathBuf::from(env::var_os("OUT_DIR").unwrap()).join("ergonomic");
+    fs::create_dir_all(&ergonomic).unwrap();
+
+    tower_grpc_build::Config::new()
+        .enable_client(true)
+        .ergonomic_clients(true)
+        .out_dir(ergonomic)
+        .build(&["proto/boxed.proto"], &["proto"])
+        .unwrap_or_else(|e| panic!("protobuf compilation failed: {}", e));
+}
@@ -0,0 +1,221 @@
+pub mod boxed {
+    include!(concat!(env!("OUT_DIR"), "/boxed.rs"));
+}
+
+/// The same service, with an ergonomic client.
+pub mod ergonomic {
+    include!(concat!(env!("OUT_DIR"), "/ergonomic/boxed.rs"));
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::boxed::client::Counter;
+    use crate::boxed::server::{self, CounterServer, MockCounter};
+    use crate::boxed::{AddReply, AddRequest, CountReply, CountRequest};
+
+    use futures::{future, stream, Future, Stream};
+    use tower_grpc::client::Direct;
+    use tower_grpc::descriptor::MethodKind;
+    use tower_grpc::generic::server::NamedService;
+    use tower_grpc::server::{BoxFuture, BoxStream};
+    use tower_grpc::{Request, Response, Status, Streaming};
+
+    #[derive(Clone, Debug)]
+    struct Count;
+
+    impl server::Counter for Count {
+        fn add(&mut self, request: Request<AddRequest>) -> BoxFuture<AddReply> {
+            // Add the offset the client sent as metadata, if any.
+            let offset = request
+                .metadata()
+                .get("offset")
+                .map_or(0, |offset| offset.to_str().unwrap().parse().unwrap());
+            let AddRequest { a, b } = request.into_inner();
+            Box::new(future::ok(Response::new(AddReply {
+                sum: a + b + offset,
+            })))
+        }
+
+        fn count(&mut self, request: Request<CountRequest>) -> BoxFuture<BoxStream<CountReply>> {
+            let replies = (1..=request.get_ref().to).map(|n| CountReply { n });
+            let replies: BoxStream<CountReply> = Box::new(stream::iter_ok(replies));
+            Box::new(future::ok(Response::new(replies)))
+        }
+
+        fn sum(&mut self, request: Request<Streaming<AddRequest>>) -> BoxFuture<AddReply> {
+            let sum = request
+                .into_inner()
+                .fold(0, |sum, AddRequest { a, b }| Ok::<_, Status>(sum + a + b));
+
+            Box::new(sum.map(|sum| Response::new(AddReply { sum })))
+        }
+
+        fn running(
+            &mut self,
+            request: Request<Streaming<AddRequest>>,
+        ) -> BoxFuture<BoxStream<AddReply>> {
+            let mut sum = 0;
+            let replies = request.into_inner().map(move |AddRequest { a, b }| {
+                sum += a + b;
+                AddReply { sum }
+            });
+            let replies: BoxStream<AddReply> = Box::new(replies);
+
+            Box::new(future::ok(Response::new(replies)))
+        }
+    }
+
+    fn adds() -> impl Stream<Item = AddRequest, Error = Status> {
+        stream::iter_ok(vec![AddRequest { a: 1, b: 2 }, AddRequest { a: 3, b: 4 }])
+    }
+
+    #[test]
+    fn boxed_service() {
+        let client = Counter::new(Direct::new(CounterServer::new(Count)));
+        let mut client = client.ready().wait().unwrap();
+
+        let response = client.add(Request::new(AddRequest { a: 1, b: 2 })).wait();
+        assert_eq!(response.unwrap().into_inner().sum, 3);
+
+        let replies = client
+            .count(Request::new(CountRequest { to: 3 }))
+            .and_then(|response| response.into_inner().collect())
+            .wait()
+            .unwrap();
+        let counted = replies.iter().map(|reply| reply.n).collect::<Vec<_>>();
+        assert_eq!(counted, vec![1, 2, 3]);
+
+        let response = client.sum(Request::new(adds())).wait();
+        assert_eq!(response.unwrap().into_inner().sum, 10);
+
+        let replies = client
+            .running(Request::new(adds()))
+            .and_then(|response| response.into_inner().collect())
+            .wait()
+            .unwrap();
+        let sums = replies.iter().map(|reply| reply.sum).collect::<Vec<_>>();
+        assert_eq!(sums, vec![3, 10]);
+    }
+
+    #[test]
+    fn boxed_mock() {
+        let mock = MockCounter::new();
+        mock.add
+            .push_response(Ok(Response::new(AddReply { sum: 42 })));
+        mock.sum
+            .push_response(Ok(Response::new(AddReply { sum: 7 })));
+
+        let client = Counter::new(Direct::new(CounterServer::new(mock.clone())));
+        let mut client = client.ready().wait().unwrap();
+
+        let response = client.add(Request::new(AddRequest { a: 1, b: 2 })).wait();
+        assert_eq!(response.unwrap().into_inner().sum, 42);
+
+        let response = client.sum(Request::new(adds())).wait();
+        assert_eq!(response.unwrap().into_inner().sum, 7);
+
+        let requests = mock.sum.take_requests();
+        assert_eq!(requests[0].get_ref().len(), 2);
+    }
+
+    #[test]
+    fn method_descriptors() {
+        let methods = <CounterServer<Count> as NamedService>::METHODS;
+        let paths = methods.iter().map(|m| m.path).collect::<Vec<_>>();
+        assert_eq!(
+            paths,
+            vec![
+                "/boxed.Counter/Add",
+                "/boxed.Counter/Count",
+                "/boxed.Counter/Sum",
+                "/boxed.Counter/Running",
+            ]
+        );
+
+        let sum = &methods[2];
+        assert_eq!(sum.name, "Sum");
+        assert_eq!(sum.input_type, "boxed.AddRequest");
+        assert_eq!(sum.output_type, "boxed.AddReply");
+        assert_eq!(sum.kind, MethodKind::ClientStreaming);
+
+        assert_eq!(Counter::<()>::NAME, "boxed.Counter");
+        assert_eq!(Counter::<()>::METHODS, methods);
+    }
+
+    /// Ergonomic clients, calling `Count` in process.
+    mod ergonomic {
+        use super::Count;
+        use crate::boxed::server::CounterServer;
+        use crate::ergonomic::client::Counter;
+        use crate::ergonomic::{AddReply, AddRequest, CountRequest};
+
+        use futures::{stream, Future, Stream};
+        use tower_grpc::client::{unary, Direct};
+        use tower_grpc::Request;
+
+        type Transport = Direct<CounterServer<Count>>;
+
+        fn client() -> Counter<Transport> {
+            Counter::new(Direct::new(CounterServer::new(Count)))
+                .ready()
+                .wait()
+                .unwrap()
+        }
+
+        #[test]
+        fn unary_requests() {
+            let mut client = client();
+
+            let response = client.add(AddRequest { a: 1, b: 2 }).wait();
+            assert_eq!(response.unwrap().into_inner().sum, 3);
+
+            let mut request = Request::new(AddRequest { a: 1, b: 2 });
+            request
+                .metadata_mut()
+                .insert("offset", "10".parse().unwrap());
+            let response = client.add(request).wait();
+            assert_eq!(response.unwrap().into_inner().sum, 13);
+
+            let replies = client
+                .count(CountRequest { to: 3 })
+                .and_then(|response| response.into_inner().collect())
+                .wait()
+                .unwrap();
+            assert_eq!(replies.len(), 3);
+        }
+
+        #[test]
+        fn streaming_requests() {
+            let mut client = client();
+
+            let adds = vec![AddRequest { a: 1, b: 2 }, AddRequest { a: 3, b: 4 }];
+
+            let response = client.sum(adds.clone()).wait();
+            assert_eq!(response.unwrap().into_inner().sum, 10);
+
+            let response = client.sum(Request::new(adds.clone())).wait();
+            assert_eq!(response.unwrap().into_inner().sum, 10);
+
+            let replies = client
+                .running(stream::iter_ok(adds))
+                .and_then(|response| response.into_inner().collect())
+                .wait()
+                .unwrap();
+            let sums = replies.iter().map(|reply| reply.sum).collect::<Vec<_>>();
+            assert_eq!(sums, vec![3, 10]);
+        }
+
+        #[test]
+        fn named_futures() {
+            struct Pending {
+                add: unary::Call<AddReply, Transport>,
+            }
+
+            let pending = Pending {
+                add: client().add(AddRequest { a: 2, b: 3 }),
+            };
+
+            assert_eq!(pending.add.wait().unwrap().into_inner().sum, 5);
+        }
+    }
+}
@@ -0,0 +1,9 @@
+fn main() {
+    tower_grpc_build::Config::new()
+        .enable_server(true)
+        .enable_client(true)
+        .build_mocks(true)
+        .service_codec("codec.Counter", "crate::scrambled::Scrambled")
+        .build(&["proto/codec.proto"], &["proto"])
+        .unwrap_or_else(|e| panic!("protobuf compilation failed: {}", e));
+}
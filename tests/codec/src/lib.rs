@@ -0,0 +1,132 @@
+pub mod codec {
+    include!(concat!(env!("OUT_DIR"), "/codec.rs"));
+}
+
+pub mod scrambled;
+
+#[cfg(test)]
+mod tests {
+    use crate::codec::client::{Counter, Plain};
+    use crate::codec::server::{self, CounterServer, MockCounter, MockPlain, PlainServer};
+    use crate::codec::{AddReply, AddRequest, CountReply, CountRequest};
+    use crate::scrambled;
+
+    use futures::{future, stream, Future, Stream};
+    use tower_grpc::client::Direct;
+    use tower_grpc::generic::Streaming;
+    use tower_grpc::{BoxBody, Request, Response, Status};
+
+    type AddRequests = Streaming<scrambled::Decoder<AddRequest>, BoxBody>;
+
+    #[derive(Clone, Debug)]
+    struct Count;
+
+    impl server::Counter for Count {
+        type AddFuture = future::FutureResult<Response<AddReply>, Status>;
+        type CountStream = Box<dyn Stream<Item = CountReply, Error = Status> + Send>;
+        type CountFuture = future::FutureResult<Response<Self::CountStream>, Status>;
+        type SumFuture = Box<dyn Future<Item = Response<AddReply>, Error = Status> + Send>;
+        type RunningStream = Box<dyn Stream<Item = AddReply, Error = Status> + Send>;
+        type RunningFuture = future::FutureResult<Response<Self::RunningStream>, Status>;
+
+        fn add(&mut self, request: Request<AddRequest>) -> Self::AddFuture {
+            let AddRequest { a, b } = request.into_inner();
+            future::ok(Response::new(AddReply { sum: a + b }))
+        }
+
+        fn count(&mut self, request: Request<CountRequest>) -> Self::CountFuture {
+            let replies = (1..=request.get_ref().to).map(|n| CountReply { n });
+            let replies: Self::CountStream = Box::new(stream::iter_ok(replies));
+            future::ok(Response::new(replies))
+        }
+
+        fn sum(&mut self, request: Request<AddRequests>) -> Self::SumFuture {
+            let sum = request
+                .into_inner()
+                .fold(0, |sum, AddRequest { a, b }| Ok::<_, Status>(sum + a + b));
+
+            Box::new(sum.map(|sum| Response::new(AddReply { sum })))
+        }
+
+        fn running(&mut self, request: Request<AddRequests>) -> Self::RunningFuture {
+            let mut sum = 0;
+            let replies = request.into_inner().map(move |AddRequest { a, b }| {
+                sum += a + b;
+                AddReply { sum }
+            });
+            let replies: Self::RunningStream = Box::new(replies);
+
+            future::ok(Response::new(replies))
+        }
+    }
+
+    fn adds() -> impl Stream<Item = AddRequest, Error = Status> + Send + 'static {
+        stream::iter_ok(vec![AddRequest { a: 1, b: 2 }, AddRequest { a: 3, b: 4 }])
+    }
+
+    #[test]
+    fn service_codec() {
+        let client = Counter::new(Direct::new(CounterServer::new(Count)));
+        let mut client = client.ready().wait().unwrap();
+
+        let response = client.add(Request::new(AddRequest { a: 1, b: 2 })).wait();
+        assert_eq!(response.unwrap().into_inner().sum, 3);
+
+        let replies = client
+            .count(Request::new(CountRequest { to: 3 }))
+            .and_then(|response| response.into_inner().collect())
+            .wait()
+            .unwrap();
+        let counted = replies.iter().map(|reply| reply.n).collect::<Vec<_>>();
+        assert_eq!(counted, vec![1, 2, 3]);
+
+        let response = client.sum(Request::new(adds())).wait();
+        assert_eq!(response.unwrap().into_inner().sum, 10);
+
+        let replies = client
+            .running(Request::new(adds()))
+            .and_then(|response| response.into_inner().collect())
+            .wait()
+            .unwrap();
+        let sums = replies.iter().map(|reply| reply.sum).collect::<Vec<_>>();
+        assert_eq!(sums, vec![3, 10]);
+    }
+
+    #[test]
+    fn content_type() {
+        let mock = MockCounter::new();
+        mock.add
+            .push_response(Ok(Response::new(AddReply { sum: 42 })));
+        mock.sum
+            .push_response(Ok(Response::new(AddReply { sum: 7 })));
+
+        let mut client = Counter::new(Direct::new(CounterServer::new(mock.clone())));
+
+        let response = client.add(Request::new(AddRequest { a: 1, b: 2 })).wait();
+        assert_eq!(response.unwrap().into_inner().sum, 42);
+
+        let response = client.sum(Request::new(adds())).wait();
+        assert_eq!(response.unwrap().into_inner().sum, 7);
+
+        let requests = mock.add.take_requests();
+        let content_type = requests[0].metadata().get("content-type").unwrap().to_str();
+        assert_eq!(content_type.unwrap(), "application/grpc+scrambled");
+
+        let requests = mock.sum.take_requests();
+        assert_eq!(requests[0].get_ref().len(), 2);
+
+        // Services without a custom codec still use protobuf.
+        let mock = MockPlain::new();
+        mock.add
+            .push_response(Ok(Response::new(AddReply { sum: 42 })));
+
+        let mut client = Plain::new(Direct::new(PlainServer::new(mock.clone())));
+
+        let response = client.add(Request::new(AddRequest { a: 1, b: 2 })).wait();
+        assert_eq!(response.unwrap().into_inner().sum, 42);
+
+        let requests = mock.add.take_requests();
+        let content_type = requests[0].metadata().get("content-type").unwrap().to_str();
+        assert_eq!(content_type.unwrap(), "application/grpc+proto");
+    }
+}
@@ -0,0 +1,89 @@
+//! A codec encoding protobuf messages with each byte inverted, so that a
+//! peer using the protobuf codec fails to decode them.
+
+use bytes::{Buf, BufMut};
+use prost::Message;
+use std::marker::PhantomData;
+use tower_grpc::generic::{self, DecodeBuf, EncodeBuf};
+use tower_grpc::{Code, Status};
+
+/// Encodes `E`s and decodes `D`s as scrambled protobuf
+#[derive(Debug)]
+pub struct Scrambled<E, D>(PhantomData<(E, D)>);
+
+#[derive(Debug)]
+pub struct Encoder<T>(PhantomData<T>);
+
+#[derive(Debug)]
+pub struct Decoder<T>(PhantomData<T>);
+
+// ===== impl Scrambled =====
+
+impl<E, D> Default for Scrambled<E, D> {
+    fn default() -> Self {
+        Scrambled(PhantomData)
+    }
+}
+
+impl<E, D> generic::Codec for Scrambled<E, D>
+where
+    E: Message,
+    D: Message + Default,
+{
+    type Encode = E;
+    type Encoder = Encoder<E>;
+    type Decode = D;
+    type Decoder = Decoder<D>;
+
+    fn encoder(&mut self) -> Self::Encoder {
+        Encoder(PhantomData)
+    }
+
+    fn decoder(&mut self) -> Self::Decoder {
+        Decoder(PhantomData)
+    }
+}
+
+// ===== impl Encoder =====
+
+impl<T> generic::Encoder for Encoder<T>
+where
+    T: Message,
+{
+    type Item = T;
+
+    const CONTENT_TYPE: &'static str = "application/grpc+scrambled";
+
+    fn encode(&mut self, item: T, buf: &mut EncodeBuf<'_>) -> Result<(), Status> {
+        let mut bytes = Vec::with_capacity(item.encoded_len());
+        item.encode(&mut bytes)
+            .map_err(|e| Status::new(Code::Internal, e.to_string()))?;
+
+        buf.reserve(bytes.len());
+        for byte in bytes {
+            buf.put_u8(!byte);
+        }
+
+        Ok(())
+    }
+}
+
+// ===== impl Decoder =====
+
+impl<T> generic::Decoder for Decoder<T>
+where
+    T: Message + Default,
+{
+    type Item = T;
+
+    fn decode(&mut self, buf: &mut DecodeBuf<'_>) -> Result<T, Status> {
+        let mut bytes = vec![0; buf.remaining()];
+        buf.copy_to_slice(&mut bytes);
+
+        for byte in &mut bytes {
+            *byte = !*byte;
+        }
+
+        T::decode(bytes).map_err(|e| Status::new(Code::Internal, e.to_string()))
+    }
+}
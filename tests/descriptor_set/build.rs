@@ -0,0 +1,9 @@
+fn main() {
+    // proto/descriptor_set.bin is the output of
+    // `protoc -I proto -o proto/descriptor_set.bin proto/descriptor_set.proto`.
+    tower_grpc_build::Config::new()
+        .enable_server(true)
+        .enable_client(true)
+        .build_from_descriptor_set("proto/descriptor_set.bin")
+        .unwrap_or_else(|e| panic!("protobuf compilation failed: {}", e));
+}
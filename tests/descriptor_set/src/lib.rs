@@ -0,0 +1,83 @@
+pub mod descriptor_set {
+    include!(concat!(env!("OUT_DIR"), "/descriptor_set.rs"));
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::descriptor_set::client::Greeter;
+    use crate::descriptor_set::server::{self, GreeterServer};
+    use crate::descriptor_set::{HelloReply, HelloRequest};
+
+    use futures::{future, stream, Future, Stream};
+    use tower_grpc::client::Direct;
+    use tower_grpc::descriptor::IdempotencyLevel;
+    use tower_grpc::generic::server::NamedService;
+    use tower_grpc::{Request, Response, Status};
+
+    #[derive(Clone, Debug)]
+    struct Greet;
+
+    impl server::Greeter for Greet {
+        type SayHelloFuture = future::FutureResult<Response<HelloReply>, Status>;
+        type SayHellosStream = stream::IterOk<std::vec::IntoIter<HelloReply>, Status>;
+        type SayHellosFuture = future::FutureResult<Response<Self::SayHellosStream>, Status>;
+
+        fn say_hello(&mut self, request: Request<HelloRequest>) -> Self::SayHelloFuture {
+            let message = format!("Hello, {}!", request.get_ref().name);
+            future::ok(Response::new(HelloReply { message }))
+        }
+
+        fn say_hellos(&mut self, request: Request<HelloRequest>) -> Self::SayHellosFuture {
+            let replies = vec!["Hello", "Hi"]
+                .into_iter()
+                .map(|greeting| HelloReply {
+                    message: format!("{}, {}!", greeting, request.get_ref().name),
+                })
+                .collect::<Vec<_>>();
+
+            future::ok(Response::new(stream::iter_ok(replies)))
+        }
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn generated_from_descriptor_set() {
+        let client = Greeter::new(Direct::new(GreeterServer::new(Greet)));
+        let mut client = client.ready().wait().unwrap();
+
+        let request = HelloRequest {
+            name: "world".to_string(),
+        };
+
+        let response = client.say_hello(Request::new(request.clone())).wait();
+        assert_eq!(response.unwrap().into_inner().message, "Hello, world!");
+
+        let replies = client
+            .say_hellos(Request::new(request))
+            .and_then(|response| response.into_inner().collect())
+            .wait()
+            .unwrap();
+        let messages = replies.into_iter().map(|reply| reply.message);
+        assert_eq!(
+            messages.collect::<Vec<_>>(),
+            vec!["Hello, world!", "Hi, world!"]
+        );
+    }
+
+    #[test]
+    fn method_options() {
+        let methods = <GreeterServer<Greet> as NamedService>::METHODS;
+        assert_eq!(Greeter::<()>::METHODS, methods);
+
+        let say_hello = &methods[0];
+        assert_eq!(say_hello.idempotency_level, IdempotencyLevel::NoSideEffects);
+        assert!(!say_hello.deprecated);
+        // (scope) = "admin"
+        assert_eq!(say_hello.extensions, b"\x82\xb5\x18\x05admin");
+
+        let say_hellos = &methods[1];
+        assert_eq!(say_hellos.idempotency_level, IdempotencyLevel::Unknown);
+        assert!(say_hellos.deprecated);
+        assert!(say_hellos.extensions.is_empty());
+    }
+}
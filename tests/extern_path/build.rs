@@ -0,0 +1,13 @@
+fn main() {
+    // The `hello` package is compiled in the `multifile` crate.
+    tower_grpc_build::Config::new()
+        .enable_server(true)
+        .enable_client(true)
+        .build_mocks(true)
+        .extern_path(".hello", "::multifile::hello")
+        .build(
+            &["proto/extern_path.proto"],
+            &["proto", "../multifile/proto"],
+        )
+        .unwrap_or_else(|e| panic!("protobuf compilation failed: {}", e));
+}
@@ -0,0 +1,116 @@
+pub mod extern_path {
+    include!(concat!(env!("OUT_DIR"), "/extern_path.rs"));
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::extern_path::client::Greeter;
+    use crate::extern_path::server::{self, GreeterServer, MockGreeter};
+    use crate::extern_path::Recorded;
+
+    use futures::{future, stream, Future, Stream};
+    use multifile::hello::{HelloReply, HelloRequest};
+    use prost_types::Timestamp;
+    use tower_grpc::client::Direct;
+    use tower_grpc::{Request, Response, Status, Streaming};
+
+    #[derive(Clone, Debug)]
+    struct Greet;
+
+    impl server::Greeter for Greet {
+        type SayHelloFuture = future::FutureResult<Response<HelloReply>, Status>;
+        type SayHellosStream = Box<dyn Stream<Item = HelloReply, Error = Status> + Send>;
+        type SayHellosFuture = future::FutureResult<Response<Self::SayHellosStream>, Status>;
+        type NowFuture = future::FutureResult<Response<Timestamp>, Status>;
+        type RecordFuture = Box<dyn Future<Item = Response<Recorded>, Error = Status> + Send>;
+
+        fn say_hello(&mut self, request: Request<HelloRequest>) -> Self::SayHelloFuture {
+            future::ok(Response::new(reply(request.get_ref())))
+        }
+
+        fn say_hellos(
+            &mut self,
+            request: Request<Streaming<HelloRequest>>,
+        ) -> Self::SayHellosFuture {
+            let replies: Self::SayHellosStream =
+                Box::new(request.into_inner().map(|request| reply(&request)));
+            future::ok(Response::new(replies))
+        }
+
+        fn now(&mut self, _: Request<HelloRequest>) -> Self::NowFuture {
+            future::ok(Response::new(timestamp(42)))
+        }
+
+        fn record(&mut self, request: Request<Streaming<Timestamp>>) -> Self::RecordFuture {
+            let latest = request
+                .into_inner()
+                .collect()
+                .map(|mut timestamps: Vec<Timestamp>| {
+                    let latest = timestamps.pop();
+                    Response::new(Recorded { latest })
+                });
+
+            Box::new(latest)
+        }
+    }
+
+    fn reply(request: &HelloRequest) -> HelloReply {
+        HelloReply {
+            message: format!("Hello, {}!", request.name),
+        }
+    }
+
+    fn hello(name: &str) -> HelloRequest {
+        HelloRequest {
+            name: name.to_string(),
+        }
+    }
+
+    fn timestamp(seconds: i64) -> Timestamp {
+        Timestamp { seconds, nanos: 0 }
+    }
+
+    #[test]
+    fn extern_messages() {
+        let client = Greeter::new(Direct::new(GreeterServer::new(Greet)));
+        let mut client = client.ready().wait().unwrap();
+
+        let response = client.say_hello(Request::new(hello("world"))).wait();
+        assert_eq!(response.unwrap().into_inner().message, "Hello, world!");
+
+        let requests = stream::iter_ok(vec![hello("a"), hello("b")]);
+        let replies = client
+            .say_hellos(Request::new(requests))
+            .and_then(|response| response.into_inner().collect())
+            .wait()
+            .unwrap();
+        let messages = replies.into_iter().map(|reply| reply.message);
+        assert_eq!(messages.collect::<Vec<_>>(), vec!["Hello, a!", "Hello, b!"]);
+    }
+
+    #[test]
+    fn well_known_types() {
+        let client = Greeter::new(Direct::new(GreeterServer::new(Greet)));
+        let mut client = client.ready().wait().unwrap();
+
+        let response = client.now(Request::new(hello("world"))).wait();
+        assert_eq!(response.unwrap().into_inner(), timestamp(42));
+
+        let timestamps = stream::iter_ok(vec![timestamp(1), timestamp(2)]);
+        let response = client.record(Request::new(timestamps)).wait();
+        assert_eq!(response.unwrap().into_inner().latest, Some(timestamp(2)));
+    }
+
+    #[test]
+    fn extern_mock() {
+        let mock = MockGreeter::new();
+        mock.now.push_response(Ok(Response::new(timestamp(7))));
+
+        let client = Greeter::new(Direct::new(GreeterServer::new(mock.clone())));
+        let mut client = client.ready().wait().unwrap();
+
+        let response = client.now(Request::new(hello("mock"))).wait();
+        assert_eq!(response.unwrap().into_inner(), timestamp(7));
+        assert_eq!(mock.now.take_requests()[0].get_ref().name, "mock");
+    }
+}
@@ -0,0 +1,9 @@
+fn main() {
+    tower_grpc_build::Config::new()
+        .enable_server(true)
+        .enable_client(true)
+        .enable_blocking_client(true)
+        .build_mocks(true)
+        .build(&["proto/in_memory.proto"], &["proto"])
+        .unwrap_or_else(|e| panic!("protobuf compilation failed: {}", e));
+}
@@ -0,0 +1,230 @@
+pub mod in_memory {
+    include!(concat!(env!("OUT_DIR"), "/in_memory.rs"));
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::in_memory::client::{blocking, Counter};
+    use crate::in_memory::server::{self, CounterServer, MockCounter};
+    use crate::in_memory::{AddReply, AddRequest, CountReply, CountRequest};
+
+    use futures::{future, stream, Future, Stream};
+    use tokio::runtime::Runtime;
+    use tower_grpc::client::Direct;
+    use tower_grpc::metadata::MetadataValue;
+    use tower_grpc::testing;
+    use tower_grpc::{Code, Request, Response, Status, Streaming};
+
+    #[derive(Clone, Debug)]
+    struct Count;
+
+    impl server::Counter for Count {
+        type AddFuture = future::FutureResult<Response<AddReply>, Status>;
+        type CountStream = Box<dyn Stream<Item = CountReply, Error = Status> + Send>;
+        type CountFuture = future::FutureResult<Response<Self::CountStream>, Status>;
+        type SumFuture = Box<dyn Future<Item = Response<AddReply>, Error = Status> + Send>;
+
+        fn add(&mut self, request: Request<AddRequest>) -> Self::AddFuture {
+            let sum = match request.get_ref().a.checked_add(request.get_ref().b) {
+                Some(sum) => sum,
+                None => return future::err(Status::new(Code::OutOfRange, "overflow")),
+            };
+
+            let mut response = Response::new(AddReply { sum });
+            if let Some(id) = request.metadata().get("x-request-id") {
+                response.metadata_mut().insert("x-request-id", id.clone());
+            }
+
+            future::ok(response)
+        }
+
+        fn count(&mut self, request: Request<CountRequest>) -> Self::CountFuture {
+            let replies = (1..=request.get_ref().to).map(|n| CountReply { n });
+            future::ok(Response::new(Box::new(stream::iter_ok(replies))))
+        }
+
+        fn sum(&mut self, request: Request<Streaming<AddRequest>>) -> Self::SumFuture {
+            let sum = request
+                .into_inner()
+                .fold(0, |sum, AddRequest { a, b }| Ok::<_, Status>(sum + a + b));
+
+            Box::new(sum.map(|sum| Response::new(AddReply { sum })))
+        }
+    }
+
+    fn client(rt: &mut Runtime) -> Counter<testing::Client> {
+        let (client, server) = testing::channel(CounterServer::new(Count));
+        rt.spawn(server.map_err(|e| panic!("server error: {}", e)));
+
+        Counter::new(client)
+    }
+
+    fn direct() -> Counter<Direct<CounterServer<Count>>> {
+        Counter::new(Direct::new(CounterServer::new(Count)))
+    }
+
+    #[test]
+    fn unary() {
+        let mut rt = Runtime::new().unwrap();
+        let client = client(&mut rt);
+
+        let mut request = Request::new(AddRequest { a: 1, b: 2 });
+        request
+            .metadata_mut()
+            .insert("x-request-id", MetadataValue::from_static("42"));
+
+        let response = rt
+            .block_on(client.ready().and_then(|mut client| client.add(request)))
+            .unwrap();
+
+        assert_eq!(response.metadata().get("x-request-id").unwrap(), "42");
+        assert_eq!(response.into_inner().sum, 3);
+    }
+
+    #[test]
+    fn status() {
+        let mut rt = Runtime::new().unwrap();
+        let client = client(&mut rt);
+
+        let request = Request::new(AddRequest {
+            a: i32::max_value(),
+            b: 1,
+        });
+        let status = rt
+            .block_on(client.ready().and_then(|mut client| client.add(request)))
+            .unwrap_err();
+
+        assert_eq!(status.code(), Code::OutOfRange);
+        assert_eq!(status.message(), "overflow");
+    }
+
+    #[test]
+    fn server_streaming() {
+        let mut rt = Runtime::new().unwrap();
+        let client = client(&mut rt);
+
+        let request = Request::new(CountRequest { to: 3 });
+        let replies = rt
+            .block_on(
+                client
+                    .ready()
+                    .and_then(|mut client| client.count(request))
+                    .and_then(|response| response.into_inner().collect()),
+            )
+            .unwrap();
+
+        let counted = replies.iter().map(|reply| reply.n).collect::<Vec<_>>();
+        assert_eq!(counted, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn direct_unary() {
+        let mut request = Request::new(AddRequest { a: 1, b: 2 });
+        request
+            .metadata_mut()
+            .insert("x-request-id", MetadataValue::from_static("42"));
+
+        let response = direct()
+            .ready()
+            .and_then(|mut client| client.add(request))
+            .wait()
+            .unwrap();
+
+        assert_eq!(response.metadata().get("x-request-id").unwrap(), "42");
+        assert_eq!(response.into_inner().sum, 3);
+    }
+
+    #[test]
+    fn direct_status() {
+        let request = Request::new(AddRequest {
+            a: i32::max_value(),
+            b: 1,
+        });
+        let status = direct()
+            .ready()
+            .and_then(|mut client| client.add(request))
+            .wait()
+            .unwrap_err();
+
+        assert_eq!(status.code(), Code::OutOfRange);
+        assert_eq!(status.message(), "overflow");
+    }
+
+    #[test]
+    fn direct_server_streaming() {
+        let request = Request::new(CountRequest { to: 3 });
+        let replies = direct()
+            .ready()
+            .and_then(|mut client| client.count(request))
+            .and_then(|response| response.into_inner().collect())
+            .wait()
+            .unwrap();
+
+        let counted = replies.iter().map(|reply| reply.n).collect::<Vec<_>>();
+        assert_eq!(counted, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn blocking() {
+        let mut client = blocking::Counter::new(Direct::new(CounterServer::new(Count))).unwrap();
+
+        let response = client.add(Request::new(AddRequest { a: 1, b: 2 }));
+        assert_eq!(response.unwrap().into_inner().sum, 3);
+
+        let status = client
+            .add(Request::new(AddRequest {
+                a: i32::max_value(),
+                b: 1,
+            }))
+            .unwrap_err();
+        assert_eq!(status.code(), Code::OutOfRange);
+
+        let replies = client.count(Request::new(CountRequest { to: 3 })).unwrap();
+        let counted = replies
+            .into_inner()
+            .map(|reply| reply.unwrap().n)
+            .collect::<Vec<_>>();
+        assert_eq!(counted, vec![1, 2, 3]);
+
+        let adds = vec![AddRequest { a: 1, b: 2 }, AddRequest { a: 3, b: 4 }];
+        let response = client.sum(Request::new(adds));
+        assert_eq!(response.unwrap().into_inner().sum, 10);
+    }
+
+    #[test]
+    fn mock() {
+        let mock = MockCounter::new();
+        mock.add
+            .push_response(Err(Status::new(Code::Unavailable, "try again")))
+            .respond_with(|request| {
+                let AddRequest { a, b } = request.get_ref();
+                Ok(Response::new(AddReply { sum: a + b }))
+            });
+        mock.count.push_response(Ok(Response::new(vec![
+            CountReply { n: 2 },
+            CountReply { n: 1 },
+        ])));
+
+        let client = Counter::new(Direct::new(CounterServer::new(mock.clone())));
+        let mut client = client.ready().wait().unwrap();
+
+        let status = client.add(Request::new(AddRequest { a: 1, b: 2 })).wait();
+        assert_eq!(status.unwrap_err().code(), Code::Unavailable);
+
+        let response = client.add(Request::new(AddRequest { a: 3, b: 4 })).wait();
+        assert_eq!(response.unwrap().into_inner().sum, 7);
+
+        let replies = client
+            .count(Request::new(CountRequest { to: 2 }))
+            .and_then(|response| response.into_inner().collect())
+            .wait()
+            .unwrap();
+        let counted = replies.iter().map(|reply| reply.n).collect::<Vec<_>>();
+        assert_eq!(counted, vec![2, 1]);
+
+        assert_eq!(mock.add.times_called(), 2);
+        let requests = mock.add.take_requests();
+        assert_eq!(requests[1].get_ref(), &AddRequest { a: 3, b: 4 });
+        assert_eq!(mock.count.take_requests()[0].get_ref().to, 2);
+    }
+}
@@ -41,4 +41,12 @@ mod tests {
             let _ = client.say_hello2(grpc::Request::new(request.clone()));
         }
     }
+
+    #[test]
+    fn service_name() {
+        use crate::hello::server::HelloServer;
+        use tower_grpc::codegen::server::grpc::NamedService;
+
+        assert_eq!(<HelloServer<()> as NamedService>::NAME, "hello.Hello");
+    }
 }
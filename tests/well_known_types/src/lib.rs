@@ -0,0 +1,126 @@
+pub mod well_known_types {
+    include!(concat!(env!("OUT_DIR"), "/well_known_types.rs"));
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::well_known_types::client::UsesWellKnownTypes;
+    use crate::well_known_types::server::{self, UsesWellKnownTypesServer};
+
+    use futures::{future, stream, Future, Stream};
+    use prost_types::{Any, Duration, Struct, Timestamp};
+    use tower_grpc::client::Direct;
+    use tower_grpc::generic::client::GrpcService;
+    use tower_grpc::{BoxBody, Request, Response, Status, Streaming};
+
+    type BoxStream<T> = Box<dyn Stream<Item = T, Error = Status> + Send>;
+
+    #[derive(Clone, Debug)]
+    struct Uses;
+
+    impl server::UsesWellKnownTypes for Uses {
+        type NowFuture = future::FutureResult<Response<Timestamp>, Status>;
+        type SleepFuture = future::FutureResult<Response<()>, Status>;
+        type EchoFuture = future::FutureResult<Response<String>, Status>;
+        type SumFuture = Box<dyn Future<Item = Response<i64>, Error = Status> + Send>;
+        type FlagsStream = BoxStream<Vec<u8>>;
+        type FlagsFuture = future::FutureResult<Response<Self::FlagsStream>, Status>;
+        type DescribeStream = BoxStream<Struct>;
+        type DescribeFuture = future::FutureResult<Response<Self::DescribeStream>, Status>;
+
+        fn now(&mut self, _: Request<()>) -> Self::NowFuture {
+            future::ok(Response::new(Timestamp {
+                seconds: 42,
+                nanos: 0,
+            }))
+        }
+
+        fn sleep(&mut self, _: Request<Duration>) -> Self::SleepFuture {
+            future::ok(Response::new(()))
+        }
+
+        fn echo(&mut self, request: Request<String>) -> Self::EchoFuture {
+            future::ok(Response::new(request.into_inner()))
+        }
+
+        fn sum(&mut self, request: Request<Streaming<i32>>) -> Self::SumFuture {
+            let sum = request
+                .into_inner()
+                .fold(0, |sum, n| Ok::<_, Status>(sum + i64::from(n)));
+
+            Box::new(sum.map(Response::new))
+        }
+
+        fn flags(&mut self, request: Request<bool>) -> Self::FlagsFuture {
+            let flag = vec![*request.get_ref() as u8];
+            let flags: Self::FlagsStream = Box::new(stream::iter_ok(vec![flag.clone(), flag]));
+            future::ok(Response::new(flags))
+        }
+
+        fn describe(&mut self, request: Request<Streaming<Any>>) -> Self::DescribeFuture {
+            let structs: Self::DescribeStream = Box::new(request.into_inner().map(|_| Struct {
+                fields: BTreeMap::new(),
+            }));
+            future::ok(Response::new(structs))
+        }
+    }
+
+    #[test]
+    fn can_call() {
+        #[allow(dead_code)]
+        fn zomg<T>(client: &mut UsesWellKnownTypes<T>)
+        where
+            T: GrpcService<BoxBody>,
+        {
+            let _ = client.now(Request::new(())).map(|resp| {
+                let inner: Timestamp = resp.into_inner();
+                inner
+            });
+            let _ = client.echo(Request::new(String::new())).map(|resp| {
+                let inner: String = resp.into_inner();
+                inner
+            });
+        }
+    }
+
+    #[test]
+    fn well_known_types() {
+        let client = UsesWellKnownTypes::new(Direct::new(UsesWellKnownTypesServer::new(Uses)));
+        let mut client = client.ready().wait().unwrap();
+
+        let response = client.now(Request::new(())).wait();
+        assert_eq!(response.unwrap().into_inner().seconds, 42);
+
+        let sleep = Duration {
+            seconds: 1,
+            nanos: 0,
+        };
+        client.sleep(Request::new(sleep)).wait().unwrap();
+
+        let response = client.echo(Request::new("hello".to_string())).wait();
+        assert_eq!(response.unwrap().into_inner(), "hello");
+
+        let response = client
+            .sum(Request::new(stream::iter_ok(vec![1, 2, 3])))
+            .wait();
+        assert_eq!(response.unwrap().into_inner(), 6);
+
+        let flags = client
+            .flags(Request::new(true))
+            .and_then(|response| response.into_inner().collect())
+            .wait()
+            .unwrap();
+        assert_eq!(flags, vec![vec![1], vec![1]]);
+
+        let any = Any {
+            type_url: "type.googleapis.com/google.protobuf.Empty".to_string(),
+            value: Vec::new(),
+        };
+        let structs = client
+            .describe(Request::new(stream::iter_ok(vec![any])))
+            .and_then(|response| response.into_inner().collect())
+            .wait()
+            .unwrap();
+        assert_eq!(structs.len(), 1);
+    }
+}
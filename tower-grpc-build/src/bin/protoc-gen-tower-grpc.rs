@@ -0,0 +1,13 @@
+//! A `protoc` plugin generating tower-grpc code.
+//!
+//! See `tower_grpc_build::plugin` for its options.
+
+fn main() {
+    let stdin = std::io::stdin();
+    let stdout = std::io::stdout();
+
+    if let Err(e) = tower_grpc_build::plugin::run(stdin.lock(), stdout.lock()) {
+        eprintln!("protoc-gen-tower-grpc: {}", e);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,269 @@
+//! Generates tower-grpc code ahead of time, for checking it in rather than
+//! generating it in a build script.
+//!
+//! A file is written for each protobuf package, formatted with `rustfmt`.
+//! With `--check`, nothing is written, and the command fails if the files in
+//! the output directory are stale.
+//!
+//! ```text
+//! tower-grpc-gen --proto proto/helloworld.proto -I proto --out src/gen --client --server
+//! ```
+
+#![deny(rust_2018_idioms)]
+
+use clap::{App, Arg, ArgMatches};
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
+
+fn main() {
+    let matches = App::new("tower-grpc-gen")
+        .version(env!("CARGO_PKG_VERSION"))
+        .about("Generates tower-grpc code from .proto definitions")
+        .arg(
+            Arg::with_name("proto")
+                .long("proto")
+                .value_name("FILE")
+                .help("A .proto file to generate code for")
+                .multiple(true)
+                .number_of_values(1)
+                .required_unless("descriptor_set"),
+        )
+        .arg(
+            Arg::with_name("include")
+                .short("I")
+                .long("include")
+                .value_name("DIR")
+                .help("A directory to search for imports")
+                .multiple(true)
+                .number_of_values(1)
+                .default_value("."),
+        )
+        .arg(
+            Arg::with_name("descriptor_set")
+                .long("descriptor-set")
+                .value_name("FILE")
+                .help("An encoded FileDescriptorSet to generate code for, rather than .proto files")
+                .conflicts_with("proto"),
+        )
+        .arg(
+            Arg::with_name("out")
+                .long("out")
+                .value_name("DIR")
+                .help("The directory to write generated files to")
+                .required(true),
+        )
+        .arg(
+            Arg::with_name("check")
+                .long("check")
+                .help("Fail if the generated files in the output directory are stale"),
+        )
+        .arg(
+            Arg::with_name("client")
+                .long("client")
+                .help("Generate clients"),
+        )
+        .arg(
+            Arg::with_name("blocking_client")
+                .long("blocking-client")
+                .help("Generate blocking clients"),
+        )
+        .arg(
+            Arg::with_name("server")
+                .long("server")
+                .help("Generate servers"),
+        )
+        .arg(
+            Arg::with_name("mocks")
+                .long("mocks")
+                .help("Generate mocks of the service traits"),
+        )
+        .arg(
+            Arg::with_name("box_services")
+                .long("box-services")
+                .help("Generate service traits returning boxed futures"),
+        )
+        .arg(
+            Arg::with_name("ergonomic_clients")
+                .long("ergonomic-clients")
+                .help("Generate clients taking any IntoRequest, and returning named futures"),
+        )
+        .arg(
+            Arg::with_name("codec")
+                .long("codec")
+                .value_name("PATH")
+                .help("The path of a custom codec to use for all services"),
+        )
+        .get_matches();
+
+    if let Err(e) = run(&matches) {
+        eprintln!("tower-grpc-gen: {}", e);
+        process::exit(1);
+    }
+}
+
+fn run(matches: &ArgMatches<'_>) -> io::Result<()> {
+    let out = Path::new(matches.value_of_os("out").unwrap());
+
+    let dir = env::temp_dir().join(format!("tower-grpc-gen-{}", process::id()));
+    fs::create_dir_all(&dir)?;
+
+    let ret = generate(matches, &dir).and_then(|files| {
+        if matches.is_present("check") {
+            check(out, &files)
+        } else {
+            write(out, &files)
+        }
+    });
+
+    let _ = fs::remove_dir_all(&dir);
+    ret
+}
+
+/// Generates formatted code into `dir`, returning the generated files.
+fn generate(matches: &ArgMatches<'_>, dir: &Path) -> io::Result<Vec<(OsString, String)>> {
+    let mut config = tower_grpc_build::Config::new();
+
+    config
+        .enable_client(matches.is_present("client"))
+        .enable_blocking_client(matches.is_present("blocking_client"))
+        .enable_server(matches.is_present("server"))
+        .build_mocks(matches.is_present("mocks"))
+        .box_services(matches.is_present("box_services"))
+        .ergonomic_clients(matches.is_present("ergonomic_clients"))
+        .out_dir(dir);
+
+    if let Some(codec) = matches.value_of("codec") {
+        config.codec(codec);
+    }
+
+    match matches.value_of_os("descriptor_set") {
+        Some(descriptor_set) => config.build_from_descriptor_set(descriptor_set)?,
+        None => {
+            let protos = paths(matches, "proto");
+            let includes = paths(matches, "include");
+            config.build(&protos, &includes)?;
+        }
+    }
+
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        rustfmt(&entry.path())?;
+        files.push((entry.file_name(), fs::read_to_string(entry.path())?));
+    }
+
+    files.sort();
+
+    Ok(files)
+}
+
+fn paths(matches: &ArgMatches<'_>, name: &str) -> Vec<PathBuf> {
+    matches
+        .values_of_os(name)
+        .into_iter()
+        .flatten()
+        .map(PathBuf::from)
+        .collect()
+}
+
+/// Formats the file at `path` in place, with the `rustfmt` of `RUSTFMT` or
+/// the `PATH`.
+fn rustfmt(path: &Path) -> io::Result<()> {
+    let rustfmt = env::var_os("RUSTFMT").unwrap_or_else(|| "rustfmt".into());
+
+    let status = Command::new(&rustfmt)
+        .arg("--edition")
+        .arg("2018")
+        .arg(path)
+        .status()
+        .map_err(|e| {
+            io::Error::new(
+                e.kind(),
+                format!("failed to run {}: {}", rustfmt.to_string_lossy(), e),
+            )
+        })?;
+
+    if !status.success() {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!("rustfmt failed on {}", path.display()),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Writes the generated `files` to `out`.
+fn write(out: &Path, files: &[(OsString, String)]) -> io::Result<()> {
+    fs::create_dir_all(out)?;
+
+    for (name, content) in files {
+        fs::write(out.join(name), content)?;
+    }
+
+    Ok(())
+}
+
+/// Fails if any of the generated `files` is missing from `out`, or differs.
+fn check(out: &Path, files: &[(OsString, String)]) -> io::Result<()> {
+    let mut stale = 0;
+
+    for (name, content) in files {
+        let path = out.join(name);
+
+        match fs::read_to_string(&path) {
+            Ok(ref existing) if existing == content => {}
+            Ok(_) => {
+                eprintln!("{} is stale", path.display());
+                stale += 1;
+            }
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => {
+                eprintln!("{} is missing", path.display());
+                stale += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    if stale > 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!(
+                "{} generated file(s) out of date, run without --check to update them",
+                stale
+            ),
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_detects_stale_and_missing_files() {
+        let out = env::temp_dir().join(format!("tower-grpc-gen-check-{}", process::id()));
+        let files = vec![
+            (OsString::from("a.rs"), "// a\n".to_string()),
+            (OsString::from("b.rs"), "// b\n".to_string()),
+        ];
+
+        write(&out, &files).unwrap();
+        check(&out, &files).unwrap();
+
+        fs::write(out.join("a.rs"), "// stale\n").unwrap();
+        assert!(check(&out, &files).is_err());
+
+        write(&out, &files).unwrap();
+        fs::remove_file(out.join("b.rs")).unwrap();
+        assert!(check(&out, &files).is_err());
+
+        fs::remove_dir_all(&out).unwrap();
+    }
+}
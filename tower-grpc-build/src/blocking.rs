@@ -0,0 +1,169 @@
+use super::ImportType;
+use crate::comments_to_rustdoc;
+
+/// Generates blocking clients, wrapping the generated clients
+pub struct ServiceGenerator {
+    /// Custom codecs of the services
+    pub codecs: crate::Codecs,
+    /// Whether the wrapped clients are generated with named futures
+    pub ergonomic: bool,
+}
+
+// ===== impl ServiceGenerator =====
+
+impl ServiceGenerator {
+    /// Generate the blocking client, in a `blocking` module of the client code
+    pub fn generate(&self, service: &prost_build::Service, scope: &mut codegen::Scope) {
+        let scope = scope
+            .get_or_new_module("client")
+            .vis("pub")
+            .scope()
+            .get_or_new_module("blocking")
+            .vis("pub")
+            .import("::tower_grpc::codegen::client", "*")
+            .scope();
+
+        for method in &service.methods {
+            scope.import_type(&method.input_type, 2);
+            scope.import_type(&method.output_type, 2);
+        }
+
+        self.define_client_struct(service, scope);
+        self.define_client_impl(service, scope);
+    }
+
+    fn define_client_struct(&self, service: &prost_build::Service, scope: &mut codegen::Scope) {
+        let doc = format!(
+            "{}\nBlocks the current thread on each call, running the calls of the\n\
+             wrapped `{}` client on its own runtime.",
+            comments_to_rustdoc(&service.comments),
+            service.name
+        );
+
+        scope
+            .new_struct(&service.name)
+            .vis("pub")
+            .generic("T")
+            .derive("Debug")
+            .doc(doc.trim_start())
+            .field("inner", &format!("super::{}<T>", service.name))
+            .field("runtime", "grpc::blocking::Runtime");
+    }
+
+    fn define_client_impl(&self, service: &prost_build::Service, scope: &mut codegen::Scope) {
+        let imp = scope
+            .new_impl(&service.name)
+            .generic("T")
+            .target_generic("T");
+
+        imp.new_fn("new")
+            .doc("Create a blocking client, with a new runtime.")
+            .vis("pub")
+            .arg("inner", "T")
+            .ret("std::io::Result<Self>")
+            .line("let runtime = grpc::blocking::Runtime::new()?;")
+            .line("Ok(Self::with_runtime(inner, runtime))");
+
+        imp.new_fn("with_runtime")
+            .doc(
+                "Create a blocking client, running calls on `runtime`.\n\
+                 \n\
+                 The transport should be created on `runtime`, or on a clone of it.",
+            )
+            .vis("pub")
+            .arg("inner", "T")
+            .arg("runtime", "grpc::blocking::Runtime")
+            .ret("Self")
+            .line(&format!("let inner = super::{}::new(inner);", service.name))
+            .line("Self { inner, runtime }");
+
+        let codec = self.codecs.get(service);
+
+        for method in &service.methods {
+            let input_type = crate::unqualified(&method.input_type, &method.input_proto_type, 2);
+            let output_type = crate::unqualified(&method.output_type, &method.output_proto_type, 2);
+
+            let func = imp
+                .new_fn(&method.name)
+                .vis("pub")
+                .arg_mut_self()
+                .doc(&comments_to_rustdoc(&method.comments));
+
+            // Calls to the deprecated client method are expected here.
+            if method.options.deprecated == Some(true) {
+                func.attr("deprecated").allow("deprecated");
+            }
+
+            // Clients with a custom codec or named futures send
+            // `grpc::BoxBody` requests.
+            let boxed = codec.is_some() || self.ergonomic;
+            let body = if boxed {
+                "grpc::BoxBody"
+            } else {
+                func.generic("R");
+                "R"
+            };
+
+            func.bound("T", &format!("grpc::GrpcService<{}>", body));
+
+            if method.client_streaming {
+                func.generic("I")
+                    .bound("I", &format!("IntoIterator<Item = {}>", input_type));
+
+                if boxed {
+                    func.bound("I::IntoIter", "Send + 'static");
+                } else {
+                    func.bound(
+                        "futures::IterOk<I::IntoIter, grpc::Status>",
+                        "grpc::Encodable<R>",
+                    );
+                }
+
+                func.arg("request", "grpc::Request<I>")
+                    .line("let request = request.map(futures::iter_ok::<_, grpc::Status>);");
+            } else {
+                if !boxed {
+                    func.bound(
+                        &format!("grpc::unary::Once<{}>", input_type),
+                        "grpc::Encodable<R>",
+                    );
+                }
+
+                func.arg("request", &format!("grpc::Request<{}>", input_type));
+            }
+
+            func.line("let inner = &mut self.inner;").line(&format!(
+                "self.runtime.block_on(futures::poll_fn(|| inner.poll_ready::<{}>()))?;",
+                body
+            ));
+
+            if method.server_streaming {
+                let stream = match codec {
+                    Some(codec) => format!(
+                        "grpc::generic::Streaming<<{}<{}, {}> as grpc::generic::Codec>::Decoder, T::ResponseBody>",
+                        codec, input_type, output_type
+                    ),
+                    None => format!("grpc::Streaming<{}, T::ResponseBody>", output_type),
+                };
+
+                func.ret(format!(
+                    "Result<grpc::Response<grpc::blocking::Iter<{}>>, grpc::Status>",
+                    stream
+                ))
+                .line(&format!(
+                    "self.runtime.block_on_stream(inner.{}(request))",
+                    method.name
+                ));
+            } else {
+                func.ret(format!(
+                    "Result<grpc::Response<{}>, grpc::Status>",
+                    output_type
+                ))
+                .line(&format!(
+                    "self.runtime.block_on(inner.{}(request))",
+                    method.name
+                ));
+            }
+        }
+    }
+}
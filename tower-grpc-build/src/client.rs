@@ -1,8 +1,17 @@
 use super::ImportType;
 use crate::comments_to_rustdoc;
 
+use std::collections::HashMap;
+
 /// Generates service code
-pub struct ServiceGenerator;
+pub struct ServiceGenerator {
+    /// Custom codecs of the services
+    pub codecs: crate::Codecs,
+    /// Encoded custom options of the methods, by path
+    pub extensions: HashMap<String, Vec<u8>>,
+    /// Whether methods take `IntoRequest`s and return named futures
+    pub ergonomic: bool,
+}
 
 // ===== impl ServiceGenerator =====
 
@@ -22,6 +31,7 @@ impl ServiceGenerator {
         self.import_message_types(service, scope);
         self.define_client_struct(service, scope);
         self.define_client_impl(service, scope);
+        self.define_descriptors(service, scope);
     }
 
     fn import_message_types(&self, service: &prost_build::Service, scope: &mut codegen::Scope) {
@@ -42,6 +52,19 @@ impl ServiceGenerator {
             .field("inner", "grpc::Grpc<T>");
     }
 
+    fn define_descriptors(&self, service: &prost_build::Service, scope: &mut codegen::Scope) {
+        scope.raw(&format!(
+            "impl<T> {}<T> {{\n    \
+             /// The fully qualified name of the service.\n    \
+             pub const NAME: &'static str = \"{}\";\n\n    \
+             /// The methods of the service.\n    \
+             pub const METHODS: &'static [grpc::MethodDescriptor] = &[\n{}    ];\n}}",
+            service.name,
+            crate::service_name(service),
+            crate::method_descriptors(service, &self.extensions, "        ")
+        ));
+    }
+
     fn define_client_impl(&self, service: &prost_build::Service, scope: &mut codegen::Scope) {
         let imp = scope
             .new_impl(&service.name)
@@ -87,8 +110,6 @@ impl ServiceGenerator {
             let func = imp
                 .new_fn(&name)
                 .vis("pub")
-                .generic("R")
-                .bound("T", "grpc::GrpcService<R>")
                 .arg_mut_self()
                 .line(format!(
                     "let path = http::PathAndQuery::from_static({});",
@@ -96,6 +117,87 @@ impl ServiceGenerator {
                 ))
                 .doc(&comments_to_rustdoc(&service.comments));
 
+            if method.options.deprecated == Some(true) {
+                func.attr("deprecated");
+            }
+
+            if self.ergonomic {
+                define_ergonomic_method(
+                    func,
+                    method,
+                    self.codecs.get(service),
+                    &input_type,
+                    &output_type,
+                );
+                continue;
+            }
+
+            if let Some(codec) = self.codecs.get(service) {
+                let codec = format!("{}<{}, {}>", codec, input_type, output_type);
+                let decoder = format!("<{} as grpc::generic::Codec>::Decoder", codec);
+                let stream_bound = format!(
+                    "futures::Stream<Item = {}, Error = grpc::Status> + Send + 'static",
+                    input_type
+                );
+
+                func.bound("T", "grpc::GrpcService<grpc::BoxBody>");
+
+                match (method.client_streaming, method.server_streaming) {
+                    (false, false) => {
+                        func.arg("request", &format!("grpc::Request<{}>", input_type))
+                            .ret(format!(
+                                "grpc::generic::client::unary::ResponseFuture<{}, T::Future, T::ResponseBody>",
+                                decoder
+                            ))
+                            .line(format!(
+                                "self.inner.unary_with_codec(<{}>::default(), request, path)",
+                                codec
+                            ));
+                    }
+                    (false, true) => {
+                        func.arg("request", &format!("grpc::Request<{}>", input_type))
+                            .ret(format!(
+                                "grpc::generic::client::server_streaming::ResponseFuture<{}, T::Future>",
+                                decoder
+                            ))
+                            .line(format!(
+                                "self.inner.server_streaming_with_codec(<{}>::default(), request, path)",
+                                codec
+                            ));
+                    }
+                    (true, false) => {
+                        func.generic("B")
+                            .bound("B", &stream_bound)
+                            .arg("request", "grpc::Request<B>")
+                            .ret(format!(
+                                "grpc::generic::client::client_streaming::ResponseFuture<{}, T::Future, T::ResponseBody>",
+                                decoder
+                            ))
+                            .line(format!(
+                                "self.inner.client_streaming_with_codec(<{}>::default(), request, path)",
+                                codec
+                            ));
+                    }
+                    (true, true) => {
+                        func.generic("B")
+                            .bound("B", &stream_bound)
+                            .arg("request", "grpc::Request<B>")
+                            .ret(format!(
+                                "grpc::generic::client::streaming::ResponseFuture<{}, T::Future>",
+                                decoder
+                            ))
+                            .line(format!(
+                                "self.inner.streaming_with_codec(<{}>::default(), request, path)",
+                                codec
+                            ));
+                    }
+                }
+
+                continue;
+            }
+
+            func.generic("R").bound("T", "grpc::GrpcService<R>");
+
             let mut request = codegen::Type::new("grpc::Request");
 
             let req_body = match (method.client_streaming, method.server_streaming) {
@@ -161,3 +263,60 @@ impl ServiceGenerator {
         }
     }
 }
+
+/// Defines the body of a method taking an `IntoRequest`, or an
+/// `IntoStreamingRequest` for client streaming methods, and returning a
+/// named `Call` future.
+fn define_ergonomic_method(
+    func: &mut codegen::Function,
+    method: &prost_build::Method,
+    codec: Option<&str>,
+    input_type: &str,
+    output_type: &str,
+) {
+    let kind = match (method.client_streaming, method.server_streaming) {
+        (false, false) => "unary",
+        (false, true) => "server_streaming",
+        (true, false) => "client_streaming",
+        (true, true) => "streaming",
+    };
+
+    func.bound("T", "grpc::GrpcService<grpc::BoxBody>");
+
+    if method.client_streaming {
+        func.generic("S")
+            .generic("K")
+            .bound(
+                "S",
+                &format!("grpc::IntoStreamingRequest<{}, K>", input_type),
+            )
+            .bound("S::Stream", "Send + 'static")
+            .arg("request", "S")
+            .line("let request = grpc::IntoStreamingRequest::into_streaming_request(request);");
+    } else {
+        func.arg(
+            "request",
+            &format!("impl grpc::IntoRequest<{}>", input_type),
+        )
+        .line("let request = grpc::IntoRequest::into_request(request);");
+    }
+
+    match codec {
+        Some(codec) => {
+            let codec = format!("{}<{}, {}>", codec, input_type, output_type);
+
+            func.ret(format!(
+                "grpc::generic::client::{}::Call<<{} as grpc::generic::Codec>::Decoder, T>",
+                kind, codec
+            ))
+            .line(format!(
+                "self.inner.{}_with_codec(<{}>::default(), request, path)",
+                kind, codec
+            ));
+        }
+        None => {
+            func.ret(format!("grpc::{}::Call<{}, T>", kind, output_type))
+                .line(format!("self.inner.{}(request, path)", kind));
+        }
+    }
+}
@@ -0,0 +1,185 @@
+//! Support for reading precompiled descriptor sets.
+//!
+//! `prost` discards the custom options of methods when decoding a
+//! `FileDescriptorSet`, so we read them from the encoded set ourselves.
+//! `protoc` plugin requests are read the same way.
+
+use std::collections::HashMap;
+use std::io;
+
+/// Returns the encoded custom options of each method in the encoded
+/// `FileDescriptorSet` `buf`, by method path.
+pub fn method_extensions(buf: &[u8]) -> io::Result<HashMap<String, Vec<u8>>> {
+    let mut ret = HashMap::new();
+
+    // FileDescriptorSet.file
+    for file in fields(buf, 1)? {
+        // FileDescriptorProto.package
+        let package = match fields(file, 2)?.last() {
+            Some(package) => string(package)?,
+            None => "",
+        };
+
+        // FileDescriptorProto.service
+        for service in fields(file, 6)? {
+            // ServiceDescriptorProto.name
+            let service_name = fields(service, 1)?.last().map_or(Ok(""), |s| string(s))?;
+
+            // ServiceDescriptorProto.method
+            for method in fields(service, 2)? {
+                // MethodDescriptorProto.name
+                let name = fields(method, 1)?.last().map_or(Ok(""), |s| string(s))?;
+                let mut extensions = Vec::new();
+
+                // MethodDescriptorProto.options
+                for options in fields(method, 4)? {
+                    // Extensions of MethodOptions are numbered from 1000.
+                    for field in Fields::new(options) {
+                        let field = field?;
+                        if field.number >= 1000 {
+                            extensions.extend_from_slice(field.raw);
+                        }
+                    }
+                }
+
+                if !extensions.is_empty() {
+                    // As `crate::method_path`
+                    let path = format!("/{}.{}/{}", package, service_name, name);
+                    ret.insert(path, extensions);
+                }
+            }
+        }
+    }
+
+    Ok(ret)
+}
+
+/// Returns the values of the length delimited fields numbered `number` in
+/// the encoded message `buf`.
+pub(crate) fn fields(buf: &[u8], number: u32) -> io::Result<Vec<&[u8]>> {
+    let mut ret = Vec::new();
+
+    for field in Fields::new(buf) {
+        let field = field?;
+        if field.number == number {
+            ret.push(field.value);
+        }
+    }
+
+    Ok(ret)
+}
+
+pub(crate) fn string(buf: &[u8]) -> io::Result<&str> {
+    std::str::from_utf8(buf).map_err(|_| invalid("invalid UTF-8 string"))
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("invalid file descriptor set: {}", msg),
+    )
+}
+
+/// Appends the length delimited field numbered `number` to `buf`.
+pub(crate) fn encode_field(number: u32, value: &[u8], buf: &mut Vec<u8>) {
+    encode_varint(u64::from(number) << 3 | 2, buf);
+    encode_varint(value.len() as u64, buf);
+    buf.extend_from_slice(value);
+}
+
+fn encode_varint(mut value: u64, buf: &mut Vec<u8>) {
+    while value >= 0x80 {
+        buf.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+/// An iterator over the fields of an encoded message.
+struct Fields<'a> {
+    buf: &'a [u8],
+}
+
+struct Field<'a> {
+    number: u32,
+    /// The value of a length delimited field, without its length.
+    value: &'a [u8],
+    /// The encoded field, including its key.
+    raw: &'a [u8],
+}
+
+impl<'a> Fields<'a> {
+    fn new(buf: &'a [u8]) -> Self {
+        Fields { buf }
+    }
+
+    fn varint(&mut self) -> io::Result<u64> {
+        let mut ret = 0;
+
+        for (i, &byte) in self.buf.iter().enumerate().take(10) {
+            ret |= u64::from(byte & 0x7f) << (7 * i);
+
+            if byte & 0x80 == 0 {
+                self.buf = &self.buf[i + 1..];
+                return Ok(ret);
+            }
+        }
+
+        Err(invalid("invalid varint"))
+    }
+
+    fn take(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        if self.buf.len() < len {
+            return Err(invalid("unexpected end of message"));
+        }
+
+        let (value, rest) = self.buf.split_at(len);
+        self.buf = rest;
+        Ok(value)
+    }
+
+    fn field(&mut self) -> io::Result<Field<'a>> {
+        let start = self.buf;
+        let key = self.varint()?;
+
+        let value = match key & 0x7 {
+            0 => {
+                self.varint()?;
+                &[][..]
+            }
+            1 => self.take(8)?,
+            2 => {
+                let len = self.varint()? as usize;
+                self.take(len)?
+            }
+            5 => self.take(4)?,
+            _ => return Err(invalid("unsupported wire type")),
+        };
+
+        let raw = &start[..start.len() - self.buf.len()];
+
+        Ok(Field {
+            number: (key >> 3) as u32,
+            value,
+            raw,
+        })
+    }
+}
+
+impl<'a> Iterator for Fields<'a> {
+    type Item = io::Result<Field<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.buf.is_empty() {
+            return None;
+        }
+
+        let field = self.field();
+        if field.is_err() {
+            // Stop after an error.
+            self.buf = &[];
+        }
+
+        Some(field)
+    }
+}
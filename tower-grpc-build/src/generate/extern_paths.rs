@@ -0,0 +1,99 @@
+use super::to_snake;
+use crate::to_upper_camel;
+
+use std::collections::{hash_map, HashMap};
+
+/// The Rust paths of protobuf packages and types that are not generated.
+///
+/// The well-known types are always resolved to `prost_types`, as
+/// `prost-build` does unless it compiles them.
+pub(super) struct ExternPaths {
+    extern_paths: HashMap<String, String>,
+}
+
+const WELL_KNOWN_TYPES: &[(&str, &str)] = &[
+    (".google.protobuf", "::prost_types"),
+    (".google.protobuf.BoolValue", "bool"),
+    (".google.protobuf.BytesValue", "::std::vec::Vec<u8>"),
+    (".google.protobuf.DoubleValue", "f64"),
+    (".google.protobuf.Empty", "()"),
+    (".google.protobuf.FloatValue", "f32"),
+    (".google.protobuf.Int32Value", "i32"),
+    (".google.protobuf.Int64Value", "i64"),
+    (".google.protobuf.StringValue", "::std::string::String"),
+    (".google.protobuf.UInt32Value", "u32"),
+    (".google.protobuf.UInt64Value", "u64"),
+];
+
+impl ExternPaths {
+    pub(super) fn new(paths: &[(String, String)]) -> Result<ExternPaths, String> {
+        let mut extern_paths = ExternPaths {
+            extern_paths: HashMap::new(),
+        };
+
+        for (proto_path, rust_path) in paths {
+            extern_paths.insert(proto_path.clone(), rust_path.clone())?;
+        }
+
+        for &(proto_path, rust_path) in WELL_KNOWN_TYPES {
+            extern_paths.insert(proto_path.to_string(), rust_path.to_string())?;
+        }
+
+        Ok(extern_paths)
+    }
+
+    fn insert(&mut self, proto_path: String, rust_path: String) -> Result<(), String> {
+        if !proto_path.starts_with('.') || proto_path.split('.').skip(1).any(str::is_empty) {
+            return Err(format!(
+                "invalid fully qualified protobuf path: {}",
+                proto_path
+            ));
+        }
+
+        match self.extern_paths.entry(proto_path) {
+            hash_map::Entry::Occupied(occupied) => Err(format!(
+                "duplicate extern protobuf path: {}",
+                occupied.key()
+            )),
+            hash_map::Entry::Vacant(vacant) => {
+                vacant.insert(rust_path);
+                Ok(())
+            }
+        }
+    }
+
+    /// Returns the Rust path of the fully qualified protobuf type `pb_ident`,
+    /// if it or one of its packages is extern.
+    pub(super) fn resolve_ident(&self, pb_ident: &str) -> Option<String> {
+        if let Some(rust_path) = self.extern_paths.get(pb_ident) {
+            return Some(rust_path.clone());
+        }
+
+        for (idx, _) in pb_ident.rmatch_indices('.') {
+            if let Some(rust_path) = self.extern_paths.get(&pb_ident[..idx]) {
+                let mut segments = pb_ident[idx + 1..].split('.');
+                let ident_type = segments.next_back().map(to_upper_camel);
+
+                let path = rust_path
+                    .split("::")
+                    .chain(segments)
+                    .enumerate()
+                    .map(|(idx, segment)| {
+                        // A leading `crate` is the keyword, not an identifier.
+                        if idx == 0 && segment == "crate" {
+                            segment.to_owned()
+                        } else {
+                            to_snake(segment)
+                        }
+                    })
+                    .chain(ident_type)
+                    .collect::<Vec<_>>()
+                    .join("::");
+
+                return Some(path);
+            }
+        }
+
+        None
+    }
+}
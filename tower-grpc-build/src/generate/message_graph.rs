@@ -0,0 +1,72 @@
+use prost_types::field_descriptor_proto::{Label, Type};
+use prost_types::{DescriptorProto, FileDescriptorProto};
+
+use std::collections::{HashMap, HashSet};
+
+/// The fully qualified names of the messages each message has a singular
+/// message field of.
+///
+/// `prost` does not box message fields, so fields of messages that are
+/// recursively nested are boxed when they are generated.
+pub(super) struct MessageGraph {
+    edges: HashMap<String, Vec<String>>,
+}
+
+impl MessageGraph {
+    /// Returns the graph of the messages of `files`, which must all have a
+    /// package.
+    pub(super) fn new(files: &[FileDescriptorProto]) -> MessageGraph {
+        let mut graph = MessageGraph {
+            edges: HashMap::new(),
+        };
+
+        for file in files {
+            let package = format!(".{}", file.package());
+
+            for message in &file.message_type {
+                graph.add_message(&package, message);
+            }
+        }
+
+        graph
+    }
+
+    fn add_message(&mut self, package: &str, message: &DescriptorProto) {
+        let name = format!("{}.{}", package, message.name());
+
+        let fields = message
+            .field
+            .iter()
+            .filter(|field| field.r#type() == Type::Message && field.label() != Label::Repeated)
+            .map(|field| field.type_name().to_string())
+            .collect();
+        self.edges.insert(name.clone(), fields);
+
+        for nested in &message.nested_type {
+            self.add_message(&name, nested);
+        }
+    }
+
+    /// Returns whether message `inner` is nested in message `outer`, or is
+    /// `outer`.
+    pub(super) fn is_nested(&self, outer: &str, inner: &str) -> bool {
+        let mut visited = HashSet::new();
+        let mut stack = vec![outer];
+
+        while let Some(name) = stack.pop() {
+            if name == inner {
+                return true;
+            }
+
+            if !visited.insert(name) {
+                continue;
+            }
+
+            if let Some(fields) = self.edges.get(name) {
+                stack.extend(fields.iter().map(String::as_str));
+            }
+        }
+
+        false
+    }
+}
@@ -0,0 +1,1067 @@
+//! Generates code from decoded `FileDescriptorProto`s.
+//!
+//! `prost-build` only generates code for `.proto` files, running `protoc`
+//! itself, so this is a port of its code generator: messages and enums are
+//! generated for `prost` as `prost-build` generates them by default, and
+//! services are handed to a `prost_build::ServiceGenerator`. Comments are
+//! only generated for files with source info.
+
+mod extern_paths;
+mod message_graph;
+
+use self::extern_paths::ExternPaths;
+use self::message_graph::MessageGraph;
+use crate::to_upper_camel;
+
+use heck::SnakeCase;
+use prost_build::{Comments, Method, Service, ServiceGenerator};
+use prost_types::field_descriptor_proto::{Label, Type};
+use prost_types::source_code_info::Location;
+use prost_types::{
+    DescriptorProto, EnumDescriptorProto, EnumValueDescriptorProto, FieldDescriptorProto,
+    FileDescriptorProto, OneofDescriptorProto, ServiceDescriptorProto,
+};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::{ascii, io, iter};
+
+#[derive(PartialEq)]
+enum Syntax {
+    Proto2,
+    Proto3,
+}
+
+struct CodeGenerator<'a> {
+    service_generator: &'a mut dyn ServiceGenerator,
+    package: String,
+    /// The locations of the file's source info, sorted by path.
+    locations: Vec<Location>,
+    syntax: Syntax,
+    message_graph: &'a MessageGraph,
+    extern_paths: &'a ExternPaths,
+    depth: u8,
+    path: Vec<i32>,
+    buf: &'a mut String,
+}
+
+/// Returns the code generated for `files`, by the name of the file it is
+/// written to, one per protobuf package.
+///
+/// `extern_paths` are the Rust paths of protobuf packages and types that are
+/// not generated, as `prost_build::Config::extern_path`. Files of extern
+/// packages, such as the well-known types, are skipped.
+pub(crate) fn generate(
+    files: Vec<FileDescriptorProto>,
+    extern_paths: &[(String, String)],
+    service_generator: &mut dyn ServiceGenerator,
+) -> io::Result<Vec<(String, String)>> {
+    // As `prost-build`, which resolves types by package.
+    if let Some(file) = files.iter().find(|file| file.package().is_empty()) {
+        return Err(invalid_data(format!(
+            "prost requires a package in all .proto files; {} has none",
+            file.name()
+        )));
+    }
+
+    let message_graph = MessageGraph::new(&files);
+    let extern_paths = ExternPaths::new(extern_paths).map_err(invalid_input)?;
+
+    let mut modules = BTreeMap::new();
+
+    for file in files {
+        let package = format!(".{}", file.package());
+        if extern_paths.resolve_ident(&package).is_some() {
+            continue;
+        }
+
+        let mut name = file
+            .package()
+            .split('.')
+            .map(to_snake)
+            .collect::<Vec<_>>()
+            .join(".");
+        name.push_str(".rs");
+
+        let buf = modules.entry(name).or_insert_with(String::new);
+        CodeGenerator::generate(
+            &mut *service_generator,
+            &message_graph,
+            &extern_paths,
+            file,
+            buf,
+        )?;
+    }
+
+    Ok(modules.into_iter().collect())
+}
+
+fn invalid_input(error: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, error)
+}
+
+fn invalid_data(error: String) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("invalid file descriptor set: {}", error),
+    )
+}
+
+impl<'a> CodeGenerator<'a> {
+    fn generate(
+        service_generator: &'a mut dyn ServiceGenerator,
+        message_graph: &'a MessageGraph,
+        extern_paths: &'a ExternPaths,
+        file: FileDescriptorProto,
+        buf: &'a mut String,
+    ) -> io::Result<()> {
+        let syntax = match file.syntax() {
+            "" | "proto2" => Syntax::Proto2,
+            "proto3" => Syntax::Proto3,
+            s => return Err(invalid_data(format!("unknown syntax: {}", s))),
+        };
+
+        let package = file.package().to_string();
+
+        let mut locations = file
+            .source_code_info
+            .map_or_else(Vec::new, |source_info| source_info.location);
+        locations.retain(|location| {
+            let len = location.path.len();
+            len > 0 && len % 2 == 0
+        });
+        locations.sort_by(|a, b| a.path.cmp(&b.path));
+
+        let mut code_gen = CodeGenerator {
+            service_generator,
+            package,
+            locations,
+            syntax,
+            message_graph,
+            extern_paths,
+            depth: 0,
+            path: Vec::new(),
+            buf,
+        };
+
+        // FileDescriptorProto.message_type
+        code_gen.path.push(4);
+        for (idx, message) in file.message_type.into_iter().enumerate() {
+            code_gen.path.push(idx as i32);
+            code_gen.append_message(message)?;
+            code_gen.path.pop();
+        }
+        code_gen.path.pop();
+
+        // FileDescriptorProto.enum_type
+        code_gen.path.push(5);
+        for (idx, desc) in file.enum_type.into_iter().enumerate() {
+            code_gen.path.push(idx as i32);
+            code_gen.append_enum(desc);
+            code_gen.path.pop();
+        }
+        code_gen.path.pop();
+
+        // FileDescriptorProto.service
+        code_gen.path.push(6);
+        for (idx, service) in file.service.into_iter().enumerate() {
+            code_gen.path.push(idx as i32);
+            code_gen.push_service(service);
+            code_gen.path.pop();
+        }
+        code_gen.path.pop();
+
+        code_gen.service_generator.finalize(code_gen.buf);
+
+        Ok(())
+    }
+
+    fn append_message(&mut self, message: DescriptorProto) -> io::Result<()> {
+        let message_name = message.name().to_string();
+        let fq_message_name = format!(".{}.{}", self.package, message.name());
+
+        // Skip external types.
+        if self.extern_paths.resolve_ident(&fq_message_name).is_some() {
+            return Ok(());
+        }
+
+        // Split the nested message types into the normal nested message
+        // types, and the entry types of map fields. The path index of the
+        // nested message types is kept, so that comments can be retrieved.
+        let mut nested_types = Vec::new();
+        let mut map_types = HashMap::new();
+        for (idx, nested_type) in message.nested_type.into_iter().enumerate() {
+            let map_entry = nested_type
+                .options
+                .as_ref()
+                .and_then(|options| options.map_entry)
+                .unwrap_or(false);
+
+            if map_entry {
+                let name = format!("{}.{}", fq_message_name, nested_type.name());
+
+                // Map entries have a key and a value field.
+                let mut fields = nested_type.field.into_iter();
+                let (key, value) = match (fields.next(), fields.next(), fields.next()) {
+                    (Some(key), Some(value), None) => (key, value),
+                    _ => {
+                        return Err(invalid_data(format!(
+                            "map entry {} does not have a key and a value field",
+                            name
+                        )));
+                    }
+                };
+
+                map_types.insert(name, (key, value));
+            } else {
+                nested_types.push((nested_type, idx));
+            }
+        }
+
+        // Split the fields into the normal fields, and the fields of each
+        // oneof, keeping their path indexes.
+        let mut fields = Vec::new();
+        let mut oneof_fields: HashMap<i32, Vec<(FieldDescriptorProto, usize)>> = HashMap::new();
+        for (idx, field) in message.field.into_iter().enumerate() {
+            match field.oneof_index {
+                Some(oneof_index) => oneof_fields
+                    .entry(oneof_index)
+                    .or_default()
+                    .push((field, idx)),
+                None => fields.push((field, idx)),
+            }
+        }
+
+        self.append_doc();
+        self.push_indent();
+        self.buf
+            .push_str("#[derive(Clone, PartialEq, ::prost::Message)]\n");
+        self.push_indent();
+        self.buf.push_str("pub struct ");
+        self.buf.push_str(&to_upper_camel(&message_name));
+        self.buf.push_str(" {\n");
+
+        self.depth += 1;
+
+        // DescriptorProto.field
+        self.path.push(2);
+        for (field, idx) in fields {
+            self.path.push(idx as i32);
+            match field
+                .type_name
+                .as_ref()
+                .and_then(|type_name| map_types.get(type_name))
+            {
+                Some((key, value)) => self.append_map_field(field, key, value),
+                None => self.append_field(&fq_message_name, field)?,
+            }
+            self.path.pop();
+        }
+        self.path.pop();
+
+        // DescriptorProto.oneof_decl
+        self.path.push(8);
+        for (idx, oneof) in message.oneof_decl.iter().enumerate() {
+            let idx = idx as i32;
+            self.path.push(idx);
+            let fields = oneof_fields.get(&idx).map_or(&[][..], Vec::as_slice);
+            self.append_oneof_field(&message_name, oneof, fields);
+            self.path.pop();
+        }
+        self.path.pop();
+
+        self.depth -= 1;
+        self.push_indent();
+        self.buf.push_str("}\n");
+
+        if !message.enum_type.is_empty() || !nested_types.is_empty() || !oneof_fields.is_empty() {
+            self.push_mod(&message_name);
+
+            // DescriptorProto.nested_type
+            self.path.push(3);
+            for (nested_type, idx) in nested_types {
+                self.path.push(idx as i32);
+                self.append_message(nested_type)?;
+                self.path.pop();
+            }
+            self.path.pop();
+
+            // DescriptorProto.enum_type
+            self.path.push(4);
+            for (idx, nested_enum) in message.enum_type.into_iter().enumerate() {
+                self.path.push(idx as i32);
+                self.append_enum(nested_enum);
+                self.path.pop();
+            }
+            self.path.pop();
+
+            for (idx, oneof) in message.oneof_decl.into_iter().enumerate() {
+                let idx = idx as i32;
+                let fields = oneof_fields.remove(&idx).unwrap_or_default();
+                self.append_oneof(&fq_message_name, oneof, idx, fields);
+            }
+
+            self.pop_mod();
+        }
+
+        Ok(())
+    }
+
+    fn append_field(&mut self, msg_name: &str, field: FieldDescriptorProto) -> io::Result<()> {
+        let type_ = field.r#type();
+        let repeated = field.label() == Label::Repeated;
+        let optional = self.optional(&field);
+        let ty = self.resolve_type(&field);
+
+        let boxed = !repeated
+            && (type_ == Type::Message || type_ == Type::Group)
+            && self.message_graph.is_nested(field.type_name(), msg_name);
+
+        self.append_doc();
+        self.push_indent();
+        self.buf.push_str("#[prost(");
+        let type_tag = self.field_type_tag(&field);
+        self.buf.push_str(&type_tag);
+
+        match field.label() {
+            Label::Optional => {
+                if optional {
+                    self.buf.push_str(", optional");
+                }
+            }
+            Label::Required => self.buf.push_str(", required"),
+            Label::Repeated => {
+                self.buf.push_str(", repeated");
+                let packed = field
+                    .options
+                    .as_ref()
+                    .map_or(self.syntax == Syntax::Proto3, |options| options.packed());
+                if can_pack(&field) && !packed {
+                    self.buf.push_str(", packed=\"false\"");
+                }
+            }
+        }
+
+        if boxed {
+            self.buf.push_str(", boxed");
+        }
+        self.buf.push_str(", tag=\"");
+        self.buf.push_str(&field.number().to_string());
+
+        if let Some(ref default) = field.default_value {
+            self.buf.push_str("\", default=\"");
+            if type_ == Type::Bytes {
+                self.buf.push_str("b\\\"");
+                for b in unescape_c_escape_string(default)? {
+                    self.buf.extend(
+                        ascii::escape_default(b).flat_map(|c| (c as char).escape_default()),
+                    );
+                }
+                self.buf.push_str("\\\"");
+            } else if type_ == Type::Enum {
+                // Field types are fully qualified, so the enum's name is
+                // their last segment.
+                let enum_type = field.type_name().split('.').next_back().unwrap_or("");
+                let enum_value = to_upper_camel(default);
+                let stripped = strip_enum_prefix(&to_upper_camel(enum_type), &enum_value);
+                self.buf.push_str(stripped);
+            } else {
+                // This is only correct if the protobuf escaping matches Rust
+                // escaping, as `prost-build` assumes.
+                self.buf.push_str(default);
+            }
+        }
+
+        self.buf.push_str("\")]\n");
+        self.push_indent();
+        self.buf.push_str("pub ");
+        self.buf.push_str(&to_snake(field.name()));
+        self.buf.push_str(": ");
+        if repeated {
+            self.buf.push_str("::std::vec::Vec<");
+        } else if optional {
+            self.buf.push_str("::std::option::Option<");
+        }
+        if boxed {
+            self.buf.push_str("::std::boxed::Box<");
+        }
+        self.buf.push_str(&ty);
+        if boxed {
+            self.buf.push('>');
+        }
+        if repeated || optional {
+            self.buf.push('>');
+        }
+        self.buf.push_str(",\n");
+
+        Ok(())
+    }
+
+    fn append_map_field(
+        &mut self,
+        field: FieldDescriptorProto,
+        key: &FieldDescriptorProto,
+        value: &FieldDescriptorProto,
+    ) {
+        let key_ty = self.resolve_type(key);
+        let value_ty = self.resolve_type(value);
+
+        self.append_doc();
+        self.push_indent();
+
+        let key_tag = self.field_type_tag(key);
+        let value_tag = self.map_value_type_tag(value);
+        self.buf.push_str(&format!(
+            "#[prost(map=\"{}, {}\", tag=\"{}\")]\n",
+            key_tag,
+            value_tag,
+            field.number()
+        ));
+        self.push_indent();
+        self.buf.push_str(&format!(
+            "pub {}: ::std::collections::HashMap<{}, {}>,\n",
+            to_snake(field.name()),
+            key_ty,
+            value_ty
+        ));
+    }
+
+    fn append_oneof_field(
+        &mut self,
+        message_name: &str,
+        oneof: &OneofDescriptorProto,
+        fields: &[(FieldDescriptorProto, usize)],
+    ) {
+        let name = format!(
+            "{}::{}",
+            to_snake(message_name),
+            to_upper_camel(oneof.name())
+        );
+        let tags = fields
+            .iter()
+            .map(|(field, _)| field.number().to_string())
+            .collect::<Vec<_>>();
+
+        self.append_doc();
+        self.push_indent();
+        self.buf.push_str(&format!(
+            "#[prost(oneof=\"{}\", tags=\"{}\")]\n",
+            name,
+            tags.join(", ")
+        ));
+        self.push_indent();
+        self.buf.push_str(&format!(
+            "pub {}: ::std::option::Option<{}>,\n",
+            to_snake(oneof.name()),
+            name
+        ));
+    }
+
+    fn append_oneof(
+        &mut self,
+        msg_name: &str,
+        oneof: OneofDescriptorProto,
+        idx: i32,
+        fields: Vec<(FieldDescriptorProto, usize)>,
+    ) {
+        // DescriptorProto.oneof_decl
+        self.path.push(8);
+        self.path.push(idx);
+        self.append_doc();
+        self.path.pop();
+        self.path.pop();
+
+        self.push_indent();
+        self.buf
+            .push_str("#[derive(Clone, PartialEq, ::prost::Oneof)]\n");
+        self.push_indent();
+        self.buf.push_str("pub enum ");
+        self.buf.push_str(&to_upper_camel(oneof.name()));
+        self.buf.push_str(" {\n");
+
+        // DescriptorProto.field
+        self.path.push(2);
+        self.depth += 1;
+        for (field, idx) in fields {
+            let type_ = field.r#type();
+
+            self.path.push(idx as i32);
+            self.append_doc();
+            self.path.pop();
+
+            self.push_indent();
+            let ty_tag = self.field_type_tag(&field);
+            self.buf.push_str(&format!(
+                "#[prost({}, tag=\"{}\")]\n",
+                ty_tag,
+                field.number()
+            ));
+
+            self.push_indent();
+            let ty = self.resolve_type(&field);
+
+            let boxed = (type_ == Type::Message || type_ == Type::Group)
+                && self.message_graph.is_nested(field.type_name(), msg_name);
+
+            if boxed {
+                self.buf
+                    .push_str(&format!("{}(Box<{}>),\n", to_upper_camel(field.name()), ty));
+            } else {
+                self.buf
+                    .push_str(&format!("{}({}),\n", to_upper_camel(field.name()), ty));
+            }
+        }
+        self.depth -= 1;
+        self.path.pop();
+
+        self.push_indent();
+        self.buf.push_str("}\n");
+    }
+
+    /// Returns the comments on the item at the current path.
+    fn comments(&self) -> Comments {
+        let location = self
+            .locations
+            .binary_search_by(|location| location.path[..].cmp(&self.path[..]))
+            .ok()
+            .map(|idx| &self.locations[idx]);
+
+        let lines = |comments: &str| comments.lines().map(str::to_owned).collect::<Vec<_>>();
+
+        match location {
+            Some(location) => Comments {
+                leading_detached: location
+                    .leading_detached_comments
+                    .iter()
+                    .map(|comments| lines(comments))
+                    .collect(),
+                leading: location
+                    .leading_comments
+                    .as_ref()
+                    .map_or_else(Vec::new, |comments| lines(comments)),
+                trailing: location
+                    .trailing_comments
+                    .as_ref()
+                    .map_or_else(Vec::new, |comments| lines(comments)),
+            },
+            None => Comments {
+                leading_detached: Vec::new(),
+                leading: Vec::new(),
+                trailing: Vec::new(),
+            },
+        }
+    }
+
+    fn append_doc(&mut self) {
+        let comments = self.comments();
+        let indent = "    ".repeat(self.depth as usize);
+
+        for block in &comments.leading_detached {
+            for line in block {
+                self.buf.push_str(&format!("{}//{}\n", indent, line));
+            }
+            self.buf.push('\n');
+        }
+
+        for line in &comments.leading {
+            self.buf.push_str(&format!("{}///{}\n", indent, line));
+        }
+
+        if !comments.leading.is_empty() && !comments.trailing.is_empty() {
+            self.buf.push_str(&format!("{}///\n", indent));
+        }
+
+        for line in &comments.trailing {
+            self.buf.push_str(&format!("{}///{}\n", indent, line));
+        }
+    }
+
+    fn append_enum(&mut self, desc: EnumDescriptorProto) {
+        let enum_name = desc.name();
+        let fq_enum_name = format!(".{}.{}", self.package, enum_name);
+
+        // Skip external types.
+        if self.extern_paths.resolve_ident(&fq_enum_name).is_some() {
+            return;
+        }
+
+        self.append_doc();
+        self.push_indent();
+        self.buf.push_str(
+            "#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]\n",
+        );
+        self.push_indent();
+        self.buf.push_str("#[repr(i32)]\n");
+        self.push_indent();
+        self.buf.push_str("pub enum ");
+        self.buf.push_str(&to_upper_camel(enum_name));
+        self.buf.push_str(" {\n");
+
+        let prefix = to_upper_camel(enum_name);
+        let mut numbers = HashSet::new();
+
+        // EnumDescriptorProto.value
+        self.depth += 1;
+        self.path.push(2);
+        for (idx, value) in desc.value.iter().enumerate() {
+            // Skip duplicate enum values, which protobuf allows with the
+            // `allow_alias` option.
+            if !numbers.insert(value.number()) {
+                continue;
+            }
+
+            self.path.push(idx as i32);
+            self.append_enum_value(value, &prefix);
+            self.path.pop();
+        }
+        self.path.pop();
+        self.depth -= 1;
+
+        self.push_indent();
+        self.buf.push_str("}\n");
+    }
+
+    fn append_enum_value(&mut self, value: &EnumValueDescriptorProto, prefix: &str) {
+        self.append_doc();
+        self.push_indent();
+        let name = to_upper_camel(value.name());
+        self.buf.push_str(strip_enum_prefix(prefix, &name));
+        self.buf.push_str(" = ");
+        self.buf.push_str(&value.number().to_string());
+        self.buf.push_str(",\n");
+    }
+
+    fn push_service(&mut self, service: ServiceDescriptorProto) {
+        let name = service.name().to_owned();
+        let comments = self.comments();
+
+        // ServiceDescriptorProto.method
+        self.path.push(2);
+        let mut methods = Vec::new();
+        for (idx, method) in service.method.into_iter().enumerate() {
+            self.path.push(idx as i32);
+            let comments = self.comments();
+            self.path.pop();
+
+            let name = method.name().to_owned();
+            let input_proto_type = method.input_type().to_owned();
+            let output_proto_type = method.output_type().to_owned();
+
+            methods.push(Method {
+                name: to_snake(&name),
+                proto_name: name,
+                comments,
+                input_type: self.resolve_ident(&input_proto_type),
+                output_type: self.resolve_ident(&output_proto_type),
+                input_proto_type,
+                output_proto_type,
+                client_streaming: method.client_streaming(),
+                server_streaming: method.server_streaming(),
+                options: method.options.unwrap_or_default(),
+            });
+        }
+        self.path.pop();
+
+        let service = Service {
+            name: to_upper_camel(&name),
+            proto_name: name,
+            package: self.package.clone(),
+            comments,
+            methods,
+            options: service.options.unwrap_or_default(),
+        };
+
+        self.service_generator.generate(service, self.buf);
+    }
+
+    fn push_indent(&mut self) {
+        for _ in 0..self.depth {
+            self.buf.push_str("    ");
+        }
+    }
+
+    fn push_mod(&mut self, module: &str) {
+        self.push_indent();
+        self.buf.push_str("pub mod ");
+        self.buf.push_str(&to_snake(module));
+        self.buf.push_str(" {\n");
+
+        self.package.push('.');
+        self.package.push_str(module);
+
+        self.depth += 1;
+    }
+
+    fn pop_mod(&mut self) {
+        self.depth -= 1;
+
+        let idx = self.package.rfind('.').expect("nested module");
+        self.package.truncate(idx);
+
+        self.push_indent();
+        self.buf.push_str("}\n");
+    }
+
+    fn resolve_type(&self, field: &FieldDescriptorProto) -> String {
+        match field.r#type() {
+            Type::Float => String::from("f32"),
+            Type::Double => String::from("f64"),
+            Type::Uint32 | Type::Fixed32 => String::from("u32"),
+            Type::Uint64 | Type::Fixed64 => String::from("u64"),
+            Type::Int32 | Type::Sfixed32 | Type::Sint32 | Type::Enum => String::from("i32"),
+            Type::Int64 | Type::Sfixed64 | Type::Sint64 => String::from("i64"),
+            Type::Bool => String::from("bool"),
+            Type::String => String::from("std::string::String"),
+            Type::Bytes => String::from("std::vec::Vec<u8>"),
+            Type::Group | Type::Message => self.resolve_ident(field.type_name()),
+        }
+    }
+
+    /// Returns the Rust path of the fully qualified protobuf type `pb_ident`,
+    /// relative to the current module.
+    fn resolve_ident(&self, pb_ident: &str) -> String {
+        if let Some(rust_path) = self.extern_paths.resolve_ident(pb_ident) {
+            return rust_path;
+        }
+
+        let mut local_path = self.package.split('.').peekable();
+
+        let mut ident_path = pb_ident.trim_start_matches('.').split('.');
+        let ident_type = ident_path.next_back().unwrap_or("");
+        let mut ident_path = ident_path.peekable();
+
+        // Skip path elements in common.
+        while local_path.peek().is_some() && local_path.peek() == ident_path.peek() {
+            local_path.next();
+            ident_path.next();
+        }
+
+        local_path
+            .map(|_| "super".to_string())
+            .chain(ident_path.map(to_snake))
+            .chain(iter::once(to_upper_camel(ident_type)))
+            .collect::<Vec<_>>()
+            .join("::")
+    }
+
+    fn field_type_tag(&self, field: &FieldDescriptorProto) -> String {
+        let tag = match field.r#type() {
+            Type::Float => "float",
+            Type::Double => "double",
+            Type::Int32 => "int32",
+            Type::Int64 => "int64",
+            Type::Uint32 => "uint32",
+            Type::Uint64 => "uint64",
+            Type::Sint32 => "sint32",
+            Type::Sint64 => "sint64",
+            Type::Fixed32 => "fixed32",
+            Type::Fixed64 => "fixed64",
+            Type::Sfixed32 => "sfixed32",
+            Type::Sfixed64 => "sfixed64",
+            Type::Bool => "bool",
+            Type::String => "string",
+            Type::Bytes => "bytes",
+            Type::Group => "group",
+            Type::Message => "message",
+            Type::Enum => {
+                return format!("enumeration={:?}", self.resolve_ident(field.type_name()));
+            }
+        };
+
+        tag.to_string()
+    }
+
+    fn map_value_type_tag(&self, field: &FieldDescriptorProto) -> String {
+        match field.r#type() {
+            Type::Enum => format!("enumeration({})", self.resolve_ident(field.type_name())),
+            _ => self.field_type_tag(field),
+        }
+    }
+
+    fn optional(&self, field: &FieldDescriptorProto) -> bool {
+        if field.label() != Label::Optional {
+            return false;
+        }
+
+        match field.r#type() {
+            Type::Message => true,
+            _ => self.syntax == Syntax::Proto2,
+        }
+    }
+}
+
+/// Converts a `camelCase` or `SCREAMING_SNAKE_CASE` identifier to a
+/// `lower_snake` case Rust field identifier, as `prost-build` does.
+fn to_snake(s: &str) -> String {
+    let mut ident = s.to_snake_case();
+
+    // Use a raw identifier if the identifier matches a Rust keyword.
+    match ident.as_str() {
+        // 2015 strict keywords.
+        | "as" | "break" | "const" | "continue" | "else" | "enum" | "false"
+        | "fn" | "for" | "if" | "impl" | "in" | "let" | "loop" | "match" | "mod" | "move" | "mut"
+        | "pub" | "ref" | "return" | "static" | "struct" | "trait" | "true"
+        | "type" | "unsafe" | "use" | "where" | "while"
+        // 2018 strict keywords.
+        | "dyn"
+        // 2015 reserved keywords.
+        | "abstract" | "become" | "box" | "do" | "final" | "macro" | "override" | "priv" | "typeof"
+        | "unsized" | "virtual" | "yield"
+        // 2018 reserved keywords.
+        | "async" | "await" | "try" => ident.insert_str(0, "r#"),
+        // These keywords cannot be raw identifiers, so are suffixed instead.
+        "self" | "super" | "extern" | "crate" => ident.push('_'),
+        _ => {}
+    }
+
+    ident
+}
+
+/// Returns whether the values of a repeated field can be packed.
+fn can_pack(field: &FieldDescriptorProto) -> bool {
+    match field.r#type() {
+        Type::Float
+        | Type::Double
+        | Type::Int32
+        | Type::Int64
+        | Type::Uint32
+        | Type::Uint64
+        | Type::Sint32
+        | Type::Sint64
+        | Type::Fixed32
+        | Type::Fixed64
+        | Type::Sfixed32
+        | Type::Sfixed64
+        | Type::Bool
+        | Type::Enum => true,
+        _ => false,
+    }
+}
+
+/// Unescapes the default value of a `bytes` field, as protobuf's
+/// `UnescapeCEscapeString` does.
+fn unescape_c_escape_string(s: &str) -> io::Result<Vec<u8>> {
+    let invalid = |reason| {
+        invalid_data(format!(
+            "invalid c-escaped default binary value ({}): {}",
+            s, reason
+        ))
+    };
+
+    let src = s.as_bytes();
+    let len = src.len();
+    let mut dst = Vec::new();
+
+    let mut p = 0;
+
+    while p < len {
+        if src[p] != b'\\' {
+            dst.push(src[p]);
+            p += 1;
+            continue;
+        }
+
+        p += 1;
+        if p == len {
+            return Err(invalid("ends with '\\'"));
+        }
+
+        let unescaped = match src[p] {
+            b'a' => 0x07,
+            b'b' => 0x08,
+            b'f' => 0x0C,
+            b'n' => 0x0A,
+            b'r' => 0x0D,
+            b't' => 0x09,
+            b'v' => 0x0B,
+            b'\\' => 0x5C,
+            b'?' => 0x3F,
+            b'\'' => 0x27,
+            b'"' => 0x22,
+            b'0'..=b'7' => {
+                let mut octal = 0u8;
+                for _ in 0..3 {
+                    if p < len && src[p] >= b'0' && src[p] <= b'7' {
+                        octal = octal.wrapping_mul(8).wrapping_add(src[p] - b'0');
+                        p += 1;
+                    } else {
+                        break;
+                    }
+                }
+                dst.push(octal);
+                continue;
+            }
+            b'x' | b'X' => {
+                let hex = src
+                    .get(p + 1..p + 3)
+                    .and_then(|hex| std::str::from_utf8(hex).ok())
+                    .ok_or_else(|| invalid("incomplete hex value"))?;
+                let b = u8::from_str_radix(hex, 16).map_err(|_| invalid("invalid hex value"))?;
+                dst.push(b);
+                p += 3;
+                continue;
+            }
+            _ => return Err(invalid("invalid escape")),
+        };
+
+        dst.push(unescaped);
+        p += 1;
+    }
+
+    Ok(dst)
+}
+
+/// Strips an enum's name from the start of the name of one of its values,
+/// both in upper camel case, unless the rest would not start a name.
+fn strip_enum_prefix<'a>(prefix: &str, name: &'a str) -> &'a str {
+    let stripped = if name.starts_with(prefix) {
+        &name[prefix.len()..]
+    } else {
+        name
+    };
+
+    // "Foo" is not a prefix of "Foobar".
+    if stripped
+        .chars()
+        .next()
+        .map(char::is_uppercase)
+        .unwrap_or(false)
+    {
+        stripped
+    } else {
+        name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prost::Message;
+    use prost_types::FileDescriptorSet;
+
+    // testdata/messages.bin is the output of `protoc --include_imports
+    // --include_source_info -o messages.bin messages.proto proto3.proto` in
+    // testdata, and the expected code is the code `prost-build` generates
+    // for the same files, with `Services`.
+    const DESCRIPTOR_SET: &[u8] = include_bytes!("testdata/messages.bin");
+
+    /// Records the services it generates as comments.
+    struct Services;
+
+    impl ServiceGenerator for Services {
+        fn generate(&mut self, service: Service, buf: &mut String) {
+            buf.push_str(&format!(
+                "// service {} ({}.{}) {:?}\n",
+                service.name, service.package, service.proto_name, service.comments.leading
+            ));
+
+            for method in service.methods {
+                buf.push_str(&format!(
+                    "// method {} ({}) {} -> {} ({} -> {}) {} -> {} {:?} {:?} {:?}\n",
+                    method.name,
+                    method.proto_name,
+                    method.input_type,
+                    method.output_type,
+                    method.input_proto_type,
+                    method.output_proto_type,
+                    method.client_streaming,
+                    method.server_streaming,
+                    method.options.deprecated,
+                    method.options.idempotency_level,
+                    method.comments.leading,
+                ));
+            }
+        }
+
+        fn finalize(&mut self, buf: &mut String) {
+            buf.push_str("// finalize\n");
+        }
+    }
+
+    fn files() -> Vec<FileDescriptorProto> {
+        FileDescriptorSet::decode(DESCRIPTOR_SET)
+            .expect("decode")
+            .file
+    }
+
+    fn tree(files: &mut [FileDescriptorProto]) -> &mut DescriptorProto {
+        files
+            .iter_mut()
+            .find(|file| file.name() == "messages.proto")
+            .and_then(|file| file.message_type.iter_mut().find(|m| m.name() == "Tree"))
+            .expect("Tree")
+    }
+
+    fn names(modules: &[(String, String)]) -> Vec<&str> {
+        modules.iter().map(|(name, _)| name.as_str()).collect()
+    }
+
+    #[test]
+    fn generates_messages_as_prost_build() {
+        let modules = generate(files(), &[], &mut Services).unwrap();
+
+        // The well-known types are not generated.
+        assert_eq!(names(&modules), ["messages.proto3.rs", "messages.rs"]);
+        assert_eq!(modules[0].1, include_str!("testdata/messages.proto3.rs"));
+        assert_eq!(modules[1].1, include_str!("testdata/messages.rs"));
+    }
+
+    #[test]
+    fn skips_extern_packages() {
+        let extern_paths = [(
+            ".messages.proto3".to_string(),
+            "::other::proto3".to_string(),
+        )];
+        let modules = generate(files(), &extern_paths, &mut Services).unwrap();
+
+        assert_eq!(names(&modules), ["messages.rs"]);
+        assert_eq!(modules[0].1, include_str!("testdata/messages.rs"));
+    }
+
+    #[test]
+    fn rejects_files_without_a_package() {
+        let mut files = files();
+        for file in &mut files {
+            if file.name() == "proto3.proto" {
+                file.package = None;
+            }
+        }
+
+        let err = generate(files, &[], &mut Services).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn rejects_invalid_bytes_defaults() {
+        for default in &["\\", "\\x7", "\\xzz", "\\q"] {
+            let mut files = files();
+            for field in &mut tree(&mut files).field {
+                if field.name() == "blob" {
+                    field.default_value = Some(default.to_string());
+                }
+            }
+
+            let err = generate(files, &[], &mut Services).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{:?}", default);
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_map_entries() {
+        let mut files = files();
+        for nested in &mut tree(&mut files).nested_type {
+            if nested.name() == "KindsEntry" {
+                nested.field.pop();
+            }
+        }
+
+        let err = generate(files, &[], &mut Services).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn unescapes_bytes_defaults() {
+        assert_eq!(
+            unescape_c_escape_string("a\\001\\x7f\\\"\\n").unwrap(),
+            b"a\x01\x7f\"\n"
+        );
+    }
+}
@@ -0,0 +1,31 @@
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Message {
+    #[prost(int32, repeated, tag="1")]
+    pub packed: ::std::vec::Vec<i32>,
+    #[prost(int32, repeated, packed="false", tag="2")]
+    pub unpacked: ::std::vec::Vec<i32>,
+    #[prost(message, optional, tag="3")]
+    pub tree: ::std::option::Option<super::Tree>,
+    #[prost(enumeration="Level", tag="6")]
+    pub level: i32,
+    #[prost(map="string, bytes", tag="7")]
+    pub blobs: ::std::collections::HashMap<std::string::String, std::vec::Vec<u8>>,
+    #[prost(oneof="message::Value", tags="4, 5")]
+    pub value: ::std::option::Option<message::Value>,
+}
+pub mod message {
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    pub enum Value {
+        #[prost(string, tag="4")]
+        Text(std::string::String),
+        #[prost(enumeration="super::Level", tag="5")]
+        Priority(i32),
+    }
+}
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum Level {
+    Low = 0,
+    High = 1,
+}
+// finalize
@@ -0,0 +1,94 @@
+// A detached comment.
+
+/// A tree, which is recursively nested.
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Tree {
+    /// Boxed, as it is recursive.
+    ///
+    /// A trailing comment.
+    #[prost(message, optional, boxed, tag="1")]
+    pub left: ::std::option::Option<::std::boxed::Box<Tree>>,
+    #[prost(message, repeated, tag="2")]
+    pub children: ::std::vec::Vec<Tree>,
+    #[prost(bytes, optional, tag="3", default="b\"a\\x01\\x7f\\\"b\"")]
+    pub blob: ::std::option::Option<std::vec::Vec<u8>>,
+    #[prost(enumeration="tree::Kind", optional, tag="4", default="Leaf")]
+    pub kind: ::std::option::Option<i32>,
+    #[prost(string, optional, tag="5", default="oak")]
+    pub r#type: ::std::option::Option<std::string::String>,
+    #[prost(map="string, enumeration(tree::Kind)", tag="6")]
+    pub kinds: ::std::collections::HashMap<std::string::String, i32>,
+    #[prost(map="int32, message", tag="7")]
+    pub trees: ::std::collections::HashMap<i32, Tree>,
+    #[prost(int32, repeated, tag="8")]
+    pub packed: ::std::vec::Vec<i32>,
+    #[prost(int32, repeated, packed="false", tag="9")]
+    pub unpacked: ::std::vec::Vec<i32>,
+    #[prost(double, required, tag="10", default="1.5")]
+    pub self_: f64,
+    #[prost(message, optional, tag="14")]
+    pub height: ::std::option::Option<i32>,
+    #[prost(message, optional, boxed, tag="15")]
+    pub inner: ::std::option::Option<::std::boxed::Box<tree::Inner>>,
+    #[prost(group, optional, tag="16")]
+    pub grp: ::std::option::Option<tree::Grp>,
+    /// A oneof.
+    #[prost(oneof="tree::Choice", tags="11, 12, 13")]
+    pub choice: ::std::option::Option<tree::Choice>,
+}
+pub mod tree {
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct Inner {
+        #[prost(message, optional, boxed, tag="1")]
+        pub back: ::std::option::Option<::std::boxed::Box<super::Tree>>,
+        #[prost(message, optional, boxed, tag="2")]
+        pub deep: ::std::option::Option<::std::boxed::Box<super::outer::Deep>>,
+    }
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct Grp {
+        #[prost(int32, optional, tag="17")]
+        pub a: ::std::option::Option<i32>,
+    }
+    /// The kind of a tree.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+    #[repr(i32)]
+    pub enum Kind {
+        Unknown = 0,
+        /// A leaf.
+        Leaf = 1,
+        Branch = 2,
+        Other = 3,
+        Kindless = 4,
+    }
+    /// A oneof.
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    pub enum Choice {
+        /// A recursive oneof field.
+        #[prost(message, tag="11")]
+        Sub(Box<super::Tree>),
+        #[prost(int64, tag="12")]
+        Num(i64),
+        #[prost(message, tag="13")]
+        Planted(::prost_types::Timestamp),
+    }
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Outer {
+}
+pub mod outer {
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct Deep {
+        #[prost(message, optional, boxed, tag="1")]
+        pub tree: ::std::option::Option<::std::boxed::Box<super::Tree>>,
+    }
+}
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum Alias {
+    A = 0,
+    C = 1,
+}
+// service Forest (messages.Forest) [" A service."]
+// method plant (Plant) Tree -> () (.messages.Tree -> .google.protobuf.Empty) false -> false Some(true) Some(2) [" Plants a tree."]
+// method grow (Grow) outer::Deep -> ::prost_types::Timestamp (.messages.Outer.Deep -> .google.protobuf.Timestamp) true -> true None None []
+// finalize
@@ -2,23 +2,49 @@
 #![deny(rust_2018_idioms)]
 #![cfg_attr(test, deny(warnings))]
 
+mod blocking;
 mod client;
+mod descriptor_set;
+mod generate;
+mod mock;
+pub mod plugin;
 mod server;
 
 use heck::CamelCase;
+use prost::Message;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
 use std::io;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 /// Code generation configuration
 pub struct Config {
     prost: prost_build::Config,
+    custom_prost: bool,
     build_client: bool,
+    build_blocking_client: bool,
     build_server: bool,
+    build_mocks: bool,
+    box_services: bool,
+    ergonomic_clients: bool,
+    codecs: Codecs,
+    extern_paths: Vec<(String, String)>,
+    out_dir: Option<PathBuf>,
+}
+
+/// The custom codecs used by generated code, rather than the protobuf codec
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Codecs {
+    default: Option<String>,
+    services: HashMap<String, String>,
 }
 
 struct ServiceGenerator {
     client: Option<client::ServiceGenerator>,
+    blocking: Option<blocking::ServiceGenerator>,
     server: Option<server::ServiceGenerator>,
+    mock: Option<mock::ServiceGenerator>,
     root_scope: codegen::Scope,
 }
 
@@ -29,17 +55,40 @@ impl Config {
     pub fn from_prost(prost: prost_build::Config) -> Self {
         Config {
             prost,
+            custom_prost: true,
             // Enable client code gen by default
             build_client: true,
 
+            // Disable blocking client code gen by default
+            build_blocking_client: false,
+
             // Disable server code gen by default
             build_server: false,
+
+            // Disable mock code gen by default
+            build_mocks: false,
+
+            // Generate an associated type per future and stream by default
+            box_services: false,
+
+            // Generate clients taking `grpc::Request`s by default
+            ergonomic_clients: false,
+
+            // Use the protobuf codec by default
+            codecs: Codecs::default(),
+
+            // Kept for generating from descriptor sets, without `prost`
+            extern_paths: Vec::new(),
+            out_dir: None,
         }
     }
 
     /// Returns a new `Config` with default values.
     pub fn new() -> Self {
-        Self::from_prost(prost_build::Config::new())
+        Config {
+            custom_prost: false,
+            ..Self::from_prost(prost_build::Config::new())
+        }
     }
 
     /// Enable gRPC client code generation
@@ -48,36 +97,254 @@ impl Config {
         self
     }
 
+    /// Enable generating a blocking `client::blocking::Foo` for each client
+    ///
+    /// Blocking clients wrap the generated client, running its calls on a
+    /// `tower_grpc::client::blocking::Runtime`, which requires `tower-grpc`'s
+    /// `blocking` feature. Streamed requests are taken from iterators, and
+    /// streamed responses are returned as iterators. Enabling blocking
+    /// clients also enables client code generation.
+    pub fn enable_blocking_client(&mut self, enable: bool) -> &mut Self {
+        self.build_blocking_client = enable;
+        self
+    }
+
     /// Enable gRPC server code generation
     pub fn enable_server(&mut self, enable: bool) -> &mut Self {
         self.build_server = enable;
         self
     }
 
+    /// Enable generating a `MockFoo` for each service trait `server::Foo`
+    ///
+    /// Mocks respond to each method as programmed, and record the requests
+    /// they receive, for testing clients or code built on a service trait.
+    /// As mocks implement the service traits, enabling them also enables
+    /// server code generation.
+    pub fn build_mocks(&mut self, enable: bool) -> &mut Self {
+        self.build_mocks = enable;
+        self
+    }
+
+    /// Generate service traits whose methods return boxed futures and streams
+    ///
+    /// Rather than declaring an associated `XFuture` type for each method,
+    /// and an `XStream` type for each server streaming method, the methods of
+    /// the generated `server::Foo` traits return a
+    /// `tower_grpc::server::BoxFuture`, resolving to a
+    /// `tower_grpc::server::BoxStream` for server streaming methods. This
+    /// costs an allocation per call, but makes services with many methods
+    /// simpler to implement.
+    pub fn box_services(&mut self, enable: bool) -> &mut Self {
+        self.box_services = enable;
+        self
+    }
+
+    /// Generate clients taking any `IntoRequest`, and returning named futures
+    ///
+    /// The methods of generated clients take a `tower_grpc::IntoRequest`,
+    /// such as a bare message, rather than a `tower_grpc::Request`. Client
+    /// streaming methods take a `tower_grpc::IntoStreamingRequest`, such as a
+    /// `Stream` or any `IntoIterator` of messages. Methods return a
+    /// `Call<Message, T>` future, such as
+    /// `tower_grpc::client::unary::Call<HelloReply, T>`, for a client on
+    /// the transport `T`, which must accept `tower_grpc::BoxBody` requests.
+    pub fn ergonomic_clients(&mut self, enable: bool) -> &mut Self {
+        self.ergonomic_clients = enable;
+        self
+    }
+
+    /// Reference the types of the protobuf package `proto_path` at the Rust
+    /// path `rust_path`, rather than generating them
+    ///
+    /// This is `prost_build::Config::extern_path`, for services whose
+    /// messages are compiled in another crate: with
+    /// `.extern_path(".common", "::common_protos::common")`, generated
+    /// clients and servers use `::common_protos::common::Msg` for the
+    /// `common.Msg` message. `rust_path` must be an absolute path. The
+    /// well-known types of `google.protobuf` are already mapped to
+    /// `prost-types`.
+    pub fn extern_path<P1, P2>(&mut self, proto_path: P1, rust_path: P2) -> &mut Self
+    where
+        P1: Into<String>,
+        P2: Into<String>,
+    {
+        let proto_path = proto_path.into();
+        let rust_path = rust_path.into();
+
+        self.prost
+            .extern_path(proto_path.clone(), rust_path.clone());
+        self.extern_paths.push((proto_path, rust_path));
+        self
+    }
+
+    /// Use a custom codec for all services
+    ///
+    /// `path` is the absolute path of a type implementing
+    /// `tower_grpc::generic::Codec` and `Default`, generic over the message
+    /// type it encodes and the one it decodes, such as
+    /// `crate::codec::JsonCodec`. Servers use a
+    /// `JsonCodec<Response, Request>` and clients a
+    /// `JsonCodec<Request, Response>` for each method.
+    pub fn codec(&mut self, path: &str) -> &mut Self {
+        self.codecs.default = Some(path.to_string());
+        self
+    }
+
+    /// Use a custom codec for a service, overriding `Config::codec`
+    ///
+    /// `service` is the fully qualified name of the service, e.g.
+    /// `helloworld.Greeter`.
+    pub fn service_codec(&mut self, service: &str, path: &str) -> &mut Self {
+        self.codecs
+            .services
+            .insert(service.to_string(), path.to_string());
+        self
+    }
+
+    /// Write generated code to `path`, rather than to `OUT_DIR`
+    ///
+    /// A file is written for each protobuf package, named after the package.
+    pub fn out_dir<P>(&mut self, path: P) -> &mut Self
+    where
+        P: Into<PathBuf>,
+    {
+        let path = path.into();
+
+        self.prost.out_dir(path.clone());
+        self.out_dir = Some(path);
+        self
+    }
+
     /// Generate code
     pub fn build<P>(&mut self, protos: &[P], includes: &[P]) -> io::Result<()>
     where
         P: AsRef<Path>,
     {
-        let client = if self.build_client {
-            Some(client::ServiceGenerator)
+        // Set or reset the service generator.
+        let service_generator = self.service_generator(HashMap::new());
+        self.prost.service_generator(Box::new(service_generator));
+
+        self.prost.compile_protos(protos, includes)
+    }
+
+    /// Generate code from a precompiled `FileDescriptorSet`
+    ///
+    /// `descriptor_set` is the path of an encoded
+    /// `google.protobuf.FileDescriptorSet`, such as the output of
+    /// `protoc --include_imports --include_source_info -o` or `buf build -o`.
+    /// Code is generated for each file in the set, as `build` would for the
+    /// same files, without running `protoc`. Generated code only carries
+    /// comments if the set includes source info.
+    ///
+    /// Files of packages mapped with `extern_path`, including
+    /// `google.protobuf`, are not generated.
+    ///
+    /// Unlike `build`, the custom options of methods are kept, as the
+    /// `extensions` of their `MethodDescriptor`s.
+    ///
+    /// As `prost-build` only generates code for `.proto` files, messages are
+    /// generated here as `prost-build` generates them by default. This fails
+    /// for a `Config` created with `from_prost`, whose settings would not
+    /// apply.
+    pub fn build_from_descriptor_set<P>(&mut self, descriptor_set: P) -> io::Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        let buf = fs::read(descriptor_set)?;
+        let files = self.generate_descriptor_set(&buf)?;
+
+        let out_dir = match self.out_dir {
+            Some(ref out_dir) => out_dir.clone(),
+            None => env::var_os("OUT_DIR").map(PathBuf::from).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::Other,
+                    "OUT_DIR environment variable is not set",
+                )
+            })?,
+        };
+
+        for (name, content) in files {
+            fs::write(out_dir.join(name), content)?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the code generated for the encoded `FileDescriptorSet` `buf`,
+    /// by the name of the file it is written to.
+    pub(crate) fn generate_descriptor_set(&self, buf: &[u8]) -> io::Result<Vec<(String, String)>> {
+        if self.custom_prost {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "a prost_build::Config cannot be applied to a descriptor set",
+            ));
+        }
+
+        // `prost` discards the custom options of methods when decoding the
+        // set, so they are read from the encoded set.
+        let extensions = descriptor_set::method_extensions(buf)?;
+        let descriptor_set = prost_types::FileDescriptorSet::decode(buf).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid file descriptor set: {}", e),
+            )
+        })?;
+
+        let mut service_generator = self.service_generator(extensions);
+        generate::generate(
+            descriptor_set.file,
+            &self.extern_paths,
+            &mut service_generator,
+        )
+    }
+
+    /// Returns a service generator for this configuration.
+    ///
+    /// `extensions` are the encoded custom options of methods, by path.
+    fn service_generator(&self, extensions: HashMap<String, Vec<u8>>) -> ServiceGenerator {
+        let client = if self.build_client || self.build_blocking_client {
+            Some(client::ServiceGenerator {
+                codecs: self.codecs.clone(),
+                extensions: extensions.clone(),
+                ergonomic: self.ergonomic_clients,
+            })
         } else {
             None
         };
-        let server = if self.build_server {
-            Some(server::ServiceGenerator)
+        let blocking = if self.build_blocking_client {
+            Some(blocking::ServiceGenerator {
+                codecs: self.codecs.clone(),
+                ergonomic: self.ergonomic_clients,
+            })
+        } else {
+            None
+        };
+        let server = if self.build_server || self.build_mocks {
+            Some(server::ServiceGenerator {
+                boxed: self.box_services,
+                codecs: self.codecs.clone(),
+                extensions,
+            })
+        } else {
+            None
+        };
+        let mock = if self.build_mocks {
+            Some(mock::ServiceGenerator {
+                boxed: self.box_services,
+                codecs: self.codecs.clone(),
+            })
         } else {
             None
         };
 
-        // Set or reset the service generator.
-        self.prost.service_generator(Box::new(ServiceGenerator {
+        ServiceGenerator {
             client,
+            blocking,
             server,
+            mock,
             root_scope: codegen::Scope::new(),
-        }));
-
-        self.prost.compile_protos(protos, includes)
+        }
     }
 }
 
@@ -91,9 +358,15 @@ impl prost_build::ServiceGenerator for ServiceGenerator {
         if let Some(ref mut client_generator) = self.client {
             client_generator.generate(&service, &mut self.root_scope);
         }
+        if let Some(ref mut blocking_generator) = self.blocking {
+            blocking_generator.generate(&service, &mut self.root_scope);
+        }
         if let Some(ref mut server_generator) = self.server {
             server_generator.generate(&service, &mut self.root_scope);
         }
+        if let Some(ref mut mock_generator) = self.mock {
+            mock_generator.generate(&service, &mut self.root_scope);
+        }
     }
 
     fn finalize(&mut self, buf: &mut String) {
@@ -127,7 +400,7 @@ trait ImportType {
 
 impl ImportType for codegen::Scope {
     fn import_type(&mut self, ty: &str, level: usize) {
-        if !is_imported_type(ty) && !is_native_type(ty) {
+        if should_import(ty) {
             let (path, ty) = super_import(ty, level);
 
             self.import(&path, &ty);
@@ -141,13 +414,83 @@ impl ImportType for codegen::Module {
     }
 }
 
+// ===== impl Codecs =====
+
+impl Codecs {
+    /// Returns the path of the custom codec `service` uses, if any.
+    fn get(&self, service: &prost_build::Service) -> Option<&str> {
+        self.services
+            .get(&service_name(service))
+            .or_else(|| self.default.as_ref())
+            .map(String::as_str)
+    }
+}
+
 // ===== utility fns =====
 
+fn service_name(service: &prost_build::Service) -> String {
+    format!("{}.{}", service.package, service.proto_name)
+}
+
 fn method_path(service: &prost_build::Service, method: &prost_build::Method) -> String {
-    format!(
-        "\"/{}.{}/{}\"",
-        service.package, service.proto_name, method.proto_name
-    )
+    format!("\"/{}/{}\"", service_name(service), method.proto_name)
+}
+
+/// Formats the `MethodDescriptor`s of `service`, as the elements of a
+/// `&[grpc::MethodDescriptor]` indented by `indent`.
+///
+/// `extensions` are the encoded custom options of the methods, by path.
+fn method_descriptors(
+    service: &prost_build::Service,
+    extensions: &HashMap<String, Vec<u8>>,
+    indent: &str,
+) -> String {
+    let mut ret = String::new();
+
+    for method in &service.methods {
+        let idempotency_level = match method.options.idempotency_level.unwrap_or(0) {
+            1 => "NoSideEffects",
+            2 => "Idempotent",
+            _ => "Unknown",
+        };
+
+        let path = format!("/{}/{}", service_name(service), method.proto_name);
+        let extensions = match extensions.get(&path) {
+            Some(extensions) => format!("{:?}", extensions),
+            None => "[]".to_string(),
+        };
+
+        let kind = match (method.client_streaming, method.server_streaming) {
+            (false, false) => "Unary",
+            (false, true) => "ServerStreaming",
+            (true, false) => "ClientStreaming",
+            (true, true) => "Streaming",
+        };
+
+        ret.push_str(&format!(
+            "{i}grpc::MethodDescriptor {{\n\
+             {i}    name: \"{}\",\n\
+             {i}    path: {},\n\
+             {i}    input_type: \"{}\",\n\
+             {i}    output_type: \"{}\",\n\
+             {i}    kind: grpc::MethodKind::{},\n\
+             {i}    idempotency_level: grpc::IdempotencyLevel::{},\n\
+             {i}    deprecated: {},\n\
+             {i}    extensions: &{},\n\
+             {i}}},\n",
+            method.proto_name,
+            method_path(service, method),
+            method.input_proto_type.trim_start_matches('.'),
+            method.output_proto_type.trim_start_matches('.'),
+            kind,
+            idempotency_level,
+            method.options.deprecated.unwrap_or(false),
+            extensions,
+            i = indent,
+        ));
+    }
+
+    ret
 }
 
 fn lower_name(name: &str) -> String {
@@ -169,16 +512,24 @@ fn lower_name(name: &str) -> String {
 }
 
 fn should_import(ty: &str) -> bool {
-    !is_imported_type(ty) && !is_native_type(ty)
+    !is_imported_type(ty) && !is_native_type(ty) && !is_absolute_type(ty)
 }
 
 fn is_imported_type(ty: &str) -> bool {
     ty.split("::").map(|t| t == "super").next().unwrap()
 }
 
+/// Returns whether `ty` is an absolute path, such as the path of a type in
+/// another crate, mapped with `Config::extern_path`, which is used as is.
+fn is_absolute_type(ty: &str) -> bool {
+    ty.starts_with("::") || ty.starts_with("crate::")
+}
+
+/// Returns whether `ty` is a primitive type, such as the types `prost` maps
+/// `google.protobuf.Empty` and the scalar well-known wrapper types to.
 fn is_native_type(ty: &str) -> bool {
     match ty {
-        "()" => true,
+        "()" | "bool" | "i32" | "i64" | "u32" | "u64" | "f32" | "f64" => true,
         _ => false,
     }
 }
@@ -186,8 +537,7 @@ fn is_native_type(ty: &str) -> bool {
 fn super_import(ty: &str, level: usize) -> (String, String) {
     let mut v: Vec<&str> = ty.split("::").collect();
 
-    assert!(!is_imported_type(ty));
-    assert!(!is_native_type(ty));
+    assert!(should_import(ty));
 
     for _ in 0..level {
         v.insert(0, "super");
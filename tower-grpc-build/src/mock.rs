@@ -0,0 +1,192 @@
+/// Generates mock implementations of service traits
+pub struct ServiceGenerator {
+    /// Whether the service traits return boxed futures and streams
+    pub boxed: bool,
+    /// Custom codecs of the services
+    pub codecs: crate::Codecs,
+}
+
+// ===== impl ServiceGenerator =====
+
+impl ServiceGenerator {
+    /// Generate the mock, alongside the service trait in the server code
+    pub fn generate(&self, service: &prost_build::Service, scope: &mut codegen::Scope) {
+        let scope = scope.get_or_new_module("server").scope();
+        let name = format!("Mock{}", service.name);
+
+        self.define_mock_struct(service, &name, scope);
+        self.define_mock_impl(service, &name, scope);
+        self.define_service_impl(service, &name, scope);
+    }
+
+    fn define_mock_struct(
+        &self,
+        service: &prost_build::Service,
+        name: &str,
+        scope: &mut codegen::Scope,
+    ) {
+        let mock = scope
+            .new_struct(name)
+            .vis("pub")
+            .derive("Debug")
+            .derive("Clone")
+            .doc(&format!(
+                "A mock `{}`, responding to each method as programmed.\n\
+                 \n\
+                 Clones share their responses and recorded requests, so that a clone can\n\
+                 be served by a `{}Server` while the original is used to check calls.",
+                service.name, service.name
+            ));
+
+        for method in &service.methods {
+            mock.field(
+                &format!("pub {}", method.name),
+                &format!(
+                    "grpc::mock::Method<{}, {}>",
+                    request_type(method),
+                    response_type(method)
+                ),
+            );
+        }
+    }
+
+    fn define_mock_impl(
+        &self,
+        service: &prost_build::Service,
+        name: &str,
+        scope: &mut codegen::Scope,
+    ) {
+        let new = scope
+            .new_impl(name)
+            .new_fn("new")
+            .vis("pub")
+            .doc("Returns a mock with no programmed responses.")
+            .ret("Self");
+
+        let mut fields = codegen::Block::new(name);
+        for method in &service.methods {
+            fields.line(&format!(
+                "{}: grpc::mock::Method::new({}),",
+                method.name,
+                crate::method_path(service, method)
+            ));
+        }
+        new.push_block(fields);
+
+        scope
+            .new_impl(name)
+            .impl_trait("Default")
+            .new_fn("default")
+            .ret("Self")
+            .line("Self::new()");
+    }
+
+    fn define_service_impl(
+        &self,
+        service: &prost_build::Service,
+        name: &str,
+        scope: &mut codegen::Scope,
+    ) {
+        let imp = scope.new_impl(name).impl_trait(&service.name);
+
+        for method in &service.methods {
+            let upper_name = crate::to_upper_camel(&method.proto_name);
+            let output_type = crate::unqualified(&method.output_type, &method.output_proto_type, 1);
+
+            let handle = match (method.client_streaming, method.server_streaming) {
+                (false, false) => "unary",
+                (false, true) => "server_streaming",
+                (true, false) => "client_streaming",
+                (true, true) => "streaming",
+            };
+
+            let input_type = crate::unqualified(&method.input_type, &method.input_proto_type, 1);
+            let request_type = if method.client_streaming {
+                format!(
+                    "grpc::Request<{}>",
+                    crate::server::streaming_input_type(method, self.codecs.get(service), 1)
+                )
+            } else {
+                format!("grpc::Request<{}>", input_type)
+            };
+
+            let call = format!("self.{}.{}(request)", method.name, handle);
+
+            if self.boxed {
+                let future = if method.server_streaming {
+                    format!("grpc::BoxFuture<grpc::BoxStream<{}>>", output_type)
+                } else {
+                    format!("grpc::BoxFuture<{}>", output_type)
+                };
+
+                // The mock already boxes the futures of client streaming methods.
+                let call = if method.client_streaming {
+                    call
+                } else {
+                    format!("Box::new({})", call)
+                };
+
+                imp.new_fn(&method.name)
+                    .arg_mut_self()
+                    .arg("request", &request_type)
+                    .ret(&future)
+                    .line(&call);
+
+                continue;
+            }
+
+            let response = if method.server_streaming {
+                let stream_name = format!("{}Stream", upper_name);
+                imp.associate_type(
+                    &stream_name,
+                    &format!("grpc::mock::ResponseStream<{}>", output_type),
+                );
+
+                format!("Self::{}", stream_name)
+            } else {
+                output_type
+            };
+
+            let future = if method.client_streaming {
+                format!("grpc::mock::ResponseFuture<{}>", response)
+            } else {
+                format!(
+                    "futures::FutureResult<grpc::Response<{}>, grpc::Status>",
+                    response
+                )
+            };
+
+            imp.associate_type(&format!("{}Future", upper_name), &future);
+
+            imp.new_fn(&method.name)
+                .arg_mut_self()
+                .arg("request", &request_type)
+                .ret(&format!("Self::{}Future", upper_name))
+                .line(&call);
+        }
+    }
+}
+
+/// The request message type a mocked method records, with the messages of
+/// streamed requests collected.
+fn request_type(method: &prost_build::Method) -> String {
+    let input_type = crate::unqualified(&method.input_type, &method.input_proto_type, 1);
+
+    if method.client_streaming {
+        format!("Vec<{}>", input_type)
+    } else {
+        input_type
+    }
+}
+
+/// The response message type a mocked method is programmed with, with the
+/// messages of streamed responses collected.
+fn response_type(method: &prost_build::Method) -> String {
+    let output_type = crate::unqualified(&method.output_type, &method.output_proto_type, 1);
+
+    if method.server_streaming {
+        format!("Vec<{}>", output_type)
+    } else {
+        output_type
+    }
+}
@@ -0,0 +1,226 @@
+//! Support for running as a `protoc` plugin.
+//!
+//! `protoc` writes a `CodeGeneratorRequest` to the standard input of
+//! `protoc-gen-tower-grpc`, and reads back a `CodeGeneratorResponse` holding
+//! the generated files, one per protobuf package. Code is generated from the
+//! files to generate of the request, as `Config::build_from_descriptor_set`
+//! would.
+//!
+//! The plugin's parameter is a comma separated list of options:
+//!
+//! * `client`: generate clients.
+//! * `blocking_client`: generate blocking clients.
+//! * `server`: generate servers.
+//! * `mocks`: generate mocks of the service traits.
+//! * `box_services`: generate service traits returning boxed futures.
+//! * `ergonomic_clients`: generate clients taking any `IntoRequest`, and
+//!   returning named futures.
+//! * `codec=<path>`: use a custom codec for all services.
+//!
+//! For example, `protoc --tower-grpc_out=client,server:src/gen foo.proto`.
+
+use crate::descriptor_set::{encode_field, fields, string};
+use crate::Config;
+
+use std::io::{self, Read, Write};
+
+/// Reads a `CodeGeneratorRequest` from `input`, and writes the
+/// `CodeGeneratorResponse` to `output`.
+///
+/// Invalid parameters are reported to `protoc` in the response, other errors
+/// are returned.
+pub fn run<R, W>(mut input: R, mut output: W) -> io::Result<()>
+where
+    R: Read,
+    W: Write,
+{
+    let mut request = Vec::new();
+    input.read_to_end(&mut request)?;
+
+    let mut response = Vec::new();
+
+    match generate(&request)? {
+        Ok(files) => {
+            for (name, content) in files {
+                // CodeGeneratorResponse.File
+                let mut file = Vec::new();
+                encode_field(1, name.as_bytes(), &mut file);
+                encode_field(15, content.as_bytes(), &mut file);
+
+                // CodeGeneratorResponse.file
+                encode_field(15, &file, &mut response);
+            }
+        }
+        Err(error) => {
+            // CodeGeneratorResponse.error
+            encode_field(1, error.as_bytes(), &mut response);
+        }
+    }
+
+    output.write_all(&response)?;
+    output.flush()
+}
+
+/// Returns the generated files, by name, or an error to report to `protoc`.
+fn generate(request: &[u8]) -> io::Result<Result<Vec<(String, String)>, String>> {
+    // CodeGeneratorRequest.parameter
+    let parameter = match fields(request, 2)?.last() {
+        Some(parameter) => string(parameter)?,
+        None => "",
+    };
+
+    let config = match config(parameter) {
+        Ok(config) => config,
+        Err(error) => return Ok(Err(error)),
+    };
+
+    // CodeGeneratorRequest.file_to_generate
+    let mut to_generate = Vec::new();
+    for name in fields(request, 1)? {
+        to_generate.push(string(name)?);
+    }
+
+    // CodeGeneratorRequest.proto_file, as a FileDescriptorSet of the files
+    // to generate.
+    let mut descriptor_set = Vec::new();
+    for file in fields(request, 15)? {
+        // FileDescriptorProto.name
+        let name = fields(file, 1)?.last().map_or(Ok(""), |s| string(s))?;
+
+        if to_generate.contains(&name) {
+            encode_field(1, file, &mut descriptor_set);
+        }
+    }
+
+    config.generate_descriptor_set(&descriptor_set).map(Ok)
+}
+
+/// Returns the `Config` described by the plugin's parameter.
+fn config(parameter: &str) -> Result<Config, String> {
+    let mut config = Config::new();
+    config.enable_client(false);
+
+    for option in parameter.split(',').filter(|option| !option.is_empty()) {
+        match option {
+            "client" => config.enable_client(true),
+            "blocking_client" => config.enable_blocking_client(true),
+            "server" => config.enable_server(true),
+            "mocks" => config.build_mocks(true),
+            "box_services" => config.box_services(true),
+            "ergonomic_clients" => config.ergonomic_clients(true),
+            _ if option.starts_with("codec=") => config.codec(&option["codec=".len()..]),
+            _ => return Err(format!("unknown option `{}`", option)),
+        };
+    }
+
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prost::Message;
+    use prost_types::{
+        DescriptorProto, FileDescriptorProto, MethodDescriptorProto, ServiceDescriptorProto,
+    };
+
+    fn file(name: &str, package: &str) -> Vec<u8> {
+        let message = |name: &str| DescriptorProto {
+            name: Some(name.to_string()),
+            ..Default::default()
+        };
+
+        let file = FileDescriptorProto {
+            name: Some(name.to_string()),
+            package: Some(package.to_string()),
+            message_type: vec![message("HelloRequest"), message("HelloReply")],
+            service: vec![ServiceDescriptorProto {
+                name: Some("Greeter".to_string()),
+                method: vec![MethodDescriptorProto {
+                    name: Some("SayHello".to_string()),
+                    input_type: Some(format!(".{}.HelloRequest", package)),
+                    output_type: Some(format!(".{}.HelloReply", package)),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+            syntax: Some("proto3".to_string()),
+            ..Default::default()
+        };
+
+        let mut buf = Vec::new();
+        file.encode(&mut buf).expect("encode");
+        buf
+    }
+
+    /// Returns the response to a request to generate `hello.proto`, which
+    /// `protoc` sends along with `other.proto`.
+    fn respond(parameter: &str) -> Vec<u8> {
+        let mut request = Vec::new();
+        encode_field(1, b"hello.proto", &mut request);
+        encode_field(2, parameter.as_bytes(), &mut request);
+        encode_field(15, &file("other.proto", "other"), &mut request);
+        encode_field(15, &file("hello.proto", "hello"), &mut request);
+
+        let mut response = Vec::new();
+        run(&request[..], &mut response).unwrap();
+        response
+    }
+
+    /// Returns the files of `response`, by name.
+    fn files(response: &[u8]) -> Vec<(&str, &str)> {
+        fields(response, 15)
+            .unwrap()
+            .into_iter()
+            .map(|file| {
+                let name = fields(file, 1).unwrap()[0];
+                let content = fields(file, 15).unwrap()[0];
+                (string(name).unwrap(), string(content).unwrap())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn generates_files_to_generate() {
+        let response = respond("client,server");
+
+        assert!(fields(&response, 1).unwrap().is_empty());
+
+        let files = files(&response);
+        assert_eq!(files.len(), 1);
+
+        let (name, content) = files[0];
+        assert_eq!(name, "hello.rs");
+        assert!(content.contains("pub struct HelloRequest"));
+        assert!(content.contains("pub mod client"));
+        assert!(content.contains("pub mod server"));
+    }
+
+    #[test]
+    fn reports_unknown_options() {
+        let response = respond("client,bogus");
+
+        let error = fields(&response, 1).unwrap();
+        assert_eq!(error, [&b"unknown option `bogus`"[..]]);
+        assert!(files(&response).is_empty());
+    }
+
+    #[test]
+    fn parses_options() {
+        let none = config("").unwrap();
+        assert!(!none.build_client);
+        assert!(!none.build_server);
+
+        let all = config(
+            "client,blocking_client,server,mocks,box_services,ergonomic_clients,codec=crate::Json",
+        )
+        .unwrap();
+        assert!(all.build_client);
+        assert!(all.build_blocking_client);
+        assert!(all.build_server);
+        assert!(all.build_mocks);
+        assert!(all.box_services);
+        assert!(all.ergonomic_clients);
+        assert_eq!(all.codecs.default.as_ref().unwrap(), "crate::Json");
+    }
+}
@@ -1,8 +1,17 @@
 use super::ImportType;
 use crate::comments_to_rustdoc;
 
+use std::collections::HashMap;
+
 /// Generates service code
-pub struct ServiceGenerator;
+pub struct ServiceGenerator {
+    /// Whether service trait methods return boxed futures and streams
+    pub boxed: bool,
+    /// Custom codecs of the services
+    pub codecs: crate::Codecs,
+    /// Encoded custom options of the methods, by path
+    pub extensions: HashMap<String, Vec<u8>>,
+}
 
 impl ServiceGenerator {
     /// Generate the gRPC server code
@@ -56,7 +65,10 @@ macro_rules! try_ready {
             for method in &service.methods {
                 methods.import_type(&method.input_type, 2);
 
-                if !method.server_streaming {
+                if !method.server_streaming
+                    || self.boxed
+                    || (method.client_streaming && self.codecs.get(service).is_some())
+                {
                     methods.import_type(&method.output_type, 2);
                 }
 
@@ -75,35 +87,47 @@ macro_rules! try_ready {
         for method in &service.methods {
             let name = &method.name;
             let upper_name = crate::to_upper_camel(&method.proto_name);
-            let future_bound;
+            let output_type = crate::unqualified(&method.output_type, &method.output_proto_type, 1);
 
-            if method.server_streaming {
-                let stream_name = format!("{}Stream", &upper_name);
-                let stream_bound = format!(
-                    "futures::Stream<Item = {}, Error = grpc::Status>",
-                    crate::unqualified(&method.output_type, &method.output_proto_type, 1)
-                );
+            let future_type = if self.boxed {
+                if method.server_streaming {
+                    format!("grpc::BoxFuture<grpc::BoxStream<{}>>", output_type)
+                } else {
+                    format!("grpc::BoxFuture<{}>", output_type)
+                }
+            } else {
+                let future_bound;
+
+                if method.server_streaming {
+                    let stream_name = format!("{}Stream", &upper_name);
+                    let stream_bound = format!(
+                        "futures::Stream<Item = {}, Error = grpc::Status>",
+                        output_type
+                    );
+
+                    future_bound = format!(
+                        "futures::Future<Item = grpc::Response<Self::{}>, Error = grpc::Status>",
+                        stream_name
+                    );
+
+                    service_trait
+                        .associated_type(&stream_name)
+                        .bound(&stream_bound);
+                } else {
+                    future_bound = format!(
+                        "futures::Future<Item = grpc::Response<{}>, Error = grpc::Status>",
+                        output_type
+                    );
+                }
 
-                future_bound = format!(
-                    "futures::Future<Item = grpc::Response<Self::{}>, Error = grpc::Status>",
-                    stream_name
-                );
+                let future_name = format!("{}Future", &upper_name);
 
                 service_trait
-                    .associated_type(&stream_name)
-                    .bound(&stream_bound);
-            } else {
-                future_bound = format!(
-                    "futures::Future<Item = grpc::Response<{}>, Error = grpc::Status>",
-                    crate::unqualified(&method.output_type, &method.output_proto_type, 1)
-                );
-            }
+                    .associated_type(&future_name)
+                    .bound(&future_bound);
 
-            let future_name = format!("{}Future", &upper_name);
-
-            service_trait
-                .associated_type(&future_name)
-                .bound(&future_bound);
+                format!("Self::{}", future_name)
+            };
 
             for &ty in [&method.input_type, &method.output_type].iter() {
                 if crate::should_import(ty) {
@@ -116,7 +140,10 @@ macro_rules! try_ready {
             let input_type = crate::unqualified(&method.input_type, &method.input_proto_type, 1);
 
             let request_type = if method.client_streaming {
-                format!("grpc::Request<grpc::Streaming<{}>>", input_type)
+                format!(
+                    "grpc::Request<{}>",
+                    streaming_input_type(method, self.codecs.get(service), 1)
+                )
             } else {
                 format!("grpc::Request<{}>", input_type)
             };
@@ -125,7 +152,7 @@ macro_rules! try_ready {
                 .new_fn(&name)
                 .arg_mut_self()
                 .arg("request", &request_type)
-                .ret(&format!("Self::{}Future", &upper_name))
+                .ret(&future_type)
                 .doc(&comments_to_rustdoc(&method.comments));
         }
 
@@ -155,6 +182,15 @@ macro_rules! try_ready {
             .ret("Self")
             .line(format!("Self {{ {} }}", lower_name));
 
+        scope.raw(&format!(
+            "impl<T> grpc::NamedService for {}<T> {{\n    \
+             const NAME: &'static str = \"{}\";\n    \
+             const METHODS: &'static [grpc::MethodDescriptor] = &[\n{}    ];\n}}",
+            name,
+            crate::service_name(service),
+            crate::method_descriptors(service, &self.extensions, "        ")
+        ));
+
         let response_type = format!("http::Response<{}::ResponseBody<T>>", lower_name);
 
         // Implement service trait
@@ -188,6 +224,15 @@ macro_rules! try_ready {
             for method in &service.methods {
                 let upper_name = crate::to_upper_camel(&method.proto_name);
 
+                // Call the generic handlers with a custom codec.
+                let (handlers, codec) = match self.codecs.get(service) {
+                    Some(codec) => (
+                        "grpc::generic::server",
+                        format!("<{}>::default(), ", codec_type(codec, method, 1)),
+                    ),
+                    None => ("grpc", String::new()),
+                };
+
                 // The service method path.
                 let match_line = format!("{} =>", crate::method_path(service, method));
 
@@ -201,7 +246,10 @@ macro_rules! try_ready {
                             lower_name, &upper_name, lower_name
                         ));
 
-                        handle.line("let response = grpc::unary(service, request);");
+                        handle.line(&format!(
+                            "let response = {}::unary({}service, request);",
+                            handlers, codec
+                        ));
                     }
                     (false, true) => {
                         handle.line(&format!(
@@ -209,7 +257,10 @@ macro_rules! try_ready {
                             lower_name, &upper_name, lower_name
                         ));
 
-                        handle.line("let response = grpc::server_streaming(service, request);");
+                        handle.line(&format!(
+                            "let response = {}::server_streaming({}service, request);",
+                            handlers, codec
+                        ));
                     }
                     (true, false) => {
                         handle.line(&format!(
@@ -217,8 +268,10 @@ macro_rules! try_ready {
                             lower_name, &upper_name, lower_name
                         ));
 
-                        handle
-                            .line("let response = grpc::client_streaming(&mut service, request);");
+                        handle.line(&format!(
+                            "let response = {}::client_streaming({}&mut service, request);",
+                            handlers, codec
+                        ));
                     }
                     (true, true) => {
                         handle.line(&format!(
@@ -226,7 +279,10 @@ macro_rules! try_ready {
                             lower_name, &upper_name, lower_name
                         ));
 
-                        handle.line("let response = grpc::streaming(&mut service, request);");
+                        handle.line(&format!(
+                            "let response = {}::streaming({}&mut service, request);",
+                            handlers, codec
+                        ));
                     }
                 }
 
@@ -321,7 +377,10 @@ macro_rules! try_ready {
             .generic("T")
             .bound("T", &service.name)
             .vis("pub")
-            .field("pub(super) kind", response_fut_kind(service));
+            .field(
+                "pub(super) kind",
+                response_fut_kind(service, self.codecs.get(service)),
+            );
 
         module
             .new_impl("ResponseFuture")
@@ -380,6 +439,11 @@ macro_rules! try_ready {
                 let (path, thing) = crate::super_import(&method.input_type, 2);
                 module.import(&path, &thing);
             }
+
+            // The codec types are generic over the output type as well.
+            if self.codecs.get(service).is_some() {
+                module.import_type(&method.output_type, 2);
+            }
         }
 
         module
@@ -387,7 +451,10 @@ macro_rules! try_ready {
             .generic("T")
             .bound("T", &service.name)
             .vis("pub")
-            .field("pub(super) kind", response_body_kind(service));
+            .field(
+                "pub(super) kind",
+                response_body_kind(service, self.codecs.get(service)),
+            );
 
         // impl grpc::Body
         {
@@ -478,8 +545,24 @@ macro_rules! try_ready {
             .tuple_field("pub T");
         let mut request = codegen::Type::new("grpc::Request");
         let mut response = codegen::Type::new("grpc::Response");
-        let request_stream = streaming_input_type(&method, 3);
-        let response_stream = format!("T::{}Stream", &upper_name);
+        let request_stream = streaming_input_type(&method, self.codecs.get(service), 3);
+        let output_type = crate::unqualified(&method.output_type, &method.output_proto_type, 3);
+
+        let (response_stream, future) = if self.boxed {
+            let response_stream = format!("grpc::BoxStream<{}>", output_type);
+            let future = if method.server_streaming {
+                format!("grpc::BoxFuture<{}>", response_stream)
+            } else {
+                format!("grpc::BoxFuture<{}>", output_type)
+            };
+
+            (response_stream, future)
+        } else {
+            (
+                format!("T::{}Stream", &upper_name),
+                format!("T::{}Future", &upper_name),
+            )
+        };
 
         match (method.client_streaming, method.server_streaming) {
             (false, false) => {
@@ -529,7 +612,7 @@ macro_rules! try_ready {
             .bound("T", &service.name)
             .associate_type("Response", response)
             .associate_type("Error", "grpc::Status")
-            .associate_type("Future", &format!("T::{}Future", &upper_name));
+            .associate_type("Future", &future);
 
         imp.new_fn("poll_ready")
             .arg_mut_self()
@@ -546,7 +629,7 @@ macro_rules! try_ready {
 
 // ===== Here be the crazy types =====
 
-fn response_fut_kind(service: &prost_build::Service) -> String {
+fn response_fut_kind(service: &prost_build::Service, codec: Option<&str>) -> String {
     use std::fmt::Write;
 
     let mut ret = "Kind<\n".to_string();
@@ -557,41 +640,69 @@ fn response_fut_kind(service: &prost_build::Service) -> String {
         write!(&mut ret, "    // {}\n", method.proto_name).unwrap();
 
         let upper_name = crate::to_upper_camel(&method.proto_name);
+        let input_type = crate::unqualified(&method.input_type, &method.input_proto_type, 2);
+
+        if let Some(codec) = codec {
+            let encoder = format!(
+                "<{} as grpc::generic::Codec>::Encoder",
+                codec_type(codec, method, 2)
+            );
+            let request_stream = streaming_input_type(method, Some(codec), 2);
+
+            match (method.client_streaming, method.server_streaming) {
+                (false, false) => {
+                    write!(
+                        &mut ret,
+                        "    grpc::generic::server::unary::ResponseFuture<methods::{}<T>, {}, {}>,\n",
+                        &upper_name, encoder, request_stream
+                    )
+                    .unwrap();
+                }
+                (false, true) => {
+                    write!(&mut ret, "    grpc::generic::server::server_streaming::ResponseFuture<methods::{}<T>, {}, {}>,\n",
+                                     &upper_name, encoder, request_stream).unwrap();
+                }
+                (true, false) => {
+                    write!(&mut ret, "    grpc::generic::server::client_streaming::ResponseFuture<<methods::{}<T> as grpc::ClientStreamingService<{}>>::Future, {}>,\n",
+                                     &upper_name, request_stream, encoder).unwrap();
+                }
+                (true, true) => {
+                    write!(&mut ret, "    grpc::generic::server::streaming::ResponseFuture<<methods::{}<T> as grpc::StreamingService<{}>>::Future, {}>,\n",
+                                     &upper_name, request_stream, encoder).unwrap();
+                }
+            }
+
+            continue;
+        }
+
         match (method.client_streaming, method.server_streaming) {
             (false, false) => {
                 write!(
                     &mut ret,
                     "    grpc::unary::ResponseFuture<methods::{}<T>, grpc::BoxBody, {}>,\n",
-                    &upper_name,
-                    crate::unqualified(&method.input_type, &method.input_proto_type, 2)
+                    &upper_name, input_type
                 )
                 .unwrap();
             }
             (false, true) => {
                 write!(&mut ret, "    grpc::server_streaming::ResponseFuture<methods::{}<T>, grpc::BoxBody, {}>,\n",
-                                 &upper_name, crate::unqualified(&method.input_type, &method.input_proto_type, 2)).unwrap();
+                                 &upper_name, input_type).unwrap();
             }
             (true, false) => {
                 write!(
                     &mut ret,
                     "    grpc::client_streaming::ResponseFuture<methods::{}<T>, {}>,\n",
                     &upper_name,
-                    streaming_input_type(&method, 2)
+                    streaming_input_type(&method, None, 2)
                 )
                 .unwrap();
             }
             (true, true) => {
-                let mut request = codegen::Type::new("grpc::Streaming");
-                request.generic(crate::unqualified(
-                    &method.input_type,
-                    &method.input_proto_type,
-                    2,
-                ));
                 write!(
                     &mut ret,
                     "    grpc::streaming::ResponseFuture<methods::{}<T>, {}>,\n",
                     &upper_name,
-                    streaming_input_type(&method, 2)
+                    streaming_input_type(&method, None, 2)
                 )
                 .unwrap();
             }
@@ -612,7 +723,7 @@ fn response_fut_kind(service: &prost_build::Service) -> String {
 
 static UNIMPLEMENTED_VARIANT: &str = "__Generated__Unimplemented";
 
-fn response_body_kind(service: &prost_build::Service) -> String {
+fn response_body_kind(service: &prost_build::Service, codec: Option<&str>) -> String {
     use std::fmt::Write;
 
     let mut ret = "Kind<\n".to_string();
@@ -622,24 +733,38 @@ fn response_body_kind(service: &prost_build::Service) -> String {
         write!(&mut ret, "    // {}\n", method.proto_name).unwrap();
         let upper_name = crate::to_upper_camel(&method.proto_name);
 
+        // Bodies encoded with a custom codec are `grpc::generic::Encode`s.
+        let encode = match codec {
+            Some(codec) => format!(
+                "grpc::generic::Encode<<{} as grpc::generic::Codec>::Encoder, ",
+                codec_type(codec, method, 2)
+            ),
+            None => "grpc::Encode<".to_string(),
+        };
+
         match (method.client_streaming, method.server_streaming) {
             (false, false) => {
-                write!(&mut ret, "    grpc::Encode<grpc::unary::Once<<methods::{}<T> as grpc::UnaryService<{}>>::Response>>,\n",
-                                 &upper_name, crate::unqualified(&method.input_type, &method.input_proto_type, 2)).unwrap();
+                write!(&mut ret, "    {}grpc::unary::Once<<methods::{}<T> as grpc::UnaryService<{}>>::Response>>,\n",
+                                 encode, &upper_name, crate::unqualified(&method.input_type, &method.input_proto_type, 2)).unwrap();
             }
             (false, true) => {
-                write!(&mut ret, "    grpc::Encode<<methods::{}<T> as grpc::ServerStreamingService<{}>>::ResponseStream>,\n",
-                                 &upper_name, crate::unqualified(&method.input_type, &method.input_proto_type, 2)).unwrap();
+                write!(&mut ret, "    {}<methods::{}<T> as grpc::ServerStreamingService<{}>>::ResponseStream>,\n",
+                                 encode, &upper_name, crate::unqualified(&method.input_type, &method.input_proto_type, 2)).unwrap();
             }
             (true, false) => {
-                write!(&mut ret, "    grpc::Encode<grpc::unary::Once<<methods::{}<T> as grpc::ClientStreamingService<{}>>::Response>>,\n",
-                                 &upper_name, streaming_input_type(&method, 2)
+                write!(&mut ret, "    {}grpc::unary::Once<<methods::{}<T> as grpc::ClientStreamingService<{}>>::Response>>,\n",
+                                 encode, &upper_name, streaming_input_type(&method, codec, 2)
                             ).unwrap();
             }
             (true, true) => {
-                write!(&mut ret, "    grpc::Encode<<methods::{}<T> as grpc::StreamingService<{}>>::ResponseStream>,\n",
-                                 &upper_name, streaming_input_type(&method, 2)
-                            ).unwrap();
+                write!(
+                    &mut ret,
+                    "    {}<methods::{}<T> as grpc::StreamingService<{}>>::ResponseStream>,\n",
+                    encode,
+                    &upper_name,
+                    streaming_input_type(&method, codec, 2)
+                )
+                .unwrap();
             }
         }
     }
@@ -656,9 +781,32 @@ fn response_body_kind(service: &prost_build::Service) -> String {
     ret
 }
 
-fn streaming_input_type(method: &prost_build::Method, level: usize) -> String {
+/// The type of the request stream of a client streaming method, decoded by
+/// the service's custom `codec`, if any.
+pub fn streaming_input_type(
+    method: &prost_build::Method,
+    codec: Option<&str>,
+    level: usize,
+) -> String {
+    match codec {
+        Some(codec) => format!(
+            "grpc::generic::Streaming<<{} as grpc::generic::Codec>::Decoder, grpc::BoxBody>",
+            codec_type(codec, method, level)
+        ),
+        None => format!(
+            "grpc::Streaming<{}>",
+            crate::unqualified(&method.input_type, &method.input_proto_type, level)
+        ),
+    }
+}
+
+/// The type of the custom `codec` a server uses for `method`, encoding its
+/// output and decoding its input.
+fn codec_type(codec: &str, method: &prost_build::Method, level: usize) -> String {
     format!(
-        "grpc::Streaming<{}>",
+        "{}<{}, {}>",
+        codec,
+        crate::unqualified(&method.output_type, &method.output_proto_type, level),
         crate::unqualified(&method.input_type, &method.input_proto_type, level)
     )
 }
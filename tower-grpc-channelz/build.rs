@@ -0,0 +1,13 @@
+fn main() {
+    let files = &["proto/grpc/channelz/v1/channelz.proto"];
+
+    tower_grpc_build::Config::new()
+        .enable_server(true)
+        .enable_client(true)
+        .build(files, &["proto"])
+        .unwrap_or_else(|e| panic!("protobuf compilation failed: {}", e));
+
+    for file in files {
+        println!("cargo:rerun-if-changed={}", file);
+    }
+}
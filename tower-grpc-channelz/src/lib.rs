@@ -0,0 +1,405 @@
+#![deny(missing_debug_implementations, rust_2018_idioms)]
+
+//! The [channelz] service, for inspecting the servers, channels and sockets of
+//! a process at runtime.
+//!
+//! Entities are registered with a [`Channelz`] registry, which is shared with
+//! the servers and endpoints to observe and with the [`ChannelzService`]
+//! serving it:
+//!
+//! ```no_run
+//! use tower_grpc::transport::{Channelz, Endpoint, Server};
+//! use tower_grpc_channelz::{server::ChannelzServer, ChannelzService};
+//!
+//! let channelz = Channelz::new();
+//!
+//! let serve = Server::builder()
+//!     .channelz(channelz.clone())
+//!     .add_service(ChannelzServer::new(ChannelzService::new(channelz.clone())))
+//!     .serve("[::1]:50051".parse().unwrap());
+//!
+//! let channel = Endpoint::from_static("http://[::1]:50052")
+//!     .channelz(channelz)
+//!     .connect();
+//! ```
+//!
+//! [channelz]: https://github.com/grpc/proposal/blob/master/A14-channelz.md
+//! [`Channelz`]: tower_grpc::transport::Channelz
+
+mod pb {
+    include!(concat!(env!("OUT_DIR"), "/grpc.channelz.v1.rs"));
+}
+
+pub use crate::pb::*;
+
+use crate::pb::channel_connectivity_state::State;
+use futures::future::{self, FutureResult};
+use prost_types::Timestamp;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tower_grpc::transport::channelz::{
+    self as registry, Address as Addr, ChannelInfo, ConnectivityState, ServerInfo, SocketInfo,
+    TlsInfo,
+};
+use tower_grpc::transport::Channelz;
+use tower_grpc::{Code, Request, Response, Status};
+
+/// Serves the entities registered with a `Channelz` registry.
+#[derive(Clone, Debug)]
+pub struct ChannelzService {
+    channelz: Channelz,
+}
+
+type ResponseFuture<T> = FutureResult<Response<T>, Status>;
+
+// ===== impl ChannelzService =====
+
+impl ChannelzService {
+    /// Returns a service serving the entities registered with `channelz`.
+    pub fn new(channelz: Channelz) -> Self {
+        ChannelzService { channelz }
+    }
+
+    /// Returns a reference to the registry being served.
+    pub fn get_ref(&self) -> &Channelz {
+        &self.channelz
+    }
+}
+
+impl server::Channelz for ChannelzService {
+    type GetTopChannelsFuture = ResponseFuture<GetTopChannelsResponse>;
+    type GetServersFuture = ResponseFuture<GetServersResponse>;
+    type GetServerFuture = ResponseFuture<GetServerResponse>;
+    type GetServerSocketsFuture = ResponseFuture<GetServerSocketsResponse>;
+    type GetChannelFuture = ResponseFuture<GetChannelResponse>;
+    type GetSubchannelFuture = ResponseFuture<GetSubchannelResponse>;
+    type GetSocketFuture = ResponseFuture<GetSocketResponse>;
+
+    fn get_top_channels(
+        &mut self,
+        request: Request<GetTopChannelsRequest>,
+    ) -> Self::GetTopChannelsFuture {
+        let request = request.into_inner();
+        let max_results = match max_results(request.max_results) {
+            Ok(max) => max,
+            Err(status) => return future::err(status),
+        };
+
+        let page = self
+            .channelz
+            .top_channels(request.start_channel_id, max_results);
+        let end = page.is_end();
+
+        future::ok(Response::new(GetTopChannelsResponse {
+            channel: page.into_items().into_iter().map(channel).collect(),
+            end,
+        }))
+    }
+
+    fn get_servers(&mut self, request: Request<GetServersRequest>) -> Self::GetServersFuture {
+        let request = request.into_inner();
+        let max_results = match max_results(request.max_results) {
+            Ok(max) => max,
+            Err(status) => return future::err(status),
+        };
+
+        let page = self.channelz.servers(request.start_server_id, max_results);
+        let end = page.is_end();
+
+        future::ok(Response::new(GetServersResponse {
+            server: page.into_items().into_iter().map(server).collect(),
+            end,
+        }))
+    }
+
+    fn get_server(&mut self, request: Request<GetServerRequest>) -> Self::GetServerFuture {
+        let id = request.get_ref().server_id;
+
+        match self.channelz.server(id) {
+            Some(info) => future::ok(Response::new(GetServerResponse {
+                server: Some(server(info)),
+            })),
+            None => future::err(not_found("server", id)),
+        }
+    }
+
+    fn get_server_sockets(
+        &mut self,
+        request: Request<GetServerSocketsRequest>,
+    ) -> Self::GetServerSocketsFuture {
+        let request = request.into_inner();
+        let max_results = match max_results(request.max_results) {
+            Ok(max) => max,
+            Err(status) => return future::err(status),
+        };
+
+        let page =
+            self.channelz
+                .server_sockets(request.server_id, request.start_socket_id, max_results);
+
+        match page {
+            Some(page) => {
+                let end = page.is_end();
+
+                future::ok(Response::new(GetServerSocketsResponse {
+                    socket_ref: page.items().iter().map(socket_ref).collect(),
+                    end,
+                }))
+            }
+            None => future::err(not_found("server", request.server_id)),
+        }
+    }
+
+    fn get_channel(&mut self, request: Request<GetChannelRequest>) -> Self::GetChannelFuture {
+        let id = request.get_ref().channel_id;
+
+        match self.channelz.channel(id) {
+            Some(info) => future::ok(Response::new(GetChannelResponse {
+                channel: Some(channel(info)),
+            })),
+            None => future::err(not_found("channel", id)),
+        }
+    }
+
+    fn get_subchannel(
+        &mut self,
+        request: Request<GetSubchannelRequest>,
+    ) -> Self::GetSubchannelFuture {
+        let id = request.get_ref().subchannel_id;
+
+        match self.channelz.subchannel(id) {
+            Some(info) => future::ok(Response::new(GetSubchannelResponse {
+                subchannel: Some(subchannel(info)),
+            })),
+            None => future::err(not_found("subchannel", id)),
+        }
+    }
+
+    fn get_socket(&mut self, request: Request<GetSocketRequest>) -> Self::GetSocketFuture {
+        let id = request.get_ref().socket_id;
+
+        match self.channelz.socket(id) {
+            Some(info) => future::ok(Response::new(GetSocketResponse {
+                socket: Some(socket(info)),
+            })),
+            None => future::err(not_found("socket", id)),
+        }
+    }
+}
+
+// ===== utility fns =====
+
+/// Converts a requested page size, where zero asks for the default.
+fn max_results(max_results: i64) -> Result<usize, Status> {
+    if max_results < 0 {
+        return Err(Status::new(
+            Code::InvalidArgument,
+            "max_results must not be negative",
+        ));
+    }
+
+    Ok(max_results as usize)
+}
+
+fn not_found(kind: &str, id: i64) -> Status {
+    Status::new(Code::NotFound, format!("{} {} not found", kind, id))
+}
+
+fn server(info: ServerInfo) -> Server {
+    let calls = info.calls();
+
+    Server {
+        ref_: Some(ServerRef {
+            server_id: info.id(),
+            name: String::new(),
+        }),
+        data: Some(ServerData {
+            trace: None,
+            calls_started: calls.started(),
+            calls_succeeded: calls.succeeded(),
+            calls_failed: calls.failed(),
+            last_call_started_timestamp: calls.last_started().map(timestamp),
+        }),
+        listen_socket: info.listen_sockets().iter().map(socket_ref).collect(),
+    }
+}
+
+fn channel(info: ChannelInfo) -> Channel {
+    Channel {
+        ref_: Some(ChannelRef {
+            channel_id: info.id(),
+            name: String::new(),
+        }),
+        data: Some(channel_data(&info)),
+        channel_ref: Vec::new(),
+        subchannel_ref: info
+            .subchannels()
+            .iter()
+            .map(|&subchannel_id| SubchannelRef {
+                subchannel_id,
+                name: String::new(),
+            })
+            .collect(),
+        socket_ref: info.sockets().iter().map(socket_ref).collect(),
+    }
+}
+
+fn subchannel(info: ChannelInfo) -> Subchannel {
+    Subchannel {
+        ref_: Some(SubchannelRef {
+            subchannel_id: info.id(),
+            name: String::new(),
+        }),
+        data: Some(channel_data(&info)),
+        channel_ref: Vec::new(),
+        subchannel_ref: Vec::new(),
+        socket_ref: info.sockets().iter().map(socket_ref).collect(),
+    }
+}
+
+fn channel_data(info: &ChannelInfo) -> ChannelData {
+    let calls = info.calls();
+
+    let state = match info.state() {
+        ConnectivityState::Idle => State::Idle,
+        ConnectivityState::Connecting => State::Connecting,
+        ConnectivityState::Ready => State::Ready,
+        ConnectivityState::TransientFailure => State::TransientFailure,
+        ConnectivityState::Shutdown => State::Shutdown,
+    };
+
+    ChannelData {
+        state: Some(ChannelConnectivityState {
+            state: state as i32,
+        }),
+        target: info.target().to_string(),
+        trace: None,
+        calls_started: calls.started(),
+        calls_succeeded: calls.succeeded(),
+        calls_failed: calls.failed(),
+        last_call_started_timestamp: calls.last_started().map(timestamp),
+    }
+}
+
+fn socket(info: SocketInfo) -> Socket {
+    Socket {
+        ref_: Some(SocketRef {
+            socket_id: info.id(),
+            name: info.name().to_string(),
+        }),
+        data: Some(SocketData {
+            streams_started: info.streams_started(),
+            streams_succeeded: info.streams_succeeded(),
+            streams_failed: info.streams_failed(),
+            messages_sent: info.messages_sent(),
+            messages_received: info.messages_received(),
+            last_local_stream_created_timestamp: info.last_local_stream_created().map(timestamp),
+            last_remote_stream_created_timestamp: info.last_remote_stream_created().map(timestamp),
+            last_message_sent_timestamp: info.last_message_sent().map(timestamp),
+            last_message_received_timestamp: info.last_message_received().map(timestamp),
+            ..SocketData::default()
+        }),
+        local: info.local().map(address),
+        remote: info.remote().map(address),
+        security: info.tls().map(security),
+        remote_name: String::new(),
+    }
+}
+
+fn security(tls: &TlsInfo) -> Security {
+    let cipher_suite = match tls.cipher_suite() {
+        "" => None,
+        name => Some(security::tls::CipherSuite::StandardName(name.to_string())),
+    };
+
+    Security {
+        model: Some(security::Model::Tls(security::Tls {
+            cipher_suite,
+            local_certificate: tls.local_certificate().unwrap_or_default().to_vec(),
+            remote_certificate: tls.remote_certificate().unwrap_or_default().to_vec(),
+        })),
+    }
+}
+
+fn socket_ref(socket: &registry::SocketRef) -> SocketRef {
+    SocketRef {
+        socket_id: socket.id(),
+        name: socket.name().to_string(),
+    }
+}
+
+fn address(addr: &Addr) -> pb::Address {
+    let address = match *addr {
+        Addr::Tcp(ref addr) => {
+            let ip_address = match addr.ip() {
+                std::net::IpAddr::V4(ip) => ip.octets().to_vec(),
+                std::net::IpAddr::V6(ip) => ip.octets().to_vec(),
+            };
+
+            address::Address::TcpipAddress(address::TcpIpAddress {
+                ip_address,
+                port: i32::from(addr.port()),
+            })
+        }
+        Addr::Unix(ref path) => address::Address::UdsAddress(address::UdsAddress {
+            filename: path.display().to_string(),
+        }),
+    };
+
+    pb::Address {
+        address: Some(address),
+    }
+}
+
+fn timestamp(time: SystemTime) -> Timestamp {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+
+    Timestamp {
+        seconds: since_epoch.as_secs() as i64,
+        nanos: since_epoch.subsec_nanos() as i32,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn converts_addresses() {
+        let tcp = address(&Addr::Tcp("[::1]:50051".parse().unwrap()));
+        match tcp.address {
+            Some(address::Address::TcpipAddress(ref addr)) => {
+                assert_eq!(addr.ip_address.len(), 16);
+                assert_eq!(addr.port, 50051);
+            }
+            ref other => panic!("unexpected address {:?}", other),
+        }
+
+        let uds = address(&Addr::Unix("/tmp/grpc.sock".into()));
+        match uds.address {
+            Some(address::Address::UdsAddress(ref addr)) => {
+                assert_eq!(addr.filename, "/tmp/grpc.sock");
+            }
+            ref other => panic!("unexpected address {:?}", other),
+        }
+    }
+
+    #[test]
+    fn converts_timestamps() {
+        let time = UNIX_EPOCH + Duration::new(1_500_000_000, 250);
+
+        assert_eq!(
+            timestamp(time),
+            Timestamp {
+                seconds: 1_500_000_000,
+                nanos: 250,
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_negative_page_sizes() {
+        assert_eq!(max_results(0).unwrap(), 0);
+        assert_eq!(max_results(-1).unwrap_err().code(), Code::InvalidArgument);
+    }
+}
@@ -1,10 +1,8 @@
 #![deny(warnings, rust_2018_idioms)]
 
 use futures::Future;
-use hyper::client::connect::{Destination, HttpConnector};
+use tower_grpc::transport::Endpoint;
 use tower_grpc::Request;
-use tower_hyper::{client, util};
-use tower_util::MakeService;
 
 pub mod metadata {
     include!(concat!(env!("OUT_DIR"), "/metadata.rs"));
@@ -13,26 +11,14 @@ pub mod metadata {
 pub fn main() {
     let _ = ::env_logger::init();
 
-    let uri: http::Uri = format!("http://[::1]:50051").parse().unwrap();
-
-    let dst = Destination::try_from_uri(uri.clone()).unwrap();
-    let connector = util::Connector::new(HttpConnector::new(4));
-    let settings = client::Builder::new().http2_only(true).clone();
-    let mut make_client = client::Connect::with_builder(connector, settings);
-
-    let doorman = make_client
-        .make_service(dst)
+    let doorman = Endpoint::from_static("http://[::1]:50051")
+        .connect()
         .map_err(|e| panic!("connect error: {:?}", e))
-        .and_then(move |conn| {
+        .and_then(|channel| {
             use crate::metadata::client::Doorman;
 
-            let conn = tower_request_modifier::Builder::new()
-                .set_origin(uri)
-                .build(conn)
-                .unwrap();
-
             // Wait until the client is ready...
-            Doorman::new(conn).ready()
+            Doorman::new(channel).ready()
         })
         .and_then(|mut client| {
             use crate::metadata::EnterRequest;
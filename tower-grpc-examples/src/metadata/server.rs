@@ -3,10 +3,8 @@
 use crate::metadata::{server, EnterReply, EnterRequest};
 
 use futures::{future, Future, Stream};
-use log::error;
-use tokio::net::TcpListener;
+use tower_grpc::transport::Server;
 use tower_grpc::{Request, Response};
-use tower_hyper::server::{Http, Server};
 
 pub mod metadata {
     include!(concat!(env!("OUT_DIR"), "/metadata.rs"));
@@ -40,28 +38,14 @@ impl server::Doorman for Door {
 pub fn main() {
     let _ = ::env_logger::init();
 
-    let new_service = server::DoormanServer::new(Door);
-
-    let mut server = Server::new(new_service);
-
-    let http = Http::new().http2_only(true).clone();
+    let service = server::DoormanServer::new(Door);
 
     let addr = "[::1]:50051".parse().unwrap();
-    let bind = TcpListener::bind(&addr).expect("bind");
-
-    let serve = bind
-        .incoming()
-        .for_each(move |sock| {
-            if let Err(e) = sock.set_nodelay(true) {
-                return Err(e);
-            }
-
-            let serve = server.serve_with(sock, http.clone());
-            tokio::spawn(serve.map_err(|e| error!("h2 error: {:?}", e)));
 
-            Ok(())
-        })
-        .map_err(|e| eprintln!("accept error: {}", e));
+    let serve = Server::builder()
+        .add_service(service)
+        .serve_with_shutdown(addr, tokio_signal::ctrl_c().flatten_stream().into_future())
+        .map_err(|e| eprintln!("server error: {}", e));
 
     tokio::run(serve);
 }
@@ -5,12 +5,10 @@ mod data;
 use crate::routeguide::{Point, RouteNote};
 
 use futures::{Future, Stream};
-use hyper::client::connect::{Destination, HttpConnector};
 use std::time::{Duration, Instant};
 use tokio::timer::Interval;
+use tower_grpc::transport::Endpoint;
 use tower_grpc::Request;
-use tower_hyper::{client, util};
-use tower_util::MakeService;
 
 pub mod routeguide {
     include!(concat!(env!("OUT_DIR"), "/routeguide.rs"));
@@ -19,27 +17,15 @@ pub mod routeguide {
 pub fn main() {
     let _ = ::env_logger::init();
 
-    let uri: http::Uri = format!("http://localhost:10000").parse().unwrap();
-
-    let dst = Destination::try_from_uri(uri.clone()).unwrap();
-    let connector = util::Connector::new(HttpConnector::new(4));
-    let settings = client::Builder::new().http2_only(true).clone();
-    let mut make_client = client::Connect::with_builder(connector, settings);
-
-    let rg = make_client
-        .make_service(dst)
+    let rg = Endpoint::from_static("http://localhost:10000")
+        .connect()
         .map_err(|e| {
             panic!("HTTP/2 connection failed; err={:?}", e);
         })
-        .and_then(move |conn| {
+        .and_then(|channel| {
             use crate::routeguide::client::RouteGuide;
 
-            let conn = tower_request_modifier::Builder::new()
-                .set_origin(uri)
-                .build(conn)
-                .unwrap();
-
-            RouteGuide::new(conn)
+            RouteGuide::new(channel)
                 // Wait until the client is ready...
                 .ready()
                 .map_err(|e| eprintln!("client closed: {:?}", e))
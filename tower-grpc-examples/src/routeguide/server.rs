@@ -6,14 +6,12 @@ use crate::routeguide::{server, Feature, Point, Rectangle, RouteNote, RouteSumma
 
 use futures::sync::mpsc;
 use futures::{future, stream, Future, Sink, Stream};
-use log::error;
 use std::collections::HashMap;
 use std::hash::{Hash, Hasher};
 use std::sync::{Arc, Mutex};
 use std::time::Instant;
-use tokio::net::TcpListener;
+use tower_grpc::transport::Server;
 use tower_grpc::{Request, Response, Streaming};
-use tower_hyper::server::{Http, Server};
 
 pub mod routeguide {
     include!(concat!(env!("OUT_DIR"), "/routeguide.rs"));
@@ -232,29 +230,16 @@ pub fn main() {
         }),
     };
 
-    let new_service = server::RouteGuideServer::new(handler);
-
-    let mut server = Server::new(new_service);
-    let http = Http::new().http2_only(true).clone();
+    let service = server::RouteGuideServer::new(handler);
 
     let addr = "127.0.0.1:10000".parse().unwrap();
-    let bind = TcpListener::bind(&addr).expect("bind");
 
     println!("listining on {:?}", addr);
 
-    let serve = bind
-        .incoming()
-        .for_each(move |sock| {
-            if let Err(e) = sock.set_nodelay(true) {
-                return Err(e);
-            }
-
-            let serve = server.serve_with(sock, http.clone());
-            tokio::spawn(serve.map_err(|e| error!("h2 error: {:?}", e)));
-
-            Ok(())
-        })
-        .map_err(|e| eprintln!("accept error: {}", e));
+    let serve = Server::builder()
+        .add_service(service)
+        .serve_with_shutdown(addr, tokio_signal::ctrl_c().flatten_stream().into_future())
+        .map_err(|e| eprintln!("server error: {}", e));
 
     tokio::run(serve);
 }
@@ -5,20 +5,17 @@ use crate::pb::StreamingInputCallRequest;
 use std::error::Error;
 
 use clap::{arg_enum, value_t, values_t};
-use futures::{future, stream, Future, Poll, Stream};
+use futures::{future, stream, Future, Stream};
 use http::uri::{self, Uri};
-use http::Version;
-use http_connection::HttpConnection;
 use log::info;
-use std::fmt;
 use std::net::{IpAddr, SocketAddr};
 use std::str::FromStr;
-use tokio_core::net::TcpStream;
+use std::{fmt, fs, io};
+use tokio::runtime::current_thread::Runtime;
 use tokio_core::reactor;
-use tower::{buffer::Buffer, Service, ServiceExt};
 use tower_grpc::metadata::MetadataValue;
+use tower_grpc::transport::{Certificate, Channel, ClientTlsConfig, Endpoint};
 use tower_grpc::Request;
-use tower_hyper::client::Connect;
 
 pub mod pb {
     #![allow(dead_code)]
@@ -109,6 +106,7 @@ enum ClientError {
     InvalidArgument(clap::Error),
     InvalidUri(uri::InvalidUri),
     Dns(DnsError),
+    Io(io::Error),
 }
 
 #[derive(Debug)]
@@ -121,6 +119,10 @@ impl ClientError {
     fn exit(&self) -> ! {
         match *self {
             ClientError::InvalidArgument(ref clap_error) => clap_error.exit(),
+            ClientError::Io(ref io) => {
+                eprintln!("error: {}", io);
+                std::process::exit(1)
+            }
             _ => unimplemented!(),
         }
     }
@@ -138,6 +140,12 @@ impl From<uri::InvalidUri> for ClientError {
     }
 }
 
+impl From<io::Error> for ClientError {
+    fn from(io: io::Error) -> Self {
+        ClientError::Io(io)
+    }
+}
+
 impl<T> From<T> for ClientError
 where
     DnsError: From<T>,
@@ -215,25 +223,8 @@ fn assert_success(
 }
 
 struct TestClients {
-    test_client: TestService<
-        Buffer<
-            tower_request_modifier::RequestModifier<
-                tower_hyper::client::Connection<tower_grpc::BoxBody>,
-                tower_grpc::BoxBody,
-            >,
-            http::Request<tower_grpc::BoxBody>,
-        >,
-    >,
-
-    unimplemented_client: UnimplementedService<
-        Buffer<
-            tower_request_modifier::RequestModifier<
-                tower_hyper::client::Connection<tower_grpc::BoxBody>,
-                tower_grpc::BoxBody,
-            >,
-            http::Request<tower_grpc::BoxBody>,
-        >,
-    >,
+    test_client: TestService<Channel>,
+    unimplemented_client: UnimplementedService<Channel>,
 }
 
 fn make_ping_pong_request(idx: usize) -> pb::StreamingOutputCallRequest {
@@ -776,93 +767,34 @@ impl Testcase {
     fn run(
         &self,
         server: &ServerInfo,
-        core: &mut tokio_core::reactor::Core,
+        rt: &mut Runtime,
     ) -> Result<Vec<TestAssertion>, Box<dyn Error>> {
-        let open_connection = |core: &mut tokio_core::reactor::Core| {
-            let reactor = core.handle();
-
-            struct TcpConnector(tokio_core::reactor::Handle);
-
-            struct Stream(TcpStream);
-
-            impl Service<SocketAddr> for TcpConnector {
-                type Response = Stream;
-                type Error = ::std::io::Error;
-                type Future = Box<dyn Future<Item = Self::Response, Error = Self::Error>>;
-
-                fn poll_ready(&mut self) -> Poll<(), Self::Error> {
-                    Ok(().into())
-                }
-
-                fn call(&mut self, req: SocketAddr) -> Self::Future {
-                    Box::new(TcpStream::connect(&req, &self.0).map(|s| Stream(s)))
-                }
-            }
-
-            impl ::std::io::Read for Stream {
-                fn read(&mut self, buf: &mut [u8]) -> ::std::io::Result<usize> {
-                    self.0.read(buf)
-                }
-            }
-
-            impl ::std::io::Write for Stream {
-                fn write(&mut self, buf: &[u8]) -> ::std::io::Result<usize> {
-                    self.0.write(&buf)
-                }
-
-                fn flush(&mut self) -> ::std::io::Result<()> {
-                    self.0.flush()
-                }
-            }
-
-            impl tokio::io::AsyncRead for Stream {}
-            impl tokio::io::AsyncWrite for Stream {
-                fn shutdown(&mut self) -> Poll<(), tokio::io::Error> {
-                    <TcpStream as tokio::io::AsyncWrite>::shutdown(&mut self.0)
-                }
-            }
-            impl HttpConnection for Stream {
-                fn negotiated_version(&self) -> Option<Version> {
-                    Some(Version::HTTP_2)
-                }
-            }
-
-            // let builder = Builder::new().http2_only(true).clone();
-            let connector = Connect::new(TcpConnector(reactor.clone()));
-
-            core.run(connector.ready().and_then(|mut connector| {
-                connector.call(server.addr).map(move |conn| {
-                    let svc = tower_request_modifier::Builder::new()
-                        .set_origin(server.uri.clone())
-                        .build(conn)
-                        .unwrap();
-
-                    Buffer::new(svc, 5)
-                })
-            }))
-            .expect("connection")
-        };
+        let mut endpoint = Endpoint::from_uri(server.uri.clone());
+        if let Some(ref tls) = server.tls {
+            endpoint = endpoint.tls_config(tls.clone()).expect("TLS config");
+        }
+        let channel = rt.block_on(endpoint.connect()).expect("connection");
 
-        // TODO(#42): This opens two separate TCP connections to the server. It
-        // would be better to open only one.
         let mut clients = TestClients {
-            test_client: TestService::new(open_connection(core)),
-            unimplemented_client: UnimplementedService::new(open_connection(core)),
+            test_client: TestService::new(channel.clone()),
+            unimplemented_client: UnimplementedService::new(channel),
         };
 
         match *self {
-            Testcase::empty_unary => core.run(clients.empty_unary_test()),
-            Testcase::large_unary => core.run(clients.large_unary_test()),
-            Testcase::cacheable_unary => core.run(clients.cacheable_unary_test()),
-            Testcase::client_streaming => core.run(clients.client_streaming_test()),
-            Testcase::server_streaming => core.run(clients.server_streaming_test()),
-            Testcase::ping_pong => core.run(clients.ping_pong_test()),
-            Testcase::empty_stream => core.run(clients.empty_stream_test()),
-            Testcase::status_code_and_message => core.run(clients.status_code_and_message_test()),
-            Testcase::special_status_message => core.run(clients.special_status_message_test()),
-            Testcase::unimplemented_method => core.run(clients.unimplemented_method_test()),
-            Testcase::unimplemented_service => core.run(clients.unimplemented_service_test()),
-            Testcase::custom_metadata => core.run(clients.custom_metadata_test()),
+            Testcase::empty_unary => rt.block_on(clients.empty_unary_test()),
+            Testcase::large_unary => rt.block_on(clients.large_unary_test()),
+            Testcase::cacheable_unary => rt.block_on(clients.cacheable_unary_test()),
+            Testcase::client_streaming => rt.block_on(clients.client_streaming_test()),
+            Testcase::server_streaming => rt.block_on(clients.server_streaming_test()),
+            Testcase::ping_pong => rt.block_on(clients.ping_pong_test()),
+            Testcase::empty_stream => rt.block_on(clients.empty_stream_test()),
+            Testcase::status_code_and_message => {
+                rt.block_on(clients.status_code_and_message_test())
+            }
+            Testcase::special_status_message => rt.block_on(clients.special_status_message_test()),
+            Testcase::unimplemented_method => rt.block_on(clients.unimplemented_method_test()),
+            Testcase::unimplemented_service => rt.block_on(clients.unimplemented_service_test()),
+            Testcase::custom_metadata => rt.block_on(clients.custom_metadata_test()),
             Testcase::compute_engine_creds
             | Testcase::jwt_token_creds
             | Testcase::oauth2_auth_token
@@ -947,9 +879,8 @@ impl fmt::Display for TestAssertion {
 }
 
 struct ServerInfo {
-    addr: SocketAddr,
     uri: Uri,
-    hostname_override: Option<String>,
+    tls: Option<ClientTlsConfig>,
 }
 
 impl ServerInfo {
@@ -960,6 +891,9 @@ impl ServerInfo {
         use domain::bits::DNameBuf;
         use domain::resolv::{lookup, Resolver};
 
+        let tls = Self::tls_from_args(matches)?;
+        let scheme = if tls.is_some() { "https" } else { "http" };
+
         let handle = core.handle();
         // XXX this could probably look neater if only the DNS query was run in
         //     a future...
@@ -992,18 +926,37 @@ impl ServerInfo {
                         "`server_host` argument was not present, clap \
                          should have already validated it was present.",
                     );
-                    future::result(Uri::from_str(&format!("http://{}:{}", host, port)))
+                    future::result(Uri::from_str(&format!("{}://{}:{}", scheme, host, port)))
                         .from_err::<ClientError>()
-                        .map(move |uri| ServerInfo {
-                            addr,
-                            uri,
-                            hostname_override: None, // unimplemented
-                        })
+                        .map(move |uri| ServerInfo { uri, tls })
                 })
                 .from_err::<ClientError>()
         });
         core.run(info)
     }
+
+    fn tls_from_args<'a>(
+        matches: &clap::ArgMatches<'a>,
+    ) -> Result<Option<ClientTlsConfig>, ClientError> {
+        if !value_t!(matches, "use_tls", bool)? {
+            return Ok(None);
+        }
+
+        let mut tls = ClientTlsConfig::new();
+
+        if matches.is_present("use_test_ca") {
+            let ca_file = matches
+                .value_of("ca_file")
+                .expect("`ca_file` has a default value");
+            tls = tls.ca_certificate(Certificate::from_pem(fs::read(ca_file)?));
+        }
+
+        if let Some(host) = matches.value_of("server_host_override") {
+            tls = tls.domain_name(host);
+        }
+
+        Ok(Some(tls))
+    }
 }
 
 fn main() {
@@ -1051,20 +1004,6 @@ fn main() {
                 .value_name("BOOLEAN")
                 .possible_values(&["true", "false"])
                 .default_value("false")
-                .validator(|s|
-                    // use a Clap validator for unimplemented flags so we get a
-                    // nicer error message than the panic from
-                    // `unimplemented!()`.
-                    if s == "true" {
-                        // unsupported, always error for now.
-                        Err(String::from(
-                            "tower-grpc does not currently support TLS."
-                        ))
-                    } else {
-                        Ok(())
-                    }
-
-                )
             )
             .arg(Arg::with_name("use_test_ca")
                 .long("use_test_ca")
@@ -1128,9 +1067,11 @@ fn main() {
 
     let test_cases = values_t!(matches, "test_case", Testcase).unwrap_or_else(|e| e.exit());
 
+    let mut rt = Runtime::new().expect("could not create runtime!");
+
     for test in test_cases {
         println!("{:?}:", test);
-        let test_results = test.run(&server, &mut core).expect("error running test!");
+        let test_results = test.run(&server, &mut rt).expect("error running test!");
         for result in test_results {
             println!("  {}", result);
         }
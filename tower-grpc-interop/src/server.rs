@@ -1,9 +1,8 @@
 use clap::value_t;
 use futures::{future, stream, Future, Stream};
-use log::error;
-use tokio::net::TcpListener;
+use std::fs;
+use tower_grpc::transport::{Identity, Server, ServerTlsConfig};
 use tower_grpc::{Code, Request, Response, Status};
-use tower_hyper::server::{Http, Server};
 
 mod pb {
     #![allow(dead_code)]
@@ -192,31 +191,60 @@ fn main() {
                 .takes_value(true)
                 .default_value("10000"),
         )
+        .arg(
+            Arg::with_name("use_tls")
+                .long("use_tls")
+                .help("Whether to use a plaintext or encrypted connection.")
+                .takes_value(true)
+                .value_name("BOOLEAN")
+                .possible_values(&["true", "false"])
+                .default_value("false"),
+        )
+        .arg(
+            Arg::with_name("tls_cert_file")
+                .long("tls_cert_file")
+                .value_name("FILE")
+                .help("The file containing the server's PEM encoded certificate chain.")
+                .takes_value(true)
+                .default_value("server1.pem"),
+        )
+        .arg(
+            Arg::with_name("tls_key_file")
+                .long("tls_key_file")
+                .value_name("FILE")
+                .help("The file containing the server's PEM encoded private key.")
+                .takes_value(true)
+                .default_value("server1.key"),
+        )
         .get_matches();
 
     let port = value_t!(matches, "port", u16).expect("port argument");
 
-    let new_service = pb::server::TestServiceServer::new(Test);
+    let addr = format!("0.0.0.0:{}", port).parse().unwrap();
 
-    let mut server = Server::new(new_service);
+    let mut server = Server::builder();
+
+    if value_t!(matches, "use_tls", bool).expect("use_tls argument") {
+        let cert = matches
+            .value_of("tls_cert_file")
+            .expect("tls_cert_file argument");
+        let key = matches
+            .value_of("tls_key_file")
+            .expect("tls_key_file argument");
+        let identity = Identity::from_pem(
+            fs::read(cert).expect("read TLS certificate"),
+            fs::read(key).expect("read TLS key"),
+        );
 
-    let addr = format!("0.0.0.0:{}", port).parse().unwrap();
-    let bind = TcpListener::bind(&addr).expect("bind");
-    let http = Http::new().http2_only(true).clone();
-
-    let serve = bind
-        .incoming()
-        .for_each(move |sock| {
-            if let Err(e) = sock.set_nodelay(true) {
-                return Err(e);
-            }
-
-            let serve = server.serve_with(sock, http.clone());
-            tokio::spawn(serve.map_err(|e| error!("hyper error: {:?}", e)));
-
-            Ok(())
-        })
-        .map_err(|e| eprintln!("accept error: {}", e));
+        server = server
+            .tls_config(ServerTlsConfig::new().identity(identity))
+            .expect("TLS config");
+    }
+
+    let serve = server
+        .add_service(pb::server::TestServiceServer::new(Test))
+        .serve_with_shutdown(addr, tokio_signal::ctrl_c().flatten_stream().into_future())
+        .map_err(|e| eprintln!("server error: {}", e));
 
     eprintln!("grpc interop server listening on {}", addr);
     tokio::run(serve)
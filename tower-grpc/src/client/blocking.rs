@@ -0,0 +1,170 @@
+//! Support for the blocking clients generated by `tower-grpc-build`.
+
+use crate::{Response, Status};
+
+use futures::{Future, Stream};
+use std::cell::RefCell;
+use std::io;
+use std::rc::Rc;
+use tokio::runtime::current_thread;
+
+/// Runs the calls of a blocking client to completion, on the current thread.
+///
+/// Tasks spawned by the client's transport, such as the connection of a
+/// `transport::Channel`, only make progress while the runtime is blocked on
+/// a call. Such a transport should be connected with `Runtime::block_on`.
+///
+/// Clones share the same runtime, so a clone kept by the caller can connect
+/// the transport of a client created `with_runtime`.
+#[derive(Clone, Debug)]
+pub struct Runtime {
+    inner: Rc<RefCell<current_thread::Runtime>>,
+}
+
+/// An iterator over a stream of response messages, blocking until each
+/// message is received.
+///
+/// The iterator shares the runtime of the call it was returned by, and so
+/// does not borrow the client. It ends after the stream returns an error.
+#[derive(Debug)]
+pub struct Iter<S> {
+    runtime: Runtime,
+    stream: Option<S>,
+}
+
+// ===== impl Runtime =====
+
+impl Runtime {
+    /// Returns a new runtime.
+    pub fn new() -> io::Result<Self> {
+        let inner = current_thread::Runtime::new()?;
+        Ok(Runtime {
+            inner: Rc::new(RefCell::new(inner)),
+        })
+    }
+
+    /// Run `future` to completion, blocking the current thread.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called from a future the runtime is already blocked on.
+    pub fn block_on<F>(&mut self, future: F) -> Result<F::Item, F::Error>
+    where
+        F: Future,
+    {
+        self.inner.borrow_mut().block_on(future)
+    }
+
+    /// Run `future` to completion, returning an iterator over the stream of
+    /// messages it responds with.
+    pub fn block_on_stream<F, S>(&mut self, future: F) -> Result<Response<Iter<S>>, Status>
+    where
+        F: Future<Item = Response<S>, Error = Status>,
+        S: Stream<Error = Status>,
+    {
+        let response = self.block_on(future)?;
+
+        Ok(response.map(|stream| Iter {
+            runtime: self.clone(),
+            stream: Some(stream),
+        }))
+    }
+}
+
+// ===== impl Iter =====
+
+impl<S> Iterator for Iter<S>
+where
+    S: Stream<Error = Status>,
+{
+    type Item = Result<S::Item, Status>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let stream = self.stream.take()?;
+
+        match self.runtime.block_on(stream.into_future()) {
+            Ok((Some(message), stream)) => {
+                self.stream = Some(stream);
+                Some(Ok(message))
+            }
+            Ok((None, _)) => None,
+            Err((status, _)) => Some(Err(status)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{future, stream};
+
+    #[test]
+    fn iterates_until_error() {
+        let mut runtime = Runtime::new().unwrap();
+        let messages = stream::iter_result(vec![
+            Ok(1),
+            Ok(2),
+            Err(Status::new(crate::Code::DataLoss, "")),
+            Ok(3),
+        ]);
+
+        let response = runtime
+            .block_on_stream(future::ok(Response::new(messages)))
+            .unwrap();
+        let messages = response.into_inner().collect::<Vec<_>>();
+
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[1].as_ref().unwrap(), &2);
+        assert_eq!(
+            messages[2].as_ref().unwrap_err().code(),
+            crate::Code::DataLoss
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "transport")]
+    fn channel() {
+        use crate::client::Grpc;
+        use crate::test_util::NotFound;
+        use crate::transport::{Endpoint, Server};
+        use crate::{BoxBody, Code, Request};
+        use tokio::net::TcpListener;
+
+        let listener = TcpListener::bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let serve = Server::builder()
+            .add_service(NotFound)
+            .serve_incoming(listener.incoming())
+            .map_err(|e| panic!("server error: {}", e));
+        let mut server = tokio::runtime::Runtime::new().unwrap();
+        server.spawn(serve);
+
+        // The channel's connection runs on the blocking runtime.
+        let mut runtime = Runtime::new().unwrap();
+        let uri = format!("http://127.0.0.1:{}", port).parse().unwrap();
+        let channel = runtime.block_on(Endpoint::from_uri(uri).connect()).unwrap();
+        let mut client = Grpc::new(channel);
+
+        runtime
+            .block_on(future::poll_fn(|| client.poll_ready()))
+            .unwrap();
+        let path = http::uri::PathAndQuery::from_static("/test.NotFound/List");
+        let messages = runtime
+            .block_on_stream(client.server_streaming::<(), (), BoxBody>(Request::new(()), path));
+        let messages = messages.unwrap().into_inner();
+
+        // The runtime remains usable while the iterator is alive.
+        runtime
+            .block_on(future::poll_fn(|| client.poll_ready()))
+            .unwrap();
+        let path = http::uri::PathAndQuery::from_static("/test.NotFound/Get");
+        let status = runtime
+            .block_on(client.unary::<(), (), BoxBody>(Request::new(()), path))
+            .unwrap_err();
+        assert_eq!(status.code(), Code::NotFound);
+
+        let messages = messages.collect::<Vec<_>>();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].as_ref().unwrap_err().code(), Code::NotFound);
+    }
+}
@@ -0,0 +1,96 @@
+use crate::body::{Body, BoxBody, HttpBody};
+use crate::error::Error;
+
+use futures::Poll;
+use http::Extensions;
+use tower_service::Service;
+
+/// Calls a server's service in process, without HTTP/2.
+///
+/// Wrapping a generated `*Server` in `Direct` lets a generated client call
+/// it directly, such as when services that may later be split out are
+/// embedded in the same binary:
+///
+/// ```ignore
+/// let mut greeter = Greeter::new(Direct::new(GreeterServer::new(Greet)));
+/// ```
+///
+/// Requests and responses are passed straight through, with messages
+/// still encoded and decoded, and statuses still sent as trailers, so that
+/// calls behave as they would over a connection. As over a connection,
+/// request extensions are not passed on to the server.
+#[derive(Clone, Debug)]
+pub struct Direct<S> {
+    inner: S,
+}
+
+impl<S> Direct<S> {
+    /// Returns a `Direct` calling `inner`.
+    pub fn new(inner: S) -> Self {
+        Direct { inner }
+    }
+
+    /// Get a reference to the server's service.
+    pub fn get_ref(&self) -> &S {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the server's service.
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.inner
+    }
+
+    /// Consumes `self`, returning the server's service.
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl<S, B> Service<http::Request<BoxBody>> for Direct<S>
+where
+    S: Service<http::Request<BoxBody>, Response = http::Response<B>>,
+    S::Error: Into<Error>,
+    B: Body + HttpBody,
+{
+    type Response = http::Response<B>;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        self.inner.poll_ready()
+    }
+
+    fn call(&mut self, mut request: http::Request<BoxBody>) -> Self::Future {
+        // Client-side options, such as wait-for-ready, are carried in
+        // extensions and are never sent to the server.
+        *request.extensions_mut() = Extensions::new();
+        self.inner.call(request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::Grpc;
+    use crate::test_util::NotFound;
+    use crate::{Code, Request};
+
+    use futures::Future;
+
+    #[test]
+    fn status_from_trailers() {
+        let mut client = Grpc::new(Direct::new(NotFound));
+
+        let mut request = Request::new(());
+        request.set_wait_for_ready(true);
+
+        let path = http::uri::PathAndQuery::from_static("/test.NotFound/Get");
+        let status = client
+            .unary::<(), (), BoxBody>(request, path)
+            .wait()
+            .unwrap_err();
+
+        assert_eq!(status.code(), Code::NotFound);
+        assert_eq!(status.message(), "not here");
+    }
+}
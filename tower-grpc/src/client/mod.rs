@@ -1,12 +1,21 @@
 //! gRPC client
 
+#[cfg(feature = "blocking")]
+pub mod blocking;
 pub mod client_streaming;
+mod direct;
 pub mod server_streaming;
 pub mod streaming;
 pub mod unary;
 
+pub use self::direct::Direct;
+pub use crate::request::{ViaIterator, ViaStream};
+
 use crate::body::BoxBody;
-use crate::generic::client::{GrpcService, IntoService};
+use crate::codec::Decoder;
+use crate::descriptor::MethodKind;
+use crate::generic::client::{self as generic, GrpcService, IntoService};
+use crate::generic::{Codec, Encode, Encoder};
 
 use futures::{stream, Future, Poll, Stream};
 use http::{uri, Uri};
@@ -67,9 +76,9 @@ impl<T> Grpc<T> {
         unary::Once<M1>: Encodable<R>,
     {
         let request = request.map(|v| stream::once(Ok(v)));
-        let response = self.client_streaming(request, path);
+        let response = self.send(MethodKind::Unary, request, path);
 
-        unary::ResponseFuture::new(response)
+        unary::ResponseFuture::new(client_streaming::ResponseFuture::new(response))
     }
 
     /// Send a client streaing gRPC request.
@@ -82,7 +91,7 @@ impl<T> Grpc<T> {
         T: GrpcService<R>,
         B: Encodable<R>,
     {
-        let response = self.streaming(request, path);
+        let response = self.send(MethodKind::ClientStreaming, request, path);
         client_streaming::ResponseFuture::new(response)
     }
 
@@ -97,7 +106,7 @@ impl<T> Grpc<T> {
         unary::Once<M1>: Encodable<R>,
     {
         let request = request.map(|v| stream::once(Ok(v)));
-        let response = self.streaming(request, path);
+        let response = self.send(MethodKind::ServerStreaming, request, path);
 
         server_streaming::ResponseFuture::new(response)
     }
@@ -117,6 +126,138 @@ impl<T> Grpc<T> {
     where
         T: GrpcService<R>,
         B: Encodable<R>,
+    {
+        self.send(MethodKind::Streaming, request, path)
+    }
+
+    /// Send a unary gRPC request, encoding the request and decoding the
+    /// response with `codec`.
+    pub fn unary_with_codec<C>(
+        &mut self,
+        codec: C,
+        request: crate::Request<C::Encode>,
+        path: uri::PathAndQuery,
+    ) -> generic::unary::ResponseFuture<C::Decoder, T::Future, T::ResponseBody>
+    where
+        T: GrpcService<BoxBody>,
+        C: Codec,
+        C::Encode: Send + 'static,
+        C::Encoder: Send + 'static,
+    {
+        let request = request.map(|v| stream::once(Ok(v)));
+        let response = self.send_with_codec(MethodKind::Unary, codec, request, path);
+        let response = generic::client_streaming::ResponseFuture::new(response);
+
+        generic::unary::ResponseFuture::new(response)
+    }
+
+    /// Send a client streaming gRPC request, encoding the request and
+    /// decoding the response with `codec`.
+    pub fn client_streaming_with_codec<C, B>(
+        &mut self,
+        codec: C,
+        request: crate::Request<B>,
+        path: uri::PathAndQuery,
+    ) -> generic::client_streaming::ResponseFuture<C::Decoder, T::Future, T::ResponseBody>
+    where
+        T: GrpcService<BoxBody>,
+        C: Codec,
+        C::Encoder: Send + 'static,
+        B: Stream<Item = C::Encode, Error = crate::Status> + Send + 'static,
+    {
+        let response = self.send_with_codec(MethodKind::ClientStreaming, codec, request, path);
+        generic::client_streaming::ResponseFuture::new(response)
+    }
+
+    /// Send a server streaming gRPC request, encoding the request and
+    /// decoding the response with `codec`.
+    pub fn server_streaming_with_codec<C>(
+        &mut self,
+        codec: C,
+        request: crate::Request<C::Encode>,
+        path: uri::PathAndQuery,
+    ) -> generic::server_streaming::ResponseFuture<C::Decoder, T::Future>
+    where
+        T: GrpcService<BoxBody>,
+        C: Codec,
+        C::Encode: Send + 'static,
+        C::Encoder: Send + 'static,
+    {
+        let request = request.map(|v| stream::once(Ok(v)));
+        let response = self.send_with_codec(MethodKind::ServerStreaming, codec, request, path);
+
+        generic::server_streaming::ResponseFuture::new(response)
+    }
+
+    /// Initiate a full streaming gRPC request, encoding the request and
+    /// decoding the response with `codec`.
+    pub fn streaming_with_codec<C, B>(
+        &mut self,
+        codec: C,
+        request: crate::Request<B>,
+        path: uri::PathAndQuery,
+    ) -> generic::streaming::ResponseFuture<C::Decoder, T::Future>
+    where
+        T: GrpcService<BoxBody>,
+        C: Codec,
+        C::Encoder: Send + 'static,
+        B: Stream<Item = C::Encode, Error = crate::Status> + Send + 'static,
+    {
+        self.send_with_codec(MethodKind::Streaming, codec, request, path)
+    }
+
+    /// Send a request to a method of `kind`, as a stream of messages.
+    fn send<B, M, R>(
+        &mut self,
+        kind: MethodKind,
+        request: crate::Request<B>,
+        path: uri::PathAndQuery,
+    ) -> streaming::ResponseFuture<M, T::Future>
+    where
+        T: GrpcService<R>,
+        B: Encodable<R>,
+    {
+        // Convert the request body
+        let request = request.map(Encodable::into_encode);
+
+        // TODO: Don't hard code this here
+        let response = self.call(kind, request, path, "application/grpc+proto");
+
+        streaming::ResponseFuture::new(Decoder::new(), response)
+    }
+
+    /// Send a request to a method of `kind`, as a stream of messages encoded
+    /// with `codec`.
+    fn send_with_codec<C, B>(
+        &mut self,
+        kind: MethodKind,
+        mut codec: C,
+        request: crate::Request<B>,
+        path: uri::PathAndQuery,
+    ) -> generic::streaming::ResponseFuture<C::Decoder, T::Future>
+    where
+        T: GrpcService<BoxBody>,
+        C: Codec,
+        C::Encoder: Send + 'static,
+        B: Stream<Item = C::Encode, Error = crate::Status> + Send + 'static,
+    {
+        let encoder = codec.encoder();
+        let request = request.map(|v| BoxBody::new(Box::new(Encode::request(encoder, v))));
+
+        let response = self.call(kind, request, path, C::Encoder::CONTENT_TYPE);
+
+        generic::streaming::ResponseFuture::new(codec.decoder(), response)
+    }
+
+    fn call<R>(
+        &mut self,
+        kind: MethodKind,
+        request: crate::Request<R>,
+        path: uri::PathAndQuery,
+        content_type: &'static str,
+    ) -> T::Future
+    where
+        T: GrpcService<R>,
     {
         use http::header::{self, HeaderValue};
 
@@ -129,28 +270,24 @@ impl<T> Grpc<T> {
         // Get the URI;
         let uri = Uri::from_parts(parts).expect("path_and_query only is valid Uri");
 
-        // Convert the request body
-        let request = request.map(Encodable::into_encode);
-
         // Convert to an HTTP request
         let mut request = request.into_http(uri);
 
+        // Let middleware, such as `metrics::Metrics`, know the kind of method
+        request.extensions_mut().insert(kind);
+
         // Add the gRPC related HTTP headers
         request
             .headers_mut()
             .insert(header::TE, HeaderValue::from_static("trailers"));
 
         // Set the content type
-        // TODO: Don't hard code this here
-        let content_type = "application/grpc+proto";
         request
             .headers_mut()
             .insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
 
         // Call the inner HTTP service
-        let response = self.inner.call(request);
-
-        streaming::ResponseFuture::new(response)
+        self.inner.call(request)
     }
 }
 
@@ -162,10 +299,7 @@ where
     U: Message + 'static,
 {
     fn into_encode(self) -> BoxBody {
-        use crate::codec::Encoder;
-        use crate::generic::Encode;
-
-        let encode = Encode::request(Encoder::new(), self);
+        let encode = Encode::request(crate::codec::Encoder::new(), self);
         BoxBody::new(Box::new(encode))
     }
 }
@@ -1,51 +1,11 @@
-use super::client_streaming;
-use crate::error::Error;
-use crate::Body;
+pub use crate::generic::client::unary::Once;
 
-use futures::{stream, Future, Poll};
-use http::Response;
-use prost::Message;
-use std::fmt;
+use crate::codec::Decoder;
+use crate::generic::client::unary;
 
-pub struct ResponseFuture<T, U, B: Body> {
-    inner: client_streaming::ResponseFuture<T, U, B>,
-}
+/// Future for a `unary` call whose response message is a protobuf `T`.
+pub type ResponseFuture<T, U, B> = unary::ResponseFuture<Decoder<T>, U, B>;
 
-pub type Once<T> = stream::Once<T, crate::Status>;
-
-impl<T, U, B: Body> ResponseFuture<T, U, B> {
-    /// Create a new client-streaming response future.
-    pub(crate) fn new(inner: client_streaming::ResponseFuture<T, U, B>) -> Self {
-        ResponseFuture { inner }
-    }
-}
-
-impl<T, U, B> Future for ResponseFuture<T, U, B>
-where
-    T: Message + Default,
-    U: Future<Item = Response<B>>,
-    U::Error: Into<Error>,
-    B: Body,
-    B::Error: Into<Error>,
-{
-    type Item = crate::Response<T>;
-    type Error = crate::Status;
-
-    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
-        self.inner.poll()
-    }
-}
-
-impl<T, U, B> fmt::Debug for ResponseFuture<T, U, B>
-where
-    T: fmt::Debug,
-    U: fmt::Debug,
-    B: Body + fmt::Debug,
-    B::Data: fmt::Debug,
-{
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.debug_struct("ResponseFuture")
-            .field("inner", &self.inner)
-            .finish()
-    }
-}
+/// Future for a `unary` call whose response message is a protobuf `T`, over
+/// the transport `S`, as returned by generated clients with named futures.
+pub type Call<T, S> = unary::Call<Decoder<T>, S>;
@@ -22,8 +22,6 @@ pub struct Decoder<T>(PhantomData<T>);
 /// A stream of inbound gRPC messages
 pub type Streaming<T, B = BoxBody> = crate::generic::Streaming<Decoder<T>, B>;
 
-pub(crate) use crate::generic::Direction;
-
 /// A protobuf encoded gRPC response body
 pub struct Encode<T>
 where
@@ -110,10 +108,7 @@ impl<T> Clone for Encoder<T> {
 
 // ===== impl Decoder =====
 
-impl<T> Decoder<T>
-where
-    T: Message + Default,
-{
+impl<T> Decoder<T> {
     /// Returns a new decoder
     pub fn new() -> Self {
         Decoder(PhantomData)
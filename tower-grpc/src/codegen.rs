@@ -3,11 +3,15 @@ pub mod server {
     /// Re-export types from this crate
     pub mod grpc {
         pub use crate::codec::{Encode, Streaming};
+        pub use crate::descriptor::{IdempotencyLevel, MethodDescriptor, MethodKind};
+        pub use crate::generic;
         pub use crate::generic::server::{
-            ClientStreamingService, ServerStreamingService, StreamingService, UnaryService,
+            ClientStreamingService, NamedService, ServerStreamingService, StreamingService,
+            UnaryService,
         };
         pub use crate::server::{
-            client_streaming, server_streaming, streaming, unary, unimplemented,
+            client_streaming, mock, server_streaming, streaming, unary, unimplemented, BoxFuture,
+            BoxStream,
         };
         pub use crate::{error::Never, Body, BoxBody, Code, Request, Response, Status};
     }
@@ -40,11 +44,18 @@ pub mod server {
 pub mod client {
     /// Re-export types from this crate
     pub mod grpc {
+        #[cfg(feature = "blocking")]
+        pub use crate::client::blocking;
         pub use crate::client::{
             client_streaming, server_streaming, streaming, unary, Encodable, Grpc,
         };
+        pub use crate::descriptor::{IdempotencyLevel, MethodDescriptor, MethodKind};
+        pub use crate::generic;
         pub use crate::generic::client::GrpcService;
-        pub use crate::{Body, Code, Request, Response, Status};
+        pub use crate::{
+            Body, BoxBody, Code, IntoRequest, IntoStreamingRequest, Request, Response, Status,
+            Streaming,
+        };
     }
 
     pub mod http {
@@ -53,6 +64,8 @@ pub mod client {
 
     /// Re-export types from the `future` crate.
     pub mod futures {
+        pub use futures::future::poll_fn;
+        pub use futures::stream::{iter_ok, IterOk};
         pub use futures::{Future, Poll, Stream};
     }
 
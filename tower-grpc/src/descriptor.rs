@@ -0,0 +1,129 @@
+//! Static descriptions of gRPC services.
+//!
+//! `tower-grpc-build` generates a table of `MethodDescriptor`s for each
+//! service, available from servers as `NamedService::METHODS` and from
+//! clients as an associated `METHODS` constant.
+
+/// Describes a method of a gRPC service.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MethodDescriptor {
+    /// The name of the method, e.g. `SayHello`.
+    pub name: &'static str,
+
+    /// The path requests to the method are sent to, e.g.
+    /// `/helloworld.Greeter/SayHello`.
+    pub path: &'static str,
+
+    /// The fully qualified name of the request message type, e.g.
+    /// `helloworld.HelloRequest`.
+    pub input_type: &'static str,
+
+    /// The fully qualified name of the response message type, e.g.
+    /// `helloworld.HelloReply`.
+    pub output_type: &'static str,
+
+    /// Whether requests and responses are streamed.
+    pub kind: MethodKind,
+
+    /// The `idempotency_level` option of the method.
+    pub idempotency_level: IdempotencyLevel,
+
+    /// The `deprecated` option of the method.
+    pub deprecated: bool,
+
+    /// The encoded custom options of the method, such as
+    /// `(auth.scope) = "admin"`, which can be decoded as a message with
+    /// fields for the extensions of `google.protobuf.MethodOptions`.
+    ///
+    /// Custom options are only available to code generated from a
+    /// `FileDescriptorSet`, as `prost` discards them when compiling protos.
+    pub extensions: &'static [u8],
+}
+
+/// Whether a method streams its requests and responses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MethodKind {
+    /// A single request and a single response.
+    Unary,
+    /// A stream of requests and a single response.
+    ClientStreaming,
+    /// A single request and a stream of responses.
+    ServerStreaming,
+    /// A stream of requests and a stream of responses.
+    Streaming,
+}
+
+/// Whether a method has side effects, as set by its `idempotency_level`
+/// option.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IdempotencyLevel {
+    /// The method may have side effects.
+    Unknown,
+    /// The method has no side effects, as `NO_SIDE_EFFECTS`.
+    NoSideEffects,
+    /// Repeating a call to the method has the same effect as the first
+    /// call, as `IDEMPOTENT`.
+    Idempotent,
+}
+
+// ===== impl MethodKind =====
+
+impl MethodKind {
+    /// Returns `true` if the method takes a stream of requests.
+    pub fn is_client_streaming(&self) -> bool {
+        match *self {
+            MethodKind::ClientStreaming | MethodKind::Streaming => true,
+            MethodKind::Unary | MethodKind::ServerStreaming => false,
+        }
+    }
+
+    /// Returns `true` if the method responds with a stream.
+    pub fn is_server_streaming(&self) -> bool {
+        match *self {
+            MethodKind::ServerStreaming | MethodKind::Streaming => true,
+            MethodKind::Unary | MethodKind::ClientStreaming => false,
+        }
+    }
+}
+
+// ===== impl IdempotencyLevel =====
+
+impl IdempotencyLevel {
+    /// Returns `true` if calls to the method may safely be retried.
+    pub fn is_idempotent(&self) -> bool {
+        match *self {
+            IdempotencyLevel::NoSideEffects | IdempotencyLevel::Idempotent => true,
+            IdempotencyLevel::Unknown => false,
+        }
+    }
+}
+
+impl Default for IdempotencyLevel {
+    fn default() -> Self {
+        IdempotencyLevel::Unknown
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn streaming_kinds() {
+        assert!(!MethodKind::Unary.is_client_streaming());
+        assert!(!MethodKind::Unary.is_server_streaming());
+        assert!(MethodKind::ClientStreaming.is_client_streaming());
+        assert!(!MethodKind::ClientStreaming.is_server_streaming());
+        assert!(!MethodKind::ServerStreaming.is_client_streaming());
+        assert!(MethodKind::ServerStreaming.is_server_streaming());
+        assert!(MethodKind::Streaming.is_client_streaming());
+        assert!(MethodKind::Streaming.is_server_streaming());
+    }
+
+    #[test]
+    fn idempotency_levels() {
+        assert!(!IdempotencyLevel::default().is_idempotent());
+        assert!(IdempotencyLevel::NoSideEffects.is_idempotent());
+        assert!(IdempotencyLevel::Idempotent.is_idempotent());
+    }
+}
@@ -0,0 +1,89 @@
+/// The length of the header preceding each message in a body.
+const HEADER_LEN: usize = 5;
+
+/// Finds the messages framed in a body's data, as it is read.
+///
+/// Used by middleware observing messages without decoding them.
+#[derive(Debug, Default)]
+pub(crate) struct Frames {
+    /// The header of the next message, as it is read.
+    header: [u8; HEADER_LEN],
+    header_len: usize,
+    /// The bytes of the current message not yet read.
+    remaining: usize,
+}
+
+/// The header of a message found in a body.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Frame {
+    pub(crate) compressed: bool,
+    pub(crate) len: u32,
+}
+
+impl Frames {
+    /// Read the next chunk of the body, calling `f` with each message header
+    /// completed in it.
+    pub(crate) fn read<F>(&mut self, mut buf: &[u8], mut f: F)
+    where
+        F: FnMut(Frame),
+    {
+        while !buf.is_empty() {
+            if self.remaining > 0 {
+                let n = self.remaining.min(buf.len());
+                self.remaining -= n;
+                buf = &buf[n..];
+                continue;
+            }
+
+            let n = (HEADER_LEN - self.header_len).min(buf.len());
+            self.header[self.header_len..self.header_len + n].copy_from_slice(&buf[..n]);
+            self.header_len += n;
+            buf = &buf[n..];
+
+            if self.header_len == HEADER_LEN {
+                let frame = Frame {
+                    compressed: self.header[0] == 1,
+                    len: u32::from_be_bytes([
+                        self.header[1],
+                        self.header[2],
+                        self.header[3],
+                        self.header[4],
+                    ]),
+                };
+
+                self.header_len = 0;
+                self.remaining = frame.len as usize;
+                f(frame);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_across_chunks() {
+        let mut frames = Frames::default();
+        let mut found = Vec::new();
+
+        frames.read(&[0, 0, 0], |frame| found.push(frame));
+        assert!(found.is_empty());
+
+        frames.read(&[0, 2, 0xaa, 0xbb, 1, 0, 0, 0, 0], |frame| {
+            found.push(frame)
+        });
+        frames.read(&[0, 0, 0, 0, 3, 0xaa], |frame| found.push(frame));
+        assert_eq!(frames.remaining, 2);
+
+        frames.read(&[0xbb, 0xcc], |frame| found.push(frame));
+        assert_eq!(frames.remaining, 0);
+
+        let frame = |compressed, len| Frame { compressed, len };
+        assert_eq!(
+            found,
+            vec![frame(false, 2), frame(true, 0), frame(false, 3)]
+        );
+    }
+}
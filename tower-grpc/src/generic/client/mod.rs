@@ -1,3 +1,8 @@
+pub mod client_streaming;
+pub mod server_streaming;
+pub mod streaming;
+pub mod unary;
+
 use crate::body::{Body, HttpBody};
 use crate::error::Error;
 
@@ -0,0 +1,37 @@
+use super::{streaming, GrpcService};
+use crate::error::Error;
+use crate::generic::{Decoder, Streaming};
+use crate::{Body, BoxBody};
+
+use futures::{Future, Poll};
+use http::Response;
+
+#[derive(Debug)]
+pub struct ResponseFuture<T, U> {
+    inner: streaming::ResponseFuture<T, U>,
+}
+
+/// Future for a `server_streaming` call sending a `BoxBody` over the transport `S`.
+pub type Call<T, S> = ResponseFuture<T, <S as GrpcService<BoxBody>>::Future>;
+
+impl<T, U> ResponseFuture<T, U> {
+    /// Create a new client-streaming response future.
+    pub(crate) fn new(inner: streaming::ResponseFuture<T, U>) -> Self {
+        ResponseFuture { inner }
+    }
+}
+
+impl<T, U, B> Future for ResponseFuture<T, U>
+where
+    T: Decoder,
+    U: Future<Item = Response<B>>,
+    U::Error: Into<Error>,
+    B: Body,
+{
+    type Item = crate::Response<Streaming<T, B>>;
+    type Error = crate::Status;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        self.inner.poll()
+    }
+}
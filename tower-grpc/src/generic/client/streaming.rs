@@ -0,0 +1,71 @@
+use super::GrpcService;
+use crate::error::Error;
+use crate::generic::{Decoder, Direction, Streaming};
+use crate::{Body, BoxBody, Code};
+
+use futures::{try_ready, Future, Poll};
+use http::Response;
+
+#[derive(Debug)]
+pub struct ResponseFuture<T, U> {
+    decoder: Option<T>,
+    inner: U,
+}
+
+/// Future for a `streaming` call sending a `BoxBody` over the transport `S`.
+pub type Call<T, S> = ResponseFuture<T, <S as GrpcService<BoxBody>>::Future>;
+
+impl<T, U> ResponseFuture<T, U> {
+    /// Create a new streaming response future, decoding the response
+    /// messages with `decoder`.
+    pub(crate) fn new(decoder: T, inner: U) -> Self {
+        ResponseFuture {
+            decoder: Some(decoder),
+            inner,
+        }
+    }
+}
+
+impl<T, U, B> Future for ResponseFuture<T, U>
+where
+    T: Decoder,
+    U: Future<Item = Response<B>>,
+    U::Error: Into<Error>,
+    B: Body,
+{
+    type Item = crate::Response<Streaming<T, B>>;
+    type Error = crate::Status;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        // Get the response
+        let response = try_ready!(self
+            .inner
+            .poll()
+            .map_err(|err| crate::Status::from_error(&*(err.into()))));
+
+        let status_code = response.status();
+
+        // Check the headers for `grpc-status`, in which case we should not parse the body.
+        let trailers_only_status = crate::Status::from_header_map(response.headers());
+        let expect_additional_trailers = trailers_only_status.is_none();
+        if let Some(status) = trailers_only_status {
+            if status.code() != Code::Ok {
+                return Err(status);
+            }
+        }
+
+        let streaming_direction = if expect_additional_trailers {
+            Direction::Response(status_code)
+        } else {
+            Direction::EmptyResponse
+        };
+
+        let decoder = self
+            .decoder
+            .take()
+            .expect("response future polled after completion");
+        let response = response.map(move |body| Streaming::new(decoder, body, streaming_direction));
+
+        Ok(crate::Response::from_http(response).into())
+    }
+}
@@ -0,0 +1,58 @@
+use super::{client_streaming, GrpcService};
+use crate::error::Error;
+use crate::generic::Decoder;
+use crate::{Body, BoxBody};
+
+use futures::{stream, Future, Poll};
+use http::Response;
+use std::fmt;
+
+pub struct ResponseFuture<T, U, B: Body> {
+    inner: client_streaming::ResponseFuture<T, U, B>,
+}
+
+/// Future for a `unary` call sending a `BoxBody` over the transport `S`.
+pub type Call<T, S> = ResponseFuture<
+    T,
+    <S as GrpcService<BoxBody>>::Future,
+    <S as GrpcService<BoxBody>>::ResponseBody,
+>;
+
+pub type Once<T> = stream::Once<T, crate::Status>;
+
+impl<T, U, B: Body> ResponseFuture<T, U, B> {
+    /// Create a new client-streaming response future.
+    pub(crate) fn new(inner: client_streaming::ResponseFuture<T, U, B>) -> Self {
+        ResponseFuture { inner }
+    }
+}
+
+impl<T, U, B> Future for ResponseFuture<T, U, B>
+where
+    T: Decoder,
+    U: Future<Item = Response<B>>,
+    U::Error: Into<Error>,
+    B: Body,
+    B::Error: Into<Error>,
+{
+    type Item = crate::Response<T::Item>;
+    type Error = crate::Status;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        self.inner.poll()
+    }
+}
+
+impl<T, U, B> fmt::Debug for ResponseFuture<T, U, B>
+where
+    T: fmt::Debug,
+    U: fmt::Debug,
+    B: Body + fmt::Debug,
+    B::Data: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ResponseFuture")
+            .field("inner", &self.inner)
+            .finish()
+    }
+}
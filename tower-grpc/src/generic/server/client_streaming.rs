@@ -22,7 +22,7 @@ where
     T: Future<Item = Response<E::Item>, Error = crate::Status>,
     E: Encoder,
 {
-    pub fn new(inner: T, encoder: E) -> Self {
+    pub(crate) fn new(inner: T, encoder: E) -> Self {
         let inner = Inner { inner };
         let inner = streaming::ResponseFuture::new(inner, encoder);
         ResponseFuture { inner }
@@ -1,17 +1,34 @@
 mod grpc;
 
-pub(crate) mod client_streaming;
-pub(crate) mod server_streaming;
-pub(crate) mod streaming;
-pub(crate) mod unary;
+pub mod client_streaming;
+pub mod server_streaming;
+pub mod streaming;
+pub mod unary;
 
 pub(crate) use self::grpc::Grpc;
 
-use crate::{Request, Response};
+use crate::descriptor::MethodDescriptor;
+use crate::generic::{Codec, Streaming};
+use crate::{Body, Request, Response};
 
 use futures::{Future, Stream};
 use tower_service::Service;
 
+/// A gRPC service that can be routed to by its fully qualified name.
+///
+/// Implemented by generated servers.
+pub trait NamedService {
+    /// The fully qualified name of the service, e.g. `helloworld.Greeter`.
+    const NAME: &'static str;
+
+    /// The methods of the service.
+    ///
+    /// Middleware such as `metrics::Metrics` looks up the kind of each call
+    /// here. Calls to methods that are not listed, as by services keeping
+    /// the default of no methods, are treated as unary calls.
+    const METHODS: &'static [MethodDescriptor] = &[];
+}
+
 /// A specialization of tower_service::Service.
 ///
 /// Existing tower_service::Service implementations with the correct form will
@@ -131,3 +148,62 @@ where
         Service::call(self, request)
     }
 }
+
+/// Handle a unary request, decoding it and encoding the response with `codec`.
+pub fn unary<C, S, B>(
+    codec: C,
+    service: S,
+    request: http::Request<B>,
+) -> unary::ResponseFuture<S, C::Encoder, Streaming<C::Decoder, B>>
+where
+    C: Codec,
+    S: UnaryService<C::Decode, Response = C::Encode>,
+    B: Body,
+{
+    Grpc::new(codec).unary(service, request)
+}
+
+/// Handle a client streaming request, decoding it and encoding the response
+/// with `codec`.
+pub fn client_streaming<C, S, B>(
+    codec: C,
+    service: &mut S,
+    request: http::Request<B>,
+) -> client_streaming::ResponseFuture<S::Future, C::Encoder>
+where
+    C: Codec,
+    S: ClientStreamingService<Streaming<C::Decoder, B>, Response = C::Encode>,
+    B: Body,
+{
+    Grpc::new(codec).client_streaming(service, request)
+}
+
+/// Handle a server streaming request, decoding it and encoding the response
+/// with `codec`.
+pub fn server_streaming<C, S, B>(
+    codec: C,
+    service: S,
+    request: http::Request<B>,
+) -> server_streaming::ResponseFuture<S, C::Encoder, Streaming<C::Decoder, B>>
+where
+    C: Codec,
+    S: ServerStreamingService<C::Decode, Response = C::Encode>,
+    B: Body,
+{
+    Grpc::new(codec).server_streaming(service, request)
+}
+
+/// Handle a bidirectional streaming request, decoding it and encoding the
+/// response with `codec`.
+pub fn streaming<C, S, B>(
+    codec: C,
+    service: &mut S,
+    request: http::Request<B>,
+) -> streaming::ResponseFuture<S::Future, C::Encoder>
+where
+    C: Codec,
+    S: StreamingService<Streaming<C::Decoder, B>, Response = C::Encode>,
+    B: Body,
+{
+    Grpc::new(codec).streaming(service, request)
+}
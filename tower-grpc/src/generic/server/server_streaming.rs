@@ -41,7 +41,7 @@ where
     E: Encoder,
     S: Stream<Error = crate::Status>,
 {
-    pub fn new(inner: T, request: Request<S>, encoder: E) -> Self {
+    pub(crate) fn new(inner: T, request: Request<S>, encoder: E) -> Self {
         let inner = Inner {
             inner,
             state: Some(State::Requesting(request)),
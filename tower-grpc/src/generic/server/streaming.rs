@@ -19,7 +19,7 @@ where
     E: Encoder,
     S: Stream<Item = E::Item>,
 {
-    pub fn new(inner: T, encoder: E) -> Self {
+    pub(crate) fn new(inner: T, encoder: E) -> Self {
         ResponseFuture {
             inner,
             encoder: Some(encoder),
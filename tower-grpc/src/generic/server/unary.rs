@@ -36,7 +36,7 @@ where
     E: Encoder,
     S: Stream<Error = crate::Status>,
 {
-    pub fn new(inner: T, request: Request<S>, encoder: E) -> Self {
+    pub(crate) fn new(inner: T, request: Request<S>, encoder: E) -> Self {
         let inner = server_streaming::ResponseFuture::new(Inner(inner), request, encoder);
         ResponseFuture { inner }
     }
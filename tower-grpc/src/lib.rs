@@ -7,17 +7,24 @@
 //! gRPC client and server implementation based on Tower.
 
 pub mod client;
+pub mod descriptor;
 pub mod generic;
 pub mod metadata;
+pub mod metrics;
+pub mod propagation;
 
 mod body;
 mod error;
+mod frames;
 mod request;
 mod response;
 mod status;
 
+#[cfg(test)]
+mod test_util;
+
 pub use crate::body::{Body, BoxBody};
-pub use crate::request::Request;
+pub use crate::request::{IntoRequest, IntoStreamingRequest, Request};
 pub use crate::response::Response;
 pub use crate::status::{Code, Status};
 
@@ -33,3 +40,12 @@ mod codec;
 
 #[cfg(feature = "protobuf")]
 pub use crate::codec::{Encode, Streaming};
+
+#[cfg(feature = "transport")]
+pub mod transport;
+
+#[cfg(feature = "transport")]
+pub mod testing;
+
+#[cfg(feature = "tracing")]
+pub mod trace;
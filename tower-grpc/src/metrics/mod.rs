@@ -0,0 +1,594 @@
+//! RPC metrics, as counted by go-grpc-prometheus.
+//!
+//! `Metrics` wraps either a server's service or a client's HTTP service,
+//! and reports each RPC to a `Recorder`: when it starts, each message sent
+//! and received, and its final `Code` and latency once it is handled.
+//!
+//! RPCs are labeled with their `MethodKind`: servers look it up in the
+//! wrapped service's `NamedService::METHODS`, and clients read it from the
+//! requests sent with `client::Grpc`. Methods not found either way are
+//! counted as unary.
+//!
+//! `Registry` is a `Recorder` keeping the same metrics as go-grpc-prometheus,
+//! such as `grpc_server_handled_total`, and rendering them in the Prometheus
+//! text format. Other metrics libraries can be used by implementing
+//! `Recorder`.
+//!
+//! ```ignore
+//! let registry = Registry::new();
+//!
+//! let server = Server::builder()
+//!     .add_service(Metrics::server(GreeterServer::new(Greet), registry.clone()));
+//!
+//! // Served on a `/metrics` endpoint:
+//! let text = registry.render();
+//! ```
+
+mod registry;
+
+pub use self::registry::Registry;
+
+use crate::body::{Body, BoxBody, HttpBody};
+use crate::descriptor::{MethodDescriptor, MethodKind};
+use crate::frames::Frames;
+use crate::generic::server::NamedService;
+use crate::{Code, Status};
+
+use bytes::Buf;
+use futures::{try_ready, Async, Future, Poll};
+use http::HeaderMap;
+use std::error::Error;
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tower_service::Service;
+
+/// Receives the events of each RPC.
+pub trait Recorder: Send + Sync + 'static {
+    /// Called when `rpc` is started.
+    fn started(&self, rpc: &Rpc);
+
+    /// Called when `rpc` completes with `code`, `latency` after it started.
+    fn handled(&self, rpc: &Rpc, code: Code, latency: Duration);
+
+    /// Called for each message of `rpc` that is received.
+    fn message_received(&self, rpc: &Rpc);
+
+    /// Called for each message of `rpc` that is sent.
+    fn message_sent(&self, rpc: &Rpc);
+}
+
+/// Identifies an RPC to a `Recorder`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Rpc {
+    side: Side,
+    kind: MethodKind,
+    service: String,
+    method: String,
+}
+
+/// Whether an RPC is being served or made.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Side {
+    Client,
+    Server,
+}
+
+/// Reports the RPCs made to or by the inner service to a `Recorder`.
+#[derive(Clone)]
+pub struct Metrics<S> {
+    inner: S,
+    side: Side,
+    /// The methods served, to find the kind of each RPC on servers.
+    methods: &'static [MethodDescriptor],
+    recorder: Arc<dyn Recorder>,
+}
+
+/// Completes the inner service's response, recording its status if it
+/// failed or has no body.
+pub struct ResponseFuture<F> {
+    inner: F,
+    handled: Option<Handled>,
+}
+
+/// Counts the messages of a response body, recording the RPC's status once
+/// the body completes.
+pub struct ResponseBody<B> {
+    inner: B,
+    messages: Messages,
+    handled: Option<Handled>,
+}
+
+/// Counts the messages of a request body.
+struct RequestBody<B> {
+    inner: B,
+    messages: Messages,
+}
+
+/// Reports the messages framed in a body.
+struct Messages {
+    rpc: Arc<Rpc>,
+    recorder: Arc<dyn Recorder>,
+    sent: bool,
+    frames: Frames,
+}
+
+/// Reports an RPC as handled, once.
+struct Handled {
+    rpc: Arc<Rpc>,
+    recorder: Arc<dyn Recorder>,
+    start: Instant,
+}
+
+// ===== impl Rpc =====
+
+impl Rpc {
+    fn new(side: Side, kind: MethodKind, path: &str) -> Self {
+        let mut parts = path.trim_start_matches('/').splitn(2, '/');
+
+        Rpc {
+            side,
+            kind,
+            service: parts.next().unwrap_or("").to_owned(),
+            method: parts.next().unwrap_or("").to_owned(),
+        }
+    }
+
+    /// Returns whether the RPC is being served or made.
+    pub fn side(&self) -> Side {
+        self.side
+    }
+
+    /// Returns whether the RPC streams its requests and responses.
+    pub fn kind(&self) -> MethodKind {
+        self.kind
+    }
+
+    /// Returns the full name of the service, such as `helloworld.Greeter`.
+    pub fn service(&self) -> &str {
+        &self.service
+    }
+
+    /// Returns the name of the method, such as `SayHello`.
+    pub fn method(&self) -> &str {
+        &self.method
+    }
+}
+
+// ===== impl Metrics =====
+
+impl<S> Metrics<S> {
+    /// Record the RPCs a server's service receives.
+    pub fn server<R: Recorder>(inner: S, recorder: R) -> Self
+    where
+        S: NamedService,
+    {
+        Metrics {
+            inner,
+            side: Side::Server,
+            methods: S::METHODS,
+            recorder: Arc::new(recorder),
+        }
+    }
+
+    /// Record the RPCs sent through a client's HTTP service.
+    pub fn client<R: Recorder>(inner: S, recorder: R) -> Self {
+        Metrics {
+            inner,
+            side: Side::Client,
+            methods: &[],
+            recorder: Arc::new(recorder),
+        }
+    }
+
+    /// Get a reference to the inner service.
+    pub fn get_ref(&self) -> &S {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the inner service.
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.inner
+    }
+
+    /// Consumes `self`, returning the inner service.
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl<S, B> Service<http::Request<BoxBody>> for Metrics<S>
+where
+    S: Service<http::Request<BoxBody>, Response = http::Response<B>>,
+    S::Error: Error + 'static,
+    B: Body,
+{
+    type Response = http::Response<ResponseBody<B>>;
+    type Error = S::Error;
+    type Future = ResponseFuture<S::Future>;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        self.inner.poll_ready()
+    }
+
+    fn call(&mut self, request: http::Request<BoxBody>) -> Self::Future {
+        let path = request.uri().path();
+        let kind = match self.side {
+            Side::Server => self
+                .methods
+                .iter()
+                .find(|method| method.path == path)
+                .map(|method| method.kind),
+            Side::Client => request.extensions().get::<MethodKind>().cloned(),
+        };
+
+        let rpc = Arc::new(Rpc::new(self.side, kind.unwrap_or(MethodKind::Unary), path));
+        self.recorder.started(&rpc);
+
+        let messages = Messages {
+            rpc: rpc.clone(),
+            recorder: self.recorder.clone(),
+            sent: self.side == Side::Client,
+            frames: Frames::default(),
+        };
+        let request = request.map(|inner| BoxBody::new(Box::new(RequestBody { inner, messages })));
+
+        ResponseFuture {
+            inner: self.inner.call(request),
+            handled: Some(Handled {
+                rpc,
+                recorder: self.recorder.clone(),
+                start: Instant::now(),
+            }),
+        }
+    }
+}
+
+impl<S: NamedService> NamedService for Metrics<S> {
+    const NAME: &'static str = S::NAME;
+    const METHODS: &'static [MethodDescriptor] = S::METHODS;
+}
+
+impl<S: fmt::Debug> fmt::Debug for Metrics<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Metrics")
+            .field("inner", &self.inner)
+            .field("side", &self.side)
+            .finish()
+    }
+}
+
+// ===== impl ResponseFuture =====
+
+impl<F, B> Future for ResponseFuture<F>
+where
+    F: Future<Item = http::Response<B>>,
+    F::Error: Error + 'static,
+    B: Body,
+{
+    type Item = http::Response<ResponseBody<B>>;
+    type Error = F::Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        let response = match self.inner.poll() {
+            Ok(Async::Ready(response)) => response,
+            Ok(Async::NotReady) => return Ok(Async::NotReady),
+            Err(e) => {
+                let handled = self.handled.take().expect("polled after complete");
+                handled.record(Status::from_error(&e).code());
+                return Err(e);
+            }
+        };
+
+        let handled = self.handled.take().expect("polled after complete");
+        let messages = Messages {
+            rpc: handled.rpc.clone(),
+            recorder: handled.recorder.clone(),
+            sent: handled.rpc.side == Side::Server,
+            frames: Frames::default(),
+        };
+
+        // Trailers-only responses carry their status in the headers.
+        let handled = match Status::from_header_map(response.headers()) {
+            Some(status) => {
+                handled.record(status.code());
+                None
+            }
+            None => Some(handled),
+        };
+
+        Ok(Async::Ready(response.map(|inner| ResponseBody {
+            inner,
+            messages,
+            handled,
+        })))
+    }
+}
+
+impl<F> Drop for ResponseFuture<F> {
+    fn drop(&mut self) {
+        // The RPC was dropped before its response was received.
+        if let Some(handled) = self.handled.take() {
+            handled.record(Code::Cancelled);
+        }
+    }
+}
+
+impl<F> fmt::Debug for ResponseFuture<F>
+where
+    F: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ResponseFuture")
+            .field("inner", &self.inner)
+            .finish()
+    }
+}
+
+// ===== impl ResponseBody =====
+
+impl<B> HttpBody for ResponseBody<B>
+where
+    B: Body,
+{
+    type Data = B::Data;
+    type Error = B::Error;
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn poll_data(&mut self) -> Poll<Option<Self::Data>, Self::Error> {
+        let data = match self.inner.poll_data() {
+            Ok(Async::Ready(data)) => data,
+            Ok(Async::NotReady) => return Ok(Async::NotReady),
+            Err(e) => {
+                self.finish(Code::Unknown);
+                return Err(e);
+            }
+        };
+
+        if let Some(ref data) = data {
+            self.messages.read(data.bytes());
+        }
+
+        Ok(Async::Ready(data))
+    }
+
+    fn poll_trailers(&mut self) -> Poll<Option<HeaderMap>, Self::Error> {
+        let trailers = match self.inner.poll_trailers() {
+            Ok(Async::Ready(trailers)) => trailers,
+            Ok(Async::NotReady) => return Ok(Async::NotReady),
+            Err(e) => {
+                self.finish(Code::Unknown);
+                return Err(e);
+            }
+        };
+
+        let code = trailers
+            .as_ref()
+            .and_then(Status::from_header_map)
+            .map_or(Code::Unknown, |status| status.code());
+        self.finish(code);
+
+        Ok(Async::Ready(trailers))
+    }
+}
+
+impl<B> ResponseBody<B> {
+    fn finish(&mut self, code: Code) {
+        if let Some(handled) = self.handled.take() {
+            handled.record(code);
+        }
+    }
+}
+
+impl<B> Drop for ResponseBody<B> {
+    fn drop(&mut self) {
+        // The body was dropped before its status was received.
+        self.finish(Code::Cancelled);
+    }
+}
+
+impl<B> fmt::Debug for ResponseBody<B>
+where
+    B: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ResponseBody")
+            .field("inner", &self.inner)
+            .finish()
+    }
+}
+
+// ===== impl RequestBody =====
+
+impl HttpBody for RequestBody<BoxBody> {
+    type Data = <BoxBody as Body>::Data;
+    type Error = Status;
+
+    fn is_end_stream(&self) -> bool {
+        Body::is_end_stream(&self.inner)
+    }
+
+    fn poll_data(&mut self) -> Poll<Option<Self::Data>, Self::Error> {
+        let data = try_ready!(Body::poll_data(&mut self.inner));
+
+        if let Some(ref data) = data {
+            self.messages.read(data.bytes());
+        }
+
+        Ok(Async::Ready(data))
+    }
+
+    fn poll_trailers(&mut self) -> Poll<Option<HeaderMap>, Self::Error> {
+        Body::poll_trailers(&mut self.inner)
+    }
+}
+
+// ===== impl Messages =====
+
+impl Messages {
+    fn read(&mut self, buf: &[u8]) {
+        let rpc = &self.rpc;
+        let recorder = &self.recorder;
+        let sent = self.sent;
+
+        self.frames.read(buf, |_| {
+            if sent {
+                recorder.message_sent(rpc);
+            } else {
+                recorder.message_received(rpc);
+            }
+        });
+    }
+}
+
+// ===== impl Handled =====
+
+impl Handled {
+    fn record(self, code: Code) {
+        self.recorder.handled(&self.rpc, code, self.start.elapsed());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::Grpc;
+    use crate::test_util::{Empty, NotFound};
+    use crate::Request;
+
+    use futures::{future, Stream};
+
+    /// Never responds.
+    struct Pending;
+
+    impl Service<http::Request<BoxBody>> for Pending {
+        type Response = http::Response<BoxBody>;
+        type Error = Status;
+        type Future = future::Empty<Self::Response, Self::Error>;
+
+        fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+            Ok(Async::Ready(()))
+        }
+
+        fn call(&mut self, _: http::Request<BoxBody>) -> Self::Future {
+            future::empty()
+        }
+    }
+
+    #[test]
+    fn client_metrics() {
+        let registry = Registry::new();
+        let mut client = Grpc::new(Metrics::client(NotFound, registry.clone()));
+
+        for _ in 0..2 {
+            let path = http::uri::PathAndQuery::from_static("/test.NotFound/Get");
+            let status = client
+                .unary::<(), (), BoxBody>(Request::new(()), path)
+                .wait()
+                .unwrap_err();
+            assert_eq!(status.code(), Code::NotFound);
+        }
+
+        let text = registry.render();
+        let labels = "grpc_type=\"unary\",grpc_service=\"test.NotFound\",grpc_method=\"Get\"";
+
+        for line in &[
+            format!("grpc_client_started_total{{{}}} 2", labels),
+            format!(
+                "grpc_client_handled_total{{{},grpc_code=\"NotFound\"}} 2",
+                labels
+            ),
+            format!("grpc_client_msg_sent_total{{{}}} 2", labels),
+            format!("grpc_client_handling_seconds_count{{{}}} 2", labels),
+            format!(
+                "grpc_client_handling_seconds_bucket{{{},le=\"+Inf\"}} 2",
+                labels
+            ),
+        ] {
+            assert!(
+                text.lines().any(|l| l == line),
+                "{} not in:\n{}",
+                line,
+                text
+            );
+        }
+
+        assert!(!text.contains("grpc_client_msg_received_total{"));
+        assert!(!text.contains("grpc_server_started_total{"));
+    }
+
+    #[test]
+    fn dropped_before_response() {
+        let registry = Registry::new();
+        let mut metrics = Metrics::client(Pending, registry.clone());
+
+        let request = http::Request::post("/test.Pending/Get")
+            .body(BoxBody::new(Box::new(Empty)))
+            .unwrap();
+        let mut response = metrics.call(request);
+        assert!(response.poll().unwrap().is_not_ready());
+        drop(response);
+
+        let text = registry.render();
+        let labels = "grpc_type=\"unary\",grpc_service=\"test.Pending\",grpc_method=\"Get\"";
+
+        for line in &[
+            format!("grpc_client_started_total{{{}}} 1", labels),
+            format!(
+                "grpc_client_handled_total{{{},grpc_code=\"Canceled\"}} 1",
+                labels
+            ),
+            format!("grpc_client_handling_seconds_count{{{}}} 1", labels),
+        ] {
+            assert!(
+                text.lines().any(|l| l == line),
+                "{} not in:\n{}",
+                line,
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn server_metrics() {
+        let registry = Registry::new();
+        let server = Metrics::server(NotFound, registry.clone());
+        let mut client = Grpc::new(Metrics::client(server, registry.clone()));
+
+        let path = http::uri::PathAndQuery::from_static("/test.NotFound/List");
+        let response = client
+            .server_streaming::<(), (), BoxBody>(Request::new(()), path)
+            .wait()
+            .unwrap();
+        let status = response.into_inner().collect().wait().unwrap_err();
+        assert_eq!(status.code(), Code::NotFound);
+
+        let text = registry.render();
+        let labels =
+            "grpc_type=\"server_stream\",grpc_service=\"test.NotFound\",grpc_method=\"List\"";
+
+        for line in &[
+            format!("grpc_server_started_total{{{}}} 1", labels),
+            format!(
+                "grpc_server_handled_total{{{},grpc_code=\"NotFound\"}} 1",
+                labels
+            ),
+            format!("grpc_server_msg_received_total{{{}}} 1", labels),
+            format!("grpc_server_handling_seconds_count{{{}}} 1", labels),
+            format!("grpc_client_started_total{{{}}} 1", labels),
+            format!("grpc_client_msg_sent_total{{{}}} 1", labels),
+        ] {
+            assert!(
+                text.lines().any(|l| l == line),
+                "{} not in:\n{}",
+                line,
+                text
+            );
+        }
+
+        assert!(!text.contains("grpc_server_msg_sent_total{"));
+    }
+}
@@ -0,0 +1,353 @@
+use super::{Recorder, Rpc, Side};
+use crate::descriptor::MethodKind;
+use crate::Code;
+
+use std::collections::BTreeMap;
+use std::fmt::{self, Write};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// The default upper bounds, in seconds, of the latency histogram buckets.
+///
+/// These are Prometheus' default buckets, as used by go-grpc-prometheus.
+const DEFAULT_BUCKETS: &[f64] = &[
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// Keeps RPC metrics in memory, to be rendered for Prometheus.
+///
+/// Clones share the same metrics. For each side, the registry keeps the
+/// following metrics, labeled by `grpc_type`, `grpc_service` and
+/// `grpc_method`:
+///
+/// - `grpc_{side}_started_total`
+/// - `grpc_{side}_handled_total`, also labeled by `grpc_code`
+/// - `grpc_{side}_handling_seconds`, a histogram of latencies
+/// - `grpc_{side}_msg_received_total`
+/// - `grpc_{side}_msg_sent_total`
+#[derive(Clone)]
+pub struct Registry {
+    inner: Arc<Mutex<Families>>,
+    buckets: Arc<[f64]>,
+}
+
+type Method = (Side, &'static str, String, String);
+
+#[derive(Default)]
+struct Families {
+    started: BTreeMap<Method, u64>,
+    handled: BTreeMap<(Method, &'static str), u64>,
+    handling: BTreeMap<Method, Histogram>,
+    received: BTreeMap<Method, u64>,
+    sent: BTreeMap<Method, u64>,
+}
+
+struct Histogram {
+    counts: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+// ===== impl Registry =====
+
+impl Registry {
+    /// Returns an empty registry, with the default latency buckets.
+    pub fn new() -> Self {
+        Self::with_buckets(DEFAULT_BUCKETS)
+    }
+
+    /// Returns an empty registry, with latency buckets bounded by `buckets`,
+    /// in seconds.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `buckets` are not in increasing order.
+    pub fn with_buckets(buckets: &[f64]) -> Self {
+        assert!(
+            buckets.windows(2).all(|w| w[0] < w[1]),
+            "buckets must be in increasing order"
+        );
+
+        Registry {
+            inner: Arc::new(Mutex::new(Families::default())),
+            buckets: buckets.into(),
+        }
+    }
+
+    /// Returns the metrics in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let families = self.lock();
+        let mut out = String::new();
+
+        for &side in &[Side::Server, Side::Client] {
+            let name = match side {
+                Side::Server => "server",
+                Side::Client => "client",
+            };
+
+            let counters = [
+                ("started_total", "RPCs started", &families.started),
+                (
+                    "msg_received_total",
+                    "stream messages received",
+                    &families.received,
+                ),
+                ("msg_sent_total", "stream messages sent", &families.sent),
+            ];
+
+            for &(metric, help, counts) in counters.iter() {
+                let counts = counts.iter().filter(|&(m, _)| m.0 == side);
+                let help = format!("Total number of {} on the {}.", help, name);
+                header(&mut out, name, metric, &help, "counter");
+
+                for (method, count) in counts {
+                    writeln!(
+                        out,
+                        "grpc_{}_{}{{{}}} {}",
+                        name,
+                        metric,
+                        labels(method),
+                        count
+                    )
+                    .unwrap();
+                }
+            }
+
+            let help = format!("Total number of RPCs completed on the {}.", name);
+            header(&mut out, name, "handled_total", &help, "counter");
+            for ((method, code), count) in families.handled.iter().filter(|(k, _)| k.0 .0 == side) {
+                writeln!(
+                    out,
+                    "grpc_{}_handled_total{{{},grpc_code=\"{}\"}} {}",
+                    name,
+                    labels(method),
+                    code,
+                    count
+                )
+                .unwrap();
+            }
+
+            let metric = "handling_seconds";
+            let help = format!("Latency in seconds of RPCs completed on the {}.", name);
+            header(&mut out, name, metric, &help, "histogram");
+            for (method, histogram) in families.handling.iter().filter(|(m, _)| m.0 == side) {
+                let labels = labels(method);
+                let mut cumulative = 0;
+
+                for (le, count) in self.buckets.iter().zip(&histogram.counts) {
+                    cumulative += count;
+                    writeln!(
+                        out,
+                        "grpc_{}_{}_bucket{{{},le=\"{}\"}} {}",
+                        name, metric, labels, le, cumulative
+                    )
+                    .unwrap();
+                }
+
+                writeln!(
+                    out,
+                    "grpc_{}_{}_bucket{{{},le=\"+Inf\"}} {}",
+                    name, metric, labels, histogram.count
+                )
+                .unwrap();
+                writeln!(
+                    out,
+                    "grpc_{}_{}_sum{{{}}} {}",
+                    name, metric, labels, histogram.sum
+                )
+                .unwrap();
+                writeln!(
+                    out,
+                    "grpc_{}_{}_count{{{}}} {}",
+                    name, metric, labels, histogram.count
+                )
+                .unwrap();
+            }
+        }
+
+        out
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Families> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Recorder for Registry {
+    fn started(&self, rpc: &Rpc) {
+        *self.lock().started.entry(key(rpc)).or_insert(0) += 1;
+    }
+
+    fn handled(&self, rpc: &Rpc, code: Code, latency: Duration) {
+        let mut families = self.lock();
+        *families
+            .handled
+            .entry((key(rpc), code_name(code)))
+            .or_insert(0) += 1;
+
+        let buckets = &self.buckets;
+        families
+            .handling
+            .entry(key(rpc))
+            .or_insert_with(|| Histogram {
+                counts: vec![0; buckets.len()],
+                sum: 0.0,
+                count: 0,
+            })
+            .observe(buckets, latency);
+    }
+
+    fn message_received(&self, rpc: &Rpc) {
+        *self.lock().received.entry(key(rpc)).or_insert(0) += 1;
+    }
+
+    fn message_sent(&self, rpc: &Rpc) {
+        *self.lock().sent.entry(key(rpc)).or_insert(0) += 1;
+    }
+}
+
+impl fmt::Debug for Registry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Registry")
+            .field("buckets", &self.buckets)
+            .finish()
+    }
+}
+
+// ===== impl Histogram =====
+
+impl Histogram {
+    fn observe(&mut self, buckets: &[f64], latency: Duration) {
+        let seconds = latency.as_secs() as f64 + f64::from(latency.subsec_nanos()) / 1e9;
+
+        if let Some(i) = buckets.iter().position(|&le| seconds <= le) {
+            self.counts[i] += 1;
+        }
+
+        self.sum += seconds;
+        self.count += 1;
+    }
+}
+
+// ===== utility fns =====
+
+fn key(rpc: &Rpc) -> Method {
+    (
+        rpc.side,
+        type_name(rpc.kind),
+        rpc.service.clone(),
+        rpc.method.clone(),
+    )
+}
+
+fn labels(method: &Method) -> String {
+    format!(
+        "grpc_type=\"{}\",grpc_service=\"{}\",grpc_method=\"{}\"",
+        method.1,
+        escape(&method.2),
+        escape(&method.3)
+    )
+}
+
+fn header(out: &mut String, side: &str, metric: &str, help: &str, kind: &str) {
+    writeln!(out, "# HELP grpc_{}_{} {}", side, metric, help).unwrap();
+    writeln!(out, "# TYPE grpc_{}_{} {}", side, metric, kind).unwrap();
+}
+
+/// Escape a label value.
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// The `grpc_type` of RPCs of `kind`, as used by go-grpc-prometheus.
+fn type_name(kind: MethodKind) -> &'static str {
+    match kind {
+        MethodKind::Unary => "unary",
+        MethodKind::ClientStreaming => "client_stream",
+        MethodKind::ServerStreaming => "server_stream",
+        MethodKind::Streaming => "bidi_stream",
+    }
+}
+
+/// The name of `code`, as used by gRPC's other implementations.
+fn code_name(code: Code) -> &'static str {
+    match code {
+        Code::Ok => "OK",
+        Code::Cancelled => "Canceled",
+        Code::Unknown => "Unknown",
+        Code::InvalidArgument => "InvalidArgument",
+        Code::DeadlineExceeded => "DeadlineExceeded",
+        Code::NotFound => "NotFound",
+        Code::AlreadyExists => "AlreadyExists",
+        Code::PermissionDenied => "PermissionDenied",
+        Code::ResourceExhausted => "ResourceExhausted",
+        Code::FailedPrecondition => "FailedPrecondition",
+        Code::Aborted => "Aborted",
+        Code::OutOfRange => "OutOfRange",
+        Code::Unimplemented => "Unimplemented",
+        Code::Internal => "Internal",
+        Code::Unavailable => "Unavailable",
+        Code::DataLoss => "DataLoss",
+        Code::Unauthenticated => "Unauthenticated",
+        Code::__NonExhaustive => "Unknown",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn latency_buckets() {
+        let registry = Registry::with_buckets(&[0.1, 1.0]);
+        let rpc = Rpc::new(Side::Server, MethodKind::Unary, "/test.Service/Method");
+
+        for &millis in &[50, 500, 5000] {
+            registry.handled(&rpc, Code::Ok, Duration::from_millis(millis));
+        }
+
+        let text = registry.render();
+        let labels = "grpc_type=\"unary\",grpc_service=\"test.Service\",grpc_method=\"Method\"";
+
+        for line in &[
+            format!("grpc_server_handled_total{{{},grpc_code=\"OK\"}} 3", labels),
+            format!(
+                "grpc_server_handling_seconds_bucket{{{},le=\"0.1\"}} 1",
+                labels
+            ),
+            format!(
+                "grpc_server_handling_seconds_bucket{{{},le=\"1\"}} 2",
+                labels
+            ),
+            format!(
+                "grpc_server_handling_seconds_bucket{{{},le=\"+Inf\"}} 3",
+                labels
+            ),
+        ] {
+            assert!(
+                text.lines().any(|l| l == line),
+                "{} not in:\n{}",
+                line,
+                text
+            );
+        }
+
+        let sum = format!("grpc_server_handling_seconds_sum{{{}}} ", labels);
+        let sum: f64 = text
+            .lines()
+            .find_map(|l| l.strip_prefix(sum.as_str()))
+            .expect("sum rendered")
+            .parse()
+            .unwrap();
+        assert!((sum - 5.55).abs() < 1e-9, "sum is {}", sum);
+    }
+}
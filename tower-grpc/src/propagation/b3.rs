@@ -0,0 +1,153 @@
+use super::{Propagator, SpanContext};
+use crate::metadata::{MetadataMap, MetadataValue};
+
+const SINGLE: &str = "b3";
+const TRACE_ID: &str = "x-b3-traceid";
+const SPAN_ID: &str = "x-b3-spanid";
+const SAMPLED: &str = "x-b3-sampled";
+const FLAGS: &str = "x-b3-flags";
+
+/// Propagates contexts in Zipkin's B3 headers.
+///
+/// Contexts are extracted from either the single `b3` header or the
+/// multiple `x-b3-*` headers, and injected in whichever `B3` was created
+/// for.
+#[derive(Clone, Copy, Debug)]
+pub struct B3 {
+    single: bool,
+}
+
+impl B3 {
+    /// Inject contexts into the single `b3` header.
+    pub fn single() -> Self {
+        B3 { single: true }
+    }
+
+    /// Inject contexts into the `x-b3-traceid`, `x-b3-spanid` and
+    /// `x-b3-sampled` headers.
+    pub fn multiple() -> Self {
+        B3 { single: false }
+    }
+}
+
+impl Propagator for B3 {
+    fn inject(&self, context: &SpanContext, metadata: &mut MetadataMap) {
+        let trace_id = format!("{:032x}", context.trace_id());
+        let span_id = format!("{:016x}", context.span_id());
+        let sampled = if context.is_sampled() { "1" } else { "0" };
+
+        let value = |s: &str| MetadataValue::from_str(s).expect("B3 headers are ASCII");
+
+        if self.single {
+            let b3 = format!("{}-{}-{}", trace_id, span_id, sampled);
+            metadata.insert(SINGLE, value(&b3));
+        } else {
+            metadata.insert(TRACE_ID, value(&trace_id));
+            metadata.insert(SPAN_ID, value(&span_id));
+            metadata.insert(SAMPLED, value(sampled));
+        }
+    }
+
+    fn extract(&self, metadata: &MetadataMap) -> Option<SpanContext> {
+        let get = |key| metadata.get(key).and_then(|value| value.to_str().ok());
+
+        if let Some(b3) = get(SINGLE) {
+            return parse_single(b3.trim());
+        }
+
+        // Debug traces are always sampled.
+        let sampled = get(FLAGS) == Some("1") || get(SAMPLED).map(is_sampled).unwrap_or(false);
+        parse(get(TRACE_ID)?.trim(), get(SPAN_ID)?.trim(), sampled)
+    }
+}
+
+/// Returns a context from B3 IDs, whose trace IDs are either 64 or 128 bits.
+fn parse(trace_id: &str, span_id: &str, sampled: bool) -> Option<SpanContext> {
+    if trace_id.len() != 16 && trace_id.len() != 32 {
+        return None;
+    }
+
+    SpanContext::parse(trace_id, span_id, sampled)
+}
+
+/// Parse the `{trace id}-{span id}-{sampling}-{parent span id}` format of
+/// the single `b3` header, where the last two fields are optional.
+fn parse_single(b3: &str) -> Option<SpanContext> {
+    let mut parts = b3.split('-');
+    let trace_id = parts.next()?;
+    let span_id = parts.next()?;
+    let sampled = parts
+        .next()
+        .map(|sampled| sampled == "d" || is_sampled(sampled))
+        .unwrap_or(false);
+
+    parse(trace_id, span_id, sampled)
+}
+
+fn is_sampled(sampled: &str) -> bool {
+    // Older implementations send `true` rather than `1`.
+    sampled == "1" || sampled == "true"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let context =
+            SpanContext::new(0x80f198ee56343ba864fe8b2a57d3eff7, 0xe457b5a2e4d86bd1, true);
+
+        for &b3 in [B3::single(), B3::multiple()].iter() {
+            let mut metadata = MetadataMap::new();
+            b3.inject(&context, &mut metadata);
+            assert_eq!(b3.extract(&metadata), Some(context.clone()));
+        }
+
+        let mut metadata = MetadataMap::new();
+        B3::single().inject(&context, &mut metadata);
+        assert_eq!(
+            metadata.get(SINGLE).unwrap(),
+            "80f198ee56343ba864fe8b2a57d3eff7-e457b5a2e4d86bd1-1"
+        );
+    }
+
+    #[test]
+    fn extract_single() {
+        let extract = |b3: &'static str| {
+            let mut metadata = MetadataMap::new();
+            metadata.insert(SINGLE, MetadataValue::from_static(b3));
+            B3::single().extract(&metadata)
+        };
+
+        let context = extract("a3ce929d0e0e4736-00f067aa0ba902b7-d-5b4185666d50f68b").unwrap();
+        assert_eq!(context.trace_id(), 0xa3ce929d0e0e4736);
+        assert_eq!(context.span_id(), 0x00f067aa0ba902b7);
+        assert!(context.is_sampled());
+
+        assert!(!extract("a3ce929d0e0e4736-00f067aa0ba902b7")
+            .unwrap()
+            .is_sampled());
+        assert!(extract("0").is_none());
+        assert!(extract("a3ce929d0e0e47-00f067aa0ba902b7-1").is_none());
+    }
+
+    #[test]
+    fn extract_multiple() {
+        let mut metadata = MetadataMap::new();
+        metadata.insert(TRACE_ID, MetadataValue::from_static("a3ce929d0e0e4736"));
+        metadata.insert(SPAN_ID, MetadataValue::from_static("00f067aa0ba902b7"));
+        metadata.insert(FLAGS, MetadataValue::from_static("1"));
+
+        let context = B3::multiple().extract(&metadata).unwrap();
+        assert_eq!(context.trace_id(), 0xa3ce929d0e0e4736);
+        assert!(context.is_sampled());
+
+        metadata.insert(TRACE_ID, MetadataValue::from_static("a3ce929d0e0e47"));
+        assert!(B3::multiple().extract(&metadata).is_none());
+
+        metadata.insert(TRACE_ID, MetadataValue::from_static("a3ce929d0e0e4736"));
+        metadata.remove(SPAN_ID);
+        assert!(B3::multiple().extract(&metadata).is_none());
+    }
+}
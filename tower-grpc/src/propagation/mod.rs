@@ -0,0 +1,337 @@
+//! Trace context propagation through request metadata.
+//!
+//! A `Propagator` injects a `SpanContext` into a request's metadata, and
+//! extracts it back out on the other side. `TraceContext` implements the W3C
+//! `traceparent` and `tracestate` headers, and `B3` Zipkin's `b3` headers.
+//! Propagators can be combined by pairing them, as `(TraceContext, B3::multiple())`,
+//! which injects both and extracts whichever is present first.
+//!
+//! `Propagate` does so for every request. On servers, the extracted context
+//! is available to handlers as `Request::span_context`, and is recorded in
+//! the RPC's span when traced with `trace::Trace`; on clients, the context
+//! set with `Request::set_span_context` is injected:
+//!
+//! ```ignore
+//! let server = Server::builder()
+//!     .add_service(Propagate::server(GreeterServer::new(Greet), TraceContext));
+//!
+//! let mut greeter = Greeter::new(Propagate::client(channel, TraceContext));
+//!
+//! let mut request = Request::new(HelloRequest { name: "world".into() });
+//! request.set_span_context(SpanContext::new(trace_id, span_id, true));
+//! ```
+
+mod b3;
+mod trace_context;
+
+pub use self::b3::B3;
+pub use self::trace_context::TraceContext;
+
+use crate::descriptor::MethodDescriptor;
+use crate::generic::server::NamedService;
+use crate::metadata::MetadataMap;
+
+use futures::Poll;
+use http::HeaderMap;
+use std::mem;
+use tower_service::Service;
+
+/// Identifies a span within a trace, as propagated between services.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SpanContext {
+    trace_id: u128,
+    span_id: u64,
+    sampled: bool,
+    trace_state: Option<String>,
+}
+
+/// Injects and extracts `SpanContext`s in request metadata.
+pub trait Propagator {
+    /// Insert `context` into `metadata`.
+    fn inject(&self, context: &SpanContext, metadata: &mut MetadataMap);
+
+    /// Returns the valid context in `metadata`, if any.
+    fn extract(&self, metadata: &MetadataMap) -> Option<SpanContext>;
+}
+
+/// Propagates the `SpanContext`s of the requests to or from the inner
+/// service.
+#[derive(Clone, Debug)]
+pub struct Propagate<S, P> {
+    inner: S,
+    propagator: P,
+    kind: Kind,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Kind {
+    Client,
+    Server,
+}
+
+// ===== impl SpanContext =====
+
+impl SpanContext {
+    /// Returns a `SpanContext` for the span `span_id` in the trace
+    /// `trace_id`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if either ID is zero, as neither is valid.
+    pub fn new(trace_id: u128, span_id: u64, sampled: bool) -> Self {
+        assert!(trace_id != 0, "trace ID must not be zero");
+        assert!(span_id != 0, "span ID must not be zero");
+
+        SpanContext {
+            trace_id,
+            span_id,
+            sampled,
+            trace_state: None,
+        }
+    }
+
+    /// Returns the ID of the trace.
+    pub fn trace_id(&self) -> u128 {
+        self.trace_id
+    }
+
+    /// Returns the ID of the span.
+    pub fn span_id(&self) -> u64 {
+        self.span_id
+    }
+
+    /// Returns whether the trace is being recorded.
+    pub fn is_sampled(&self) -> bool {
+        self.sampled
+    }
+
+    /// Returns the vendor-specific trace state, in the W3C `tracestate`
+    /// format.
+    pub fn trace_state(&self) -> Option<&str> {
+        self.trace_state.as_deref()
+    }
+
+    /// Set the vendor-specific trace state, in the W3C `tracestate` format.
+    pub fn with_trace_state(mut self, trace_state: impl Into<String>) -> Self {
+        self.trace_state = Some(trace_state.into());
+        self
+    }
+
+    /// Returns a context for the span `span_id` in the same trace, such as
+    /// for a call made while handling this one.
+    pub fn child(&self, span_id: u64) -> Self {
+        assert!(span_id != 0, "span ID must not be zero");
+
+        SpanContext {
+            span_id,
+            ..self.clone()
+        }
+    }
+
+    /// Returns a context from parsed IDs, if both are valid.
+    fn parse(trace_id: &str, span_id: &str, sampled: bool) -> Option<Self> {
+        let trace_id = parse_hex(trace_id).and_then(|id| u128::from_str_radix(id, 16).ok())?;
+        let span_id = parse_hex(span_id).and_then(|id| u64::from_str_radix(id, 16).ok())?;
+
+        if trace_id == 0 || span_id == 0 {
+            return None;
+        }
+
+        Some(SpanContext::new(trace_id, span_id, sampled))
+    }
+}
+
+/// Returns `s` if it is only lowercase hex digits.
+fn parse_hex(s: &str) -> Option<&str> {
+    let is_hex = |b: u8| b.is_ascii_digit() || (b'a'..=b'f').contains(&b);
+
+    if !s.is_empty() && s.bytes().all(is_hex) {
+        Some(s)
+    } else {
+        None
+    }
+}
+
+// ===== impl Propagator =====
+
+impl<A, B> Propagator for (A, B)
+where
+    A: Propagator,
+    B: Propagator,
+{
+    fn inject(&self, context: &SpanContext, metadata: &mut MetadataMap) {
+        self.0.inject(context, metadata);
+        self.1.inject(context, metadata);
+    }
+
+    fn extract(&self, metadata: &MetadataMap) -> Option<SpanContext> {
+        self.0
+            .extract(metadata)
+            .or_else(|| self.1.extract(metadata))
+    }
+}
+
+// ===== impl Propagate =====
+
+impl<S, P> Propagate<S, P> {
+    /// Extract the `SpanContext` of requests received by a server's service.
+    pub fn server(inner: S, propagator: P) -> Self {
+        Propagate {
+            inner,
+            propagator,
+            kind: Kind::Server,
+        }
+    }
+
+    /// Inject the `SpanContext` of requests sent through a client's HTTP
+    /// service.
+    pub fn client(inner: S, propagator: P) -> Self {
+        Propagate {
+            inner,
+            propagator,
+            kind: Kind::Client,
+        }
+    }
+
+    /// Get a reference to the inner service.
+    pub fn get_ref(&self) -> &S {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the inner service.
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.inner
+    }
+
+    /// Consumes `self`, returning the inner service.
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl<S, P, B> Service<http::Request<B>> for Propagate<S, P>
+where
+    S: Service<http::Request<B>>,
+    P: Propagator,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        self.inner.poll_ready()
+    }
+
+    fn call(&mut self, mut request: http::Request<B>) -> Self::Future {
+        match self.kind {
+            Kind::Client => {
+                if let Some(context) = request.extensions().get::<SpanContext>().cloned() {
+                    let headers = mem::replace(request.headers_mut(), HeaderMap::new());
+                    let mut metadata = MetadataMap::from_headers(headers);
+                    self.propagator.inject(&context, &mut metadata);
+                    *request.headers_mut() = metadata.into_headers();
+                }
+            }
+            Kind::Server => {
+                let headers = mem::replace(request.headers_mut(), HeaderMap::new());
+                let metadata = MetadataMap::from_headers(headers);
+                let context = self.propagator.extract(&metadata);
+                *request.headers_mut() = metadata.into_headers();
+
+                if let Some(context) = context {
+                    // When wrapped by `Trace`, its span has been entered.
+                    #[cfg(feature = "tracing")]
+                    crate::trace::record_context(&tracing::Span::current(), &context);
+
+                    request.extensions_mut().insert(context);
+                }
+            }
+        }
+
+        self.inner.call(request)
+    }
+}
+
+impl<S: NamedService, P> NamedService for Propagate<S, P> {
+    const NAME: &'static str = S::NAME;
+    const METHODS: &'static [MethodDescriptor] = S::METHODS;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::MetadataValue;
+    use crate::Request;
+
+    use futures::future::{self, Future, FutureResult};
+
+    /// Responds with the context of each request.
+    struct Context;
+
+    impl Service<http::Request<()>> for Context {
+        type Response = Option<SpanContext>;
+        type Error = ();
+        type Future = FutureResult<Self::Response, ()>;
+
+        fn poll_ready(&mut self) -> Poll<(), ()> {
+            Ok(().into())
+        }
+
+        fn call(&mut self, request: http::Request<()>) -> Self::Future {
+            future::ok(Request::from_http(request).span_context().cloned())
+        }
+    }
+
+    /// Drops request extensions, as sending requests over a connection does.
+    struct Wire<S>(S);
+
+    impl<S: Service<http::Request<()>>> Service<http::Request<()>> for Wire<S> {
+        type Response = S::Response;
+        type Error = S::Error;
+        type Future = S::Future;
+
+        fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+            self.0.poll_ready()
+        }
+
+        fn call(&mut self, mut request: http::Request<()>) -> Self::Future {
+            *request.extensions_mut() = http::Extensions::new();
+            self.0.call(request)
+        }
+    }
+
+    #[test]
+    fn client_to_server() {
+        let server = Propagate::server(Context, TraceContext);
+        let mut client = Propagate::client(Wire(server), TraceContext);
+        let uri = http::Uri::from_static("/test.Context/Get");
+
+        let context = SpanContext::new(1, 2, false).with_trace_state("a=b");
+        let mut request = Request::new(());
+        request.set_span_context(context.clone());
+
+        let received = client.call(request.into_http(uri.clone())).wait();
+        assert_eq!(received, Ok(Some(context)));
+
+        let received = client.call(Request::new(()).into_http(uri)).wait();
+        assert_eq!(received, Ok(None));
+    }
+
+    #[test]
+    fn pairs_extract_either() {
+        let propagator = (TraceContext, B3::multiple());
+        let context = SpanContext::new(1, 2, true);
+
+        let mut metadata = MetadataMap::new();
+        B3::multiple().inject(&context, &mut metadata);
+        assert_eq!(propagator.extract(&metadata), Some(context.clone()));
+
+        let mut metadata = MetadataMap::new();
+        propagator.inject(&context, &mut metadata);
+        assert!(metadata.contains_key("traceparent"));
+        assert!(metadata.contains_key("x-b3-traceid"));
+
+        metadata.insert("traceparent", MetadataValue::from_static("invalid"));
+        assert_eq!(propagator.extract(&metadata), Some(context));
+    }
+}
@@ -0,0 +1,145 @@
+use super::{Propagator, SpanContext};
+use crate::metadata::{MetadataMap, MetadataValue};
+
+use log::debug;
+
+const TRACEPARENT: &str = "traceparent";
+const TRACESTATE: &str = "tracestate";
+
+/// The only version of `traceparent` defined so far.
+const VERSION: &str = "00";
+
+/// The `traceparent` flag set for sampled traces.
+const SAMPLED: u8 = 0x01;
+
+/// Propagates contexts in the W3C Trace Context `traceparent` and
+/// `tracestate` headers.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TraceContext;
+
+impl Propagator for TraceContext {
+    fn inject(&self, context: &SpanContext, metadata: &mut MetadataMap) {
+        let flags = if context.is_sampled() { SAMPLED } else { 0 };
+        let traceparent = format!(
+            "{}-{:032x}-{:016x}-{:02x}",
+            VERSION,
+            context.trace_id(),
+            context.span_id(),
+            flags
+        );
+        let traceparent = MetadataValue::from_str(&traceparent).expect("traceparent is ASCII");
+        metadata.insert(TRACEPARENT, traceparent);
+
+        match context.trace_state().map(MetadataValue::from_str) {
+            Some(Ok(trace_state)) => {
+                metadata.insert(TRACESTATE, trace_state);
+            }
+            Some(Err(_)) => debug!("not propagating invalid tracestate"),
+            None => {}
+        }
+    }
+
+    fn extract(&self, metadata: &MetadataMap) -> Option<SpanContext> {
+        let traceparent = metadata.get(TRACEPARENT)?.to_str().ok()?;
+        let context = parse_traceparent(traceparent.trim())?;
+
+        // Multiple `tracestate` headers are combined as a list.
+        let trace_state = metadata
+            .get_all(TRACESTATE)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+            .collect::<Vec<_>>()
+            .join(",");
+
+        if trace_state.is_empty() {
+            Some(context)
+        } else {
+            Some(context.with_trace_state(trace_state))
+        }
+    }
+}
+
+fn parse_traceparent(traceparent: &str) -> Option<SpanContext> {
+    let mut parts = traceparent.split('-');
+    let version = parts.next()?;
+    let trace_id = parts.next()?;
+    let span_id = parts.next()?;
+    let flags = parts.next()?;
+
+    // Later versions may append fields, but must keep these.
+    let is_valid_version = version.len() == 2 && super::parse_hex(version).is_some();
+    if !is_valid_version || version == "ff" || (version == VERSION && parts.next().is_some()) {
+        return None;
+    }
+
+    if trace_id.len() != 32 || span_id.len() != 16 || flags.len() != 2 {
+        return None;
+    }
+
+    let flags = super::parse_hex(flags).and_then(|flags| u8::from_str_radix(flags, 16).ok())?;
+    SpanContext::parse(trace_id, span_id, flags & SAMPLED != 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn extract(traceparent: &'static str) -> Option<SpanContext> {
+        let mut metadata = MetadataMap::new();
+        metadata.insert(TRACEPARENT, MetadataValue::from_static(traceparent));
+        TraceContext.extract(&metadata)
+    }
+
+    #[test]
+    fn round_trip() {
+        let context =
+            SpanContext::new(0x4bf92f3577b34da6a3ce929d0e0e4736, 0x00f067aa0ba902b7, true)
+                .with_trace_state("congo=t61rcWkgMzE");
+
+        let mut metadata = MetadataMap::new();
+        TraceContext.inject(&context, &mut metadata);
+
+        assert_eq!(
+            metadata.get(TRACEPARENT).unwrap(),
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"
+        );
+        assert_eq!(metadata.get(TRACESTATE).unwrap(), "congo=t61rcWkgMzE");
+        assert_eq!(TraceContext.extract(&metadata), Some(context));
+    }
+
+    #[test]
+    fn tracestate_headers_combined() {
+        let mut metadata = MetadataMap::new();
+        metadata.insert(
+            TRACEPARENT,
+            MetadataValue::from_static("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-00"),
+        );
+        metadata.append(
+            TRACESTATE,
+            MetadataValue::from_static("rojo=00f067aa0ba902b7"),
+        );
+        metadata.append(TRACESTATE, MetadataValue::from_static("congo=t61rcWkgMzE"));
+
+        let context = TraceContext.extract(&metadata).unwrap();
+        assert!(!context.is_sampled());
+        assert_eq!(
+            context.trace_state(),
+            Some("rojo=00f067aa0ba902b7,congo=t61rcWkgMzE")
+        );
+    }
+
+    #[test]
+    fn invalid_traceparents_ignored() {
+        // Unknown versions may add fields.
+        assert!(extract("01-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-xyz").is_some());
+
+        assert!(extract("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-xyz").is_none());
+        assert!(extract("ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01").is_none());
+        assert!(extract("00-00000000000000000000000000000000-00f067aa0ba902b7-01").is_none());
+        assert!(extract("00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01").is_none());
+        assert!(extract("00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01").is_none());
+        assert!(extract("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7").is_none());
+    }
+}
@@ -1,17 +1,68 @@
 use crate::metadata::MetadataMap;
+use crate::propagation::SpanContext;
+#[cfg(feature = "tls")]
+use crate::transport::PeerCertificates;
+use crate::Status;
+
+use futures::stream::{self, IterOk};
+use futures::Stream;
 
 #[derive(Debug)]
 pub struct Request<T> {
     metadata: MetadataMap,
     message: T,
+    wait_for_ready: bool,
+    span_context: Option<SpanContext>,
+    #[cfg(feature = "tls")]
+    peer_certificates: Option<PeerCertificates>,
+}
+
+/// Conversion into a `Request`, taken by the methods of generated clients.
+///
+/// Implemented by messages, which are sent without metadata, and by
+/// requests themselves.
+pub trait IntoRequest<T> {
+    /// Wrap the message in a `Request`.
+    fn into_request(self) -> Request<T>;
 }
 
+/// Conversion into a `Request` streaming messages, taken by the client
+/// streaming methods of generated clients.
+///
+/// Implemented by streams of messages and iterators, or anything else
+/// implementing `IntoIterator`, over messages, and by requests of either.
+/// `K` is either `ViaStream` or `ViaIterator`, and is inferred.
+pub trait IntoStreamingRequest<T, K> {
+    /// The stream of messages.
+    type Stream: Stream<Item = T, Error = Status>;
+
+    /// Wrap the messages in a `Request`.
+    fn into_streaming_request(self) -> Request<Self::Stream>;
+}
+
+/// Marks the `IntoStreamingRequest` implementations of streams.
+#[derive(Debug)]
+pub enum ViaStream {}
+
+/// Marks the `IntoStreamingRequest` implementations of iterators.
+#[derive(Debug)]
+pub enum ViaIterator {}
+
+/// Marks an HTTP request as waiting for its channel to connect, rather than
+/// failing fast while the channel is disconnected.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct WaitForReady;
+
 impl<T> Request<T> {
     /// Create a new gRPC request
     pub fn new(message: T) -> Self {
         Request {
             metadata: MetadataMap::new(),
             message,
+            wait_for_ready: false,
+            span_context: None,
+            #[cfg(feature = "tls")]
+            peer_certificates: None,
         }
     }
 
@@ -35,15 +86,70 @@ impl<T> Request<T> {
         &mut self.metadata
     }
 
+    /// Returns whether the request waits for a disconnected channel to
+    /// reconnect.
+    pub fn wait_for_ready(&self) -> bool {
+        self.wait_for_ready
+    }
+
+    /// Set whether the request waits for a disconnected channel to reconnect,
+    /// until its deadline, rather than failing with `Code::Unavailable`.
+    ///
+    /// Defaults to `false`.
+    pub fn set_wait_for_ready(&mut self, wait_for_ready: bool) {
+        self.wait_for_ready = wait_for_ready;
+    }
+
+    /// Returns the trace context the request was received with, or is sent
+    /// with.
+    ///
+    /// Servers only receive a context when wrapped in
+    /// `propagation::Propagate`.
+    pub fn span_context(&self) -> Option<&SpanContext> {
+        self.span_context.as_ref()
+    }
+
+    /// Set the trace context the request is sent with, when the client is
+    /// wrapped in `propagation::Propagate`.
+    pub fn set_span_context(&mut self, context: SpanContext) {
+        self.span_context = Some(context);
+    }
+
+    /// Returns the certificates the client authenticated its connection
+    /// with, if it was received over mutually authenticated TLS.
+    #[cfg(feature = "tls")]
+    pub fn peer_certificates(&self) -> Option<&PeerCertificates> {
+        self.peer_certificates.as_ref()
+    }
+
     /// Consumes `self`, returning the message
     pub fn into_inner(self) -> T {
         self.message
     }
 
+    /// Split the request into its message and the request without it.
+    pub(crate) fn into_parts(self) -> (Request<()>, T) {
+        let message = self.message;
+        let request = Request {
+            metadata: self.metadata,
+            message: (),
+            wait_for_ready: self.wait_for_ready,
+            span_context: self.span_context,
+            #[cfg(feature = "tls")]
+            peer_certificates: self.peer_certificates,
+        };
+
+        (request, message)
+    }
+
     /// Convert an HTTP request to a gRPC request
     pub fn from_http(http: http::Request<T>) -> Self {
         let (head, message) = http.into_parts();
         Request {
+            wait_for_ready: head.extensions.get::<WaitForReady>().is_some(),
+            span_context: head.extensions.get::<SpanContext>().cloned(),
+            #[cfg(feature = "tls")]
+            peer_certificates: head.extensions.get::<PeerCertificates>().cloned(),
             metadata: MetadataMap::from_headers(head.headers),
             message,
         }
@@ -57,6 +163,14 @@ impl<T> Request<T> {
         *request.uri_mut() = uri;
         *request.headers_mut() = self.metadata.into_headers();
 
+        if self.wait_for_ready {
+            request.extensions_mut().insert(WaitForReady);
+        }
+
+        if let Some(context) = self.span_context {
+            request.extensions_mut().insert(context);
+        }
+
         request
     }
 
@@ -69,6 +183,109 @@ impl<T> Request<T> {
         Request {
             metadata: self.metadata,
             message,
+            wait_for_ready: self.wait_for_ready,
+            span_context: self.span_context,
+            #[cfg(feature = "tls")]
+            peer_certificates: self.peer_certificates,
         }
     }
 }
+
+// ===== impl IntoRequest =====
+
+impl<T> IntoRequest<T> for T {
+    fn into_request(self) -> Request<T> {
+        Request::new(self)
+    }
+}
+
+impl<T> IntoRequest<T> for Request<T> {
+    fn into_request(self) -> Request<T> {
+        self
+    }
+}
+
+// ===== impl IntoStreamingRequest =====
+
+impl<S> IntoStreamingRequest<S::Item, ViaStream> for S
+where
+    S: Stream<Error = Status>,
+{
+    type Stream = S;
+
+    fn into_streaming_request(self) -> Request<S> {
+        Request::new(self)
+    }
+}
+
+impl<S> IntoStreamingRequest<S::Item, ViaStream> for Request<S>
+where
+    S: Stream<Error = Status>,
+{
+    type Stream = S;
+
+    fn into_streaming_request(self) -> Request<S> {
+        self
+    }
+}
+
+impl<I> IntoStreamingRequest<I::Item, ViaIterator> for I
+where
+    I: IntoIterator,
+{
+    type Stream = IterOk<I::IntoIter, Status>;
+
+    fn into_streaming_request(self) -> Request<Self::Stream> {
+        Request::new(stream::iter_ok(self))
+    }
+}
+
+impl<I> IntoStreamingRequest<I::Item, ViaIterator> for Request<I>
+where
+    I: IntoIterator,
+{
+    type Stream = IterOk<I::IntoIter, Status>;
+
+    fn into_streaming_request(self) -> Request<Self::Stream> {
+        self.map(stream::iter_ok)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::Future;
+
+    fn messages<R, K>(request: R) -> Vec<u32>
+    where
+        R: IntoStreamingRequest<u32, K>,
+    {
+        let request = request.into_streaming_request();
+        request.into_inner().collect().wait().unwrap()
+    }
+
+    #[test]
+    fn into_streaming_request() {
+        assert_eq!(messages(vec![1, 2]), vec![1, 2]);
+        assert_eq!(messages(Request::new(1..3)), vec![1, 2]);
+        assert_eq!(messages(stream::iter_ok(vec![1, 2])), vec![1, 2]);
+
+        let mut request = Request::new(stream::once(Ok(1)));
+        request.set_wait_for_ready(true);
+        let request = request.into_streaming_request();
+        assert!(request.wait_for_ready());
+    }
+
+    #[test]
+    fn into_request() {
+        fn request<R: IntoRequest<u32>>(request: R) -> Request<u32> {
+            request.into_request()
+        }
+
+        assert_eq!(request(1).into_inner(), 1);
+
+        let mut wait = Request::new(1);
+        wait.set_wait_for_ready(true);
+        assert!(request(wait).wait_for_ready());
+    }
+}
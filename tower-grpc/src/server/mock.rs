@@ -0,0 +1,276 @@
+//! Programmable responses for mock services.
+//!
+//! `tower-grpc-build` generates a `MockFoo` for each service when
+//! `Config::build_mocks` is enabled, with a `Method` for each of the
+//! service's methods. Programming a method's responses and checking the
+//! requests it received looks like:
+//!
+//! ```ignore
+//! let mock = MockGreeter::new();
+//! mock.say_hello
+//!     .push_response(Ok(Response::new(HelloReply { message: "hi".into() })));
+//!
+//! let server = GreeterServer::new(mock.clone());
+//! // ...
+//!
+//! let requests = mock.say_hello.take_requests();
+//! assert_eq!(requests[0].get_ref().name, "world");
+//! ```
+//!
+//! Streamed requests and responses are handled as `Vec`s of messages: a
+//! client streaming method's requests are collected before they are
+//! recorded, and a server streaming method's responses are sent one message
+//! at a time.
+
+use crate::{Code, Request, Response, Status};
+
+use futures::{future, stream, Future, Stream};
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// A future resolving to a mocked response.
+pub type ResponseFuture<T> = Box<dyn Future<Item = Response<T>, Error = Status> + Send>;
+
+/// A mocked stream of response messages.
+pub type ResponseStream<T> = Box<dyn Stream<Item = T, Error = Status> + Send>;
+
+/// The responses and recorded requests of one mocked method.
+///
+/// Cloning a `Method` shares its state, so that responses can be programmed
+/// and requests checked while a clone of the mock is being served.
+pub struct Method<Req, Res> {
+    inner: Arc<Mutex<Inner<Req, Res>>>,
+}
+
+type Handler<Req, Res> = Box<dyn FnMut(&Request<Req>) -> Result<Response<Res>, Status> + Send>;
+
+struct Inner<Req, Res> {
+    path: &'static str,
+    responses: VecDeque<Result<Response<Res>, Status>>,
+    handler: Option<Handler<Req, Res>>,
+    requests: Vec<Request<Req>>,
+    calls: usize,
+}
+
+// ===== impl Method =====
+
+impl<Req, Res> Method<Req, Res> {
+    /// Returns a `Method` for the method at `path`, with no programmed
+    /// responses.
+    pub fn new(path: &'static str) -> Self {
+        Method {
+            inner: Arc::new(Mutex::new(Inner {
+                path,
+                responses: VecDeque::new(),
+                handler: None,
+                requests: Vec::new(),
+                calls: 0,
+            })),
+        }
+    }
+
+    /// Queue a response to the next call.
+    ///
+    /// Queued responses are used in order, before the handler set with
+    /// `respond_with`.
+    pub fn push_response(&self, response: Result<Response<Res>, Status>) -> &Self {
+        self.lock().responses.push_back(response);
+        self
+    }
+
+    /// Respond to calls with `f` once there are no queued responses.
+    ///
+    /// Calls made without a queued response or a handler fail with
+    /// `Code::Unimplemented`. As `f` is called with the method's state
+    /// locked, it must not use the method itself.
+    pub fn respond_with<F>(&self, f: F) -> &Self
+    where
+        F: FnMut(&Request<Req>) -> Result<Response<Res>, Status> + Send + 'static,
+    {
+        self.lock().handler = Some(Box::new(f));
+        self
+    }
+
+    /// Returns the number of times the method has been called.
+    pub fn times_called(&self) -> usize {
+        self.lock().calls
+    }
+
+    /// Removes and returns the requests the method has received, in the
+    /// order they were received.
+    pub fn take_requests(&self) -> Vec<Request<Req>> {
+        self.lock().requests.drain(..).collect()
+    }
+
+    /// Respond to `request`, recording it.
+    pub fn call(&self, request: Request<Req>) -> Result<Response<Res>, Status> {
+        let mut inner = self.lock();
+        let inner = &mut *inner;
+        inner.calls += 1;
+
+        let response = match inner.responses.pop_front() {
+            Some(response) => response,
+            None => match inner.handler {
+                Some(ref mut handler) => handler(&request),
+                None => Err(Status::new(
+                    Code::Unimplemented,
+                    format!("no mocked response for {}", inner.path),
+                )),
+            },
+        };
+
+        inner.requests.push(request);
+        response
+    }
+
+    /// Respond to a unary `request`.
+    pub fn unary(&self, request: Request<Req>) -> future::FutureResult<Response<Res>, Status> {
+        future::result(self.call(request))
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Inner<Req, Res>> {
+        // A handler panicking fails the test anyway, so the state can still
+        // be used.
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl<Req, T> Method<Req, Vec<T>>
+where
+    T: Send + 'static,
+{
+    /// Respond to a server streaming `request`.
+    pub fn server_streaming(
+        &self,
+        request: Request<Req>,
+    ) -> future::FutureResult<Response<ResponseStream<T>>, Status> {
+        future::result(self.call(request).map(into_stream))
+    }
+}
+
+impl<T, Res> Method<Vec<T>, Res>
+where
+    T: Send + 'static,
+    Res: Send + 'static,
+{
+    /// Respond to a client streaming `request`, once all of its messages
+    /// have been received.
+    pub fn client_streaming<S>(&self, request: Request<S>) -> ResponseFuture<Res>
+    where
+        S: Stream<Item = T, Error = Status> + Send + 'static,
+    {
+        let method = self.clone();
+        Box::new(collect(request).and_then(move |request| method.call(request)))
+    }
+}
+
+impl<T, U> Method<Vec<T>, Vec<U>>
+where
+    T: Send + 'static,
+    U: Send + 'static,
+{
+    /// Respond to a bidirectional streaming `request`, once all of its
+    /// messages have been received.
+    pub fn streaming<S>(&self, request: Request<S>) -> ResponseFuture<ResponseStream<U>>
+    where
+        S: Stream<Item = T, Error = Status> + Send + 'static,
+    {
+        let method = self.clone();
+        Box::new(collect(request).and_then(move |request| method.call(request).map(into_stream)))
+    }
+}
+
+impl<Req, Res> Clone for Method<Req, Res> {
+    fn clone(&self) -> Self {
+        Method {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<Req, Res> fmt::Debug for Method<Req, Res> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let inner = self.lock();
+
+        f.debug_struct("Method")
+            .field("path", &inner.path)
+            .field("responses", &inner.responses.len())
+            .field("handler", &inner.handler.is_some())
+            .field("calls", &inner.calls)
+            .finish()
+    }
+}
+
+/// Collect the messages of a streamed request.
+fn collect<S>(request: Request<S>) -> impl Future<Item = Request<Vec<S::Item>>, Error = Status>
+where
+    S: Stream<Error = Status>,
+{
+    let (request, messages) = request.into_parts();
+
+    messages
+        .collect()
+        .map(move |messages| request.map(|()| messages))
+}
+
+fn into_stream<T>(response: Response<Vec<T>>) -> Response<ResponseStream<T>>
+where
+    T: Send + 'static,
+{
+    response.map(|messages| Box::new(stream::iter_ok(messages)) as ResponseStream<T>)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn queued_responses_before_handler() {
+        let method = Method::<u32, u32>::new("/test.Mock/Double");
+        method
+            .push_response(Ok(Response::new(1)))
+            .push_response(Err(Status::new(Code::NotFound, "")))
+            .respond_with(|request| Ok(Response::new(request.get_ref() * 2)));
+
+        let mut responses = (0..3).map(|n| method.unary(Request::new(n)).wait());
+        assert_eq!(responses.next().unwrap().unwrap().into_inner(), 1);
+        assert_eq!(
+            responses.next().unwrap().unwrap_err().code(),
+            Code::NotFound
+        );
+        assert_eq!(responses.next().unwrap().unwrap().into_inner(), 4);
+
+        assert_eq!(method.times_called(), 3);
+        let requests = method.take_requests();
+        let requests = requests.iter().map(|r| *r.get_ref()).collect::<Vec<_>>();
+        assert_eq!(requests, vec![0, 1, 2]);
+        assert!(method.take_requests().is_empty());
+    }
+
+    #[test]
+    fn unprogrammed_calls_are_unimplemented() {
+        let method = Method::<(), ()>::new("/test.Mock/Get");
+        let status = method.unary(Request::new(())).wait().unwrap_err();
+
+        assert_eq!(status.code(), Code::Unimplemented);
+        assert_eq!(status.message(), "no mocked response for /test.Mock/Get");
+        assert_eq!(method.times_called(), 1);
+    }
+
+    #[test]
+    fn streams_are_collected() {
+        let method = Method::<Vec<u32>, Vec<u32>>::new("/test.Mock/Echo");
+        method.respond_with(|request| Ok(Response::new(request.get_ref().clone())));
+
+        let request = Request::new(stream::iter_ok(vec![1, 2, 3]));
+        let replies = method
+            .streaming(request)
+            .and_then(|response| response.into_inner().collect())
+            .wait()
+            .unwrap();
+
+        assert_eq!(replies, vec![1, 2, 3]);
+        assert_eq!(method.take_requests()[0].get_ref(), &vec![1, 2, 3]);
+    }
+}
@@ -1,4 +1,5 @@
 pub mod client_streaming;
+pub mod mock;
 pub mod server_streaming;
 pub mod streaming;
 pub mod unary;
@@ -8,7 +9,17 @@ use crate::codec::{Codec, Streaming};
 use crate::generic::server::{
     ClientStreamingService, Grpc, ServerStreamingService, StreamingService, UnaryService,
 };
-use crate::Body;
+use crate::{Body, Response, Status};
+
+use futures::{Future, Stream};
+
+/// A boxed future resolving to a response, as returned by the methods of
+/// service traits generated with boxed futures and streams.
+pub type BoxFuture<T> = Box<dyn Future<Item = Response<T>, Error = Status> + Send>;
+
+/// A boxed stream of response messages, as returned by the server streaming
+/// methods of service traits generated with boxed futures and streams.
+pub type BoxStream<T> = Box<dyn Stream<Item = T, Error = Status> + Send>;
 
 pub fn unary<T, B, R>(service: T, request: http::Request<B>) -> unary::ResponseFuture<T, B, R>
 where
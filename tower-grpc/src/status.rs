@@ -94,6 +94,16 @@ impl Status {
                 return Some(Status::from_h2_error(h2));
             }
 
+            #[cfg(feature = "transport")]
+            {
+                if let Some(status) = err
+                    .downcast_ref::<crate::transport::Error>()
+                    .and_then(|err| err.to_status())
+                {
+                    return Some(status);
+                }
+            }
+
             cause = err.source();
         }
 
@@ -479,6 +489,14 @@ mod tests {
         assert_eq!(found.code(), Code::Cancelled);
     }
 
+    #[test]
+    fn from_error_h2_enhance_your_calm() {
+        let orig = h2::Error::from(h2::Reason::ENHANCE_YOUR_CALM);
+        let found = Status::from_error(&orig);
+
+        assert_eq!(found.code(), Code::ResourceExhausted);
+    }
+
     #[test]
     fn to_h2_error() {
         let orig = Status::new(Code::Cancelled, "stop eet!");
@@ -0,0 +1,123 @@
+//! Fixtures shared by the crate's unit tests.
+
+// Which fixtures are used depends on the features enabled.
+#![allow(dead_code)]
+
+use crate::body::{Body, BoxBody, HttpBody};
+use crate::descriptor::{IdempotencyLevel, MethodDescriptor, MethodKind};
+use crate::generic::server::NamedService;
+use crate::request::WaitForReady;
+use crate::{Code, Status};
+
+use bytes::Bytes;
+use futures::{future, try_ready, Async, Future, Poll};
+use http::HeaderMap;
+use std::io;
+use tower_service::Service;
+
+/// A body with no data and no trailers.
+#[derive(Debug)]
+pub(crate) struct Empty;
+
+/// Reads the request, and then fails it with `NotFound` in the trailers.
+///
+/// Requests still carrying client-side options, such as wait-for-ready,
+/// fail with `Internal` instead, as those are never sent to a server.
+#[derive(Clone, Debug)]
+pub(crate) struct NotFound;
+
+/// A body with no data, ending with the given trailers.
+#[derive(Debug)]
+pub(crate) struct Trailers(Option<HeaderMap>);
+
+// ===== impl Empty =====
+
+impl HttpBody for Empty {
+    type Data = io::Cursor<Bytes>;
+    type Error = Status;
+
+    fn is_end_stream(&self) -> bool {
+        true
+    }
+
+    fn poll_data(&mut self) -> Poll<Option<Self::Data>, Self::Error> {
+        Ok(Async::Ready(None))
+    }
+
+    fn poll_trailers(&mut self) -> Poll<Option<HeaderMap>, Self::Error> {
+        Ok(Async::Ready(None))
+    }
+}
+
+// ===== impl NotFound =====
+
+impl NamedService for NotFound {
+    const NAME: &'static str = "test.NotFound";
+    const METHODS: &'static [MethodDescriptor] = &[
+        MethodDescriptor {
+            name: "Get",
+            path: "/test.NotFound/Get",
+            input_type: "google.protobuf.Empty",
+            output_type: "google.protobuf.Empty",
+            kind: MethodKind::Unary,
+            idempotency_level: IdempotencyLevel::NoSideEffects,
+            deprecated: false,
+            extensions: &[],
+        },
+        MethodDescriptor {
+            name: "List",
+            path: "/test.NotFound/List",
+            input_type: "google.protobuf.Empty",
+            output_type: "google.protobuf.Empty",
+            kind: MethodKind::ServerStreaming,
+            idempotency_level: IdempotencyLevel::NoSideEffects,
+            deprecated: false,
+            extensions: &[],
+        },
+    ];
+}
+
+impl Service<http::Request<BoxBody>> for NotFound {
+    type Response = http::Response<Trailers>;
+    type Error = Status;
+    type Future = Box<dyn Future<Item = Self::Response, Error = Self::Error> + Send>;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        Ok(Async::Ready(()))
+    }
+
+    fn call(&mut self, request: http::Request<BoxBody>) -> Self::Future {
+        if request.extensions().get::<WaitForReady>().is_some() {
+            return Box::new(future::err(Status::new(
+                Code::Internal,
+                "extensions were passed",
+            )));
+        }
+
+        let mut body = request.into_body();
+        let read = future::poll_fn(move || {
+            while try_ready!(Body::poll_data(&mut body)).is_some() {}
+            Ok(Async::Ready(()))
+        });
+
+        Box::new(read.map(|()| {
+            let trailers = Status::new(Code::NotFound, "not here").to_header_map();
+            http::Response::new(Trailers(trailers.ok()))
+        }))
+    }
+}
+
+// ===== impl Trailers =====
+
+impl HttpBody for Trailers {
+    type Data = io::Cursor<Bytes>;
+    type Error = Status;
+
+    fn poll_data(&mut self) -> Poll<Option<Self::Data>, Self::Error> {
+        Ok(Async::Ready(None))
+    }
+
+    fn poll_trailers(&mut self) -> Poll<Option<HeaderMap>, Self::Error> {
+        Ok(Async::Ready(self.0.take()))
+    }
+}
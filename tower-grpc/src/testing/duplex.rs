@@ -0,0 +1,151 @@
+use futures::task::{self, Task};
+use futures::{Async, Poll};
+use std::io::{self, Read, Write};
+use std::sync::{Arc, Mutex};
+use std::{cmp, fmt};
+use tokio::io::{AsyncRead, AsyncWrite};
+
+/// Returns a pair of connected in-memory byte streams.
+///
+/// Bytes written to one stream are read from the other. Once a stream is
+/// shut down or dropped, its peer reads EOF after the buffered bytes, and
+/// writes to the peer fail with `BrokenPipe` once the stream is dropped.
+pub fn duplex() -> (DuplexStream, DuplexStream) {
+    let a = Arc::new(Mutex::new(Pipe::default()));
+    let b = Arc::new(Mutex::new(Pipe::default()));
+
+    let one = DuplexStream {
+        read: a.clone(),
+        write: b.clone(),
+    };
+    let two = DuplexStream { read: b, write: a };
+
+    (one, two)
+}
+
+/// One end of an in-memory byte stream, created by `duplex`.
+pub struct DuplexStream {
+    read: Arc<Mutex<Pipe>>,
+    write: Arc<Mutex<Pipe>>,
+}
+
+/// Bytes flowing in one direction.
+#[derive(Default)]
+struct Pipe {
+    buf: Vec<u8>,
+    /// Set once either end is done with the pipe.
+    closed: bool,
+    /// The reader waiting for bytes to be written.
+    reader: Option<Task>,
+}
+
+// ===== impl DuplexStream =====
+
+impl Read for DuplexStream {
+    fn read(&mut self, dst: &mut [u8]) -> io::Result<usize> {
+        let mut pipe = self.read.lock().unwrap();
+
+        if pipe.buf.is_empty() {
+            if pipe.closed {
+                return Ok(0);
+            }
+
+            pipe.reader = Some(task::current());
+            return Err(io::ErrorKind::WouldBlock.into());
+        }
+
+        let n = cmp::min(dst.len(), pipe.buf.len());
+        dst[..n].copy_from_slice(&pipe.buf[..n]);
+        pipe.buf.drain(..n);
+        Ok(n)
+    }
+}
+
+impl Write for DuplexStream {
+    fn write(&mut self, src: &[u8]) -> io::Result<usize> {
+        let mut pipe = self.write.lock().unwrap();
+
+        if pipe.closed {
+            return Err(io::ErrorKind::BrokenPipe.into());
+        }
+
+        pipe.buf.extend_from_slice(src);
+        pipe.notify();
+        Ok(src.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl AsyncRead for DuplexStream {}
+
+impl AsyncWrite for DuplexStream {
+    fn shutdown(&mut self) -> Poll<(), io::Error> {
+        self.write.lock().unwrap().close();
+        Ok(Async::Ready(()))
+    }
+}
+
+impl Drop for DuplexStream {
+    fn drop(&mut self) {
+        self.read.lock().unwrap().close();
+        self.write.lock().unwrap().close();
+    }
+}
+
+impl fmt::Debug for DuplexStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DuplexStream").finish()
+    }
+}
+
+// ===== impl Pipe =====
+
+impl Pipe {
+    fn close(&mut self) {
+        self.closed = true;
+        self.notify();
+    }
+
+    fn notify(&mut self) {
+        if let Some(reader) = self.reader.take() {
+            reader.notify();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::Future;
+    use tokio::io::{read_exact, read_to_end, write_all};
+    use tokio::runtime::current_thread::Runtime;
+
+    #[test]
+    fn bytes_flow_both_ways() {
+        let mut rt = Runtime::new().unwrap();
+        let (one, two) = duplex();
+
+        let one = write_all(one, b"ping").and_then(|(one, _)| read_to_end(one, Vec::new()));
+        let two = read_exact(two, [0; 4]).and_then(|(two, buf)| {
+            assert_eq!(&buf, b"ping");
+            // Dropping the stream ends the peer's read.
+            write_all(two, b"pong").map(drop)
+        });
+
+        let ((_, received), ()) = rt.block_on(one.join(two)).unwrap();
+        assert_eq!(received, b"pong");
+    }
+
+    #[test]
+    fn writes_fail_once_peer_dropped() {
+        let (mut one, two) = duplex();
+        drop(two);
+
+        let err = one.write(b"ping").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+        assert_eq!(one.read(&mut [0; 4]).unwrap(), 0);
+    }
+}
@@ -0,0 +1,179 @@
+//! Utilities for testing gRPC services in process.
+//!
+//! `channel` connects a client to a service over an in-memory stream, using
+//! the same HTTP/2 transport as real connections, so that generated clients
+//! can be tested against generated servers without binding sockets.
+//!
+//! ```ignore
+//! let (client, server) = tower_grpc::testing::channel(GreeterServer::new(Greet));
+//! tokio::spawn(server.map_err(|e| panic!("server error: {}", e)));
+//!
+//! let mut greeter = Greeter::new(client);
+//! ```
+
+mod duplex;
+
+pub use self::duplex::{duplex, DuplexStream};
+
+use crate::body::{Body, BoxBody};
+use crate::generic::client::GrpcService;
+use crate::transport::client::{self, Handshake, ResponseFuture};
+use crate::transport::{server, Error, RecvBody};
+
+use futures::future::Shared;
+use futures::{Async, Future, Poll};
+use http::uri::{PathAndQuery, Uri};
+use std::fmt;
+use tower_service::Service;
+
+/// The origin requests sent by a `Client` are given.
+const ORIGIN: &str = "http://localhost";
+
+/// A client connected to an in-memory server, created by `channel`.
+///
+/// Cloning the client shares its connection. Requests only need a path, as
+/// they are sent to `http://localhost`.
+#[derive(Clone)]
+pub struct Client {
+    handshake: Shared<Handshake<DuplexStream>>,
+    conn: Option<client::Connection>,
+}
+
+/// The server end of an in-memory connection, created by `channel`.
+///
+/// Serves requests until the connection is closed, once every clone of the
+/// `Client` has been dropped.
+pub type Server<S> = server::Connection<DuplexStream, S>;
+
+/// Connects a `Client` to `service` over an in-memory stream.
+///
+/// Both the client and the server use the default executor once polled, so
+/// they must be used from within a Tokio runtime, and the returned `Server`
+/// must be spawned for requests to be served.
+pub fn channel<S>(service: S) -> (Client, Server<S>)
+where
+    S: GrpcService<BoxBody>,
+    <S::ResponseBody as Body>::Data: 'static,
+{
+    let (client_io, server_io) = duplex();
+
+    let client = Client {
+        handshake: client::Builder::new().handshake(client_io).shared(),
+        conn: None,
+    };
+    let server = server::Builder::new().serve_connection(server_io, service);
+
+    (client, server)
+}
+
+// ===== impl Client =====
+
+impl Service<http::Request<BoxBody>> for Client {
+    type Response = http::Response<RecvBody>;
+    type Error = Error;
+    type Future = ResponseFuture;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        if self.conn.is_none() {
+            match self.handshake.poll() {
+                Ok(Async::Ready(conn)) => self.conn = Some((*conn).clone()),
+                Ok(Async::NotReady) => return Ok(Async::NotReady),
+                Err(e) => return Err(Error::connect(e.to_string())),
+            }
+        }
+
+        let conn = self.conn.as_mut().expect("connection established");
+        Service::poll_ready(conn)
+    }
+
+    fn call(&mut self, mut request: http::Request<BoxBody>) -> Self::Future {
+        let conn = self
+            .conn
+            .as_mut()
+            .expect("Client::call invoked without first polling poll_ready");
+
+        let mut parts = Uri::from_static(ORIGIN).into_parts();
+        parts.path_and_query = Some(
+            request
+                .uri()
+                .path_and_query()
+                .cloned()
+                .unwrap_or_else(|| PathAndQuery::from_static("/")),
+        );
+
+        match Uri::from_parts(parts) {
+            Ok(uri) => *request.uri_mut() = uri,
+            Err(e) => return ResponseFuture::failed(Error::transport(e)),
+        }
+
+        Service::call(conn, request)
+    }
+}
+
+impl fmt::Debug for Client {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Client")
+            .field("connected", &self.conn.is_some())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::Empty;
+    use crate::{Code, Status};
+
+    use futures::future;
+    use tokio::runtime::Runtime;
+
+    /// Fails every request with the value of its `x-echo` header.
+    #[derive(Clone, Debug)]
+    struct Echo;
+
+    impl Service<http::Request<BoxBody>> for Echo {
+        type Response = http::Response<BoxBody>;
+        type Error = Status;
+        type Future = future::FutureResult<Self::Response, Self::Error>;
+
+        fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+            Ok(Async::Ready(()))
+        }
+
+        fn call(&mut self, request: http::Request<BoxBody>) -> Self::Future {
+            let echo = request.headers()["x-echo"].to_str().unwrap();
+            future::err(Status::new(Code::FailedPrecondition, echo))
+        }
+    }
+
+    #[test]
+    fn requests_are_served_in_memory() {
+        let mut rt = Runtime::new().unwrap();
+        let (client, server) = channel(Echo);
+        rt.spawn(server.map_err(|e| panic!("server error: {}", e)));
+
+        let call = |i| {
+            let mut client = client.clone();
+
+            future::poll_fn(move || {
+                futures::try_ready!(Service::poll_ready(&mut client));
+                Ok(Async::Ready(client.clone()))
+            })
+            .and_then(move |mut client| {
+                let request = http::Request::builder()
+                    .uri("/test.Echo/Echo")
+                    .header("x-echo", format!("hello-{}", i))
+                    .body(BoxBody::new(Box::new(Empty)))
+                    .unwrap();
+                Service::call(&mut client, request)
+            })
+        };
+        let calls = vec![call(0), call(1)];
+        let responses = rt.block_on(future::join_all(calls)).unwrap();
+
+        for (i, response) in responses.iter().enumerate() {
+            assert_eq!(response.headers()["grpc-status"], "9");
+            assert_eq!(response.headers()["grpc-message"], &*format!("hello-{}", i));
+        }
+    }
+}
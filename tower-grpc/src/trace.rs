@@ -0,0 +1,606 @@
+//! Spans for each RPC, using `tracing`.
+//!
+//! `Trace` wraps either a server's service or a client's HTTP service, and
+//! opens a span for each call, with fields following the OpenTelemetry
+//! semantic conventions for RPCs:
+//!
+//! ```ignore
+//! let server = Server::builder().add_service(Trace::server(GreeterServer::new(Greet)));
+//!
+//! let greeter = Greeter::new(Trace::client(channel));
+//! ```
+//!
+//! Spans are named `grpc`, with `otel.name` set to the method's full name,
+//! and record:
+//!
+//! - `otel.kind`: `"server"` or `"client"`.
+//! - `rpc.system`, `rpc.service` and `rpc.method`.
+//! - `net.peer.ip` or `net.peer.name`, and `net.peer.port`. Clients record
+//!   the request URI's authority, and servers the `SocketAddr` request
+//!   extension, which the transport server sets to the client's address on
+//!   TCP connections.
+//! - `trace_id` and `parent_id`, as hex, on servers that received a
+//!   `SpanContext` through `propagation::Propagate`, whether it wraps
+//!   `Trace` or is wrapped by it.
+//! - `rpc.grpc.status_code`, once the call completes, along with
+//!   `otel.status_code` and `otel.status_description` if it failed.
+//!
+//! Each message sent or received is recorded as a `DEBUG` event in the span,
+//! with `message.type`, `message.id` and its size.
+
+use crate::body::{Body, BoxBody, HttpBody};
+use crate::descriptor::MethodDescriptor;
+use crate::frames::{Frame, Frames};
+use crate::generic::server::NamedService;
+use crate::propagation::SpanContext;
+use crate::{Code, Status};
+
+use bytes::Buf;
+use futures::{Async, Future, Poll};
+use http::{HeaderMap, Uri};
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+use tower_service::Service;
+use tracing::field::{display, Empty};
+use tracing::{event, info_span, Level, Span};
+
+/// Opens a span for each RPC made to or by the inner service.
+#[derive(Clone, Debug)]
+pub struct Trace<S> {
+    inner: S,
+    kind: Kind,
+}
+
+/// Completes the inner service's response, recording its status in the
+/// RPC's span.
+#[derive(Debug)]
+pub struct ResponseFuture<F> {
+    inner: F,
+    span: Span,
+    kind: Kind,
+}
+
+/// Records the messages and status of a response body in the RPC's span.
+#[derive(Debug)]
+pub struct ResponseBody<B> {
+    inner: B,
+    messages: Messages,
+}
+
+/// Records the messages of a request body in the RPC's span.
+#[derive(Debug)]
+struct RequestBody<B> {
+    inner: B,
+    messages: Messages,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Kind {
+    Client,
+    Server,
+}
+
+/// Emits an event for each message framed in a body.
+#[derive(Debug)]
+struct Messages {
+    span: Span,
+    direction: &'static str,
+    count: u64,
+    frames: Frames,
+}
+
+// ===== impl Trace =====
+
+impl<S> Trace<S> {
+    /// Trace the RPCs a server's service receives.
+    pub fn server(inner: S) -> Self {
+        Trace {
+            inner,
+            kind: Kind::Server,
+        }
+    }
+
+    /// Trace the RPCs sent through a client's HTTP service.
+    pub fn client(inner: S) -> Self {
+        Trace {
+            inner,
+            kind: Kind::Client,
+        }
+    }
+
+    /// Get a reference to the inner service.
+    pub fn get_ref(&self) -> &S {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the inner service.
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.inner
+    }
+
+    /// Consumes `self`, returning the inner service.
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl<S, B> Service<http::Request<BoxBody>> for Trace<S>
+where
+    S: Service<http::Request<BoxBody>, Response = http::Response<B>>,
+    S::Error: fmt::Display,
+    B: Body,
+{
+    type Response = http::Response<ResponseBody<B>>;
+    type Error = S::Error;
+    type Future = ResponseFuture<S::Future>;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        self.inner.poll_ready()
+    }
+
+    fn call(&mut self, request: http::Request<BoxBody>) -> Self::Future {
+        let span = self.kind.span(request.uri());
+
+        match self.kind {
+            Kind::Client => record_peer(&span, request.uri()),
+            Kind::Server => {
+                if let Some(addr) = request.extensions().get::<SocketAddr>() {
+                    span.record("net.peer.ip", display(addr.ip()));
+                    span.record("net.peer.port", addr.port());
+                }
+
+                if let Some(context) = request.extensions().get::<SpanContext>() {
+                    record_context(&span, context);
+                }
+            }
+        }
+
+        let messages = Messages::new(span.clone(), self.kind.request_messages());
+        let request = request.map(|inner| BoxBody::new(Box::new(RequestBody { inner, messages })));
+
+        let inner = {
+            let _enter = span.enter();
+            self.inner.call(request)
+        };
+
+        ResponseFuture {
+            inner,
+            span,
+            kind: self.kind,
+        }
+    }
+}
+
+impl<S: NamedService> NamedService for Trace<S> {
+    const NAME: &'static str = S::NAME;
+    const METHODS: &'static [MethodDescriptor] = S::METHODS;
+}
+
+// ===== impl Kind =====
+
+impl Kind {
+    fn span(self, uri: &Uri) -> Span {
+        let path = uri.path().trim_start_matches('/');
+        let mut parts = path.splitn(2, '/');
+        let service = parts.next().unwrap_or("");
+        let method = parts.next().unwrap_or("");
+
+        macro_rules! rpc_span {
+            ($kind:expr) => {
+                info_span!(
+                    "grpc",
+                    otel.name = path,
+                    otel.kind = $kind,
+                    rpc.system = "grpc",
+                    rpc.service = service,
+                    rpc.method = method,
+                    net.peer.ip = Empty,
+                    net.peer.name = Empty,
+                    net.peer.port = Empty,
+                    trace_id = Empty,
+                    parent_id = Empty,
+                    rpc.grpc.status_code = Empty,
+                    otel.status_code = Empty,
+                    otel.status_description = Empty,
+                )
+            };
+        }
+
+        match self {
+            Kind::Client => rpc_span!("client"),
+            Kind::Server => rpc_span!("server"),
+        }
+    }
+
+    /// The `message.type` of messages in request bodies.
+    fn request_messages(self) -> &'static str {
+        match self {
+            Kind::Client => "SENT",
+            Kind::Server => "RECEIVED",
+        }
+    }
+
+    /// The `message.type` of messages in response bodies.
+    fn response_messages(self) -> &'static str {
+        match self {
+            Kind::Client => "RECEIVED",
+            Kind::Server => "SENT",
+        }
+    }
+}
+
+fn record_peer(span: &Span, uri: &Uri) {
+    if let Some(host) = uri.host() {
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+
+        match host.parse::<IpAddr>() {
+            Ok(ip) => span.record("net.peer.ip", display(ip)),
+            Err(_) => span.record("net.peer.name", host),
+        };
+    }
+
+    if let Some(port) = uri.port_u16() {
+        span.record("net.peer.port", port);
+    }
+}
+
+/// Record the remote parent of a server's span.
+pub(crate) fn record_context(span: &Span, context: &SpanContext) {
+    span.record(
+        "trace_id",
+        display(format_args!("{:032x}", context.trace_id())),
+    );
+    span.record(
+        "parent_id",
+        display(format_args!("{:016x}", context.span_id())),
+    );
+}
+
+fn record_status(span: &Span, status: &Status) {
+    span.record("rpc.grpc.status_code", status.code() as i32);
+
+    if status.code() != Code::Ok {
+        span.record("otel.status_code", "ERROR");
+        span.record("otel.status_description", status.message());
+    }
+}
+
+fn record_error(span: &Span, error: &dyn fmt::Display) {
+    span.record("otel.status_code", "ERROR");
+    span.record("otel.status_description", display(error));
+}
+
+// ===== impl ResponseFuture =====
+
+impl<F, B> Future for ResponseFuture<F>
+where
+    F: Future<Item = http::Response<B>>,
+    F::Error: fmt::Display,
+    B: Body,
+{
+    type Item = http::Response<ResponseBody<B>>;
+    type Error = F::Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        let _enter = self.span.enter();
+
+        let response = match self.inner.poll() {
+            Ok(Async::Ready(response)) => response,
+            Ok(Async::NotReady) => return Ok(Async::NotReady),
+            Err(e) => {
+                record_error(&self.span, &e);
+                return Err(e);
+            }
+        };
+
+        // Trailers-only responses carry their status in the headers.
+        if let Some(status) = Status::from_header_map(response.headers()) {
+            record_status(&self.span, &status);
+        }
+
+        let messages = Messages::new(self.span.clone(), self.kind.response_messages());
+        Ok(Async::Ready(
+            response.map(|inner| ResponseBody { inner, messages }),
+        ))
+    }
+}
+
+// ===== impl ResponseBody =====
+
+impl<B> HttpBody for ResponseBody<B>
+where
+    B: Body,
+{
+    type Data = B::Data;
+    type Error = B::Error;
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn poll_data(&mut self) -> Poll<Option<Self::Data>, Self::Error> {
+        let data = futures::try_ready!(self.inner.poll_data());
+
+        if let Some(ref data) = data {
+            self.messages.read(data.bytes());
+        }
+
+        Ok(Async::Ready(data))
+    }
+
+    fn poll_trailers(&mut self) -> Poll<Option<HeaderMap>, Self::Error> {
+        let trailers = futures::try_ready!(self.inner.poll_trailers());
+
+        if let Some(status) = trailers.as_ref().and_then(Status::from_header_map) {
+            record_status(&self.messages.span, &status);
+        }
+
+        Ok(Async::Ready(trailers))
+    }
+}
+
+// ===== impl RequestBody =====
+
+impl HttpBody for RequestBody<BoxBody> {
+    type Data = <BoxBody as Body>::Data;
+    type Error = Status;
+
+    fn is_end_stream(&self) -> bool {
+        Body::is_end_stream(&self.inner)
+    }
+
+    fn poll_data(&mut self) -> Poll<Option<Self::Data>, Self::Error> {
+        let data = futures::try_ready!(Body::poll_data(&mut self.inner));
+
+        if let Some(ref data) = data {
+            self.messages.read(data.bytes());
+        }
+
+        Ok(Async::Ready(data))
+    }
+
+    fn poll_trailers(&mut self) -> Poll<Option<HeaderMap>, Self::Error> {
+        Body::poll_trailers(&mut self.inner)
+    }
+}
+
+// ===== impl Messages =====
+
+impl Messages {
+    fn new(span: Span, direction: &'static str) -> Self {
+        Messages {
+            span,
+            direction,
+            count: 0,
+            frames: Frames::default(),
+        }
+    }
+
+    /// Read the next chunk of the body.
+    fn read(&mut self, buf: &[u8]) {
+        let span = &self.span;
+        let direction = self.direction;
+        let count = &mut self.count;
+
+        self.frames.read(buf, |frame| {
+            *count += 1;
+            message(span, direction, *count, frame);
+        });
+    }
+}
+
+/// Record a message whose header has just been read.
+fn message(span: &Span, direction: &'static str, id: u64, frame: Frame) {
+    if frame.compressed {
+        event!(
+            parent: span,
+            Level::DEBUG,
+            message.type = direction,
+            message.id = id,
+            message.compressed_size = frame.len,
+            "message"
+        );
+    } else {
+        event!(
+            parent: span,
+            Level::DEBUG,
+            message.type = direction,
+            message.id = id,
+            message.uncompressed_size = frame.len,
+            "message"
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::Grpc;
+    use crate::propagation::{Propagate, B3};
+    use crate::test_util::{Empty, NotFound};
+    use crate::Request;
+
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata, Subscriber};
+    use tracing_core::span::Current;
+
+    type Fields = HashMap<&'static str, String>;
+
+    /// Records the fields of the one span it expects, and of its events.
+    #[derive(Clone, Default)]
+    struct Recorder {
+        span: Arc<Mutex<Fields>>,
+        events: Arc<Mutex<Vec<Fields>>>,
+        /// The span's metadata while it is entered.
+        entered: Arc<Mutex<Option<&'static Metadata<'static>>>>,
+        metadata: Arc<Mutex<Option<&'static Metadata<'static>>>>,
+    }
+
+    struct Visitor<'a>(&'a mut Fields);
+
+    impl<'a> Visit for Visitor<'a> {
+        fn record_str(&mut self, field: &Field, value: &str) {
+            self.0.insert(field.name(), value.to_owned());
+        }
+
+        fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+            self.0.insert(field.name(), format!("{:?}", value));
+        }
+    }
+
+    impl Subscriber for Recorder {
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &Attributes<'_>) -> Id {
+            *self.metadata.lock().unwrap() = Some(span.metadata());
+            span.record(&mut Visitor(&mut self.span.lock().unwrap()));
+            Id::from_u64(1)
+        }
+
+        fn record(&self, _: &Id, values: &Record<'_>) {
+            values.record(&mut Visitor(&mut self.span.lock().unwrap()));
+        }
+
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+        fn event(&self, event: &Event<'_>) {
+            let mut fields = Fields::new();
+            event.record(&mut Visitor(&mut fields));
+            self.events.lock().unwrap().push(fields);
+        }
+
+        fn enter(&self, _: &Id) {
+            *self.entered.lock().unwrap() = *self.metadata.lock().unwrap();
+        }
+
+        fn exit(&self, _: &Id) {
+            *self.entered.lock().unwrap() = None;
+        }
+
+        fn current_span(&self) -> Current {
+            match *self.entered.lock().unwrap() {
+                Some(metadata) => Current::new(Id::from_u64(1), metadata),
+                None => Current::none(),
+            }
+        }
+    }
+
+    #[test]
+    fn client_span() {
+        let recorder = Recorder::default();
+
+        tracing::subscriber::with_default(recorder.clone(), || {
+            let mut client = Grpc::new(Trace::client(NotFound));
+            let path = http::uri::PathAndQuery::from_static("/test.NotFound/Get");
+            let status = client
+                .unary::<(), (), BoxBody>(Request::new(()), path)
+                .wait()
+                .unwrap_err();
+
+            assert_eq!(status.code(), Code::NotFound);
+        });
+
+        let span = recorder.span.lock().unwrap();
+        assert_eq!(span["otel.name"], "test.NotFound/Get");
+        assert_eq!(span["otel.kind"], "client");
+        assert_eq!(span["rpc.system"], "grpc");
+        assert_eq!(span["rpc.service"], "test.NotFound");
+        assert_eq!(span["rpc.method"], "Get");
+        assert_eq!(span["rpc.grpc.status_code"], "5");
+        assert_eq!(span["otel.status_code"], "ERROR");
+        assert_eq!(span["otel.status_description"], "not here");
+
+        let events = recorder.events.lock().unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0]["message.type"], "SENT");
+        assert_eq!(events[0]["message.id"], "1");
+        assert_eq!(events[0]["message.uncompressed_size"], "0");
+    }
+
+    #[cfg(feature = "transport")]
+    #[test]
+    fn server_span() {
+        use crate::transport::{Endpoint, Server};
+        use futures::sync::oneshot;
+        use tokio::runtime::current_thread::Runtime;
+
+        let recorder = Recorder::default();
+        let addr = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+
+        // The server and client run on this thread, so that the server's span
+        // is recorded.
+        tracing::subscriber::with_default(recorder.clone(), || {
+            let mut rt = Runtime::new().unwrap();
+            let (_shutdown, signal) = oneshot::channel::<()>();
+            let serve = Server::builder()
+                .add_service(Trace::server(NotFound))
+                .serve_with_shutdown(addr, signal)
+                .map_err(|e| panic!("server error: {}", e));
+            rt.spawn(serve);
+
+            let channel = Endpoint::from_shared(format!("http://{}", addr))
+                .unwrap()
+                .connect();
+            let channel = rt.block_on(channel).unwrap();
+
+            let path = http::uri::PathAndQuery::from_static("/test.NotFound/Get");
+            let call = Grpc::new(channel)
+                .ready()
+                .and_then(|mut client| client.unary::<(), (), BoxBody>(Request::new(()), path));
+            let status = rt.block_on(call).unwrap_err();
+
+            assert_eq!(status.code(), Code::NotFound);
+        });
+
+        let span = recorder.span.lock().unwrap();
+        assert_eq!(span["otel.kind"], "server");
+        assert_eq!(span["rpc.service"], "test.NotFound");
+        assert_eq!(span["rpc.method"], "Get");
+        assert_eq!(span["net.peer.ip"], "127.0.0.1");
+        assert!(span.contains_key("net.peer.port"));
+        assert_eq!(span["rpc.grpc.status_code"], "5");
+
+        let events = recorder.events.lock().unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0]["message.type"], "RECEIVED");
+    }
+
+    #[test]
+    fn server_span_records_remote_parent() {
+        fn call<S>(mut service: S) -> Fields
+        where
+            S: Service<http::Request<BoxBody>>,
+            S::Response: fmt::Debug,
+            S::Error: fmt::Debug,
+        {
+            let recorder = Recorder::default();
+
+            tracing::subscriber::with_default(recorder.clone(), || {
+                let request = http::Request::post("/test.NotFound/Get")
+                    .header("b3", "a3ce929d0e0e4736-00f067aa0ba902b7-1")
+                    .body(BoxBody::new(Box::new(Empty)))
+                    .unwrap();
+                service.call(request).wait().unwrap();
+            });
+
+            let span = recorder.span.lock().unwrap().clone();
+            span
+        }
+
+        let outer = call(Trace::server(Propagate::server(NotFound, B3::single())));
+        let inner = call(Propagate::server(Trace::server(NotFound), B3::single()));
+
+        for span in [outer, inner].iter() {
+            assert_eq!(span["trace_id"], "0000000000000000a3ce929d0e0e4736");
+            assert_eq!(span["parent_id"], "00f067aa0ba902b7");
+        }
+    }
+}
@@ -0,0 +1,272 @@
+use super::channelz::{status_code, Call};
+use super::keepalive::ActiveStream;
+use crate::body::{Body, HttpBody};
+use crate::error::Error;
+use crate::Status;
+
+use bytes::{Buf, Bytes};
+use futures::{try_ready, Async, Future, Poll, Stream};
+use http::HeaderMap;
+use log::trace;
+use std::fmt;
+
+/// A body received on an HTTP/2 stream.
+///
+/// Flow control capacity is released back to the peer as data is read.
+pub struct RecvBody {
+    inner: h2::RecvStream,
+    active: Option<ActiveStream>,
+    /// The channelz call the body is the response to.
+    call: Option<Call>,
+}
+
+/// A chunk of data received on an HTTP/2 stream.
+#[derive(Debug)]
+pub struct Data {
+    bytes: Bytes,
+}
+
+/// The buffer type handed to `h2`, allowing for empty end-of-stream frames.
+#[derive(Debug)]
+pub(crate) enum SendBuf<B> {
+    Buf(B),
+    None,
+}
+
+/// Writes an HTTP body to an HTTP/2 stream, followed by its trailers.
+pub(crate) struct SendBody<B: Body> {
+    body: B,
+    stream: h2::SendStream<SendBuf<B::Data>>,
+    data_done: bool,
+}
+
+// ===== impl RecvBody =====
+
+impl RecvBody {
+    pub(crate) fn new(inner: h2::RecvStream) -> Self {
+        RecvBody {
+            inner,
+            active: None,
+            call: None,
+        }
+    }
+
+    pub(crate) fn tracked(inner: h2::RecvStream, active: ActiveStream) -> Self {
+        RecvBody {
+            inner,
+            active: Some(active),
+            call: None,
+        }
+    }
+
+    /// End `call` once the body has been received, counting its messages.
+    pub(crate) fn track(&mut self, call: Call) {
+        match self.call {
+            Some(ref mut tracked) => tracked.join(call),
+            None => self.call = Some(call),
+        }
+    }
+
+    fn error(&self, err: h2::Error) -> super::Error {
+        match self.active {
+            Some(ref active) => active.error(err),
+            None => err.into(),
+        }
+    }
+}
+
+impl HttpBody for RecvBody {
+    type Data = Data;
+    type Error = super::Error;
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn poll_data(&mut self) -> Poll<Option<Self::Data>, Self::Error> {
+        let bytes = try_ready!(self.inner.poll().map_err(|e| self.error(e)));
+
+        if let Some(ref bytes) = bytes {
+            self.inner
+                .release_capacity()
+                .release_capacity(bytes.len())
+                .map_err(|e| self.error(e))?;
+
+            if let Some(ref mut call) = self.call {
+                call.data(bytes);
+            }
+        }
+
+        Ok(Async::Ready(bytes.map(|bytes| Data { bytes })))
+    }
+
+    fn poll_trailers(&mut self) -> Poll<Option<HeaderMap>, Self::Error> {
+        let trailers = try_ready!(self.inner.poll_trailers().map_err(|e| self.error(e)));
+
+        if let Some(ref mut call) = self.call {
+            call.finish(Some(status_code(trailers.as_ref())));
+        }
+
+        Ok(Async::Ready(trailers))
+    }
+}
+
+impl fmt::Debug for RecvBody {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RecvBody").finish()
+    }
+}
+
+// ===== impl Data =====
+
+impl Buf for Data {
+    #[inline]
+    fn remaining(&self) -> usize {
+        self.bytes.len()
+    }
+
+    #[inline]
+    fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    #[inline]
+    fn advance(&mut self, cnt: usize) {
+        self.bytes.advance(cnt);
+    }
+}
+
+impl From<Data> for Bytes {
+    fn from(data: Data) -> Self {
+        data.bytes
+    }
+}
+
+// ===== impl SendBuf =====
+
+impl<B: Buf> Buf for SendBuf<B> {
+    #[inline]
+    fn remaining(&self) -> usize {
+        match *self {
+            SendBuf::Buf(ref buf) => buf.remaining(),
+            SendBuf::None => 0,
+        }
+    }
+
+    #[inline]
+    fn bytes(&self) -> &[u8] {
+        match *self {
+            SendBuf::Buf(ref buf) => buf.bytes(),
+            SendBuf::None => &[],
+        }
+    }
+
+    #[inline]
+    fn advance(&mut self, cnt: usize) {
+        match *self {
+            SendBuf::Buf(ref mut buf) => buf.advance(cnt),
+            SendBuf::None => assert_eq!(cnt, 0, "advanced an empty SendBuf"),
+        }
+    }
+}
+
+// ===== impl SendBody =====
+
+impl<B: Body> SendBody<B> {
+    pub(crate) fn new(body: B, stream: h2::SendStream<SendBuf<B::Data>>) -> Self {
+        SendBody {
+            body,
+            stream,
+            data_done: false,
+        }
+    }
+
+    /// Ends the stream early with `status` as its trailers.
+    pub(crate) fn cancel(&mut self, status: &Status) {
+        match status.to_header_map() {
+            Ok(trailers) => {
+                if let Err(e) = self.stream.send_trailers(trailers) {
+                    trace!("failed to send trailers: {}", e);
+                }
+            }
+            Err(_) => self.stream.send_reset(h2::Reason::INTERNAL_ERROR),
+        }
+    }
+
+    /// Resets the stream, abandoning the rest of the body.
+    pub(crate) fn send_reset(&mut self, reason: h2::Reason) {
+        self.stream.send_reset(reason);
+    }
+}
+
+impl<B> Future for SendBody<B>
+where
+    B: Body,
+{
+    type Item = ();
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        loop {
+            if !self.data_done {
+                // Reserve a single byte so that we learn when some capacity is
+                // available; h2 accounts for the full chunk once it is sent.
+                self.stream.reserve_capacity(1);
+
+                if self.stream.capacity() == 0 {
+                    loop {
+                        match try_ready!(self.stream.poll_capacity()) {
+                            Some(0) => {}
+                            Some(_) => break,
+                            None => {
+                                trace!("stream closed before the body was sent");
+                                return Ok(Async::Ready(()));
+                            }
+                        }
+                    }
+                } else if let Async::Ready(reason) = self.stream.poll_reset()? {
+                    trace!("stream reset by peer: {:?}", reason);
+                    return Ok(Async::Ready(()));
+                }
+
+                match try_ready!(self.body.poll_data().map_err(Into::into)) {
+                    Some(data) => {
+                        let eos = self.body.is_end_stream();
+                        self.stream.send_data(SendBuf::Buf(data), eos)?;
+
+                        if eos {
+                            return Ok(Async::Ready(()));
+                        }
+                    }
+                    None => {
+                        self.data_done = true;
+                    }
+                }
+            } else {
+                if let Async::Ready(reason) = self.stream.poll_reset()? {
+                    trace!("stream reset by peer: {:?}", reason);
+                    return Ok(Async::Ready(()));
+                }
+
+                match try_ready!(self.body.poll_trailers().map_err(Into::into)) {
+                    Some(trailers) => self.stream.send_trailers(trailers)?,
+                    None => self.stream.send_data(SendBuf::None, true)?,
+                }
+
+                return Ok(Async::Ready(()));
+            }
+        }
+    }
+}
+
+impl<B> fmt::Debug for SendBody<B>
+where
+    B: Body + fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SendBody")
+            .field("body", &self.body)
+            .field("data_done", &self.data_done)
+            .finish()
+    }
+}
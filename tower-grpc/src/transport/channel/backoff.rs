@@ -0,0 +1,139 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+/// The default delay before the first reconnection attempt.
+const DEFAULT_INITIAL: Duration = Duration::from_secs(1);
+
+/// The default upper bound on the delay between reconnection attempts.
+const DEFAULT_MAX: Duration = Duration::from_secs(120);
+
+const DEFAULT_MULTIPLIER: f64 = 1.6;
+
+const DEFAULT_JITTER: f64 = 0.2;
+
+/// How long a channel waits between failed connection attempts.
+///
+/// The delay grows exponentially with each consecutive failure, and is
+/// randomized by the jitter factor so that clients do not reconnect in
+/// lockstep. The defaults follow gRPC's connection backoff protocol.
+#[derive(Clone, Debug)]
+pub struct Backoff {
+    initial: Duration,
+    max: Duration,
+    multiplier: f64,
+    jitter: f64,
+}
+
+impl Backoff {
+    /// Returns a new `Backoff` with default values.
+    pub fn new() -> Self {
+        Backoff {
+            initial: DEFAULT_INITIAL,
+            max: DEFAULT_MAX,
+            multiplier: DEFAULT_MULTIPLIER,
+            jitter: DEFAULT_JITTER,
+        }
+    }
+
+    /// Set the delay after the first failed attempt.
+    ///
+    /// Defaults to 1 second.
+    pub fn initial(mut self, initial: Duration) -> Self {
+        self.initial = initial;
+        self
+    }
+
+    /// Set the upper bound on the delay, before jitter is applied.
+    ///
+    /// Defaults to 120 seconds.
+    pub fn max(mut self, max: Duration) -> Self {
+        self.max = max;
+        self
+    }
+
+    /// Set the factor the delay grows by after each failed attempt.
+    ///
+    /// Defaults to 1.6.
+    pub fn multiplier(mut self, multiplier: f64) -> Self {
+        self.multiplier = multiplier.max(1.0);
+        self
+    }
+
+    /// Set the fraction by which each delay is randomly lengthened or
+    /// shortened.
+    ///
+    /// Defaults to 0.2.
+    pub fn jitter(mut self, jitter: f64) -> Self {
+        self.jitter = jitter.max(0.0).min(1.0);
+        self
+    }
+
+    /// Returns the delay after `failures` consecutive failed attempts.
+    pub(crate) fn delay(&self, failures: u32) -> Duration {
+        let max = duration_to_secs(self.max);
+        let mut delay = duration_to_secs(self.initial);
+
+        for _ in 1..failures {
+            delay *= self.multiplier;
+
+            if delay >= max {
+                delay = max;
+                break;
+            }
+        }
+
+        let delay = delay.min(max) * (1.0 + self.jitter * (random() * 2.0 - 1.0));
+        secs_to_duration(delay)
+    }
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Backoff::new()
+    }
+}
+
+fn duration_to_secs(duration: Duration) -> f64 {
+    duration.as_secs() as f64 + f64::from(duration.subsec_nanos()) / 1e9
+}
+
+fn secs_to_duration(secs: f64) -> Duration {
+    let secs = secs.max(0.0);
+    Duration::new(secs.trunc() as u64, (secs.fract() * 1e9) as u32)
+}
+
+/// Returns a pseudo-random number in `[0, 1)`.
+///
+/// Randomly seeded hashers are a cheap source of randomness, which is all
+/// jitter needs.
+fn random() -> f64 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u8(0);
+    (hasher.finish() >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delay_grows_exponentially() {
+        let backoff = Backoff::new().jitter(0.0);
+
+        assert_eq!(backoff.delay(1), Duration::from_secs(1));
+        assert_eq!(backoff.delay(2), Duration::from_millis(1600));
+        assert_eq!(backoff.delay(20), Duration::from_secs(120));
+    }
+
+    #[test]
+    fn delay_is_jittered() {
+        let backoff = Backoff::new().initial(Duration::from_secs(10));
+
+        for _ in 0..100 {
+            let delay = backoff.delay(1);
+            assert!(delay >= Duration::from_secs(8), "{:?}", delay);
+            assert!(delay <= Duration::from_secs(12), "{:?}", delay);
+        }
+    }
+}
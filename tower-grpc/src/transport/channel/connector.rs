@@ -0,0 +1,343 @@
+use super::Endpoint;
+use crate::transport::channelz::{self, ChannelEntry, SocketEntry};
+use crate::transport::client::{self, Connection};
+#[cfg(feature = "tls")]
+use crate::transport::tls::TlsConnector;
+use crate::transport::Error;
+
+use futures::future::{self, Either, Loop};
+use futures::sync::oneshot;
+use futures::{Async, Future};
+use log::{debug, trace};
+use std::any::Any;
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+#[cfg(unix)]
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use std::{io, thread, vec};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpStream;
+#[cfg(unix)]
+use tokio::net::UnixStream;
+use tokio::timer::Timeout;
+
+/// A future resolving to a new connection.
+pub(crate) type Connecting = Box<dyn Future<Item = Connection, Error = Error> + Send>;
+
+/// Establishes connections to an endpoint.
+#[derive(Clone, Debug)]
+pub(crate) struct Connector {
+    target: Target,
+    builder: client::Builder,
+    connect_timeout: Option<Duration>,
+    tcp_nodelay: bool,
+    #[cfg(feature = "tls")]
+    tls: Option<TlsConnector>,
+    /// The channelz subchannel the connections are reported to.
+    subchannel: Option<Arc<channelz::Handle<ChannelEntry>>>,
+}
+
+/// Where connections are established to.
+#[derive(Clone, Debug, PartialEq)]
+enum Target {
+    Tcp {
+        host: String,
+        port: u16,
+    },
+    #[cfg(unix)]
+    Unix(PathBuf),
+}
+
+// ===== impl Connector =====
+
+impl Connector {
+    pub(crate) fn new(endpoint: &Endpoint) -> Result<Self, Error> {
+        Ok(Connector {
+            target: Target::new(endpoint)?,
+            builder: endpoint.builder.clone(),
+            connect_timeout: endpoint.connect_timeout,
+            tcp_nodelay: endpoint.tcp_nodelay,
+            #[cfg(feature = "tls")]
+            tls: endpoint.tls.clone(),
+            subchannel: None,
+        })
+    }
+
+    /// Report established connections as sockets of `subchannel`.
+    pub(crate) fn with_channelz(mut self, subchannel: Arc<channelz::Handle<ChannelEntry>>) -> Self {
+        self.subchannel = Some(subchannel);
+        self
+    }
+
+    /// Connect to the endpoint, performing the TLS handshake if configured,
+    /// and then the HTTP/2 handshake.
+    pub(crate) fn connect(&self) -> Connecting {
+        match self.target {
+            Target::Tcp { ref host, port } => {
+                trace!("connecting to {}:{}", host, port);
+                let tcp_nodelay = self.tcp_nodelay;
+
+                let tcp = resolve(host, port)
+                    .and_then(connect_any)
+                    .map_err(Error::connect)
+                    .map(move |io| {
+                        if let Err(e) = io.set_nodelay(tcp_nodelay) {
+                            debug!("failed to set TCP_NODELAY: {}", e);
+                        }
+
+                        io
+                    });
+
+                self.handshake(tcp)
+            }
+            #[cfg(unix)]
+            Target::Unix(ref path) => {
+                trace!("connecting to {}", path.display());
+                self.handshake(UnixStream::connect(path).map_err(Error::connect))
+            }
+        }
+    }
+
+    /// Perform the handshakes on the connection `io` resolves to.
+    fn handshake<F>(&self, io: F) -> Connecting
+    where
+        F: Future<Error = Error> + Send + 'static,
+        F::Item: AsyncRead + AsyncWrite + Send + 'static,
+    {
+        let builder = self.builder.clone();
+        let subchannel = self.subchannel.clone();
+
+        // The socket is registered before TLS wraps it.
+        let io = io.map(move |io| {
+            let socket = subchannel.map(|subchannel| {
+                let (local, remote) = channelz::addresses(&io as &dyn Any);
+                subchannel.add_socket(local, remote)
+            });
+
+            (io, socket)
+        });
+
+        #[cfg(feature = "tls")]
+        {
+            if let Some(tls) = self.tls.clone() {
+                let connect = io
+                    .and_then(move |(io, socket)| {
+                        tls.connect(io)
+                            .map(|(io, info)| {
+                                if let Some(ref socket) = socket {
+                                    socket.entry().set_tls(info);
+                                }
+
+                                (io, socket)
+                            })
+                            .map_err(Error::connect)
+                    })
+                    .and_then(move |(io, socket)| handshake(&builder, io, socket));
+
+                return self.with_timeout(connect);
+            }
+        }
+
+        self.with_timeout(io.and_then(move |(io, socket)| handshake(&builder, io, socket)))
+    }
+
+    fn with_timeout<F>(&self, connect: F) -> Connecting
+    where
+        F: Future<Item = Connection, Error = Error> + Send + 'static,
+    {
+        match self.connect_timeout {
+            Some(timeout) => Box::new(Timeout::new(connect, timeout).map_err(|e| {
+                if e.is_elapsed() {
+                    Error::connect(io::Error::new(io::ErrorKind::TimedOut, "connect timed out"))
+                } else if e.is_inner() {
+                    e.into_inner().expect("inner error")
+                } else {
+                    Error::connect(e.into_timer().expect("timer error"))
+                }
+            })),
+            None => Box::new(connect),
+        }
+    }
+}
+
+// ===== impl Target =====
+
+impl Target {
+    fn new(endpoint: &Endpoint) -> Result<Self, Error> {
+        #[cfg(unix)]
+        {
+            if let Some(ref path) = endpoint.unix {
+                return Ok(Target::Unix(path.clone()));
+            }
+        }
+
+        let uri = &endpoint.uri;
+
+        let default_port = match uri.scheme_str() {
+            Some("http") => 80,
+            Some("https") if endpoint.uses_tls() => 443,
+            Some("https") => return Err(Error::connect("https endpoints require a TLS config")),
+            Some(scheme) => return Err(Error::connect(format!("unsupported scheme: {}", scheme))),
+            None => return Err(Error::connect("endpoint URI is missing a scheme")),
+        };
+
+        let host = match uri.host() {
+            Some(host) if !host.is_empty() => host,
+            _ => return Err(Error::connect("endpoint URI is missing a host")),
+        };
+
+        Ok(Target::Tcp {
+            // IPv6 literals are bracketed in URIs, but not when parsed.
+            host: host
+                .trim_start_matches('[')
+                .trim_end_matches(']')
+                .to_owned(),
+            port: uri.port_u16().unwrap_or(default_port),
+        })
+    }
+}
+
+/// Perform the HTTP/2 handshake on `io`, reporting the connection to its
+/// channelz `socket`.
+fn handshake<I>(
+    builder: &client::Builder,
+    io: I,
+    socket: Option<channelz::Handle<SocketEntry>>,
+) -> impl Future<Item = Connection, Error = Error>
+where
+    I: AsyncRead + AsyncWrite + Send + 'static,
+{
+    builder.handshake(io).map(move |conn| match socket {
+        Some(socket) => conn.with_channelz(socket),
+        None => conn,
+    })
+}
+
+/// Resolve `host` to the addresses to attempt connecting to.
+///
+/// IP addresses are used as is. Host names are resolved with the system
+/// resolver, which blocks, so resolution is handed off with
+/// `tokio_threadpool::blocking`. Outside of the threadpool, for example on a
+/// current thread runtime, a thread is spawned for the lookup instead.
+fn resolve(host: &str, port: u16) -> impl Future<Item = Vec<SocketAddr>, Error = io::Error> + Send {
+    if let Ok(ip) = host.parse::<IpAddr>() {
+        return Either::A(future::ok(vec![SocketAddr::new(ip, port)]));
+    }
+
+    let host = host.to_owned();
+    let mut fallback = None;
+
+    Either::B(future::poll_fn(move || {
+        if fallback.is_none() {
+            match tokio_threadpool::blocking(|| lookup(&host, port)) {
+                Ok(Async::Ready(addrs)) => return addrs.map(Async::Ready),
+                Ok(Async::NotReady) => return Ok(Async::NotReady),
+                Err(_) => {
+                    trace!("not on a threadpool; resolving {} on a new thread", host);
+                    let (tx, rx) = oneshot::channel();
+                    let host = host.clone();
+                    thread::spawn(move || {
+                        let _ = tx.send(lookup(&host, port));
+                    });
+                    fallback = Some(rx);
+                }
+            }
+        }
+
+        let rx = fallback.as_mut().expect("fallback resolver");
+        match rx.poll() {
+            Ok(Async::Ready(addrs)) => addrs.map(Async::Ready),
+            Ok(Async::NotReady) => Ok(Async::NotReady),
+            Err(_canceled) => Err(io::Error::new(
+                io::ErrorKind::Other,
+                "resolver thread panicked",
+            )),
+        }
+    }))
+}
+
+fn lookup(host: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+    (host, port).to_socket_addrs().map(|addrs| addrs.collect())
+}
+
+/// Connect to each address in turn, until one succeeds.
+fn connect_any(addrs: Vec<SocketAddr>) -> impl Future<Item = TcpStream, Error = io::Error> + Send {
+    future::loop_fn(
+        (addrs.into_iter(), None),
+        |(mut addrs, last_err): (vec::IntoIter<SocketAddr>, Option<io::Error>)| match addrs.next() {
+            Some(addr) => Either::A(TcpStream::connect(&addr).then(move |result| match result {
+                Ok(io) => Ok(Loop::Break(io)),
+                Err(e) => {
+                    debug!("failed to connect to {}: {}", addr, e);
+                    Ok(Loop::Continue((addrs, Some(e))))
+                }
+            })),
+            None => Either::B(future::err(last_err.unwrap_or_else(|| {
+                io::Error::new(io::ErrorKind::NotFound, "host resolved to no addresses")
+            }))),
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new(uri: &'static str) -> Result<Connector, Error> {
+        Connector::new(&Endpoint::from_static(uri))
+    }
+
+    #[test]
+    fn host_and_port_from_uri() {
+        let tcp = |host: &str, port| Target::Tcp {
+            host: host.to_owned(),
+            port,
+        };
+
+        let connector = new("http://example.com:50051").unwrap();
+        assert_eq!(connector.target, tcp("example.com", 50051));
+
+        let connector = new("http://[::1]:50051").unwrap();
+        assert_eq!(connector.target, tcp("::1", 50051));
+
+        let connector = new("http://example.com").unwrap();
+        assert_eq!(connector.target, tcp("example.com", 80));
+    }
+
+    #[test]
+    fn invalid_uris_rejected() {
+        assert!(new("ftp://example.com").is_err());
+        assert!(new("https://example.com").is_err());
+        assert!(new("/helloworld.Greeter/SayHello").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unix_targets() {
+        let unix = |path: &str| Target::Unix(PathBuf::from(path));
+
+        let connector = new("unix:///run/greeter.sock").unwrap();
+        assert_eq!(connector.target, unix("/run/greeter.sock"));
+
+        let connector = new("unix:greeter.sock").unwrap();
+        assert_eq!(connector.target, unix("greeter.sock"));
+
+        assert!(Endpoint::from_shared("unix://host/greeter.sock").is_err());
+        assert!(Endpoint::from_shared("unix:").is_err());
+    }
+
+    #[test]
+    fn resolves_on_any_runtime() {
+        let localhost = |addrs: Vec<SocketAddr>| {
+            assert!(!addrs.is_empty());
+            assert!(addrs.iter().all(|addr| addr.ip().is_loopback()));
+        };
+
+        let mut rt = tokio::runtime::Runtime::new().unwrap();
+        localhost(rt.block_on(resolve("localhost", 80)).unwrap());
+
+        let mut rt = tokio::runtime::current_thread::Runtime::new().unwrap();
+        localhost(rt.block_on(resolve("localhost", 80)).unwrap());
+    }
+}
@@ -0,0 +1,794 @@
+//! Connecting clients to a gRPC server.
+
+mod backoff;
+mod connector;
+mod worker;
+
+pub use self::backoff::Backoff;
+
+use self::connector::Connector;
+use self::worker::{Connectivity, Message, Worker};
+use crate::body::BoxBody;
+use crate::request::WaitForReady;
+use crate::transport::channelz::{Call, Calls, Channelz};
+#[cfg(feature = "tls")]
+use crate::transport::tls::TlsConnector;
+#[cfg(feature = "tls")]
+use crate::transport::ClientTlsConfig;
+use crate::transport::{client, Error, KeepAlive, RecvBody};
+use crate::Status;
+
+use futures::sync::{mpsc, oneshot};
+use futures::{try_ready, Async, Future, Poll};
+use http::uri::{self, Uri};
+use std::fmt;
+#[cfg(unix)]
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::executor::{DefaultExecutor, Executor};
+use tokio::timer::Delay;
+use tower_service::Service;
+
+/// The default number of requests a channel buffers while its connection is
+/// busy or being established.
+const DEFAULT_BUFFER_SIZE: usize = 1024;
+
+/// The URI requests to Unix domain sockets are sent with.
+#[cfg(unix)]
+const UNIX_URI: &str = "http://localhost";
+
+/// A gRPC server to connect to.
+///
+/// ```ignore
+/// let greeter = Endpoint::from_static("http://[::1]:50051")
+///     .timeout(Duration::from_secs(5))
+///     .connect()
+///     .map(Greeter::new);
+/// ```
+///
+/// On Unix, servers listening on a Unix domain socket are connected to with
+/// a `unix:` target, such as `unix:///run/greeter.sock`.
+#[derive(Clone, Debug)]
+pub struct Endpoint {
+    uri: Uri,
+    /// The Unix domain socket to connect to, rather than the URI's host.
+    #[cfg(unix)]
+    unix: Option<PathBuf>,
+    builder: client::Builder,
+    timeout: Option<Duration>,
+    connect_timeout: Option<Duration>,
+    backoff: Backoff,
+    tcp_nodelay: bool,
+    buffer_size: usize,
+    #[cfg(feature = "tls")]
+    tls: Option<TlsConnector>,
+    channelz: Option<Channelz>,
+}
+
+/// A cloneable handle to a connection to an `Endpoint`.
+///
+/// Requests are buffered and dispatched by a background task, which
+/// reconnects with backoff if the connection is lost. Request URIs only need
+/// a path, as the endpoint's origin is set on each request.
+///
+/// The channel is not ready while it is connecting. While it is waiting to
+/// retry a failed connection attempt, requests fail with `Code::Unavailable`,
+/// unless they are marked with `Request::set_wait_for_ready`, in which case
+/// they are sent once the channel reconnects or fail once their deadline
+/// passes.
+#[derive(Clone)]
+pub struct Channel {
+    tx: mpsc::Sender<Message>,
+    connectivity: Arc<Connectivity>,
+    scheme: uri::Scheme,
+    authority: uri::Authority,
+    timeout: Option<Duration>,
+    /// The channelz call counts of the channel and its subchannel, if
+    /// reported.
+    calls: Vec<Arc<Calls>>,
+}
+
+/// A future resolving to a `Channel` once its first connection has been
+/// established.
+pub struct Connect {
+    state: ConnectState,
+}
+
+enum ConnectState {
+    Connecting {
+        /// Spawned on first poll.
+        worker: Option<Worker>,
+        channel: Option<Channel>,
+        connected: oneshot::Receiver<Result<(), Error>>,
+    },
+    Failed(Option<Error>),
+}
+
+/// The response to a request sent on a `Channel`.
+pub struct ResponseFuture {
+    state: ResponseState,
+    timeout: Option<Delay>,
+    call: Option<Call>,
+}
+
+enum ResponseState {
+    Dispatching(oneshot::Receiver<Result<client::ResponseFuture, Error>>),
+    Pending(client::ResponseFuture),
+    Failed(Option<Error>),
+}
+
+// ===== impl Endpoint =====
+
+impl Endpoint {
+    /// Returns an `Endpoint` for the server at `uri`, which must have an
+    /// `http` scheme and an authority.
+    pub fn from_uri(uri: Uri) -> Self {
+        Endpoint {
+            uri,
+            #[cfg(unix)]
+            unix: None,
+            builder: client::Builder::new(),
+            timeout: None,
+            connect_timeout: None,
+            backoff: Backoff::new(),
+            tcp_nodelay: true,
+            buffer_size: DEFAULT_BUFFER_SIZE,
+            #[cfg(feature = "tls")]
+            tls: None,
+            channelz: None,
+        }
+    }
+
+    /// Returns an `Endpoint` for `target`, which is either a URI or, on Unix,
+    /// a `unix:` target naming a Unix domain socket.
+    ///
+    /// Unix domain socket targets are either `unix:///absolute/path` or
+    /// `unix:relative/path`.
+    pub fn from_shared(target: impl Into<String>) -> Result<Self, Error> {
+        let target = target.into();
+
+        #[cfg(unix)]
+        {
+            if target.starts_with("unix:") {
+                return Endpoint::from_unix_target(&target["unix:".len()..]);
+            }
+        }
+
+        target
+            .parse()
+            .map(Endpoint::from_uri)
+            .map_err(Error::connect)
+    }
+
+    /// Returns an `Endpoint` for the server at a static URI or `unix:`
+    /// target.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `target` is not a valid URI or Unix domain socket target.
+    pub fn from_static(target: &'static str) -> Self {
+        #[cfg(unix)]
+        {
+            if target.starts_with("unix:") {
+                return Endpoint::from_shared(target).expect("invalid unix target");
+            }
+        }
+
+        Endpoint::from_uri(Uri::from_static(target))
+    }
+
+    /// Returns an `Endpoint` for the server listening on the Unix domain
+    /// socket at `path`.
+    #[cfg(unix)]
+    pub fn from_unix(path: impl Into<PathBuf>) -> Self {
+        let mut endpoint = Endpoint::from_uri(Uri::from_static(UNIX_URI));
+        endpoint.unix = Some(path.into());
+        endpoint
+    }
+
+    #[cfg(unix)]
+    fn from_unix_target(path: &str) -> Result<Self, Error> {
+        // Only an empty authority is allowed, as in `unix:///path`.
+        let path = if path.starts_with("//") {
+            let path = &path[2..];
+            if !path.starts_with('/') {
+                return Err(Error::connect(
+                    "unix targets must be `unix:///absolute/path` or `unix:relative/path`",
+                ));
+            }
+            path
+        } else {
+            path
+        };
+
+        if path.is_empty() {
+            return Err(Error::connect("unix target is missing a path"));
+        }
+
+        Ok(Endpoint::from_unix(path))
+    }
+
+    /// Returns the URI of the endpoint.
+    ///
+    /// Requests to Unix domain sockets are sent to `http://localhost`, so
+    /// that servers receive a valid `:authority`.
+    pub fn uri(&self) -> &Uri {
+        &self.uri
+    }
+
+    /// Fail requests that have not received response headers within
+    /// `timeout` with `Code::DeadlineExceeded`.
+    ///
+    /// Requests with a shorter `grpc-timeout` use it instead.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Fail connection attempts that take longer than `timeout`.
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Set how long to wait between failed connection attempts.
+    pub fn backoff(mut self, backoff: Backoff) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Set the initial flow control window size of each stream.
+    pub fn initial_stream_window_size(mut self, size: u32) -> Self {
+        self.builder.initial_stream_window_size(size);
+        self
+    }
+
+    /// Set the initial flow control window size of each connection.
+    pub fn initial_connection_window_size(mut self, size: u32) -> Self {
+        self.builder.initial_connection_window_size(size);
+        self
+    }
+
+    /// Send keepalive pings to the server.
+    pub fn keepalive(mut self, keepalive: KeepAlive) -> Self {
+        self.builder.keepalive(keepalive);
+        self
+    }
+
+    /// Set whether `TCP_NODELAY` is set on connections.
+    ///
+    /// Defaults to `true`.
+    pub fn tcp_nodelay(mut self, enabled: bool) -> Self {
+        self.tcp_nodelay = enabled;
+        self
+    }
+
+    /// Set the number of requests buffered by the channel before it stops
+    /// being ready.
+    ///
+    /// Defaults to 1024.
+    pub fn buffer_size(mut self, size: usize) -> Self {
+        self.buffer_size = size;
+        self
+    }
+
+    /// Report the channel, its connections and the calls made on it to
+    /// `channelz`.
+    pub fn channelz(mut self, channelz: Channelz) -> Self {
+        self.channelz = Some(channelz);
+        self
+    }
+
+    /// Connect over TLS, negotiating HTTP/2 with ALPN.
+    ///
+    /// TLS is used whenever it is configured, regardless of the URI's scheme.
+    /// Returns an error if the certificates or key in `config` are invalid,
+    /// or if the server's domain name cannot be determined.
+    #[cfg(feature = "tls")]
+    pub fn tls_config(mut self, config: ClientTlsConfig) -> Result<Self, Error> {
+        let host = self
+            .uri
+            .host()
+            .ok_or_else(|| Error::connect("endpoint URI is missing a host"))?;
+
+        self.tls = Some(config.connector(host)?);
+        Ok(self)
+    }
+
+    #[cfg(feature = "tls")]
+    fn uses_tls(&self) -> bool {
+        self.tls.is_some()
+    }
+
+    #[cfg(not(feature = "tls"))]
+    fn uses_tls(&self) -> bool {
+        false
+    }
+
+    /// Returns the target connected to, as reported to channelz.
+    fn target(&self) -> String {
+        #[cfg(unix)]
+        {
+            if let Some(ref path) = self.unix {
+                return format!("unix:{}", path.display());
+            }
+        }
+
+        self.uri.to_string()
+    }
+
+    /// Connect to the endpoint, resolving to a `Channel` once connected.
+    ///
+    /// The channel is driven by a task spawned on the default executor when
+    /// the returned future is first polled.
+    pub fn connect(&self) -> Connect {
+        let mut connector = match Connector::new(self) {
+            Ok(connector) => connector,
+            Err(e) => {
+                return Connect {
+                    state: ConnectState::Failed(Some(e)),
+                };
+            }
+        };
+
+        let parts = self.uri.clone().into_parts();
+        let (tx, rx) = mpsc::channel(self.buffer_size);
+        let (connected_tx, connected_rx) = oneshot::channel();
+        let connectivity = Arc::new(Connectivity::default());
+
+        let mut calls = Vec::new();
+        let channelz = match self.channelz {
+            Some(ref channelz) => {
+                let channel = channelz.add_channel(self.target());
+                let subchannel = Arc::new(channel.add_subchannel());
+                calls.push(channel.entry().calls().clone());
+                calls.push(subchannel.entry().calls().clone());
+                connector = connector.with_channelz(subchannel.clone());
+                Some((channel, subchannel))
+            }
+            None => None,
+        };
+
+        let channel = Channel {
+            tx,
+            connectivity: connectivity.clone(),
+            scheme: parts.scheme.expect("checked by Connector::new"),
+            authority: parts.authority.expect("checked by Connector::new"),
+            timeout: self.timeout,
+            calls,
+        };
+        let worker = Worker::new(
+            rx,
+            connector,
+            self.backoff.clone(),
+            connectivity,
+            connected_tx,
+            channelz,
+        );
+
+        Connect {
+            state: ConnectState::Connecting {
+                worker: Some(worker),
+                channel: Some(channel),
+                connected: connected_rx,
+            },
+        }
+    }
+}
+
+impl From<Uri> for Endpoint {
+    fn from(uri: Uri) -> Self {
+        Endpoint::from_uri(uri)
+    }
+}
+
+// ===== impl Channel =====
+
+impl Channel {
+    /// Replace the scheme and authority of `uri` with the endpoint's.
+    fn set_origin(&self, uri: &Uri) -> Result<Uri, Error> {
+        let mut parts = uri::Parts::default();
+        parts.scheme = Some(self.scheme.clone());
+        parts.authority = Some(self.authority.clone());
+        parts.path_and_query = Some(
+            uri.path_and_query()
+                .cloned()
+                .unwrap_or_else(|| uri::PathAndQuery::from_static("/")),
+        );
+
+        Uri::from_parts(parts).map_err(Error::transport)
+    }
+}
+
+impl Service<http::Request<BoxBody>> for Channel {
+    type Response = http::Response<RecvBody>;
+    type Error = Error;
+    type Future = ResponseFuture;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        if self.connectivity.poll_connecting() {
+            return Ok(Async::NotReady);
+        }
+
+        self.tx.poll_ready().map_err(|_| Error::closed())
+    }
+
+    fn call(&mut self, mut request: http::Request<BoxBody>) -> Self::Future {
+        let timeout = match (self.timeout, grpc_timeout(request.headers())) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        let timeout = timeout.map(|timeout| Delay::new(Instant::now() + timeout));
+        let wait_for_ready = request.extensions().get::<WaitForReady>().is_some();
+
+        // Calls that fail before they are sent are counted as failed too.
+        let call = match self.calls.len() {
+            0 => None,
+            _ => Some(Call::client(self.calls.clone())),
+        };
+
+        match self.set_origin(request.uri()) {
+            Ok(uri) => *request.uri_mut() = uri,
+            Err(e) => return ResponseFuture::failed(e),
+        }
+
+        let (tx, rx) = oneshot::channel();
+
+        if let Err(e) = self.tx.try_send(Message {
+            request,
+            wait_for_ready,
+            tx,
+        }) {
+            if e.is_full() {
+                panic!("Channel::call invoked without first polling poll_ready");
+            }
+
+            return ResponseFuture::failed(Error::closed());
+        }
+
+        ResponseFuture {
+            state: ResponseState::Dispatching(rx),
+            timeout,
+            call,
+        }
+    }
+}
+
+impl fmt::Debug for Channel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Channel")
+            .field("scheme", &self.scheme)
+            .field("authority", &self.authority)
+            .field("timeout", &self.timeout)
+            .finish()
+    }
+}
+
+// ===== impl Connect =====
+
+impl Future for Connect {
+    type Item = Channel;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        match self.state {
+            ConnectState::Connecting {
+                ref mut worker,
+                ref mut channel,
+                ref mut connected,
+            } => {
+                if let Some(worker) = worker.take() {
+                    DefaultExecutor::current()
+                        .spawn(Box::new(worker))
+                        .map_err(Error::executor)?;
+                }
+
+                match connected.poll() {
+                    Ok(Async::Ready(Ok(()))) => {
+                        Ok(Async::Ready(channel.take().expect("polled after complete")))
+                    }
+                    Ok(Async::Ready(Err(e))) => Err(e),
+                    Ok(Async::NotReady) => Ok(Async::NotReady),
+                    Err(_canceled) => Err(Error::closed()),
+                }
+            }
+            ConnectState::Failed(ref mut err) => Err(err.take().expect("polled after complete")),
+        }
+    }
+}
+
+impl fmt::Debug for Connect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Connect").finish()
+    }
+}
+
+// ===== impl ResponseFuture =====
+
+impl ResponseFuture {
+    fn failed(err: Error) -> Self {
+        ResponseFuture {
+            state: ResponseState::Failed(Some(err)),
+            timeout: None,
+            call: None,
+        }
+    }
+
+    fn poll_response(&mut self) -> Poll<http::Response<RecvBody>, Error> {
+        loop {
+            self.state = match self.state {
+                ResponseState::Dispatching(ref mut rx) => match rx.poll() {
+                    Ok(Async::Ready(Ok(future))) => ResponseState::Pending(future),
+                    Ok(Async::Ready(Err(e))) => return Err(e),
+                    Ok(Async::NotReady) => return Ok(Async::NotReady),
+                    // The worker is gone, so the request was never sent.
+                    Err(_canceled) => return Err(Error::closed()),
+                },
+                ResponseState::Pending(ref mut future) => return future.poll(),
+                ResponseState::Failed(ref mut err) => {
+                    return Err(err.take().expect("polled after complete"));
+                }
+            };
+        }
+    }
+}
+
+impl Future for ResponseFuture {
+    type Item = http::Response<RecvBody>;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        if let Async::Ready(mut response) = self.poll_response()? {
+            if let Some(mut call) = self.call.take() {
+                // Trailers-only responses carry their status in the headers.
+                if let Some(status) = Status::from_header_map(response.headers()) {
+                    call.finish(Some(status.code()));
+                }
+
+                response.body_mut().track(call);
+            }
+
+            return Ok(Async::Ready(response));
+        }
+
+        if let Some(ref mut timeout) = self.timeout {
+            try_ready!(timeout.poll());
+            return Err(Error::timed_out());
+        }
+
+        Ok(Async::NotReady)
+    }
+}
+
+impl fmt::Debug for ResponseFuture {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ResponseFuture").finish()
+    }
+}
+
+/// Parses the `grpc-timeout` header, if the request has a valid one.
+fn grpc_timeout(headers: &http::HeaderMap) -> Option<Duration> {
+    let value = headers.get("grpc-timeout")?.to_str().ok()?;
+
+    // See https://github.com/grpc/grpc/blob/master/doc/PROTOCOL-HTTP2.md#requests
+    if value.len() < 2 || value.len() > 9 {
+        return None;
+    }
+
+    let (amount, unit) = value.split_at(value.len() - 1);
+    let amount = amount.parse::<u64>().ok()?;
+
+    let timeout = match unit {
+        "H" => Duration::from_secs(amount * 60 * 60),
+        "M" => Duration::from_secs(amount * 60),
+        "S" => Duration::from_secs(amount),
+        "m" => Duration::from_millis(amount),
+        "u" => Duration::from_micros(amount),
+        "n" => Duration::from_nanos(amount),
+        _ => return None,
+    };
+
+    Some(timeout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::Empty;
+    use crate::transport::server::NamedService;
+    use crate::transport::Server;
+    use crate::{Code, Status};
+
+    use futures::future;
+    use std::net::SocketAddr;
+
+    fn timeout(value: &'static str) -> Option<Duration> {
+        let mut headers = http::HeaderMap::new();
+        headers.insert("grpc-timeout", http::HeaderValue::from_static(value));
+        grpc_timeout(&headers)
+    }
+
+    #[test]
+    fn parse_grpc_timeout() {
+        assert_eq!(timeout("3S"), Some(Duration::from_secs(3)));
+        assert_eq!(timeout("250m"), Some(Duration::from_millis(250)));
+        assert_eq!(timeout("2H"), Some(Duration::from_secs(7200)));
+        assert_eq!(timeout("10n"), Some(Duration::from_nanos(10)));
+        assert_eq!(timeout("S"), None);
+        assert_eq!(timeout("123456789S"), None);
+        assert_eq!(timeout("5x"), None);
+    }
+
+    #[derive(Clone, Debug)]
+    struct Origin;
+
+    impl NamedService for Origin {
+        const NAME: &'static str = "test.Origin";
+    }
+
+    impl Service<http::Request<BoxBody>> for Origin {
+        type Response = http::Response<BoxBody>;
+        type Error = Status;
+        type Future = future::FutureResult<Self::Response, Self::Error>;
+
+        fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+            Ok(Async::Ready(()))
+        }
+
+        fn call(&mut self, request: http::Request<BoxBody>) -> Self::Future {
+            // Answers with the URI the request was received with.
+            future::err(Status::new(Code::NotFound, request.uri().to_string()))
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unix_socket_has_authority() {
+        let path = std::env::temp_dir().join(format!("tower-grpc-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let mut rt = tokio::runtime::Runtime::new().unwrap();
+        let (shutdown, signal) = oneshot::channel::<()>();
+        let serve = Server::builder()
+            .add_service(Origin)
+            .serve_unix_with_shutdown(&path, signal)
+            .map_err(|e| panic!("server error: {}", e));
+        rt.spawn(serve);
+
+        let target = format!("unix://{}", path.display());
+        let call = Endpoint::from_shared(target)
+            .unwrap()
+            .connect()
+            .and_then(|mut channel| {
+                future::poll_fn(move || {
+                    try_ready!(channel.poll_ready());
+                    Ok(Async::Ready(channel.clone()))
+                })
+                .and_then(|mut channel| {
+                    let request = http::Request::builder()
+                        .uri("/test.Origin/Get")
+                        .body(BoxBody::new(Box::new(Empty)))
+                        .unwrap();
+                    channel.call(request)
+                })
+            });
+        let response = rt.block_on(call).unwrap();
+
+        assert_eq!(
+            response.headers()["grpc-message"],
+            "http://localhost/test.Origin/Get"
+        );
+
+        drop(shutdown);
+    }
+
+    /// Serves `Origin` on `addr` until the returned sender is dropped.
+    fn serve(rt: &mut tokio::runtime::Runtime, addr: &SocketAddr) -> oneshot::Sender<()> {
+        let (shutdown, signal) = oneshot::channel::<()>();
+        let serve = Server::builder()
+            .add_service(Origin)
+            .serve_with_shutdown(*addr, signal)
+            .map_err(|e| panic!("server error: {}", e));
+        rt.spawn(serve);
+        shutdown
+    }
+
+    /// Sends a request for `test.Origin` once `channel` is ready.
+    fn call(
+        channel: &Channel,
+        wait_for_ready: bool,
+    ) -> impl Future<Item = http::Response<RecvBody>, Error = Error> {
+        let mut channel = channel.clone();
+
+        future::poll_fn(move || {
+            try_ready!(channel.poll_ready());
+            Ok(Async::Ready(channel.clone()))
+        })
+        .and_then(move |mut channel| {
+            let mut request = http::Request::builder()
+                .uri("/test.Origin/Get")
+                .body(BoxBody::new(Box::new(Empty)))
+                .unwrap();
+
+            if wait_for_ready {
+                request.extensions_mut().insert(WaitForReady);
+            }
+
+            channel.call(request)
+        })
+    }
+
+    /// Connects a channel to a server on a free port, then shuts the server
+    /// down, returning once fail-fast calls on the channel are failing.
+    fn disconnected(rt: &mut tokio::runtime::Runtime) -> (Channel, SocketAddr) {
+        let addr = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let shutdown = serve(rt, &addr);
+
+        let backoff = Backoff::new()
+            .initial(Duration::from_millis(50))
+            .max(Duration::from_millis(50))
+            .jitter(0.0);
+        let connect = Endpoint::from_shared(format!("http://{}", addr))
+            .unwrap()
+            .backoff(backoff)
+            .connect();
+        let channel = rt.block_on(connect).unwrap();
+        assert!(rt.block_on(call(&channel, false)).is_ok());
+
+        drop(shutdown);
+        for _ in 0..100 {
+            if rt.block_on(call(&channel, false)).is_err() {
+                return (channel, addr);
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        }
+
+        panic!("channel never observed the server shutting down");
+    }
+
+    #[test]
+    fn reconnects_after_failure() {
+        let mut rt = tokio::runtime::Runtime::new().unwrap();
+        let (channel, addr) = disconnected(&mut rt);
+
+        let err = rt.block_on(call(&channel, false)).unwrap_err();
+        assert_eq!(err.to_status().map(|s| s.code()), Some(Code::Unavailable));
+
+        let _shutdown = serve(&mut rt, &addr);
+        for _ in 0..100 {
+            if rt.block_on(call(&channel, false)).is_ok() {
+                return;
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        }
+
+        panic!("channel never reconnected");
+    }
+
+    #[test]
+    fn wait_for_ready_succeeds_once_connected() {
+        let mut rt = tokio::runtime::Runtime::new().unwrap();
+        let (channel, addr) = disconnected(&mut rt);
+
+        let (tx, rx) = oneshot::channel();
+        rt.spawn(call(&channel, true).then(|result| {
+            let _ = tx.send(result.map(|response| response.status()));
+            Ok(())
+        }));
+
+        // The call waits for the channel to reconnect rather than failing.
+        let delay = Delay::new(Instant::now() + Duration::from_millis(200));
+        let rx = match rt.block_on(rx.select2(delay)) {
+            Ok(future::Either::B((_, rx))) => rx,
+            _ => panic!("wait-for-ready call completed while disconnected"),
+        };
+
+        let _shutdown = serve(&mut rt, &addr);
+        let status = rt.block_on(rx).unwrap().unwrap();
+        assert_eq!(status, http::StatusCode::OK);
+    }
+}
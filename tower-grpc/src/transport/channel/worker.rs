@@ -0,0 +1,332 @@
+use super::connector::{Connecting, Connector};
+use super::Backoff;
+use crate::body::BoxBody;
+use crate::transport::channelz::{self, ChannelEntry, ConnectivityState};
+use crate::transport::client::{Connection, ResponseFuture};
+use crate::transport::Error;
+
+use futures::sync::{mpsc, oneshot};
+use futures::task::{self, Task};
+use futures::{Async, Future, Poll, Stream};
+use log::{debug, trace};
+use std::collections::VecDeque;
+use std::error::Error as _;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio::timer::Delay;
+use tower_service::Service;
+
+const CONNECTING: usize = 0;
+const READY: usize = 1;
+const TRANSIENT_FAILURE: usize = 2;
+
+/// A request sent to a `Worker` by a `Channel`.
+pub(crate) struct Message {
+    pub(crate) request: http::Request<BoxBody>,
+    pub(crate) wait_for_ready: bool,
+    pub(crate) tx: oneshot::Sender<Result<ResponseFuture, Error>>,
+}
+
+/// The connectivity state of a channel, shared by the worker and handles.
+#[derive(Debug, Default)]
+pub(crate) struct Connectivity {
+    state: AtomicUsize,
+    /// Handles waiting for a connection attempt to complete.
+    waiters: Mutex<Vec<Task>>,
+}
+
+/// Owns a channel's connection, dispatching requests buffered by its
+/// handles and reconnecting with backoff once the connection is lost.
+///
+/// The worker completes once every handle has been dropped and no queued
+/// requests remain.
+pub(crate) struct Worker {
+    rx: mpsc::Receiver<Message>,
+    rx_closed: bool,
+    connector: Connector,
+    backoff: Backoff,
+    connectivity: Arc<Connectivity>,
+    state: State,
+    /// The number of consecutive failed connection attempts.
+    failures: u32,
+    /// Why the last connection attempt failed.
+    last_error: Option<String>,
+    /// Wait-for-ready requests received while disconnected.
+    queued: VecDeque<Message>,
+    /// Notified of the outcome of the first connection attempt.
+    connected: Option<oneshot::Sender<Result<(), Error>>>,
+    /// The channel and subchannel reported to channelz, registered until the
+    /// worker completes.
+    channelz: Option<ChannelzHandles>,
+}
+
+type ChannelzHandles = (
+    channelz::Handle<ChannelEntry>,
+    Arc<channelz::Handle<ChannelEntry>>,
+);
+
+enum State {
+    Connecting(Connecting),
+    Connected(Connection),
+    /// Waiting to retry after a failed connection attempt.
+    Backoff(Delay),
+}
+
+// ===== impl Connectivity =====
+
+impl Connectivity {
+    /// Returns whether a connection attempt is in progress, registering the
+    /// current task to be notified once it completes if so.
+    pub(crate) fn poll_connecting(&self) -> bool {
+        if self.state.load(Ordering::Acquire) != CONNECTING {
+            return false;
+        }
+
+        {
+            let mut waiters = self.waiters.lock().unwrap();
+            if !waiters.iter().any(Task::will_notify_current) {
+                waiters.push(task::current());
+            }
+        }
+
+        // The attempt may have completed before the task was registered.
+        self.state.load(Ordering::Acquire) == CONNECTING
+    }
+
+    fn set(&self, state: usize) {
+        let prev = self.state.swap(state, Ordering::AcqRel);
+
+        if prev == CONNECTING && state != CONNECTING {
+            for waiter in self.waiters.lock().unwrap().drain(..) {
+                waiter.notify();
+            }
+        }
+    }
+}
+
+// ===== impl Worker =====
+
+impl Worker {
+    /// Returns a worker that connects immediately, notifying `connected` of
+    /// the outcome of the first attempt.
+    pub(crate) fn new(
+        rx: mpsc::Receiver<Message>,
+        connector: Connector,
+        backoff: Backoff,
+        connectivity: Arc<Connectivity>,
+        connected: oneshot::Sender<Result<(), Error>>,
+        channelz: Option<ChannelzHandles>,
+    ) -> Self {
+        let state = State::Connecting(connector.connect());
+
+        if let Some((ref channel, ref subchannel)) = channelz {
+            channel.entry().set_state(ConnectivityState::Connecting);
+            subchannel.entry().set_state(ConnectivityState::Connecting);
+        }
+
+        Worker {
+            rx,
+            rx_closed: false,
+            connector,
+            backoff,
+            connectivity,
+            state,
+            failures: 0,
+            last_error: None,
+            queued: VecDeque::new(),
+            connected: Some(connected),
+            channelz,
+        }
+    }
+
+    fn set_state(&mut self, state: State) {
+        self.connectivity.set(match state {
+            State::Connecting(_) => CONNECTING,
+            State::Connected(_) => READY,
+            State::Backoff(_) => TRANSIENT_FAILURE,
+        });
+
+        if let Some((ref channel, ref subchannel)) = self.channelz {
+            let state = match state {
+                State::Connecting(_) => ConnectivityState::Connecting,
+                State::Connected(_) => ConnectivityState::Ready,
+                State::Backoff(_) => ConnectivityState::TransientFailure,
+            };
+
+            channel.entry().set_state(state);
+            subchannel.entry().set_state(state);
+        }
+
+        self.state = state;
+    }
+
+    /// Drive connection attempts and backoff until the state settles.
+    fn poll_state(&mut self) {
+        loop {
+            let next = match self.state {
+                State::Connecting(ref mut connecting) => match connecting.poll() {
+                    Ok(Async::Ready(conn)) => {
+                        debug!("connected");
+                        self.failures = 0;
+                        self.last_error = None;
+
+                        if let Some(tx) = self.connected.take() {
+                            let _ = tx.send(Ok(()));
+                        }
+
+                        State::Connected(conn)
+                    }
+                    Ok(Async::NotReady) => return,
+                    Err(e) => {
+                        self.failures += 1;
+                        let delay = self.backoff.delay(self.failures);
+                        debug!("failed to connect: {}; retrying in {:?}", e, delay);
+
+                        // Fail-fast requests are failed with a connect error
+                        // of their own, so only the cause is kept.
+                        self.last_error = Some(match e.source() {
+                            Some(source) => source.to_string(),
+                            None => e.to_string(),
+                        });
+                        if let Some(tx) = self.connected.take() {
+                            let _ = tx.send(Err(e));
+                        }
+
+                        State::Backoff(Delay::new(Instant::now() + delay))
+                    }
+                },
+                State::Connected(ref mut conn) => match conn.poll_closed() {
+                    Async::Ready(()) => {
+                        debug!("connection lost; reconnecting");
+                        State::Connecting(self.connector.connect())
+                    }
+                    Async::NotReady => return,
+                },
+                State::Backoff(ref mut delay) => match delay.poll() {
+                    Ok(Async::NotReady) => return,
+                    Ok(Async::Ready(())) | Err(_) => {
+                        trace!("backoff elapsed; reconnecting");
+                        State::Connecting(self.connector.connect())
+                    }
+                },
+            };
+
+            self.set_state(next);
+        }
+    }
+
+    /// Dispatch queued and buffered requests on the connection, while it is
+    /// ready.
+    ///
+    /// Returns an error if the connection has been lost.
+    fn dispatch(&mut self) -> Result<(), Error> {
+        let conn = match self.state {
+            State::Connected(ref mut conn) => conn,
+            _ => return Ok(()),
+        };
+
+        loop {
+            if conn.poll_ready()?.is_not_ready() {
+                return Ok(());
+            }
+
+            let msg = match self.queued.pop_front() {
+                Some(msg) => msg,
+                None => match poll_message(&mut self.rx, &mut self.rx_closed) {
+                    Some(msg) => msg,
+                    None => return Ok(()),
+                },
+            };
+
+            // The caller is no longer waiting for a response.
+            if msg.tx.is_canceled() {
+                continue;
+            }
+
+            let _ = msg.tx.send(Ok(conn.call(msg.request)));
+        }
+    }
+
+    /// While disconnected, fail requests fast unless they wait for ready.
+    fn fail_fast(&mut self) {
+        while let Some(msg) = poll_message(&mut self.rx, &mut self.rx_closed) {
+            if msg.tx.is_canceled() {
+                continue;
+            }
+
+            if msg.wait_for_ready {
+                self.queued.push_back(msg);
+            } else {
+                let err = match self.last_error {
+                    Some(ref e) => Error::connect(e.clone()),
+                    None => Error::connect("not connected"),
+                };
+                let _ = msg.tx.send(Err(err));
+            }
+        }
+    }
+}
+
+impl Future for Worker {
+    type Item = ();
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        // Requests whose deadline passed, or that were dropped, stop being
+        // waited for.
+        self.queued.retain(|msg| !msg.tx.is_canceled());
+
+        loop {
+            self.poll_state();
+
+            match self.state {
+                State::Connected(_) => {
+                    if let Err(e) = self.dispatch() {
+                        debug!("connection lost: {}; reconnecting", e);
+                        let connecting = State::Connecting(self.connector.connect());
+                        self.set_state(connecting);
+                        continue;
+                    }
+                }
+                State::Backoff(_) => self.fail_fast(),
+                // Handles are not ready while connecting, so requests are
+                // left buffered until the attempt completes.
+                State::Connecting(_) => {}
+            }
+
+            break;
+        }
+
+        if self.rx_closed && self.queued.is_empty() {
+            return Ok(Async::Ready(()));
+        }
+
+        Ok(Async::NotReady)
+    }
+}
+
+/// Receives the next buffered request, if any, noting whether every handle
+/// has been dropped.
+fn poll_message(rx: &mut mpsc::Receiver<Message>, closed: &mut bool) -> Option<Message> {
+    if *closed {
+        return None;
+    }
+
+    match rx.poll() {
+        Ok(Async::Ready(Some(msg))) => Some(msg),
+        Ok(Async::Ready(None)) | Err(()) => {
+            *closed = true;
+            None
+        }
+        Ok(Async::NotReady) => None,
+    }
+}
+
+impl Drop for Worker {
+    fn drop(&mut self) {
+        // Wake handles waiting on a connection attempt, so they observe that
+        // the channel has closed.
+        self.connectivity.set(TRANSIENT_FAILURE);
+    }
+}
@@ -0,0 +1,1199 @@
+//! Runtime introspection of servers, channels and their sockets.
+//!
+//! A `Channelz` registry is shared by the servers and channels it is passed
+//! to, with `Server::channelz` and `Endpoint::channelz`. They report their
+//! sockets, and the calls and streams on them, which can then be inspected
+//! while they run. The `tower-grpc-channelz` crate serves the registry as the
+//! `grpc.channelz.v1.Channelz` service.
+//!
+//! Entities are identified by IDs unique within a registry. A channel has a
+//! single subchannel, owning the sockets of the connections it establishes.
+//! Entities are removed once they are closed.
+
+use crate::body::{Body, HttpBody};
+use crate::frames::Frames;
+use crate::{Code, Status};
+
+use bytes::Buf;
+use futures::{try_ready, Async, Poll};
+use http::HeaderMap;
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::SystemTime;
+use tokio::net::TcpStream;
+#[cfg(unix)]
+use tokio::net::UnixStream;
+
+/// The number of results returned by a query that does not limit them.
+const DEFAULT_MAX_RESULTS: usize = 100;
+
+/// A registry of the servers and channels to inspect.
+///
+/// Clones share the same registry.
+#[derive(Clone, Default)]
+pub struct Channelz {
+    registry: Arc<Registry>,
+}
+
+/// A server and its call counts.
+#[derive(Clone, Debug)]
+pub struct ServerInfo {
+    id: i64,
+    calls: CallCounts,
+    listen_sockets: Vec<SocketRef>,
+}
+
+/// A channel or subchannel, and its call counts.
+#[derive(Clone, Debug)]
+pub struct ChannelInfo {
+    id: i64,
+    target: String,
+    state: ConnectivityState,
+    calls: CallCounts,
+    subchannels: Vec<i64>,
+    sockets: Vec<SocketRef>,
+}
+
+/// A socket, and the streams and messages sent on it.
+#[derive(Clone, Debug)]
+pub struct SocketInfo {
+    id: i64,
+    name: String,
+    local: Option<Address>,
+    remote: Option<Address>,
+    streams_started: i64,
+    streams_succeeded: i64,
+    streams_failed: i64,
+    messages_sent: i64,
+    messages_received: i64,
+    last_local_stream_created: Option<SystemTime>,
+    last_remote_stream_created: Option<SystemTime>,
+    last_message_sent: Option<SystemTime>,
+    last_message_received: Option<SystemTime>,
+    tls: Option<TlsInfo>,
+}
+
+/// The TLS session of a socket.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TlsInfo {
+    cipher_suite: String,
+    local_certificate: Option<Vec<u8>>,
+    remote_certificate: Option<Vec<u8>>,
+}
+
+/// Identifies a socket.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SocketRef {
+    id: i64,
+    name: String,
+}
+
+/// The calls handled by a server, or made on a channel.
+#[derive(Clone, Debug, Default)]
+pub struct CallCounts {
+    started: i64,
+    succeeded: i64,
+    failed: i64,
+    last_started: Option<SystemTime>,
+}
+
+/// A page of results, from a query starting at an ID.
+#[derive(Clone, Debug)]
+pub struct Page<T> {
+    items: Vec<T>,
+    end: bool,
+}
+
+/// The address of a socket.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Address {
+    Tcp(SocketAddr),
+    Unix(PathBuf),
+}
+
+/// The connectivity state of a channel.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConnectivityState {
+    Idle,
+    Connecting,
+    Ready,
+    TransientFailure,
+    Shutdown,
+}
+
+/// Owns an entity, removing it from the registry once dropped.
+pub(crate) struct Handle<T: Entity> {
+    registry: Arc<Registry>,
+    id: i64,
+    entry: Arc<T>,
+}
+
+/// Tracks a call on a stream until it ends, counting the messages of the
+/// response body.
+///
+/// If it is dropped before the call ended, the call is counted as failed.
+pub(crate) struct Call {
+    calls: Vec<Arc<Calls>>,
+    socket: Option<Arc<SocketEntry>>,
+    /// Whether the body's messages are sent, rather than received.
+    sent: bool,
+    frames: Frames,
+    done: bool,
+}
+
+/// Counts the messages of a request body.
+pub(crate) struct Counted<B> {
+    inner: B,
+    socket: Arc<SocketEntry>,
+    /// Whether the body's messages are sent, rather than received.
+    sent: bool,
+    frames: Frames,
+}
+
+/// A response body, ending its call once complete.
+pub(crate) struct Tracked<B> {
+    inner: B,
+    call: Call,
+}
+
+#[derive(Default)]
+struct Registry {
+    next_id: AtomicI64,
+    entities: Mutex<Entities>,
+}
+
+#[derive(Default)]
+pub(crate) struct Entities {
+    servers: BTreeMap<i64, Arc<ServerEntry>>,
+    channels: BTreeMap<i64, Arc<ChannelEntry>>,
+    subchannels: BTreeMap<i64, Arc<ChannelEntry>>,
+    sockets: BTreeMap<i64, Arc<SocketEntry>>,
+}
+
+/// An entity kept in the registry.
+pub(crate) trait Entity: Sized {
+    fn entries<'a>(&self, entities: &'a mut Entities) -> &'a mut BTreeMap<i64, Arc<Self>>;
+}
+
+pub(crate) struct ServerEntry {
+    calls: Arc<Calls>,
+}
+
+pub(crate) struct ChannelEntry {
+    target: String,
+    /// The channel owning a subchannel.
+    parent: Option<i64>,
+    state: Mutex<ConnectivityState>,
+    calls: Arc<Calls>,
+}
+
+pub(crate) struct SocketEntry {
+    name: String,
+    owner: Owner,
+    local: Option<Address>,
+    remote: Option<Address>,
+    streams: Mutex<Streams>,
+    tls: Mutex<Option<TlsInfo>>,
+}
+
+/// The entity a socket belongs to.
+#[derive(Clone, Copy, PartialEq)]
+enum Owner {
+    Listener(i64),
+    Server(i64),
+    Subchannel(i64),
+}
+
+#[derive(Default)]
+pub(crate) struct Calls {
+    counts: Mutex<CallCounts>,
+}
+
+#[derive(Default)]
+struct Streams {
+    started: i64,
+    succeeded: i64,
+    failed: i64,
+    messages_sent: i64,
+    messages_received: i64,
+    last_local_created: Option<SystemTime>,
+    last_remote_created: Option<SystemTime>,
+    last_message_sent: Option<SystemTime>,
+    last_message_received: Option<SystemTime>,
+}
+
+// ===== impl Channelz =====
+
+impl Channelz {
+    /// Returns an empty registry.
+    pub fn new() -> Self {
+        Channelz::default()
+    }
+
+    /// Returns the servers with an ID of at least `start_id`, in order of
+    /// their IDs.
+    ///
+    /// At most `max_results` are returned, or a default number if it is
+    /// zero.
+    pub fn servers(&self, start_id: i64, max_results: usize) -> Page<ServerInfo> {
+        let entities = self.registry.lock();
+        let servers = entities.servers.range(start_id..);
+
+        Page::collect(servers, max_results, |(&id, server)| {
+            entities.server_info(id, server)
+        })
+    }
+
+    /// Returns the server `id`, if it is still serving.
+    pub fn server(&self, id: i64) -> Option<ServerInfo> {
+        let entities = self.registry.lock();
+        let server = entities.servers.get(&id)?;
+        Some(entities.server_info(id, server))
+    }
+
+    /// Returns the sockets of connections accepted by the server `id`, with
+    /// an ID of at least `start_id`.
+    ///
+    /// Returns `None` if there is no such server.
+    pub fn server_sockets(
+        &self,
+        id: i64,
+        start_id: i64,
+        max_results: usize,
+    ) -> Option<Page<SocketRef>> {
+        let entities = self.registry.lock();
+        entities.servers.get(&id)?;
+
+        let sockets = entities
+            .sockets
+            .range(start_id..)
+            .filter(|(_, socket)| socket.owner == Owner::Server(id));
+
+        Some(Page::collect(sockets, max_results, |(&id, socket)| {
+            socket.to_ref(id)
+        }))
+    }
+
+    /// Returns the channels with an ID of at least `start_id`, in order of
+    /// their IDs.
+    pub fn top_channels(&self, start_id: i64, max_results: usize) -> Page<ChannelInfo> {
+        let entities = self.registry.lock();
+        let channels = entities.channels.range(start_id..);
+
+        Page::collect(channels, max_results, |(&id, channel)| {
+            entities.channel_info(id, channel)
+        })
+    }
+
+    /// Returns the channel `id`, if it is still open.
+    pub fn channel(&self, id: i64) -> Option<ChannelInfo> {
+        let entities = self.registry.lock();
+        let channel = entities.channels.get(&id)?;
+        Some(entities.channel_info(id, channel))
+    }
+
+    /// Returns the subchannel `id`, if it is still open.
+    pub fn subchannel(&self, id: i64) -> Option<ChannelInfo> {
+        let entities = self.registry.lock();
+        let subchannel = entities.subchannels.get(&id)?;
+        Some(entities.channel_info(id, subchannel))
+    }
+
+    /// Returns the socket `id`, if it is still open.
+    pub fn socket(&self, id: i64) -> Option<SocketInfo> {
+        let entities = self.registry.lock();
+        let socket = entities.sockets.get(&id)?;
+        Some(socket.info(id))
+    }
+
+    /// Register a server, until the returned handle is dropped.
+    pub(crate) fn add_server(&self) -> Handle<ServerEntry> {
+        self.registry.add(ServerEntry {
+            calls: Arc::default(),
+        })
+    }
+
+    /// Register a channel to `target`, until the returned handle is dropped.
+    pub(crate) fn add_channel(&self, target: String) -> Handle<ChannelEntry> {
+        self.registry.add(ChannelEntry::new(target, None))
+    }
+}
+
+impl fmt::Debug for Channelz {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Channelz").finish()
+    }
+}
+
+// ===== impl ServerInfo =====
+
+impl ServerInfo {
+    /// Returns the ID of the server.
+    pub fn id(&self) -> i64 {
+        self.id
+    }
+
+    /// Returns the calls the server has handled.
+    pub fn calls(&self) -> &CallCounts {
+        &self.calls
+    }
+
+    /// Returns the sockets the server is listening on.
+    pub fn listen_sockets(&self) -> &[SocketRef] {
+        &self.listen_sockets
+    }
+}
+
+// ===== impl ChannelInfo =====
+
+impl ChannelInfo {
+    /// Returns the ID of the channel.
+    pub fn id(&self) -> i64 {
+        self.id
+    }
+
+    /// Returns the target the channel connects to.
+    pub fn target(&self) -> &str {
+        &self.target
+    }
+
+    /// Returns the connectivity state of the channel.
+    pub fn state(&self) -> ConnectivityState {
+        self.state
+    }
+
+    /// Returns the calls made on the channel.
+    pub fn calls(&self) -> &CallCounts {
+        &self.calls
+    }
+
+    /// Returns the IDs of the channel's subchannels.
+    pub fn subchannels(&self) -> &[i64] {
+        &self.subchannels
+    }
+
+    /// Returns the sockets of a subchannel's connections.
+    pub fn sockets(&self) -> &[SocketRef] {
+        &self.sockets
+    }
+}
+
+// ===== impl SocketInfo =====
+
+impl SocketInfo {
+    /// Returns the ID of the socket.
+    pub fn id(&self) -> i64 {
+        self.id
+    }
+
+    /// Returns the name of the socket, which is its remote address if it is
+    /// connected.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the local address of the socket, if known.
+    pub fn local(&self) -> Option<&Address> {
+        self.local.as_ref()
+    }
+
+    /// Returns the remote address of the socket, if known.
+    pub fn remote(&self) -> Option<&Address> {
+        self.remote.as_ref()
+    }
+
+    /// Returns the number of streams started on the socket.
+    pub fn streams_started(&self) -> i64 {
+        self.streams_started
+    }
+
+    /// Returns the number of streams that ended with a status.
+    pub fn streams_succeeded(&self) -> i64 {
+        self.streams_succeeded
+    }
+
+    /// Returns the number of streams that ended without a status, such as
+    /// when reset.
+    pub fn streams_failed(&self) -> i64 {
+        self.streams_failed
+    }
+
+    /// Returns the number of messages sent on the socket.
+    pub fn messages_sent(&self) -> i64 {
+        self.messages_sent
+    }
+
+    /// Returns the number of messages received on the socket.
+    pub fn messages_received(&self) -> i64 {
+        self.messages_received
+    }
+
+    /// Returns when a stream was last started by this side of the socket.
+    pub fn last_local_stream_created(&self) -> Option<SystemTime> {
+        self.last_local_stream_created
+    }
+
+    /// Returns when a stream was last started by the peer.
+    pub fn last_remote_stream_created(&self) -> Option<SystemTime> {
+        self.last_remote_stream_created
+    }
+
+    /// Returns when a message was last sent.
+    pub fn last_message_sent(&self) -> Option<SystemTime> {
+        self.last_message_sent
+    }
+
+    /// Returns when a message was last received.
+    pub fn last_message_received(&self) -> Option<SystemTime> {
+        self.last_message_received
+    }
+
+    /// Returns the TLS session of the socket, if it is secured with TLS.
+    pub fn tls(&self) -> Option<&TlsInfo> {
+        self.tls.as_ref()
+    }
+}
+
+// ===== impl TlsInfo =====
+
+impl TlsInfo {
+    #[cfg(feature = "tls")]
+    pub(crate) fn new(
+        cipher_suite: String,
+        local_certificate: Option<Vec<u8>>,
+        remote_certificate: Option<Vec<u8>>,
+    ) -> Self {
+        TlsInfo {
+            cipher_suite,
+            local_certificate,
+            remote_certificate,
+        }
+    }
+
+    /// Returns the name of the negotiated cipher suite, as registered with
+    /// IANA, such as `TLS_AES_128_GCM_SHA256`.
+    pub fn cipher_suite(&self) -> &str {
+        &self.cipher_suite
+    }
+
+    /// Returns the DER encoded certificate this side presented, if any.
+    pub fn local_certificate(&self) -> Option<&[u8]> {
+        self.local_certificate.as_ref().map(Vec::as_slice)
+    }
+
+    /// Returns the DER encoded certificate the peer presented, if any.
+    pub fn remote_certificate(&self) -> Option<&[u8]> {
+        self.remote_certificate.as_ref().map(Vec::as_slice)
+    }
+}
+
+// ===== impl SocketRef =====
+
+impl SocketRef {
+    /// Returns the ID of the socket.
+    pub fn id(&self) -> i64 {
+        self.id
+    }
+
+    /// Returns the name of the socket.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+// ===== impl CallCounts =====
+
+impl CallCounts {
+    /// Returns the number of calls started.
+    pub fn started(&self) -> i64 {
+        self.started
+    }
+
+    /// Returns the number of calls that completed with `Code::Ok`.
+    pub fn succeeded(&self) -> i64 {
+        self.succeeded
+    }
+
+    /// Returns the number of calls that completed with any other code.
+    pub fn failed(&self) -> i64 {
+        self.failed
+    }
+
+    /// Returns when a call was last started.
+    pub fn last_started(&self) -> Option<SystemTime> {
+        self.last_started
+    }
+}
+
+// ===== impl Page =====
+
+impl<T> Page<T> {
+    fn collect<I, F>(iter: I, max_results: usize, f: F) -> Self
+    where
+        I: Iterator,
+        F: FnMut(I::Item) -> T,
+    {
+        let max_results = match max_results {
+            0 => DEFAULT_MAX_RESULTS,
+            max => max,
+        };
+
+        let mut iter = iter.map(f).peekable();
+        let items = iter.by_ref().take(max_results).collect();
+
+        Page {
+            items,
+            end: iter.peek().is_none(),
+        }
+    }
+
+    /// Returns the results in this page.
+    pub fn items(&self) -> &[T] {
+        &self.items
+    }
+
+    /// Consumes `self`, returning the results in this page.
+    pub fn into_items(self) -> Vec<T> {
+        self.items
+    }
+
+    /// Returns whether this is the last page.
+    pub fn is_end(&self) -> bool {
+        self.end
+    }
+}
+
+// ===== impl Address =====
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Address::Tcp(ref addr) => addr.fmt(f),
+            Address::Unix(ref path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+// ===== impl Registry =====
+
+impl Registry {
+    fn lock(&self) -> MutexGuard<'_, Entities> {
+        self.entities.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn add<T: Entity>(self: &Arc<Self>, entry: T) -> Handle<T> {
+        // IDs start at 1, as 0 means unset in channelz requests.
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let entry = Arc::new(entry);
+        entry.entries(&mut self.lock()).insert(id, entry.clone());
+
+        Handle {
+            registry: self.clone(),
+            id,
+            entry,
+        }
+    }
+}
+
+// ===== impl Entities =====
+
+impl Entities {
+    fn server_info(&self, id: i64, server: &ServerEntry) -> ServerInfo {
+        ServerInfo {
+            id,
+            calls: server.calls.get(),
+            listen_sockets: self.socket_refs(Owner::Listener(id)),
+        }
+    }
+
+    fn channel_info(&self, id: i64, channel: &ChannelEntry) -> ChannelInfo {
+        let subchannels = match channel.parent {
+            Some(_) => Vec::new(),
+            None => self
+                .subchannels
+                .iter()
+                .filter(|(_, subchannel)| subchannel.parent == Some(id))
+                .map(|(&id, _)| id)
+                .collect(),
+        };
+
+        ChannelInfo {
+            id,
+            target: channel.target.clone(),
+            state: *channel.state.lock().unwrap_or_else(|e| e.into_inner()),
+            calls: channel.calls.get(),
+            subchannels,
+            sockets: self.socket_refs(Owner::Subchannel(id)),
+        }
+    }
+
+    fn socket_refs(&self, owner: Owner) -> Vec<SocketRef> {
+        self.sockets
+            .iter()
+            .filter(|(_, socket)| socket.owner == owner)
+            .map(|(&id, socket)| socket.to_ref(id))
+            .collect()
+    }
+}
+
+// ===== impl Handle =====
+
+impl<T: Entity> Handle<T> {
+    /// Returns the entry, to record to after the handle is moved.
+    pub(crate) fn entry(&self) -> &Arc<T> {
+        &self.entry
+    }
+}
+
+impl Handle<ServerEntry> {
+    /// Register a socket the server listens on.
+    pub(crate) fn add_listen_socket(&self, local: Option<Address>) -> Handle<SocketEntry> {
+        let name = local.as_ref().map(ToString::to_string).unwrap_or_default();
+        let owner = Owner::Listener(self.id);
+        self.registry
+            .add(SocketEntry::new(name, owner, local, None))
+    }
+
+    /// Register the socket of an accepted connection.
+    pub(crate) fn add_socket(
+        &self,
+        local: Option<Address>,
+        remote: Option<Address>,
+    ) -> Handle<SocketEntry> {
+        let name = remote.as_ref().map(ToString::to_string).unwrap_or_default();
+        let owner = Owner::Server(self.id);
+        self.registry
+            .add(SocketEntry::new(name, owner, local, remote))
+    }
+}
+
+impl Handle<ChannelEntry> {
+    /// Register a subchannel of this channel.
+    pub(crate) fn add_subchannel(&self) -> Handle<ChannelEntry> {
+        let target = self.entry.target.clone();
+        self.registry.add(ChannelEntry::new(target, Some(self.id)))
+    }
+
+    /// Register the socket of a connection established by this subchannel.
+    pub(crate) fn add_socket(
+        &self,
+        local: Option<Address>,
+        remote: Option<Address>,
+    ) -> Handle<SocketEntry> {
+        let name = remote.as_ref().map(ToString::to_string).unwrap_or_default();
+        let owner = Owner::Subchannel(self.id);
+        self.registry
+            .add(SocketEntry::new(name, owner, local, remote))
+    }
+}
+
+impl<T: Entity> Drop for Handle<T> {
+    fn drop(&mut self) {
+        self.entry
+            .entries(&mut self.registry.lock())
+            .remove(&self.id);
+    }
+}
+
+impl<T: Entity> fmt::Debug for Handle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Handle").field("id", &self.id).finish()
+    }
+}
+
+// ===== impl Entity =====
+
+impl Entity for ServerEntry {
+    fn entries<'a>(&self, entities: &'a mut Entities) -> &'a mut BTreeMap<i64, Arc<Self>> {
+        &mut entities.servers
+    }
+}
+
+impl Entity for ChannelEntry {
+    fn entries<'a>(&self, entities: &'a mut Entities) -> &'a mut BTreeMap<i64, Arc<Self>> {
+        match self.parent {
+            Some(_) => &mut entities.subchannels,
+            None => &mut entities.channels,
+        }
+    }
+}
+
+impl Entity for SocketEntry {
+    fn entries<'a>(&self, entities: &'a mut Entities) -> &'a mut BTreeMap<i64, Arc<Self>> {
+        &mut entities.sockets
+    }
+}
+
+// ===== impl ServerEntry =====
+
+impl ServerEntry {
+    pub(crate) fn calls(&self) -> &Arc<Calls> {
+        &self.calls
+    }
+}
+
+// ===== impl ChannelEntry =====
+
+impl ChannelEntry {
+    fn new(target: String, parent: Option<i64>) -> Self {
+        ChannelEntry {
+            target,
+            parent,
+            state: Mutex::new(ConnectivityState::Idle),
+            calls: Arc::default(),
+        }
+    }
+
+    pub(crate) fn calls(&self) -> &Arc<Calls> {
+        &self.calls
+    }
+
+    pub(crate) fn set_state(&self, state: ConnectivityState) {
+        *self.state.lock().unwrap_or_else(|e| e.into_inner()) = state;
+    }
+}
+
+// ===== impl SocketEntry =====
+
+impl SocketEntry {
+    fn new(name: String, owner: Owner, local: Option<Address>, remote: Option<Address>) -> Self {
+        SocketEntry {
+            name,
+            owner,
+            local,
+            remote,
+            streams: Mutex::default(),
+            tls: Mutex::default(),
+        }
+    }
+
+    /// Report the TLS session established on the socket.
+    #[cfg(feature = "tls")]
+    pub(crate) fn set_tls(&self, tls: TlsInfo) {
+        *self.tls.lock().unwrap_or_else(|e| e.into_inner()) = Some(tls);
+    }
+
+    fn to_ref(&self, id: i64) -> SocketRef {
+        SocketRef {
+            id,
+            name: self.name.clone(),
+        }
+    }
+
+    fn info(&self, id: i64) -> SocketInfo {
+        let streams = self.streams();
+
+        SocketInfo {
+            id,
+            name: self.name.clone(),
+            local: self.local.clone(),
+            remote: self.remote.clone(),
+            streams_started: streams.started,
+            streams_succeeded: streams.succeeded,
+            streams_failed: streams.failed,
+            messages_sent: streams.messages_sent,
+            messages_received: streams.messages_received,
+            last_local_stream_created: streams.last_local_created,
+            last_remote_stream_created: streams.last_remote_created,
+            last_message_sent: streams.last_message_sent,
+            last_message_received: streams.last_message_received,
+            tls: self.tls.lock().unwrap_or_else(|e| e.into_inner()).clone(),
+        }
+    }
+
+    fn streams(&self) -> MutexGuard<'_, Streams> {
+        self.streams.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn message(&self, sent: bool) {
+        let mut streams = self.streams();
+        let now = Some(SystemTime::now());
+
+        if sent {
+            streams.messages_sent += 1;
+            streams.last_message_sent = now;
+        } else {
+            streams.messages_received += 1;
+            streams.last_message_received = now;
+        }
+    }
+}
+
+// ===== impl Calls =====
+
+impl Calls {
+    fn get(&self) -> CallCounts {
+        self.lock().clone()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, CallCounts> {
+        self.counts.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn start(&self) {
+        let mut counts = self.lock();
+        counts.started += 1;
+        counts.last_started = Some(SystemTime::now());
+    }
+
+    fn finish(&self, code: Code) {
+        let mut counts = self.lock();
+
+        if code == Code::Ok {
+            counts.succeeded += 1;
+        } else {
+            counts.failed += 1;
+        }
+    }
+}
+
+// ===== impl Call =====
+
+impl Call {
+    /// Start a call received by a server on `socket`.
+    pub(crate) fn server(calls: Arc<Calls>, socket: Arc<SocketEntry>) -> Self {
+        Call::start(vec![calls], Some(socket), true)
+    }
+
+    /// Start a call made on a channel.
+    pub(crate) fn client(calls: Vec<Arc<Calls>>) -> Self {
+        Call::start(calls, None, false)
+    }
+
+    /// Start a stream opened by a client on `socket`.
+    pub(crate) fn stream(socket: Arc<SocketEntry>) -> Self {
+        Call::start(Vec::new(), Some(socket), false)
+    }
+
+    fn start(calls: Vec<Arc<Calls>>, socket: Option<Arc<SocketEntry>>, sent: bool) -> Self {
+        for calls in &calls {
+            calls.start();
+        }
+
+        if let Some(ref socket) = socket {
+            let mut streams = socket.streams();
+            streams.started += 1;
+
+            // Servers send the response, to streams started by the client.
+            if sent {
+                streams.last_remote_created = Some(SystemTime::now());
+            } else {
+                streams.last_local_created = Some(SystemTime::now());
+            }
+        }
+
+        Call {
+            calls,
+            socket,
+            sent,
+            frames: Frames::default(),
+            done: false,
+        }
+    }
+
+    /// Track `other` along with this call, as the same stream.
+    pub(crate) fn join(&mut self, mut other: Call) {
+        self.calls.append(&mut other.calls);
+
+        if self.socket.is_none() {
+            self.socket = other.socket.take();
+        }
+
+        other.done = true;
+    }
+
+    /// Count the messages in the next chunk of the response body.
+    pub(crate) fn data(&mut self, buf: &[u8]) {
+        if let Some(ref socket) = self.socket {
+            let sent = self.sent;
+            self.frames.read(buf, |_| socket.message(sent));
+        }
+    }
+
+    /// End the call, with `code` if the stream completed with a status.
+    pub(crate) fn finish(&mut self, code: Option<Code>) {
+        if self.done {
+            return;
+        }
+
+        self.done = true;
+
+        for calls in &self.calls {
+            calls.finish(code.unwrap_or(Code::Unknown));
+        }
+
+        if let Some(ref socket) = self.socket {
+            let mut streams = socket.streams();
+
+            if code.is_some() {
+                streams.succeeded += 1;
+            } else {
+                streams.failed += 1;
+            }
+        }
+    }
+}
+
+impl Drop for Call {
+    fn drop(&mut self) {
+        self.finish(None);
+    }
+}
+
+impl fmt::Debug for Call {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Call").field("done", &self.done).finish()
+    }
+}
+
+// ===== impl Counted =====
+
+impl<B> Counted<B> {
+    /// Count the messages of a body received on `socket`.
+    pub(crate) fn received(inner: B, socket: Arc<SocketEntry>) -> Self {
+        Counted {
+            inner,
+            socket,
+            sent: false,
+            frames: Frames::default(),
+        }
+    }
+
+    /// Count the messages of a body sent on `socket`.
+    pub(crate) fn sent(inner: B, socket: Arc<SocketEntry>) -> Self {
+        Counted {
+            inner,
+            socket,
+            sent: true,
+            frames: Frames::default(),
+        }
+    }
+}
+
+impl<B: Body> HttpBody for Counted<B> {
+    type Data = B::Data;
+    type Error = B::Error;
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn poll_data(&mut self) -> Poll<Option<Self::Data>, Self::Error> {
+        let data = try_ready!(self.inner.poll_data());
+
+        if let Some(ref data) = data {
+            let socket = &self.socket;
+            let sent = self.sent;
+            self.frames.read(data.bytes(), |_| socket.message(sent));
+        }
+
+        Ok(Async::Ready(data))
+    }
+
+    fn poll_trailers(&mut self) -> Poll<Option<HeaderMap>, Self::Error> {
+        self.inner.poll_trailers()
+    }
+}
+
+// ===== impl Tracked =====
+
+impl<B> Tracked<B> {
+    pub(crate) fn new(inner: B, call: Call) -> Self {
+        Tracked { inner, call }
+    }
+}
+
+impl<B: Body> HttpBody for Tracked<B> {
+    type Data = B::Data;
+    type Error = B::Error;
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn poll_data(&mut self) -> Poll<Option<Self::Data>, Self::Error> {
+        let data = try_ready!(self.inner.poll_data());
+
+        if let Some(ref data) = data {
+            self.call.data(data.bytes());
+        }
+
+        Ok(Async::Ready(data))
+    }
+
+    fn poll_trailers(&mut self) -> Poll<Option<HeaderMap>, Self::Error> {
+        let trailers = try_ready!(self.inner.poll_trailers());
+        self.call.finish(Some(status_code(trailers.as_ref())));
+        Ok(Async::Ready(trailers))
+    }
+}
+
+/// Returns the local and remote addresses of a connection's `io`, if it is a
+/// TCP or Unix domain socket.
+pub(crate) fn addresses(io: &dyn Any) -> (Option<Address>, Option<Address>) {
+    if let Some(tcp) = io.downcast_ref::<TcpStream>() {
+        let local = tcp.local_addr().ok().map(Address::Tcp);
+        let remote = tcp.peer_addr().ok().map(Address::Tcp);
+        return (local, remote);
+    }
+
+    #[cfg(unix)]
+    {
+        if let Some(unix) = io.downcast_ref::<UnixStream>() {
+            let path = |addr: std::os::unix::net::SocketAddr| {
+                addr.as_pathname()
+                    .map(|path| Address::Unix(path.to_owned()))
+            };
+            let local = unix.local_addr().ok().and_then(path);
+            let remote = unix.peer_addr().ok().and_then(path);
+            return (local, remote);
+        }
+    }
+
+    (None, None)
+}
+
+/// Returns the code of the status in `headers`, or `Code::Unknown` if there
+/// is none.
+pub(crate) fn status_code(headers: Option<&HeaderMap>) -> Code {
+    headers
+        .and_then(Status::from_header_map)
+        .map_or(Code::Unknown, |status| status.code())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::body::BoxBody;
+    use crate::test_util::NotFound;
+    use crate::transport::{Endpoint, Server};
+
+    use bytes::Bytes;
+    use futures::sync::oneshot;
+    use futures::{future, Future};
+    use std::io::Cursor;
+    use tower_service::Service;
+
+    /// A request body of a single empty message.
+    struct Message(Option<Bytes>);
+
+    impl HttpBody for Message {
+        type Data = Cursor<Bytes>;
+        type Error = Status;
+
+        fn poll_data(&mut self) -> Poll<Option<Self::Data>, Self::Error> {
+            Ok(Async::Ready(self.0.take().map(Cursor::new)))
+        }
+
+        fn poll_trailers(&mut self) -> Poll<Option<HeaderMap>, Self::Error> {
+            Ok(Async::Ready(None))
+        }
+    }
+
+    #[test]
+    fn counts_calls_and_streams() {
+        let path =
+            std::env::temp_dir().join(format!("tower-grpc-channelz-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let channelz = Channelz::new();
+        let mut rt = tokio::runtime::Runtime::new().unwrap();
+        let (shutdown, signal) = oneshot::channel::<()>();
+        let serve = Server::builder()
+            .add_service(NotFound)
+            .channelz(channelz.clone())
+            .serve_unix_with_shutdown(&path, signal)
+            .map_err(|e| panic!("server error: {}", e));
+        rt.spawn(serve);
+
+        let connect = Endpoint::from_unix(&path)
+            .channelz(channelz.clone())
+            .connect();
+        // The channel is reported until it is dropped.
+        let channel = rt.block_on(connect).unwrap();
+
+        let mut handle = channel.clone();
+        let call = future::poll_fn(move || {
+            try_ready!(handle.poll_ready());
+            Ok(Async::Ready(handle.clone()))
+        })
+        .and_then(|mut handle| {
+            let body = Message(Some(Bytes::from_static(&[0, 0, 0, 0, 0])));
+            let request = http::Request::builder()
+                .uri("/test.NotFound/Get")
+                .body(BoxBody::new(Box::new(body)))
+                .unwrap();
+            handle.call(request)
+        })
+        .and_then(|response| {
+            let mut body = response.into_body();
+            future::poll_fn(move || {
+                while try_ready!(Body::poll_data(&mut body)).is_some() {}
+                Body::poll_trailers(&mut body)
+            })
+            .map_err(crate::transport::Error::from)
+        });
+        let trailers = rt.block_on(call).unwrap().expect("trailers");
+        assert_eq!(trailers["grpc-status"], "5");
+
+        let servers = channelz.servers(0, 0);
+        assert!(servers.is_end());
+        let server = &servers.items()[0];
+        assert_eq!(server.calls().started(), 1);
+        assert_eq!(server.calls().failed(), 1);
+        assert!(server.calls().last_started().is_some());
+
+        let listen = channelz.socket(server.listen_sockets()[0].id()).unwrap();
+        assert_eq!(listen.local(), Some(&Address::Unix(path.clone())));
+
+        let sockets = channelz.server_sockets(server.id(), 0, 0).unwrap();
+        let socket = channelz.socket(sockets.items()[0].id()).unwrap();
+        assert_eq!(socket.streams_started(), 1);
+        assert_eq!(socket.streams_succeeded(), 1);
+        assert_eq!(socket.messages_received(), 1);
+        assert!(socket.last_remote_stream_created().is_some());
+
+        let channels = channelz.top_channels(0, 0);
+        let top = &channels.items()[0];
+        assert_eq!(top.target(), format!("unix:{}", path.display()));
+        assert_eq!(top.state(), ConnectivityState::Ready);
+        assert_eq!(top.calls().started(), 1);
+        assert_eq!(top.calls().failed(), 1);
+
+        let subchannel = channelz.subchannel(top.subchannels()[0]).unwrap();
+        assert_eq!(subchannel.calls().failed(), 1);
+
+        let socket = channelz.socket(subchannel.sockets()[0].id()).unwrap();
+        assert_eq!(socket.remote(), Some(&Address::Unix(path.clone())));
+        assert_eq!(socket.streams_started(), 1);
+        assert_eq!(socket.streams_succeeded(), 1);
+        assert_eq!(socket.messages_sent(), 1);
+
+        drop(channel);
+        drop(shutdown);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn pages() {
+        let channelz = Channelz::new();
+        let servers: Vec<_> = (0..3).map(|_| channelz.add_server()).collect();
+
+        let page = channelz.servers(0, 2);
+        let ids: Vec<_> = page.items().iter().map(ServerInfo::id).collect();
+        assert_eq!(ids, [servers[0].id, servers[1].id]);
+        assert!(!page.is_end());
+
+        let page = channelz.servers(servers[2].id, 2);
+        assert_eq!(page.items().len(), 1);
+        assert!(page.is_end());
+
+        drop(servers);
+        assert!(channelz.servers(0, 0).items().is_empty());
+    }
+}
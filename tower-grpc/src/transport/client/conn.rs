@@ -0,0 +1,263 @@
+use crate::body::{Body, BoxBody};
+use crate::error::Never;
+use crate::transport::body::{RecvBody, SendBody, SendBuf};
+use crate::transport::channelz::{self, Call, Counted, SocketEntry};
+use crate::transport::keepalive::{ActiveStream, ActiveStreams, KeepAlive, Pinger};
+use crate::transport::Error;
+use crate::Status;
+
+use futures::future::Shared;
+use futures::sync::oneshot;
+use futures::{try_ready, Async, Future, Poll};
+use log::{debug, trace};
+use std::fmt;
+use std::sync::Arc;
+use tokio::executor::{DefaultExecutor, Executor};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tower_service::Service;
+
+type SendData = SendBuf<<BoxBody as Body>::Data>;
+
+/// A future resolving to an established `Connection`.
+pub struct Handshake<I> {
+    inner: h2::client::Handshake<I, SendData>,
+    keepalive: Option<KeepAlive>,
+}
+
+/// An HTTP/2 connection to a gRPC server.
+///
+/// Requests must have absolute URIs. Cloning the connection shares it; the
+/// connection closes once every clone has been dropped and all in-flight
+/// requests have completed.
+#[derive(Clone)]
+pub struct Connection {
+    send_request: h2::client::SendRequest<SendData>,
+    streams: ActiveStreams,
+    closed: Shared<oneshot::Receiver<Never>>,
+    /// The connection's channelz socket, registered until every clone has
+    /// been dropped.
+    socket: Option<Arc<channelz::Handle<SocketEntry>>>,
+}
+
+/// The response to a request sent on a `Connection`.
+pub struct ResponseFuture {
+    state: ResponseState,
+}
+
+enum ResponseState {
+    Pending(
+        h2::client::ResponseFuture,
+        Option<ActiveStream>,
+        Option<Call>,
+    ),
+    Failed(Option<Error>),
+}
+
+/// Drives the connection, sending keepalive pings if configured.
+struct Background<I> {
+    conn: h2::client::Connection<I, SendData>,
+    pinger: Option<Pinger>,
+    streams: ActiveStreams,
+    /// Dropped once the connection has closed.
+    _closed: oneshot::Sender<Never>,
+}
+
+// ===== impl Handshake =====
+
+impl<I> Handshake<I> {
+    pub(super) fn new(
+        inner: h2::client::Handshake<I, SendData>,
+        keepalive: Option<KeepAlive>,
+    ) -> Self {
+        Handshake { inner, keepalive }
+    }
+}
+
+impl<I> Future for Handshake<I>
+where
+    I: AsyncRead + AsyncWrite + Send + 'static,
+{
+    type Item = Connection;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        let (send_request, mut conn) = try_ready!(self.inner.poll());
+        trace!("connection handshake complete");
+
+        let pinger = match self.keepalive {
+            Some(ref keepalive) => conn
+                .ping_pong()
+                .map(|ping_pong| Pinger::new(ping_pong, keepalive.clone())),
+            None => None,
+        };
+
+        let streams = ActiveStreams::default();
+        let (closed_tx, closed_rx) = oneshot::channel();
+        let background = Background {
+            conn,
+            pinger,
+            streams: streams.clone(),
+            _closed: closed_tx,
+        };
+
+        DefaultExecutor::current()
+            .spawn(Box::new(background))
+            .map_err(Error::executor)?;
+
+        Ok(Async::Ready(Connection {
+            send_request,
+            streams,
+            closed: closed_rx.shared(),
+            socket: None,
+        }))
+    }
+}
+
+impl<I> fmt::Debug for Handshake<I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Handshake")
+            .field("keepalive", &self.keepalive)
+            .finish()
+    }
+}
+
+// ===== impl Connection =====
+
+impl Connection {
+    /// Polls whether the connection has closed, and can no longer be used.
+    pub(crate) fn poll_closed(&mut self) -> Async<()> {
+        match self.closed.poll() {
+            Ok(Async::NotReady) => Async::NotReady,
+            Ok(Async::Ready(never)) => match *never {},
+            Err(_) => Async::Ready(()),
+        }
+    }
+
+    /// Report the streams on the connection to its channelz `socket`.
+    pub(crate) fn with_channelz(mut self, socket: channelz::Handle<SocketEntry>) -> Self {
+        self.socket = Some(Arc::new(socket));
+        self
+    }
+}
+
+impl Service<http::Request<BoxBody>> for Connection {
+    type Response = http::Response<RecvBody>;
+    type Error = Error;
+    type Future = ResponseFuture;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        self.send_request.poll_ready().map_err(Into::into)
+    }
+
+    fn call(&mut self, request: http::Request<BoxBody>) -> Self::Future {
+        let (parts, mut body) = request.into_parts();
+        let call = match self.socket {
+            Some(ref socket) => {
+                let socket = socket.entry().clone();
+                body = BoxBody::new(Box::new(Counted::sent(body, socket.clone())));
+                Some(Call::stream(socket))
+            }
+            None => None,
+        };
+
+        let request = http::Request::from_parts(parts, ());
+        let eos = body.is_end_stream();
+
+        let (response, stream) = match self.send_request.send_request(request, eos) {
+            Ok(sent) => sent,
+            Err(e) => return ResponseFuture::failed(e.into()),
+        };
+
+        if !eos {
+            let send_body = SendBody::new(body, stream).map_err(|e| {
+                debug!("failed to send request body: {}", e);
+            });
+
+            if let Err(e) = DefaultExecutor::current().spawn(Box::new(send_body)) {
+                return ResponseFuture::failed(Error::executor(e));
+            }
+        }
+
+        ResponseFuture {
+            state: ResponseState::Pending(response, Some(self.streams.track()), call),
+        }
+    }
+}
+
+impl fmt::Debug for Connection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Connection")
+            .field("streams", &self.streams)
+            .finish()
+    }
+}
+
+// ===== impl ResponseFuture =====
+
+impl ResponseFuture {
+    pub(crate) fn failed(err: Error) -> Self {
+        ResponseFuture {
+            state: ResponseState::Failed(Some(err)),
+        }
+    }
+}
+
+impl Future for ResponseFuture {
+    type Item = http::Response<RecvBody>;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        match self.state {
+            ResponseState::Pending(ref mut future, ref mut active, ref mut call) => {
+                let response = try_ready!(future.poll().map_err(|e| match *active {
+                    Some(ref active) => active.error(e),
+                    None => e.into(),
+                }));
+                let active = active.take().expect("polled after complete");
+                let mut response = response.map(|body| RecvBody::tracked(body, active));
+
+                if let Some(mut call) = call.take() {
+                    // Trailers-only responses carry their status in the headers.
+                    if let Some(status) = Status::from_header_map(response.headers()) {
+                        call.finish(Some(status.code()));
+                    }
+
+                    response.body_mut().track(call);
+                }
+
+                Ok(Async::Ready(response))
+            }
+            ResponseState::Failed(ref mut err) => Err(err.take().expect("polled after complete")),
+        }
+    }
+}
+
+impl fmt::Debug for ResponseFuture {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ResponseFuture").finish()
+    }
+}
+
+// ===== impl Background =====
+
+impl<I> Future for Background<I>
+where
+    I: AsyncRead + AsyncWrite,
+{
+    type Item = ();
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        if let Some(ref mut pinger) = self.pinger {
+            if let Err(e) = pinger.poll(self.streams.is_idle()) {
+                debug!("closing connection: {}", e);
+                self.streams.keepalive_timed_out();
+                return Err(());
+            }
+        }
+
+        self.conn.poll().map_err(|e| {
+            debug!("connection error: {}", e);
+        })
+    }
+}
@@ -0,0 +1,64 @@
+//! Establishing HTTP/2 connections to gRPC servers.
+
+mod conn;
+
+pub use self::conn::{Connection, Handshake, ResponseFuture};
+
+use super::keepalive::KeepAlive;
+
+use tokio::io::{AsyncRead, AsyncWrite};
+
+/// Configures and establishes HTTP/2 client connections.
+#[derive(Clone, Debug)]
+pub struct Builder {
+    h2: h2::client::Builder,
+    keepalive: Option<KeepAlive>,
+}
+
+impl Builder {
+    /// Returns a new `Builder` with default values.
+    pub fn new() -> Self {
+        Builder {
+            h2: h2::client::Builder::new(),
+            keepalive: None,
+        }
+    }
+
+    /// Set the initial flow control window size of each stream.
+    pub fn initial_stream_window_size(&mut self, size: u32) -> &mut Self {
+        self.h2.initial_window_size(size);
+        self
+    }
+
+    /// Set the initial flow control window size of each connection.
+    pub fn initial_connection_window_size(&mut self, size: u32) -> &mut Self {
+        self.h2.initial_connection_window_size(size);
+        self
+    }
+
+    /// Send keepalive pings to the server.
+    ///
+    /// If the server fails to acknowledge a ping in time, the connection is
+    /// closed and in-flight requests fail with `Code::Unavailable`.
+    pub fn keepalive(&mut self, keepalive: KeepAlive) -> &mut Self {
+        self.keepalive = Some(keepalive);
+        self
+    }
+
+    /// Perform the HTTP/2 handshake on `io`.
+    ///
+    /// Once established, the connection is driven by a task spawned on the
+    /// default executor.
+    pub fn handshake<I>(&self, io: I) -> Handshake<I>
+    where
+        I: AsyncRead + AsyncWrite + Send + 'static,
+    {
+        Handshake::new(self.h2.handshake(io), self.keepalive.clone())
+    }
+}
+
+impl Default for Builder {
+    fn default() -> Self {
+        Builder::new()
+    }
+}
@@ -0,0 +1,169 @@
+use crate::{Code, Status};
+
+use std::{error, fmt};
+
+type Source = Box<dyn error::Error + Send + Sync>;
+
+/// Errors produced by the transport.
+pub struct Error {
+    kind: Kind,
+    source: Option<Source>,
+}
+
+#[derive(Debug)]
+enum Kind {
+    Transport,
+    Service,
+    Connect,
+    KeepAliveTimedOut,
+    TimedOut,
+    Closed,
+    Executor,
+    #[cfg(feature = "tls")]
+    Tls,
+}
+
+// ===== impl Error =====
+
+impl Error {
+    pub(crate) fn transport<E>(source: E) -> Self
+    where
+        E: Into<Source>,
+    {
+        Error {
+            kind: Kind::Transport,
+            source: Some(source.into()),
+        }
+    }
+
+    pub(crate) fn service<E>(source: E) -> Self
+    where
+        E: Into<Source>,
+    {
+        Error {
+            kind: Kind::Service,
+            source: Some(source.into()),
+        }
+    }
+
+    pub(crate) fn connect<E>(source: E) -> Self
+    where
+        E: Into<Source>,
+    {
+        Error {
+            kind: Kind::Connect,
+            source: Some(source.into()),
+        }
+    }
+
+    pub(crate) fn keepalive_timed_out() -> Self {
+        Error {
+            kind: Kind::KeepAliveTimedOut,
+            source: None,
+        }
+    }
+
+    pub(crate) fn timed_out() -> Self {
+        Error {
+            kind: Kind::TimedOut,
+            source: None,
+        }
+    }
+
+    pub(crate) fn closed() -> Self {
+        Error {
+            kind: Kind::Closed,
+            source: None,
+        }
+    }
+
+    pub(crate) fn executor<E>(source: E) -> Self
+    where
+        E: Into<Source>,
+    {
+        Error {
+            kind: Kind::Executor,
+            source: Some(source.into()),
+        }
+    }
+
+    #[cfg(feature = "tls")]
+    pub(crate) fn tls<E>(source: E) -> Self
+    where
+        E: Into<Source>,
+    {
+        Error {
+            kind: Kind::Tls,
+            source: Some(source.into()),
+        }
+    }
+
+    /// Returns the `Status` a request failing with this error should
+    /// surface, or `None` if the status depends on the error's source.
+    pub(crate) fn to_status(&self) -> Option<Status> {
+        let code = match self.kind {
+            Kind::Connect | Kind::KeepAliveTimedOut | Kind::Closed => Code::Unavailable,
+            Kind::TimedOut => Code::DeadlineExceeded,
+            _ => return None,
+        };
+
+        Some(Status::new(code, self.to_string()))
+    }
+
+    fn description(&self) -> &str {
+        match self.kind {
+            Kind::Transport => "transport error",
+            Kind::Service => "service error",
+            Kind::Connect => "failed to connect",
+            Kind::KeepAliveTimedOut => "keepalive ping timed out",
+            Kind::TimedOut => "request timed out",
+            Kind::Closed => "channel closed",
+            Kind::Executor => "failed to spawn connection task",
+            #[cfg(feature = "tls")]
+            Kind::Tls => "TLS error",
+        }
+    }
+}
+
+impl From<h2::Error> for Error {
+    fn from(err: h2::Error) -> Self {
+        Error::transport(err)
+    }
+}
+
+impl From<tokio::timer::Error> for Error {
+    fn from(err: tokio::timer::Error) -> Self {
+        Error::transport(err)
+    }
+}
+
+impl fmt::Debug for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut builder = f.debug_struct("Error");
+
+        builder.field("kind", &self.kind);
+
+        if let Some(ref source) = self.source {
+            builder.field("source", source);
+        }
+
+        builder.finish()
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.source {
+            Some(ref source) => write!(f, "{}: {}", self.description(), source),
+            None => f.write_str(self.description()),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        self.source
+            .as_ref()
+            .map(|source| &**source as &(dyn error::Error + 'static))
+    }
+}
@@ -0,0 +1,623 @@
+use super::Error;
+
+use futures::{Async, Future, Poll};
+use log::{debug, trace};
+use std::io::{self, Read, Write};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use std::{cmp, fmt};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::timer::Delay;
+
+/// The default time to wait for a keepalive ping to be acknowledged.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// The default minimum interval between pings accepted by a server.
+const DEFAULT_MIN_PING_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// The minimum interval between pings on a connection with no active
+/// streams, when the policy does not permit such pings.
+const IDLE_MIN_PING_INTERVAL: Duration = Duration::from_secs(2 * 60 * 60);
+
+/// The number of policy violations tolerated before a client is sent a
+/// `GOAWAY`.
+const MAX_PING_STRIKES: usize = 2;
+
+/// The length of the HTTP/2 client connection preface.
+const PREFACE_LEN: usize = 24;
+
+/// The length of an HTTP/2 frame header.
+const FRAME_HEADER_LEN: usize = 9;
+
+const PING_FRAME: u8 = 0x6;
+const ACK_FLAG: u8 = 0x1;
+
+/// HTTP/2 keepalive configuration.
+///
+/// When enabled, a `PING` frame is sent to the peer every `interval`. If the
+/// peer does not acknowledge the ping within `timeout`, the connection is
+/// considered dead and is closed.
+#[derive(Clone, Debug)]
+pub struct KeepAlive {
+    interval: Duration,
+    timeout: Duration,
+    while_idle: bool,
+}
+
+/// How a server treats keepalive pings sent by its clients.
+///
+/// Clients that ping more often than the policy allows are sent a `GOAWAY`
+/// frame with the `ENHANCE_YOUR_CALM` error code, which surfaces on the
+/// client as `Code::ResourceExhausted`.
+#[derive(Clone, Debug)]
+pub struct PingPolicy {
+    min_interval: Duration,
+    permit_without_stream: bool,
+}
+
+/// Sends keepalive pings on a connection.
+pub(crate) struct Pinger {
+    ping_pong: h2::PingPong,
+    config: KeepAlive,
+    state: PingState,
+}
+
+enum PingState {
+    /// Waiting for the next ping to be due.
+    Waiting(Delay),
+    /// Waiting for a sent ping to be acknowledged.
+    Pinging(Delay),
+}
+
+/// Tracks pings received by a server, enforcing its `PingPolicy`.
+#[derive(Debug)]
+pub(crate) struct PingEnforcer {
+    policy: PingPolicy,
+    received: PingCount,
+    seen: usize,
+    last_ping: Option<Instant>,
+    strikes: usize,
+}
+
+/// The number of `PING` frames received on a connection.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct PingCount(Arc<AtomicUsize>);
+
+/// Wraps a server's IO, counting the `PING` frames sent by the client.
+///
+/// `h2` acknowledges pings internally, so they are counted by following the
+/// frame headers on the inbound byte stream.
+#[derive(Debug)]
+pub(crate) struct CountPings<T> {
+    io: T,
+    count: PingCount,
+    preface: usize,
+    header: [u8; FRAME_HEADER_LEN],
+    header_len: usize,
+    payload: usize,
+}
+
+/// Tracks the streams in flight on a client connection.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct ActiveStreams(Arc<Streams>);
+
+/// A stream in flight, counted until dropped.
+#[derive(Debug)]
+pub(crate) struct ActiveStream(Arc<Streams>);
+
+#[derive(Debug, Default)]
+struct Streams {
+    count: AtomicUsize,
+    /// Set once the connection was closed because a ping went unacknowledged.
+    timed_out: AtomicBool,
+}
+
+// ===== impl KeepAlive =====
+
+impl KeepAlive {
+    /// Send a keepalive ping every `interval`.
+    pub fn new(interval: Duration) -> Self {
+        KeepAlive {
+            interval,
+            timeout: DEFAULT_TIMEOUT,
+            while_idle: false,
+        }
+    }
+
+    /// Set how long to wait for a ping to be acknowledged before closing the
+    /// connection.
+    ///
+    /// Defaults to 20 seconds.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Set whether pings are sent while there are no active streams.
+    ///
+    /// Defaults to `false`. Servers reject such pings unless their
+    /// `PingPolicy` permits them.
+    pub fn while_idle(mut self, enabled: bool) -> Self {
+        self.while_idle = enabled;
+        self
+    }
+}
+
+// ===== impl PingPolicy =====
+
+impl PingPolicy {
+    /// Returns a new `PingPolicy` with default values.
+    pub fn new() -> Self {
+        PingPolicy {
+            min_interval: DEFAULT_MIN_PING_INTERVAL,
+            permit_without_stream: false,
+        }
+    }
+
+    /// Set the minimum interval a client should wait between pings.
+    ///
+    /// Defaults to 5 minutes.
+    pub fn min_interval(mut self, interval: Duration) -> Self {
+        self.min_interval = interval;
+        self
+    }
+
+    /// Set whether clients may ping while there are no active streams.
+    ///
+    /// Defaults to `false`.
+    pub fn permit_without_stream(mut self, permit: bool) -> Self {
+        self.permit_without_stream = permit;
+        self
+    }
+}
+
+impl Default for PingPolicy {
+    fn default() -> Self {
+        PingPolicy::new()
+    }
+}
+
+// ===== impl Pinger =====
+
+impl Pinger {
+    pub(crate) fn new(ping_pong: h2::PingPong, config: KeepAlive) -> Self {
+        let state = PingState::Waiting(Delay::new(Instant::now() + config.interval));
+
+        Pinger {
+            ping_pong,
+            config,
+            state,
+        }
+    }
+
+    /// Sends pings as they come due, failing if one is not acknowledged in
+    /// time.
+    pub(crate) fn poll(&mut self, is_idle: bool) -> Result<(), Error> {
+        loop {
+            match self.state {
+                PingState::Waiting(ref mut delay) => {
+                    if let Async::NotReady = delay.poll()? {
+                        return Ok(());
+                    }
+
+                    if is_idle && !self.config.while_idle {
+                        delay.reset(Instant::now() + self.config.interval);
+                        continue;
+                    }
+
+                    trace!("sending keepalive ping");
+                    self.ping_pong.send_ping(h2::Ping::opaque())?;
+                    self.state =
+                        PingState::Pinging(Delay::new(Instant::now() + self.config.timeout));
+                }
+                PingState::Pinging(ref mut timeout) => {
+                    if let Async::Ready(_) = self.ping_pong.poll_pong()? {
+                        trace!("received keepalive pong");
+                        self.state =
+                            PingState::Waiting(Delay::new(Instant::now() + self.config.interval));
+                        continue;
+                    }
+
+                    if let Async::Ready(()) = timeout.poll()? {
+                        debug!(
+                            "keepalive ping was not acknowledged within {:?}",
+                            self.config.timeout
+                        );
+                        return Err(Error::keepalive_timed_out());
+                    }
+
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Pinger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Pinger")
+            .field("config", &self.config)
+            .finish()
+    }
+}
+
+// ===== impl PingEnforcer =====
+
+impl PingEnforcer {
+    pub(crate) fn new(policy: PingPolicy, received: PingCount) -> Self {
+        PingEnforcer {
+            policy,
+            received,
+            seen: 0,
+            last_ping: None,
+            strikes: 0,
+        }
+    }
+
+    /// Accounts for newly received pings, returning `true` once the client
+    /// has violated the policy too many times.
+    pub(crate) fn check(&mut self, is_idle: bool) -> bool {
+        let received = self.received.get();
+
+        while self.seen < received {
+            self.seen += 1;
+
+            let now = Instant::now();
+            let min_interval = if is_idle && !self.policy.permit_without_stream {
+                IDLE_MIN_PING_INTERVAL
+            } else {
+                self.policy.min_interval
+            };
+
+            if let Some(last_ping) = self.last_ping {
+                if now - last_ping < min_interval {
+                    self.strikes += 1;
+                    trace!("ping strike {}", self.strikes);
+                }
+            }
+
+            self.last_ping = Some(now);
+        }
+
+        self.strikes > MAX_PING_STRIKES
+    }
+
+    /// Forgives previous violations, as the connection is doing useful work.
+    pub(crate) fn reset_strikes(&mut self) {
+        self.strikes = 0;
+    }
+}
+
+// ===== impl PingCount =====
+
+impl PingCount {
+    fn increment(&self) {
+        self.0.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn get(&self) -> usize {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+// ===== impl CountPings =====
+
+impl<T> CountPings<T> {
+    pub(crate) fn new(io: T, count: PingCount) -> Self {
+        CountPings {
+            io,
+            count,
+            preface: PREFACE_LEN,
+            header: [0; FRAME_HEADER_LEN],
+            header_len: 0,
+            payload: 0,
+        }
+    }
+
+    fn observe(&mut self, mut bytes: &[u8]) {
+        while !bytes.is_empty() {
+            if self.preface > 0 {
+                let n = cmp::min(self.preface, bytes.len());
+                self.preface -= n;
+                bytes = &bytes[n..];
+                continue;
+            }
+
+            if self.payload > 0 {
+                let n = cmp::min(self.payload, bytes.len());
+                self.payload -= n;
+                bytes = &bytes[n..];
+                continue;
+            }
+
+            let n = cmp::min(FRAME_HEADER_LEN - self.header_len, bytes.len());
+            self.header[self.header_len..self.header_len + n].copy_from_slice(&bytes[..n]);
+            self.header_len += n;
+            bytes = &bytes[n..];
+
+            if self.header_len == FRAME_HEADER_LEN {
+                let head = &self.header;
+                let kind = head[3];
+                let flags = head[4];
+
+                if kind == PING_FRAME && flags & ACK_FLAG == 0 {
+                    self.count.increment();
+                }
+
+                self.payload =
+                    (head[0] as usize) << 16 | (head[1] as usize) << 8 | head[2] as usize;
+                self.header_len = 0;
+            }
+        }
+    }
+}
+
+impl<T: Read> Read for CountPings<T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.io.read(buf)?;
+        self.observe(&buf[..n]);
+        Ok(n)
+    }
+}
+
+impl<T: Write> Write for CountPings<T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.io.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.io.flush()
+    }
+}
+
+impl<T: AsyncRead> AsyncRead for CountPings<T> {}
+
+impl<T: AsyncWrite> AsyncWrite for CountPings<T> {
+    fn shutdown(&mut self) -> Poll<(), io::Error> {
+        self.io.shutdown()
+    }
+}
+
+// ===== impl ActiveStreams =====
+
+impl ActiveStreams {
+    pub(crate) fn track(&self) -> ActiveStream {
+        self.0.count.fetch_add(1, Ordering::SeqCst);
+        ActiveStream(self.0.clone())
+    }
+
+    pub(crate) fn is_idle(&self) -> bool {
+        self.0.count.load(Ordering::SeqCst) == 0
+    }
+
+    /// Records that the connection is closing because a keepalive ping was
+    /// not acknowledged.
+    ///
+    /// Must be called before the connection is dropped, so that the streams
+    /// it fails report the timeout.
+    pub(crate) fn keepalive_timed_out(&self) {
+        self.0.timed_out.store(true, Ordering::SeqCst);
+    }
+}
+
+// ===== impl ActiveStream =====
+
+impl ActiveStream {
+    /// Returns the error the stream surfaces when `h2` fails it.
+    ///
+    /// Once the connection has been closed by keepalive, `h2` fails its
+    /// streams with an io error, which would otherwise surface as
+    /// `Code::Unknown`.
+    pub(crate) fn error(&self, err: h2::Error) -> Error {
+        if self.0.timed_out.load(Ordering::SeqCst) {
+            Error::keepalive_timed_out()
+        } else {
+            err.into()
+        }
+    }
+}
+
+impl Drop for ActiveStream {
+    fn drop(&mut self) {
+        self.0.count.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::body::BoxBody;
+    use crate::test_util::Empty;
+    use crate::transport::client;
+    use crate::{Code, Status};
+
+    use futures::{future, Stream};
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio::runtime::Runtime;
+    use tower_service::Service;
+
+    const PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+
+    fn frame(kind: u8, flags: u8, payload: &[u8]) -> Vec<u8> {
+        let len = payload.len();
+        let mut frame = vec![(len >> 16) as u8, (len >> 8) as u8, len as u8, kind, flags];
+        frame.extend_from_slice(&[0, 0, 0, 0]);
+        frame.extend_from_slice(payload);
+        frame
+    }
+
+    fn count(chunks: &[&[u8]]) -> usize {
+        let count = PingCount::default();
+        let mut io = CountPings::new(io::empty(), count.clone());
+
+        for chunk in chunks {
+            io.observe(chunk);
+        }
+
+        count.get()
+    }
+
+    #[test]
+    fn counts_pings_after_preface() {
+        let mut bytes = PREFACE.to_vec();
+        bytes.extend(frame(0x4, 0, &[]));
+        bytes.extend(frame(PING_FRAME, 0, &[0; 8]));
+        bytes.extend(frame(0x0, 0, &[PING_FRAME; 16]));
+        bytes.extend(frame(PING_FRAME, 0, &[0; 8]));
+
+        assert_eq!(count(&[&bytes]), 2);
+    }
+
+    #[test]
+    fn ignores_ping_acks() {
+        let mut bytes = PREFACE.to_vec();
+        bytes.extend(frame(PING_FRAME, ACK_FLAG, &[0; 8]));
+
+        assert_eq!(count(&[&bytes]), 0);
+    }
+
+    #[test]
+    fn counts_pings_split_across_reads() {
+        let mut bytes = PREFACE.to_vec();
+        bytes.extend(frame(PING_FRAME, 0, &[0; 8]));
+        bytes.extend(frame(PING_FRAME, 0, &[0; 8]));
+
+        let chunks = bytes.chunks(5).collect::<Vec<_>>();
+        assert_eq!(count(&chunks), 2);
+    }
+
+    #[test]
+    fn enforcer_strikes_frequent_pings() {
+        let count = PingCount::default();
+        let policy = PingPolicy::new().permit_without_stream(true);
+        let mut enforcer = PingEnforcer::new(policy, count.clone());
+
+        for _ in 0..=MAX_PING_STRIKES {
+            count.increment();
+            assert!(!enforcer.check(false));
+        }
+
+        count.increment();
+        assert!(enforcer.check(false));
+    }
+
+    #[test]
+    fn enforcer_resets_strikes() {
+        let count = PingCount::default();
+        let mut enforcer = PingEnforcer::new(PingPolicy::new(), count.clone());
+
+        for _ in 0..10 {
+            count.increment();
+            assert!(!enforcer.check(false));
+            enforcer.reset_strikes();
+        }
+    }
+
+    /// Connects a client sending a keepalive ping every 50ms to a peer served
+    /// by `serve`, returning whether the connection closed within a second.
+    fn closes<F, S>(serve: F) -> bool
+    where
+        F: FnOnce(TcpStream) -> S + Send + 'static,
+        S: Future<Item = (), Error = ()> + Send + 'static,
+    {
+        let mut rt = Runtime::new().unwrap();
+
+        let listener = TcpListener::bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let accept = listener
+            .incoming()
+            .into_future()
+            .map_err(|_| ())
+            .and_then(|(io, _)| serve(io.unwrap()));
+        rt.spawn(accept);
+
+        let keepalive = KeepAlive::new(Duration::from_millis(50))
+            .timeout(Duration::from_millis(50))
+            .while_idle(true);
+
+        let connect = TcpStream::connect(&addr)
+            .map_err(Error::connect)
+            .and_then(move |io| client::Builder::new().keepalive(keepalive).handshake(io));
+        let mut conn = rt.block_on(connect).unwrap();
+
+        let closed = future::poll_fn(move || Ok::<_, ()>(conn.poll_closed()));
+        let timeout = Delay::new(Instant::now() + Duration::from_secs(1));
+
+        let first = closed
+            .map(|_| true)
+            .select(timeout.map(|_| false).map_err(|_| ()))
+            .map(|(closed, _)| closed)
+            .map_err(|_| ());
+
+        rt.block_on(first).unwrap()
+    }
+
+    #[test]
+    fn keepalive_timeout_closes_connection() {
+        // The peer accepts the connection, but never reads from it, so pings
+        // are never acknowledged.
+        let closed = closes(|io| {
+            Delay::new(Instant::now() + Duration::from_secs(5))
+                .map(move |_| drop(io))
+                .map_err(|_| ())
+        });
+
+        assert!(closed);
+    }
+
+    #[test]
+    fn acknowledged_pings_keep_connection_open() {
+        let closed = closes(|io| {
+            h2::server::handshake(io)
+                .and_then(|conn| conn.for_each(|_| Ok(())))
+                .map_err(|_| ())
+        });
+
+        assert!(!closed);
+    }
+
+    #[test]
+    fn keepalive_timeout_fails_in_flight_requests() {
+        let mut rt = Runtime::new().unwrap();
+
+        let listener = TcpListener::bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        // The peer accepts a request, then stops polling the connection, so
+        // pings are no longer acknowledged.
+        let serve = listener
+            .incoming()
+            .into_future()
+            .map_err(|_| ())
+            .and_then(|(io, _)| h2::server::handshake(io.unwrap()).map_err(|_| ()))
+            .and_then(|conn| conn.into_future().map_err(|_| ()))
+            .and_then(|(request, conn)| {
+                Delay::new(Instant::now() + Duration::from_secs(5))
+                    .map(move |_| drop((request, conn)))
+                    .map_err(|_| ())
+            });
+        rt.spawn(serve);
+
+        let keepalive =
+            KeepAlive::new(Duration::from_millis(50)).timeout(Duration::from_millis(50));
+
+        let connect = TcpStream::connect(&addr)
+            .map_err(Error::connect)
+            .and_then(move |io| client::Builder::new().keepalive(keepalive).handshake(io));
+        let mut conn = rt.block_on(connect).unwrap();
+
+        let call = future::lazy(move || {
+            let request = http::Request::builder()
+                .uri(format!("http://{}/test.Origin/Get", addr))
+                .body(BoxBody::new(Box::new(Empty)))
+                .unwrap();
+
+            conn.call(request)
+        });
+
+        let err = rt.block_on(call).unwrap_err();
+        assert_eq!(Status::from_error(&err).code(), Code::Unavailable);
+    }
+}
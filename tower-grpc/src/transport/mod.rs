@@ -0,0 +1,36 @@
+//! A batteries-included HTTP/2 transport for gRPC.
+//!
+//! Connections are driven directly on `h2`, which allows the transport to
+//! manage connection-level concerns that generic HTTP stacks do not expose,
+//! such as keepalive pings and connection lifetime limits.
+//!
+//! With the `tls` feature enabled, servers and channels can be secured with
+//! `rustls`, by passing a `ServerTlsConfig` or `ClientTlsConfig` to
+//! `Server::tls_config` or `Endpoint::tls_config`.
+//!
+//! Servers and channels report their sockets, calls and streams to a
+//! `Channelz` registry passed to `Server::channelz` or `Endpoint::channelz`.
+
+pub mod channel;
+pub mod channelz;
+pub mod client;
+pub mod server;
+
+mod body;
+mod error;
+mod keepalive;
+#[cfg(feature = "tls")]
+mod peer;
+#[cfg(feature = "tls")]
+mod tls;
+
+pub use self::body::{Data, RecvBody};
+pub use self::channel::{Backoff, Channel, Endpoint};
+pub use self::channelz::Channelz;
+pub use self::error::Error;
+pub use self::keepalive::{KeepAlive, PingPolicy};
+#[cfg(feature = "tls")]
+pub use self::peer::PeerCertificates;
+pub use self::server::Server;
+#[cfg(feature = "tls")]
+pub use self::tls::{Certificate, ClientTlsConfig, Identity, ServerTlsConfig};
@@ -0,0 +1,317 @@
+//! The verified identity of a TLS peer.
+
+use crate::transport::Error;
+
+use std::fmt::{self, Write};
+use std::sync::Arc;
+use x509_parser::extensions::{GeneralName, ParsedExtension};
+use x509_parser::x509::X509Name;
+
+/// Short names of the attribute types RFC 4514 defines, by OID.
+const ATTRIBUTE_NAMES: &[(&str, &str)] = &[
+    ("2.5.4.3", "CN"),
+    ("2.5.4.6", "C"),
+    ("2.5.4.7", "L"),
+    ("2.5.4.8", "ST"),
+    ("2.5.4.9", "STREET"),
+    ("2.5.4.10", "O"),
+    ("2.5.4.11", "OU"),
+    ("0.9.2342.19200300.100.1.1", "UID"),
+    ("0.9.2342.19200300.100.1.25", "DC"),
+];
+
+/// The certificate chain a TLS peer authenticated with, and the identities
+/// its end-entity certificate asserts.
+///
+/// Servers attach the client's certificates to every request received on a
+/// connection that was authenticated with one, where handlers can retrieve
+/// them with `Request::peer_certificates`.
+///
+/// Certificates are only present once they have been verified against the
+/// server's `client_ca_root`, so the identities they assert can be used to
+/// authorize requests.
+#[derive(Clone)]
+pub struct PeerCertificates {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    chain: Vec<Vec<u8>>,
+    subject: Option<String>,
+    dns_names: Vec<String>,
+    uris: Vec<String>,
+}
+
+impl PeerCertificates {
+    /// Returns the identities asserted by the end-entity certificate of a
+    /// DER encoded `chain`.
+    ///
+    /// Fails if the chain is empty, or its end-entity certificate cannot be
+    /// parsed.
+    pub(crate) fn new(chain: Vec<Vec<u8>>) -> Result<Self, Error> {
+        let leaf = match chain.first() {
+            Some(leaf) => parse_certificate(leaf)?,
+            None => return Err(Error::tls("empty peer certificate chain")),
+        };
+
+        Ok(PeerCertificates {
+            inner: Arc::new(Inner {
+                chain,
+                subject: leaf.subject,
+                dns_names: leaf.dns_names,
+                uris: leaf.uris,
+            }),
+        })
+    }
+
+    /// Returns the DER encoded certificate chain, starting with the peer's
+    /// end-entity certificate.
+    pub fn chain(&self) -> &[Vec<u8>] {
+        &self.inner.chain
+    }
+
+    /// Returns the DER encoded end-entity certificate.
+    pub fn end_entity(&self) -> &[u8] {
+        &self.inner.chain[0]
+    }
+
+    /// Returns the subject of the end-entity certificate, formatted as an
+    /// RFC 4514 distinguished name, such as `CN=client,O=Example`.
+    ///
+    /// Attribute values that are not strings, such as a `BMPString`, are
+    /// formatted as the hex of their DER encoding, such as
+    /// `2.5.4.3=#1e0400680069`. Returns `None` if a value cannot be encoded.
+    pub fn subject(&self) -> Option<&str> {
+        self.inner.subject.as_ref().map(String::as_str)
+    }
+
+    /// Returns the DNS names in the end-entity certificate's subject
+    /// alternative names.
+    pub fn dns_names(&self) -> &[String] {
+        &self.inner.dns_names
+    }
+
+    /// Returns the URIs in the end-entity certificate's subject alternative
+    /// names.
+    pub fn uris(&self) -> &[String] {
+        &self.inner.uris
+    }
+
+    /// Returns the SPIFFE IDs among the end-entity certificate's URI subject
+    /// alternative names.
+    pub fn spiffe_ids(&self) -> impl Iterator<Item = &str> {
+        self.inner
+            .uris
+            .iter()
+            .map(String::as_str)
+            .filter(|uri| uri.starts_with("spiffe://"))
+    }
+}
+
+impl fmt::Debug for PeerCertificates {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PeerCertificates")
+            .field("subject", &self.inner.subject)
+            .field("dns_names", &self.inner.dns_names)
+            .field("uris", &self.inner.uris)
+            .finish()
+    }
+}
+
+// ===== X.509 =====
+
+/// The parts of an X.509 certificate that identify its subject.
+struct Certificate {
+    subject: Option<String>,
+    dns_names: Vec<String>,
+    uris: Vec<String>,
+}
+
+fn parse_certificate(der: &[u8]) -> Result<Certificate, Error> {
+    let cert = match x509_parser::parse_x509_der(der) {
+        Ok((rest, _)) if !rest.is_empty() => {
+            return Err(Error::tls("trailing data after peer certificate"));
+        }
+        Ok((_, cert)) => cert,
+        Err(e) => return Err(Error::tls(format!("malformed peer certificate: {:?}", e))),
+    };
+
+    let tbs = &cert.tbs_certificate;
+    let mut parsed = Certificate {
+        subject: format_name(&tbs.subject),
+        dns_names: Vec::new(),
+        uris: Vec::new(),
+    };
+
+    for extension in tbs.extensions.values() {
+        if let ParsedExtension::SubjectAlternativeName(ref names) = *extension.parsed_extension() {
+            for name in &names.general_names {
+                match *name {
+                    GeneralName::DNSName(name) => parsed.dns_names.push(name.to_owned()),
+                    GeneralName::URI(uri) => parsed.uris.push(uri.to_owned()),
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    Ok(parsed)
+}
+
+/// Formats an X.509 `Name` as an RFC 4514 string.
+fn format_name(name: &X509Name<'_>) -> Option<String> {
+    let mut rdns = Vec::new();
+
+    for rdn in &name.rdn_seq {
+        let mut attributes = Vec::new();
+
+        for attribute in &rdn.set {
+            let oid = attribute.attr_type.to_id_string();
+
+            let formatted = match attribute.attr_value.as_str() {
+                Ok(value) => {
+                    let mut formatted = match ATTRIBUTE_NAMES.iter().find(|&&(o, _)| o == oid) {
+                        Some(&(_, short)) => short.to_owned(),
+                        None => oid,
+                    };
+                    formatted.push('=');
+                    escape(value, &mut formatted);
+                    formatted
+                }
+                Err(_) => {
+                    // RFC 4514, section 2.4: values without a string form are
+                    // written as the hex of their encoding.
+                    let value = &attribute.attr_value;
+                    let content = value.as_slice().ok()?;
+
+                    let mut formatted = oid;
+                    formatted.push_str("=#");
+                    for byte in encode(value.class, value.structured, value.tag.0, content) {
+                        write!(formatted, "{:02x}", byte).expect("write to String");
+                    }
+                    formatted
+                }
+            };
+
+            attributes.push(formatted);
+        }
+
+        rdns.push(attributes.join("+"));
+    }
+
+    // RFC 4514 lists the most specific RDN first.
+    rdns.reverse();
+    Some(rdns.join(","))
+}
+
+/// Returns the DER encoding of a value with the given identifier and
+/// `content`.
+fn encode(class: u8, structured: u8, tag: u32, content: &[u8]) -> Vec<u8> {
+    let mut der = Vec::with_capacity(content.len() + 6);
+
+    let identifier = (class << 6) | (structured << 5);
+    if tag < 0x1f {
+        der.push(identifier | tag as u8);
+    } else {
+        der.push(identifier | 0x1f);
+        let groups = (0..5).rev().skip_while(|&i| tag >> (7 * i) == 0);
+        for i in groups {
+            let more = if i > 0 { 0x80 } else { 0 };
+            der.push(more | ((tag >> (7 * i)) as u8 & 0x7f));
+        }
+    }
+
+    let len = content.len();
+    if len < 0x80 {
+        der.push(len as u8);
+    } else {
+        let bytes = (len as u64).to_be_bytes();
+        let skip = bytes.iter().take_while(|&&b| b == 0).count();
+        der.push(0x80 | (bytes.len() - skip) as u8);
+        der.extend_from_slice(&bytes[skip..]);
+    }
+
+    der.extend_from_slice(content);
+    der
+}
+
+/// Escapes an attribute value as RFC 4514 requires.
+fn escape(value: &str, out: &mut String) {
+    let last = value.chars().count().saturating_sub(1);
+
+    for (i, c) in value.chars().enumerate() {
+        let special = match c {
+            ',' | '+' | '"' | '\\' | '<' | '>' | ';' => true,
+            '#' => i == 0,
+            ' ' => i == 0 || i == last,
+            _ => false,
+        };
+
+        if special {
+            out.push('\\');
+        }
+
+        if c == '\0' {
+            out.push_str("\\00");
+        } else {
+            out.push(c);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A self-signed certificate for `C=US, O=Example, Inc., CN=client`,
+    /// with DNS, IP and URI subject alternative names.
+    const PEER: &[u8] = include_bytes!("testdata/peer.der");
+
+    /// A self-signed certificate for `DC=example, 2.999.3=arc`.
+    const OIDS: &[u8] = include_bytes!("testdata/oids.der");
+
+    /// A self-signed certificate for `O=Example, CN=client`, with the common
+    /// name as a `BMPString`.
+    const BMP: &[u8] = include_bytes!("testdata/bmp.der");
+
+    #[test]
+    fn identities_from_end_entity() {
+        let peer = PeerCertificates::new(vec![PEER.to_vec(), OIDS.to_vec()]).unwrap();
+
+        assert_eq!(peer.chain().len(), 2);
+        assert_eq!(peer.end_entity(), PEER);
+        assert_eq!(peer.subject(), Some("CN=client,O=Example\\, Inc.,C=US"));
+        assert_eq!(peer.dns_names(), &["client.example.com".to_owned()]);
+        assert_eq!(peer.uris().len(), 2);
+        assert_eq!(
+            peer.spiffe_ids().collect::<Vec<_>>(),
+            vec!["spiffe://example.com/ns/default/sa/client"]
+        );
+    }
+
+    #[test]
+    fn unknown_attributes_use_dotted_oids() {
+        let peer = PeerCertificates::new(vec![OIDS.to_vec()]).unwrap();
+        assert_eq!(peer.subject(), Some("2.999.3=arc,DC=example"));
+    }
+
+    #[test]
+    fn non_string_attributes_use_hex() {
+        let peer = PeerCertificates::new(vec![BMP.to_vec()]).unwrap();
+        assert_eq!(
+            peer.subject(),
+            Some("2.5.4.3=#1e0c0063006c00690065006e0074,O=Example")
+        );
+    }
+
+    #[test]
+    fn malformed_certificates() {
+        assert!(PeerCertificates::new(vec![]).is_err());
+        assert!(PeerCertificates::new(vec![vec![0x30, 0x05, 0x00]]).is_err());
+        assert!(PeerCertificates::new(vec![PEER[..PEER.len() - 1].to_vec()]).is_err());
+
+        let mut trailing = PEER.to_vec();
+        trailing.push(0);
+        assert!(PeerCertificates::new(vec![trailing]).is_err());
+    }
+}
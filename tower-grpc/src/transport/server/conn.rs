@@ -0,0 +1,656 @@
+use super::{Config, Watch};
+use crate::body::{Body, BoxBody};
+use crate::error::Never;
+use crate::generic::client::GrpcService;
+use crate::transport::body::{RecvBody, SendBody, SendBuf};
+use crate::transport::keepalive::{CountPings, PingCount, PingEnforcer, Pinger};
+use crate::transport::Error;
+use crate::{Code, Status};
+
+use futures::stream::FuturesUnordered;
+use futures::{task, try_ready, Async, Future, Poll, Stream};
+use http::header::{HeaderValue, CONTENT_TYPE};
+use log::{debug, trace};
+use std::time::{Duration, Instant};
+use std::{cmp, fmt};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::timer::Delay;
+
+/// How long to wait for the client to close cancelled streams before closing
+/// the connection.
+const CANCEL_LINGER: Duration = Duration::from_secs(1);
+
+type SendData<S> = SendBuf<<<S as GrpcService<BoxBody>>::ResponseBody as Body>::Data>;
+
+/// A future that serves gRPC requests on a single HTTP/2 connection.
+///
+/// Completes once the connection has been closed.
+pub struct Connection<I, S>
+where
+    S: GrpcService<BoxBody>,
+{
+    state: State<I, S>,
+    service: S,
+    watch: Option<Watch>,
+}
+
+enum State<I, S>
+where
+    S: GrpcService<BoxBody>,
+{
+    Handshaking {
+        handshake: h2::server::Handshake<CountPings<I>, SendData<S>>,
+        config: Config,
+        pings: PingCount,
+    },
+    Serving(Serving<I, S>),
+    Done,
+}
+
+struct Serving<I, S>
+where
+    S: GrpcService<BoxBody>,
+{
+    conn: h2::server::Connection<CountPings<I>, SendData<S>>,
+    streams: FuturesUnordered<Respond<S::Future, S::ResponseBody>>,
+    pinger: Option<Pinger>,
+    enforcer: PingEnforcer,
+    idle: Option<IdleTimer>,
+    age: Option<Delay>,
+    age_grace: Option<Duration>,
+    watch: Option<Watch>,
+    signaled: bool,
+    closing: Closing,
+}
+
+/// How far the connection has progressed towards closing.
+enum Closing {
+    Open,
+    /// A graceful shutdown is in progress, optionally bounded by a deadline
+    /// after which in-flight streams are cancelled.
+    Draining(Option<Delay>),
+    /// In-flight streams were cancelled; waiting for the client to close them.
+    Cancelled(Delay),
+    /// The connection is being torn down.
+    Closed,
+}
+
+/// Fires once the connection has had no active streams for a while.
+struct IdleTimer {
+    timeout: Duration,
+    delay: Delay,
+    idle: bool,
+}
+
+/// Responds to a single request.
+struct Respond<F, B>
+where
+    B: Body,
+{
+    state: RespondState<F, B>,
+}
+
+enum RespondState<F, B>
+where
+    B: Body,
+{
+    Pending(F, h2::server::SendResponse<SendBuf<B::Data>>),
+    Streaming(SendBody<B>),
+    Done,
+}
+
+// ===== impl Connection =====
+
+impl<I, S> Connection<I, S>
+where
+    I: AsyncRead + AsyncWrite,
+    S: GrpcService<BoxBody>,
+    <S::ResponseBody as Body>::Data: 'static,
+{
+    pub(super) fn new(h2: &h2::server::Builder, config: Config, io: I, service: S) -> Self {
+        let pings = PingCount::default();
+        let handshake = h2.handshake(CountPings::new(io, pings.clone()));
+
+        Connection {
+            state: State::Handshaking {
+                handshake,
+                config,
+                pings,
+            },
+            service,
+            watch: None,
+        }
+    }
+
+    /// Gracefully shut down this connection once `watch` is signaled.
+    ///
+    /// The connection holds on to `watch` until it has closed.
+    pub fn with_shutdown(mut self, watch: Watch) -> Self {
+        self.watch = Some(watch);
+        self
+    }
+}
+
+impl<I, S> Future for Connection<I, S>
+where
+    I: AsyncRead + AsyncWrite,
+    S: GrpcService<BoxBody>,
+    <S::ResponseBody as Body>::Data: 'static,
+{
+    type Item = ();
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        loop {
+            let serving = match self.state {
+                State::Handshaking {
+                    ref mut handshake,
+                    ref config,
+                    ref pings,
+                } => {
+                    let conn = try_ready!(handshake.poll());
+                    Serving::new(conn, config, pings.clone(), self.watch.take())
+                }
+                State::Serving(ref mut serving) => {
+                    let ready = serving.poll(&mut self.service);
+
+                    if let Ok(Async::NotReady) = ready {
+                        return ready;
+                    }
+
+                    self.state = State::Done;
+                    return ready;
+                }
+                State::Done => panic!("polled after complete"),
+            };
+
+            trace!("connection handshake complete");
+            self.state = State::Serving(serving);
+        }
+    }
+}
+
+impl<I, S> fmt::Debug for Connection<I, S>
+where
+    I: fmt::Debug,
+    S: GrpcService<BoxBody> + fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = match self.state {
+            State::Handshaking { .. } => "Handshaking",
+            State::Serving(..) => "Serving",
+            State::Done => "Done",
+        };
+
+        f.debug_struct("Connection")
+            .field("state", &state)
+            .field("service", &self.service)
+            .finish()
+    }
+}
+
+// ===== impl Serving =====
+
+impl<I, S> Serving<I, S>
+where
+    I: AsyncRead + AsyncWrite,
+    S: GrpcService<BoxBody>,
+    <S::ResponseBody as Body>::Data: 'static,
+{
+    fn new(
+        mut conn: h2::server::Connection<CountPings<I>, SendData<S>>,
+        config: &Config,
+        pings: PingCount,
+        watch: Option<Watch>,
+    ) -> Self {
+        let now = Instant::now();
+
+        let pinger = match config.keepalive {
+            Some(ref keepalive) => conn
+                .ping_pong()
+                .map(|ping_pong| Pinger::new(ping_pong, keepalive.clone())),
+            None => None,
+        };
+
+        let idle = config.max_connection_idle.map(|timeout| IdleTimer {
+            timeout,
+            delay: Delay::new(now + timeout),
+            idle: true,
+        });
+
+        Serving {
+            conn,
+            streams: FuturesUnordered::new(),
+            pinger,
+            enforcer: PingEnforcer::new(config.ping_policy.clone(), pings),
+            idle,
+            age: config.max_connection_age.map(|age| Delay::new(now + age)),
+            age_grace: config.max_connection_age_grace,
+            watch,
+            signaled: false,
+            closing: Closing::Open,
+        }
+    }
+
+    fn poll(&mut self, service: &mut S) -> Poll<(), Error> {
+        if let Async::Ready(()) = self.poll_accept(service)? {
+            trace!("connection closed");
+            return Ok(Async::Ready(()));
+        }
+
+        // Drive in-flight responses.
+        loop {
+            match self.streams.poll() {
+                Ok(Async::Ready(Some(()))) => {}
+                Ok(Async::Ready(None)) | Ok(Async::NotReady) => break,
+                Err(never) => match never {},
+            }
+        }
+
+        let is_idle = self.streams.is_empty();
+
+        if let Some(ref mut pinger) = self.pinger {
+            pinger.poll(is_idle)?;
+        }
+
+        if self.poll_limits(is_idle)? {
+            // The connection must be polled again to send the `GOAWAY`.
+            task::current().notify();
+        }
+
+        Ok(Async::NotReady)
+    }
+
+    /// Accepts new streams while the service is ready, driving the connection.
+    fn poll_accept(&mut self, service: &mut S) -> Poll<(), Error> {
+        loop {
+            match service.poll_ready() {
+                Ok(Async::Ready(())) => {}
+                Ok(Async::NotReady) => {
+                    // Keep the connection moving; new streams are queued by
+                    // `h2` until the service can accept them.
+                    return self.conn.poll_close().map_err(Into::into);
+                }
+                Err(e) => return Err(Error::service(e)),
+            }
+
+            let (request, respond) = match try_ready!(self.conn.poll()) {
+                Some(accepted) => accepted,
+                None => return Ok(Async::Ready(())),
+            };
+
+            trace!("accepted stream; path={}", request.uri().path());
+            self.enforcer.reset_strikes();
+
+            let request = request.map(|body| BoxBody::map_from(RecvBody::new(body)));
+            let future = service.call(request);
+
+            self.streams.push(Respond {
+                state: RespondState::Pending(future, respond),
+            });
+        }
+    }
+
+    /// Applies shutdown, idle, age and ping limits, returning `true` if the
+    /// connection must be polled again to make progress on closing.
+    fn poll_limits(&mut self, is_idle: bool) -> Result<bool, Error> {
+        let mut closing = false;
+
+        if let Closing::Closed = self.closing {
+            return Ok(false);
+        }
+
+        if self.enforcer.check(is_idle) {
+            debug!("client sent too many pings; closing connection");
+            self.conn.abrupt_shutdown(h2::Reason::ENHANCE_YOUR_CALM);
+            self.closing = Closing::Closed;
+            return Ok(true);
+        }
+
+        if let Some(deadline) = self.poll_drain(is_idle)? {
+            if let Closing::Open = self.closing {
+                self.conn.graceful_shutdown();
+            }
+
+            // Keep the earliest deadline if the connection is already draining.
+            let deadline = match (self.closing.deadline(), deadline) {
+                (Some(a), Some(b)) => Some(cmp::min(a, b)),
+                (a, b) => a.or(b),
+            };
+
+            self.closing = Closing::Draining(deadline.map(Delay::new));
+            closing = true;
+        }
+
+        if let Closing::Draining(Some(ref mut deadline)) = self.closing {
+            if let Async::Ready(()) = deadline.poll()? {
+                debug!("cancelling {} in-flight streams", self.streams.len());
+                let status = Status::new(Code::Unavailable, "connection shutting down");
+
+                for respond in self.streams.iter_mut() {
+                    respond.cancel(&status);
+                }
+
+                self.closing = Closing::Cancelled(Delay::new(Instant::now() + CANCEL_LINGER));
+                closing = true;
+            }
+        }
+
+        if let Closing::Cancelled(ref mut linger) = self.closing {
+            if let Async::Ready(()) = linger.poll()? {
+                debug!("cancelled streams were not closed; closing connection");
+                self.conn.abrupt_shutdown(h2::Reason::NO_ERROR);
+                self.closing = Closing::Closed;
+                closing = true;
+            }
+        }
+
+        Ok(closing)
+    }
+
+    /// Returns `Some` with an optional deadline for in-flight streams if the
+    /// connection should start draining.
+    fn poll_drain(&mut self, is_idle: bool) -> Result<Option<Option<Instant>>, Error> {
+        if let Some(ref mut watch) = self.watch {
+            if !self.signaled {
+                if let Async::Ready(deadline) = watch.poll_deadline() {
+                    debug!("shutdown signaled; draining");
+                    self.signaled = true;
+                    return Ok(Some(Some(deadline)));
+                }
+            }
+        }
+
+        if let Some(mut age) = self.age.take() {
+            if let Async::Ready(()) = age.poll()? {
+                debug!("connection reached its maximum age; draining");
+                let deadline = self.age_grace.map(|grace| Instant::now() + grace);
+                return Ok(Some(deadline));
+            }
+
+            self.age = Some(age);
+        }
+
+        if let Closing::Open = self.closing {
+            if let Some(ref mut idle) = self.idle {
+                if idle.poll(is_idle)? {
+                    debug!("connection idle for {:?}; draining", idle.timeout);
+                    return Ok(Some(None));
+                }
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+// ===== impl Closing =====
+
+impl Closing {
+    fn deadline(&self) -> Option<Instant> {
+        match *self {
+            Closing::Draining(Some(ref delay)) => Some(delay.deadline()),
+            _ => None,
+        }
+    }
+}
+
+// ===== impl IdleTimer =====
+
+impl IdleTimer {
+    /// Returns `true` once the connection has been idle for the timeout.
+    fn poll(&mut self, is_idle: bool) -> Result<bool, Error> {
+        if !is_idle {
+            self.idle = false;
+            return Ok(false);
+        }
+
+        if !self.idle {
+            self.idle = true;
+            self.delay.reset(Instant::now() + self.timeout);
+        }
+
+        Ok(self.delay.poll()?.is_ready())
+    }
+}
+
+// ===== impl Respond =====
+
+impl<F, B> Respond<F, B>
+where
+    B: Body,
+{
+    /// Ends the stream early, responding with `status`.
+    fn cancel(&mut self, status: &Status) {
+        match self.state {
+            RespondState::Pending(_, ref mut respond) => send_status(respond, status),
+            RespondState::Streaming(ref mut body) => body.cancel(status),
+            RespondState::Done => {}
+        }
+
+        self.state = RespondState::Done;
+    }
+}
+
+impl<F, B> Future for Respond<F, B>
+where
+    F: Future<Item = http::Response<B>>,
+    F::Error: Into<crate::error::Error>,
+    B: Body,
+{
+    type Item = ();
+    type Error = Never;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        loop {
+            let next = match self.state {
+                RespondState::Pending(ref mut future, ref mut respond) => {
+                    if let Ok(Async::Ready(reason)) = respond.poll_reset() {
+                        trace!("stream reset by client before responding: {:?}", reason);
+                        return Ok(Async::Ready(()));
+                    }
+
+                    let response = match future.poll() {
+                        Ok(Async::Ready(response)) => response,
+                        Ok(Async::NotReady) => return Ok(Async::NotReady),
+                        Err(e) => {
+                            let status = Status::map_error(e);
+                            debug!("service failed to respond: {:?}", status);
+                            send_status(respond, &status);
+                            return Ok(Async::Ready(()));
+                        }
+                    };
+
+                    let (parts, body) = response.into_parts();
+                    let eos = body.is_end_stream();
+
+                    let stream =
+                        match respond.send_response(http::Response::from_parts(parts, ()), eos) {
+                            Ok(stream) => stream,
+                            Err(e) => {
+                                debug!("failed to send response: {}", e);
+                                return Ok(Async::Ready(()));
+                            }
+                        };
+
+                    if eos {
+                        return Ok(Async::Ready(()));
+                    }
+
+                    SendBody::new(body, stream)
+                }
+                RespondState::Streaming(ref mut body) => {
+                    return match body.poll() {
+                        Ok(Async::NotReady) => Ok(Async::NotReady),
+                        Ok(Async::Ready(())) => Ok(Async::Ready(())),
+                        Err(e) => {
+                            debug!("failed to send response body: {}", e);
+                            body.send_reset(h2::Reason::INTERNAL_ERROR);
+                            Ok(Async::Ready(()))
+                        }
+                    };
+                }
+                RespondState::Done => return Ok(Async::Ready(())),
+            };
+
+            self.state = RespondState::Streaming(next);
+        }
+    }
+}
+
+/// Sends a trailers-only response carrying `status`.
+fn send_status<B>(respond: &mut h2::server::SendResponse<B>, status: &Status)
+where
+    B: bytes::IntoBuf,
+{
+    let mut response = http::Response::new(());
+    response
+        .headers_mut()
+        .insert(CONTENT_TYPE, HeaderValue::from_static("application/grpc"));
+
+    if let Err(e) = status.add_header(response.headers_mut()) {
+        debug!("failed to encode status: {:?}", e);
+        respond.send_reset(h2::Reason::INTERNAL_ERROR);
+        return;
+    }
+
+    if let Err(e) = respond.send_response(response, true) {
+        debug!("failed to send status: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{shutdown, Builder};
+    use crate::{BoxBody, Code, Status};
+
+    use bytes::Bytes;
+    use futures::sync::mpsc;
+    use futures::{future, Async, Future, Poll, Stream};
+    use std::io;
+    use std::time::Duration;
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio::runtime::Runtime;
+    use tower_service::Service;
+
+    /// Never completes its response, reporting each call on `called`.
+    #[derive(Clone)]
+    struct Hang {
+        /// Whether to send response headers, and hang on the body instead.
+        streaming: bool,
+        called: mpsc::UnboundedSender<()>,
+    }
+
+    /// A response body that never ends.
+    struct Pending;
+
+    impl Service<http::Request<BoxBody>> for Hang {
+        type Response = http::Response<Pending>;
+        type Error = Status;
+        type Future = Box<dyn Future<Item = Self::Response, Error = Status> + Send>;
+
+        fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+            Ok(Async::Ready(()))
+        }
+
+        fn call(&mut self, _: http::Request<BoxBody>) -> Self::Future {
+            let _ = self.called.unbounded_send(());
+
+            if self.streaming {
+                Box::new(future::ok(http::Response::new(Pending)))
+            } else {
+                Box::new(future::empty())
+            }
+        }
+    }
+
+    impl http_body::Body for Pending {
+        type Data = io::Cursor<Bytes>;
+        type Error = Status;
+
+        fn poll_data(&mut self) -> Poll<Option<Self::Data>, Self::Error> {
+            Ok(Async::NotReady)
+        }
+
+        fn poll_trailers(&mut self) -> Poll<Option<http::HeaderMap>, Self::Error> {
+            Ok(Async::NotReady)
+        }
+    }
+
+    /// Sends a request to a server hanging on it, then shuts the server down
+    /// with a short grace period, returning the status the client receives.
+    fn status_after_grace(streaming: bool) -> Status {
+        let mut rt = Runtime::new().unwrap();
+
+        let listener = TcpListener::bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let (signal, watch) = shutdown();
+        let (called_tx, called_rx) = mpsc::unbounded();
+        let service = Hang {
+            streaming,
+            called: called_tx,
+        };
+
+        let serve = listener
+            .incoming()
+            .into_future()
+            .map_err(|(e, _)| panic!("accept error: {}", e))
+            .and_then(move |(io, _)| {
+                Builder::new()
+                    .serve_connection(io.unwrap(), service)
+                    .with_shutdown(watch)
+                    .map_err(|e| panic!("connection error: {}", e))
+            });
+        rt.spawn(serve);
+
+        let client = TcpStream::connect(&addr)
+            .map_err(|e| panic!("connect error: {}", e))
+            .and_then(|io| h2::client::handshake(io).map_err(|e| panic!("handshake error: {}", e)));
+        let (mut send_request, conn) = rt.block_on(client).unwrap();
+        rt.spawn(conn.map_err(|_| ()));
+
+        let request = http::Request::post("http://test/test.Hang/Call")
+            .header("content-type", "application/grpc")
+            .body(())
+            .unwrap();
+        let (response, _) = send_request.send_request(request, true).unwrap();
+
+        // Wait for the stream to be in flight before shutting down.
+        rt.block_on(called_rx.into_future().map_err(|_| ()))
+            .unwrap();
+        let drained = signal.drain(Duration::from_millis(50));
+
+        let response = rt.block_on(response).unwrap();
+        let status = match Status::from_header_map(response.headers()) {
+            Some(status) => status,
+            None => {
+                let mut body = response.into_body();
+                let trailers = future::poll_fn(move || {
+                    while futures::try_ready!(body.poll()).is_some() {}
+                    body.poll_trailers()
+                });
+
+                let trailers = rt.block_on(trailers).unwrap().expect("trailers");
+                Status::from_header_map(&trailers).expect("grpc-status")
+            }
+        };
+
+        // Once its streams are cancelled, the connection closes.
+        rt.block_on(drained).unwrap();
+
+        status
+    }
+
+    #[test]
+    fn grace_period_cancels_pending_responses() {
+        let status = status_after_grace(false);
+        assert_eq!(status.code(), Code::Unavailable);
+    }
+
+    #[test]
+    fn grace_period_cancels_streaming_responses() {
+        let status = status_after_grace(true);
+        assert_eq!(status.code(), Code::Unavailable);
+    }
+}
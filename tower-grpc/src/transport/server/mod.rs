@@ -0,0 +1,131 @@
+//! Serving gRPC over HTTP/2 connections.
+
+mod conn;
+mod router;
+mod serve;
+mod shutdown;
+
+pub use self::conn::Connection;
+#[cfg(unix)]
+pub use self::serve::UnixIncoming;
+pub use self::serve::{Serve, Server, TcpIncoming};
+pub use self::shutdown::{shutdown, Drained, Signal, Signaled, Watch};
+
+use super::keepalive::{KeepAlive, PingPolicy};
+use crate::body::{Body, BoxBody};
+use crate::generic::client::GrpcService;
+pub use crate::generic::server::NamedService;
+
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite};
+
+/// Configures and serves HTTP/2 connections.
+#[derive(Clone, Debug)]
+pub struct Builder {
+    h2: h2::server::Builder,
+    config: Config,
+}
+
+/// Connection settings that are managed by the transport rather than `h2`.
+#[derive(Clone, Debug)]
+pub(crate) struct Config {
+    keepalive: Option<KeepAlive>,
+    ping_policy: PingPolicy,
+    max_connection_idle: Option<Duration>,
+    max_connection_age: Option<Duration>,
+    max_connection_age_grace: Option<Duration>,
+}
+
+impl Builder {
+    /// Returns a new `Builder` with default values.
+    pub fn new() -> Self {
+        Builder {
+            h2: h2::server::Builder::new(),
+            config: Config {
+                keepalive: None,
+                ping_policy: PingPolicy::default(),
+                max_connection_idle: None,
+                max_connection_age: None,
+                max_connection_age_grace: None,
+            },
+        }
+    }
+
+    /// Set the initial flow control window size of each stream.
+    pub fn initial_stream_window_size(&mut self, size: u32) -> &mut Self {
+        self.h2.initial_window_size(size);
+        self
+    }
+
+    /// Set the initial flow control window size of each connection.
+    pub fn initial_connection_window_size(&mut self, size: u32) -> &mut Self {
+        self.h2.initial_connection_window_size(size);
+        self
+    }
+
+    /// Set the maximum number of concurrent streams a client may open on a
+    /// connection.
+    pub fn max_concurrent_streams(&mut self, max: u32) -> &mut Self {
+        self.h2.max_concurrent_streams(max);
+        self
+    }
+
+    /// Set the largest frame payload the server is willing to receive.
+    pub fn max_frame_size(&mut self, max: u32) -> &mut Self {
+        self.h2.max_frame_size(max);
+        self
+    }
+
+    /// Send keepalive pings to clients.
+    ///
+    /// Clients that fail to acknowledge a ping in time are disconnected.
+    pub fn keepalive(&mut self, keepalive: KeepAlive) -> &mut Self {
+        self.config.keepalive = Some(keepalive);
+        self
+    }
+
+    /// Set the policy enforced on keepalive pings sent by clients.
+    pub fn ping_policy(&mut self, policy: PingPolicy) -> &mut Self {
+        self.config.ping_policy = policy;
+        self
+    }
+
+    /// Gracefully close connections that have had no active streams for
+    /// `idle`.
+    pub fn max_connection_idle(&mut self, idle: Duration) -> &mut Self {
+        self.config.max_connection_idle = Some(idle);
+        self
+    }
+
+    /// Gracefully close connections once they have been open for `age`.
+    pub fn max_connection_age(&mut self, age: Duration) -> &mut Self {
+        self.config.max_connection_age = Some(age);
+        self
+    }
+
+    /// Set how long in-flight streams may run once a connection has reached
+    /// its maximum age, after which they are cancelled with
+    /// `Code::Unavailable`.
+    ///
+    /// By default, streams are allowed to complete.
+    pub fn max_connection_age_grace(&mut self, grace: Duration) -> &mut Self {
+        self.config.max_connection_age_grace = Some(grace);
+        self
+    }
+
+    /// Serve a single connection on `io`, dispatching requests to `service`.
+    pub fn serve_connection<I, S>(&self, io: I, service: S) -> Connection<I, S>
+    where
+        I: AsyncRead + AsyncWrite,
+        S: GrpcService<BoxBody>,
+        <S::ResponseBody as Body>::Data: 'static,
+    {
+        Connection::new(&self.h2, self.config.clone(), io, service)
+    }
+}
+
+impl Default for Builder {
+    fn default() -> Self {
+        Builder::new()
+    }
+}
@@ -0,0 +1,426 @@
+use crate::body::{Body, BoxBody, HttpBody};
+use crate::error::Error;
+use crate::generic::server::NamedService;
+use crate::transport::channelz::{Call, Calls, Counted, SocketEntry, Tracked};
+#[cfg(feature = "tls")]
+use crate::transport::PeerCertificates;
+use crate::{Code, Status};
+
+use futures::sync::oneshot;
+use futures::{future, Async, Future, Poll};
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tower_service::Service;
+
+type BoxFuture = Box<dyn Future<Item = http::Response<BoxBody>, Error = Error> + Send>;
+
+/// Dispatches requests to services by the service name in the request path.
+#[derive(Default)]
+pub(crate) struct Routes {
+    routes: HashMap<&'static str, Box<dyn Route>>,
+    /// The address of the client the routes serve a connection for.
+    remote_addr: Option<SocketAddr>,
+    /// The certificates of the client the routes serve a connection for.
+    #[cfg(feature = "tls")]
+    peer_certificates: Option<PeerCertificates>,
+    /// Where the calls on the connection are reported to channelz.
+    channelz: Option<(Arc<Calls>, Arc<SocketEntry>)>,
+}
+
+/// A type-erased service that can be cloned for each connection.
+trait Route: Send {
+    /// Calls the service once it is ready, without blocking other routes
+    /// while it is not.
+    fn call(&mut self, request: http::Request<BoxBody>) -> BoxFuture;
+
+    /// Calls the service with the requests queued while it was not ready.
+    fn poll_queued(&mut self);
+
+    fn clone_route(&self) -> Box<dyn Route>;
+}
+
+struct BoxRoute<S> {
+    service: S,
+    /// Requests waiting for `service` to become ready, in arrival order.
+    queued: VecDeque<(http::Request<BoxBody>, oneshot::Sender<BoxFuture>)>,
+}
+
+// ===== impl Routes =====
+
+impl Routes {
+    /// Route requests for `S::NAME` to `service`, replacing any service
+    /// previously added under that name.
+    pub(crate) fn add<S, B>(&mut self, service: S)
+    where
+        S: NamedService + Service<http::Request<BoxBody>, Response = http::Response<B>>,
+        S: Clone + Send + 'static,
+        S::Future: Send + 'static,
+        S::Error: Into<Error>,
+        B: HttpBody<Data = <BoxBody as Body>::Data, Error = Status> + Send + 'static,
+    {
+        self.routes
+            .insert(S::NAME, Box::new(BoxRoute::new(service)));
+    }
+
+    /// Attach `addr` to every request routed.
+    pub(crate) fn with_remote_addr(mut self, addr: Option<SocketAddr>) -> Self {
+        self.remote_addr = addr;
+        self
+    }
+
+    /// Attach `peer` to every request routed.
+    #[cfg(feature = "tls")]
+    pub(crate) fn with_peer_certificates(mut self, peer: Option<PeerCertificates>) -> Self {
+        self.peer_certificates = peer;
+        self
+    }
+
+    /// Report every request routed as a call received by the server on
+    /// `socket`.
+    pub(crate) fn with_channelz(mut self, calls: Arc<Calls>, socket: Arc<SocketEntry>) -> Self {
+        self.channelz = Some((calls, socket));
+        self
+    }
+}
+
+impl Service<http::Request<BoxBody>> for Routes {
+    type Response = http::Response<BoxBody>;
+    type Error = Error;
+    type Future = BoxFuture;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        // The service a request is routed to is not known until it is called,
+        // so readiness is awaited per route instead, and requests for a route
+        // that is not ready are queued until it is.
+        for route in self.routes.values_mut() {
+            route.poll_queued();
+        }
+
+        Ok(Async::Ready(()))
+    }
+
+    fn call(&mut self, mut request: http::Request<BoxBody>) -> Self::Future {
+        if let Some(addr) = self.remote_addr {
+            request.extensions_mut().insert(addr);
+        }
+
+        #[cfg(feature = "tls")]
+        {
+            if let Some(ref peer) = self.peer_certificates {
+                request.extensions_mut().insert(peer.clone());
+            }
+        }
+
+        let mut call = None;
+
+        if let Some((ref calls, ref socket)) = self.channelz {
+            call = Some(Call::server(calls.clone(), socket.clone()));
+
+            let socket = socket.clone();
+            request = request.map(|body| BoxBody::new(Box::new(Counted::received(body, socket))));
+        }
+
+        let name = service_name(request.uri().path());
+
+        let future = match self.routes.get_mut(name) {
+            Some(route) => route.call(request),
+            None => {
+                let status = Status::new(Code::Unimplemented, format!("unknown service: {}", name));
+                Box::new(future::err(status.into()))
+            }
+        };
+
+        match call {
+            Some(call) => Box::new(track(future, call)),
+            None => future,
+        }
+    }
+}
+
+impl Clone for Routes {
+    fn clone(&self) -> Self {
+        let routes = self
+            .routes
+            .iter()
+            .map(|(name, route)| (*name, route.clone_route()))
+            .collect();
+
+        Routes {
+            routes,
+            remote_addr: self.remote_addr,
+            #[cfg(feature = "tls")]
+            peer_certificates: self.peer_certificates.clone(),
+            channelz: self.channelz.clone(),
+        }
+    }
+}
+
+impl fmt::Debug for Routes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.routes.keys()).finish()
+    }
+}
+
+// ===== impl BoxRoute =====
+
+impl<S, B> BoxRoute<S>
+where
+    S: Service<http::Request<BoxBody>, Response = http::Response<B>>,
+    S::Future: Send + 'static,
+    S::Error: Into<Error>,
+    B: HttpBody<Data = <BoxBody as Body>::Data, Error = Status> + Send + 'static,
+{
+    fn new(service: S) -> Self {
+        BoxRoute {
+            service,
+            queued: VecDeque::new(),
+        }
+    }
+
+    /// Calls the service, which must be ready.
+    fn dispatch(&mut self, request: http::Request<BoxBody>) -> BoxFuture {
+        let future = self
+            .service
+            .call(request)
+            .map(|response| response.map(|body| BoxBody::new(Box::new(body))))
+            .map_err(Into::into);
+
+        Box::new(future)
+    }
+}
+
+impl<S, B> Route for BoxRoute<S>
+where
+    S: Service<http::Request<BoxBody>, Response = http::Response<B>>,
+    S: Clone + Send + 'static,
+    S::Future: Send + 'static,
+    S::Error: Into<Error>,
+    B: HttpBody<Data = <BoxBody as Body>::Data, Error = Status> + Send + 'static,
+{
+    fn call(&mut self, request: http::Request<BoxBody>) -> BoxFuture {
+        if self.queued.is_empty() {
+            match self.service.poll_ready() {
+                Ok(Async::Ready(())) => return self.dispatch(request),
+                Ok(Async::NotReady) => {}
+                Err(e) => return Box::new(future::err(e.into())),
+            }
+        }
+
+        // Wait for this instance of the service, rather than a clone, to
+        // become ready, so that readiness it tracks itself is respected.
+        let (tx, rx) = oneshot::channel();
+        self.queued.push_back((request, tx));
+
+        let future = rx
+            .map_err(|_| Error::from(Status::new(Code::Unavailable, "service dropped")))
+            .and_then(|future| future);
+
+        Box::new(future)
+    }
+
+    fn poll_queued(&mut self) {
+        while let Some((_, tx)) = self.queued.front() {
+            if tx.is_canceled() {
+                self.queued.pop_front();
+                continue;
+            }
+
+            match self.service.poll_ready() {
+                Ok(Async::Ready(())) => {}
+                Ok(Async::NotReady) => return,
+                Err(e) => {
+                    let e: Error = e.into();
+                    let status = Status::from_error(&*e);
+
+                    for (_, tx) in self.queued.drain(..) {
+                        let _ = tx.send(Box::new(future::err(status.clone().into())));
+                    }
+
+                    return;
+                }
+            }
+
+            let (request, tx) = self.queued.pop_front().expect("queued request");
+            let _ = tx.send(self.dispatch(request));
+        }
+    }
+
+    fn clone_route(&self) -> Box<dyn Route> {
+        Box::new(BoxRoute::new(self.service.clone()))
+    }
+}
+
+/// Ends `call` once the response has been sent.
+fn track(
+    future: BoxFuture,
+    mut call: Call,
+) -> impl Future<Item = http::Response<BoxBody>, Error = Error> {
+    future.then(move |result| match result {
+        Ok(response) => {
+            // Trailers-only responses carry their status in the headers.
+            if let Some(status) = Status::from_header_map(response.headers()) {
+                call.finish(Some(status.code()));
+            }
+
+            Ok(response.map(|body| BoxBody::new(Box::new(Tracked::new(body, call)))))
+        }
+        Err(e) => {
+            call.finish(Some(Status::from_error(&*e).code()));
+            Err(e)
+        }
+    })
+}
+
+/// Returns the service name of a gRPC request path, `/{service}/{method}`.
+fn service_name(path: &str) -> &str {
+    let path = path.trim_start_matches('/');
+
+    match path.find('/') {
+        Some(end) => &path[..end],
+        None => path,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::Empty;
+
+    /// Responds with `Code::NotFound`, once `ready`.
+    #[derive(Clone)]
+    struct Svc<N> {
+        ready: bool,
+        _name: N,
+    }
+
+    #[derive(Clone)]
+    struct Busy;
+
+    #[derive(Clone)]
+    struct Idle;
+
+    impl NamedService for Svc<Busy> {
+        const NAME: &'static str = "test.Busy";
+    }
+
+    impl NamedService for Svc<Idle> {
+        const NAME: &'static str = "test.Idle";
+    }
+
+    /// Responds with `Code::NotFound` to as many requests as it has permits
+    /// for, tracking its permits in each clone.
+    #[derive(Clone)]
+    struct Limited {
+        permits: usize,
+    }
+
+    impl NamedService for Limited {
+        const NAME: &'static str = "test.Limited";
+    }
+
+    impl Service<http::Request<BoxBody>> for Limited {
+        type Response = http::Response<BoxBody>;
+        type Error = Status;
+        type Future = future::FutureResult<Self::Response, Self::Error>;
+
+        fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+            if self.permits > 0 {
+                Ok(Async::Ready(()))
+            } else {
+                Ok(Async::NotReady)
+            }
+        }
+
+        fn call(&mut self, _: http::Request<BoxBody>) -> Self::Future {
+            assert!(self.permits > 0, "called without a permit");
+            self.permits -= 1;
+            future::err(Status::new(Code::NotFound, ""))
+        }
+    }
+
+    impl<N> Service<http::Request<BoxBody>> for Svc<N> {
+        type Response = http::Response<BoxBody>;
+        type Error = Status;
+        type Future = future::FutureResult<Self::Response, Self::Error>;
+
+        fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+            if self.ready {
+                Ok(Async::Ready(()))
+            } else {
+                Ok(Async::NotReady)
+            }
+        }
+
+        fn call(&mut self, _: http::Request<BoxBody>) -> Self::Future {
+            assert!(self.ready, "called before ready");
+            future::err(Status::new(Code::NotFound, ""))
+        }
+    }
+
+    fn request(path: &str) -> http::Request<BoxBody> {
+        let body = BoxBody::new(Box::new(Empty));
+        http::Request::post(path).body(body).unwrap()
+    }
+
+    #[test]
+    fn unready_route_does_not_block_others() {
+        let mut routes = Routes::default();
+        routes.add(Svc {
+            ready: false,
+            _name: Busy,
+        });
+        routes.add(Svc {
+            ready: true,
+            _name: Idle,
+        });
+
+        future::lazy(move || {
+            assert!(routes.poll_ready().unwrap().is_ready());
+
+            let mut busy = routes.call(request("/test.Busy/Call"));
+            assert!(busy.poll().unwrap().is_not_ready());
+
+            let idle = routes.call(request("/test.Idle/Call")).poll();
+            let status = Status::from_error(&*idle.expect_err("idle route responds"));
+            assert_eq!(status.code(), Code::NotFound);
+
+            Ok::<_, ()>(())
+        })
+        .wait()
+        .unwrap();
+    }
+
+    #[test]
+    fn unready_route_is_not_cloned() {
+        let mut routes = Routes::default();
+        routes.add(Limited { permits: 1 });
+
+        future::lazy(move || {
+            let first = routes.call(request("/test.Limited/Call")).poll();
+            let status = Status::from_error(&*first.expect_err("route responds"));
+            assert_eq!(status.code(), Code::NotFound);
+
+            assert!(routes.poll_ready().unwrap().is_ready());
+            let mut second = routes.call(request("/test.Limited/Call"));
+
+            assert!(routes.poll_ready().unwrap().is_ready());
+            assert!(second.poll().unwrap().is_not_ready());
+
+            Ok::<_, ()>(())
+        })
+        .wait()
+        .unwrap();
+    }
+
+    #[test]
+    fn service_name_from_path() {
+        assert_eq!(
+            service_name("/helloworld.Greeter/SayHello"),
+            "helloworld.Greeter"
+        );
+        assert_eq!(service_name("/helloworld.Greeter"), "helloworld.Greeter");
+        assert_eq!(service_name("/"), "");
+    }
+}
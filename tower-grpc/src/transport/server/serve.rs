@@ -0,0 +1,708 @@
+use super::router::Routes;
+use super::{shutdown, Builder, Drained, Signal, Watch};
+use crate::body::{Body, BoxBody, HttpBody};
+use crate::generic::server::NamedService;
+use crate::transport::channelz::{self, Address, Channelz, ServerEntry, SocketEntry};
+#[cfg(feature = "tls")]
+use crate::transport::tls::TlsAcceptor;
+#[cfg(feature = "tls")]
+use crate::transport::ServerTlsConfig;
+use crate::transport::{Error, KeepAlive, PingPolicy};
+use crate::Status;
+
+use futures::future::Empty;
+use futures::{future, try_ready, Async, Future, Poll, Stream};
+use log::{debug, error, trace};
+use std::any::Any;
+#[cfg(unix)]
+use std::fs;
+use std::net::SocketAddr;
+#[cfg(unix)]
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use std::{fmt, io};
+use tokio::executor::{DefaultExecutor, Executor};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::{tcp, TcpListener, TcpStream};
+#[cfg(unix)]
+use tokio::net::{unix, UnixListener, UnixStream};
+use tokio::timer::Delay;
+use tower_service::Service;
+
+/// The default time in-flight requests are given to complete on shutdown.
+const DEFAULT_SHUTDOWN_GRACE: Duration = Duration::from_secs(10);
+
+/// How long to wait before accepting again once the process has run out of
+/// file descriptors or memory.
+const ACCEPT_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Serves one or more gRPC services.
+///
+/// ```ignore
+/// let serve = Server::builder()
+///     .add_service(GreeterServer::new(Greet))
+///     .serve(addr);
+/// ```
+#[derive(Clone, Debug)]
+pub struct Server {
+    builder: Builder,
+    routes: Routes,
+    tcp_nodelay: bool,
+    shutdown_grace: Duration,
+    channelz: Option<Channelz>,
+    #[cfg(feature = "tls")]
+    tls: Option<TlsAcceptor>,
+}
+
+/// A future that accepts and serves connections.
+///
+/// Completes once the listener is exhausted, or once the shutdown signal has
+/// completed and all connections have been drained.
+pub struct Serve<I, F> {
+    incoming: Option<I>,
+    /// Set while accepting is paused after running out of resources.
+    accept_backoff: Option<Delay>,
+    builder: Builder,
+    routes: Routes,
+    shutdown: Option<Shutdown<F>>,
+    drained: Option<Drained>,
+    channelz: Option<ServerChannelz>,
+    #[cfg(feature = "tls")]
+    tls: Option<TlsAcceptor>,
+}
+
+/// Accepts TCP connections, configuring each accepted socket.
+#[derive(Debug)]
+pub struct TcpIncoming {
+    inner: Option<tcp::Incoming>,
+    bind_error: Option<io::Error>,
+    local_addr: Option<SocketAddr>,
+    nodelay: bool,
+}
+
+/// Accepts Unix domain socket connections.
+#[cfg(unix)]
+#[derive(Debug)]
+pub struct UnixIncoming {
+    inner: Option<unix::Incoming>,
+    bind_error: Option<io::Error>,
+    path: PathBuf,
+}
+
+/// The channelz entities of a server, registered while it serves.
+struct ServerChannelz {
+    server: channelz::Handle<ServerEntry>,
+    _listen_socket: channelz::Handle<SocketEntry>,
+}
+
+struct Shutdown<F> {
+    future: F,
+    signal: Signal,
+    watch: Watch,
+    grace: Duration,
+}
+
+// ===== impl Server =====
+
+impl Server {
+    /// Returns a new `Server` with default settings and no services.
+    pub fn builder() -> Self {
+        Server {
+            builder: Builder::new(),
+            routes: Routes::default(),
+            tcp_nodelay: true,
+            shutdown_grace: DEFAULT_SHUTDOWN_GRACE,
+            channelz: None,
+            #[cfg(feature = "tls")]
+            tls: None,
+        }
+    }
+
+    /// Set the initial flow control window size of each stream.
+    pub fn initial_stream_window_size(mut self, size: u32) -> Self {
+        self.builder.initial_stream_window_size(size);
+        self
+    }
+
+    /// Set the initial flow control window size of each connection.
+    pub fn initial_connection_window_size(mut self, size: u32) -> Self {
+        self.builder.initial_connection_window_size(size);
+        self
+    }
+
+    /// Set the maximum number of concurrent streams a client may open on a
+    /// connection.
+    pub fn max_concurrent_streams(mut self, max: u32) -> Self {
+        self.builder.max_concurrent_streams(max);
+        self
+    }
+
+    /// Set the largest frame payload the server is willing to receive.
+    pub fn max_frame_size(mut self, max: u32) -> Self {
+        self.builder.max_frame_size(max);
+        self
+    }
+
+    /// Send keepalive pings to clients.
+    pub fn keepalive(mut self, keepalive: KeepAlive) -> Self {
+        self.builder.keepalive(keepalive);
+        self
+    }
+
+    /// Set the policy enforced on keepalive pings sent by clients.
+    pub fn ping_policy(mut self, policy: PingPolicy) -> Self {
+        self.builder.ping_policy(policy);
+        self
+    }
+
+    /// Gracefully close connections that have had no active streams for
+    /// `idle`.
+    pub fn max_connection_idle(mut self, idle: Duration) -> Self {
+        self.builder.max_connection_idle(idle);
+        self
+    }
+
+    /// Gracefully close connections once they have been open for `age`.
+    pub fn max_connection_age(mut self, age: Duration) -> Self {
+        self.builder.max_connection_age(age);
+        self
+    }
+
+    /// Set how long in-flight streams may run once a connection has reached
+    /// its maximum age.
+    pub fn max_connection_age_grace(mut self, grace: Duration) -> Self {
+        self.builder.max_connection_age_grace(grace);
+        self
+    }
+
+    /// Set whether `TCP_NODELAY` is set on accepted sockets.
+    ///
+    /// Defaults to `true`.
+    pub fn tcp_nodelay(mut self, enabled: bool) -> Self {
+        self.tcp_nodelay = enabled;
+        self
+    }
+
+    /// Set how long in-flight requests may run once shutdown is signaled,
+    /// after which they are cancelled with `Code::Unavailable`.
+    ///
+    /// Defaults to 10 seconds.
+    pub fn shutdown_grace(mut self, grace: Duration) -> Self {
+        self.shutdown_grace = grace;
+        self
+    }
+
+    /// Report the server, its sockets and the calls it handles to
+    /// `channelz`.
+    pub fn channelz(mut self, channelz: Channelz) -> Self {
+        self.channelz = Some(channelz);
+        self
+    }
+
+    /// Serve connections over TLS, negotiating HTTP/2 with ALPN.
+    ///
+    /// Returns an error if the certificates or key in `config` are invalid.
+    #[cfg(feature = "tls")]
+    pub fn tls_config(mut self, config: ServerTlsConfig) -> Result<Self, Error> {
+        self.tls = Some(config.acceptor()?);
+        Ok(self)
+    }
+
+    /// Add a service, routing requests to it by its service name.
+    ///
+    /// Each connection is served by its own clone of `service`. Requests
+    /// arriving while that clone is not ready are queued until it is, without
+    /// holding up requests for other services. To apply readiness limits
+    /// across connections, such as a rate limit, wrap `service` in one that
+    /// shares its readiness state between clones, like `tower_buffer::Buffer`.
+    ///
+    /// Adding a service with the same name as a previous one replaces it.
+    pub fn add_service<S, B>(mut self, service: S) -> Self
+    where
+        S: NamedService + Service<http::Request<BoxBody>, Response = http::Response<B>>,
+        S: Clone + Send + 'static,
+        S::Future: Send + 'static,
+        S::Error: Into<crate::error::Error>,
+        B: HttpBody<Data = <BoxBody as Body>::Data, Error = Status> + Send + 'static,
+    {
+        self.routes.add(service);
+        self
+    }
+
+    /// Bind to `addr` and serve connections until the listener fails.
+    pub fn serve(self, addr: SocketAddr) -> Serve<TcpIncoming, Empty<(), ()>> {
+        self.serve_with_shutdown(addr, future::empty())
+    }
+
+    /// Bind to `addr` and serve connections until `signal` completes.
+    ///
+    /// Once `signal` completes, successfully or not, the server stops
+    /// accepting connections and drains those that are open.
+    pub fn serve_with_shutdown<F>(self, addr: SocketAddr, signal: F) -> Serve<TcpIncoming, F>
+    where
+        F: Future,
+    {
+        let incoming = TcpIncoming::bind(&addr, self.tcp_nodelay);
+        let local = incoming.local_addr.map(Address::Tcp);
+        self.serve_listener(incoming, local, signal)
+    }
+
+    /// Bind a Unix domain socket at `path` and serve connections until the
+    /// listener fails.
+    ///
+    /// Binding fails if a file already exists at `path`.
+    #[cfg(unix)]
+    pub fn serve_unix<P>(self, path: P) -> Serve<UnixIncoming, Empty<(), ()>>
+    where
+        P: AsRef<Path>,
+    {
+        self.serve_unix_with_shutdown(path, future::empty())
+    }
+
+    /// Bind a Unix domain socket at `path` and serve connections until
+    /// `signal` completes.
+    #[cfg(unix)]
+    pub fn serve_unix_with_shutdown<P, F>(self, path: P, signal: F) -> Serve<UnixIncoming, F>
+    where
+        P: AsRef<Path>,
+        F: Future,
+    {
+        let incoming = UnixIncoming::bind(path.as_ref());
+        let local = Some(Address::Unix(incoming.path.clone()));
+        self.serve_listener(incoming, local, signal)
+    }
+
+    /// Serve connections accepted from `incoming`, such as an existing
+    /// `TcpListener` or `UnixListener`'s `incoming()` stream.
+    pub fn serve_incoming<I>(self, incoming: I) -> Serve<I, Empty<(), ()>>
+    where
+        I: Stream<Error = io::Error>,
+        I::Item: AsyncRead + AsyncWrite + Send + 'static,
+    {
+        self.serve_incoming_with_shutdown(incoming, future::empty())
+    }
+
+    /// Serve connections accepted from `incoming` until `signal` completes.
+    pub fn serve_incoming_with_shutdown<I, F>(self, incoming: I, signal: F) -> Serve<I, F>
+    where
+        F: Future,
+    {
+        self.serve_listener(incoming, None, signal)
+    }
+
+    /// Serve connections accepted from `incoming`, listening on `local` if
+    /// known.
+    fn serve_listener<I, F>(self, incoming: I, local: Option<Address>, signal: F) -> Serve<I, F>
+    where
+        F: Future,
+    {
+        let (tx, watch) = shutdown();
+        let channelz = self.channelz.map(|channelz| {
+            let server = channelz.add_server();
+            let listen_socket = server.add_listen_socket(local);
+
+            ServerChannelz {
+                server,
+                _listen_socket: listen_socket,
+            }
+        });
+
+        Serve {
+            incoming: Some(incoming),
+            accept_backoff: None,
+            builder: self.builder,
+            routes: self.routes,
+            shutdown: Some(Shutdown {
+                future: signal,
+                signal: tx,
+                watch,
+                grace: self.shutdown_grace,
+            }),
+            drained: None,
+            channelz,
+            #[cfg(feature = "tls")]
+            tls: self.tls,
+        }
+    }
+}
+
+// ===== impl Serve =====
+
+impl<I, F> Serve<I, F>
+where
+    I: Stream<Error = io::Error>,
+    I::Item: AsyncRead + AsyncWrite + Send + 'static,
+    F: Future,
+{
+    fn poll_shutdown(&mut self) -> Poll<(), Error> {
+        if let Some(mut shutdown) = self.shutdown.take() {
+            match shutdown.future.poll() {
+                Ok(Async::NotReady) => {
+                    self.shutdown = Some(shutdown);
+                    return Ok(Async::NotReady);
+                }
+                Ok(Async::Ready(_)) | Err(_) => {
+                    debug!("shutdown signaled; draining connections");
+                    // Stop accepting connections, and let go of the watch so
+                    // that the server is drained once connections close.
+                    self.incoming = None;
+                    drop(shutdown.watch);
+                    self.drained = Some(shutdown.signal.drain(shutdown.grace));
+                }
+            }
+        }
+
+        match self.drained {
+            Some(ref mut drained) => match drained.poll() {
+                Ok(ready) => Ok(ready),
+                Err(never) => match never {},
+            },
+            None => Ok(Async::NotReady),
+        }
+    }
+
+    fn poll_incoming(&mut self) -> Poll<(), Error> {
+        let incoming = match self.incoming {
+            Some(ref mut incoming) => incoming,
+            None => return Ok(Async::NotReady),
+        };
+
+        loop {
+            if let Some(ref mut backoff) = self.accept_backoff {
+                try_ready!(backoff.poll());
+            }
+            self.accept_backoff = None;
+
+            let io = match incoming.poll() {
+                Ok(Async::Ready(Some(io))) => io,
+                Ok(Async::Ready(None)) => return Ok(Async::Ready(())),
+                Ok(Async::NotReady) => return Ok(Async::NotReady),
+                Err(ref e) if is_connection_error(e) => {
+                    debug!("accepted connection already errored: {}", e);
+                    continue;
+                }
+                Err(ref e) if is_resource_error(e) => {
+                    // Connections closing may free up resources, so keep the
+                    // listener open rather than shutting the server down.
+                    error!("failed to accept connection: {}; retrying", e);
+                    self.accept_backoff = Some(Delay::new(Instant::now() + ACCEPT_BACKOFF));
+                    continue;
+                }
+                Err(e) => return Err(Error::transport(e)),
+            };
+
+            trace!("accepted connection");
+            let builder = self.builder.clone();
+            let mut routes = self.routes.clone();
+            let watch = self
+                .shutdown
+                .as_ref()
+                .map(|shutdown| shutdown.watch.clone());
+
+            let (local, remote) = channelz::addresses(&io as &dyn Any);
+            if let Some(Address::Tcp(addr)) = remote {
+                routes = routes.with_remote_addr(Some(addr));
+            }
+
+            // The socket is registered until the connection closes.
+            let socket = match self.channelz {
+                Some(ref channelz) => {
+                    let socket = channelz.server.add_socket(local, remote);
+                    let calls = channelz.server.entry().calls().clone();
+                    routes = routes.with_channelz(calls, socket.entry().clone());
+                    Some(socket)
+                }
+                None => None,
+            };
+
+            #[cfg(feature = "tls")]
+            {
+                if let Some(ref tls) = self.tls {
+                    let entry = socket.as_ref().map(|socket| socket.entry().clone());
+                    let conn = tls
+                        .accept(io)
+                        .map_err(|e| debug!("TLS handshake failed: {}", e))
+                        .and_then(move |(io, peer, info)| {
+                            if let Some(entry) = entry {
+                                entry.set_tls(info);
+                            }
+
+                            let routes = routes.with_peer_certificates(peer);
+                            serve(&builder, io, routes, watch)
+                        })
+                        .then(move |result| {
+                            drop(socket);
+                            result
+                        });
+
+                    spawn(conn)?;
+                    continue;
+                }
+            }
+
+            let conn = serve(&builder, io, routes, watch).then(move |result| {
+                drop(socket);
+                result
+            });
+
+            spawn(conn)?;
+        }
+    }
+}
+
+/// Returns a future serving `io` until it closes.
+fn serve<I>(
+    builder: &Builder,
+    io: I,
+    routes: Routes,
+    watch: Option<Watch>,
+) -> impl Future<Item = (), Error = ()>
+where
+    I: AsyncRead + AsyncWrite,
+{
+    let mut conn = builder.serve_connection(io, routes);
+
+    if let Some(watch) = watch {
+        conn = conn.with_shutdown(watch);
+    }
+
+    conn.map_err(|e| debug!("connection error: {}", e))
+}
+
+fn spawn<F>(conn: F) -> Result<(), Error>
+where
+    F: Future<Item = (), Error = ()> + Send + 'static,
+{
+    DefaultExecutor::current()
+        .spawn(Box::new(conn))
+        .map_err(Error::executor)
+}
+
+impl<I, F> Future for Serve<I, F>
+where
+    I: Stream<Error = io::Error>,
+    I::Item: AsyncRead + AsyncWrite + Send + 'static,
+    F: Future,
+{
+    type Item = ();
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        if let Async::Ready(()) = self.poll_shutdown()? {
+            debug!("server drained");
+            return Ok(Async::Ready(()));
+        }
+
+        if let Async::Ready(()) = self.poll_incoming()? {
+            debug!("listener closed");
+            return Ok(Async::Ready(()));
+        }
+
+        Ok(Async::NotReady)
+    }
+}
+
+impl<I, F> fmt::Debug for Serve<I, F>
+where
+    I: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Serve")
+            .field("incoming", &self.incoming)
+            .field("routes", &self.routes)
+            .finish()
+    }
+}
+
+// ===== impl TcpIncoming =====
+
+impl TcpIncoming {
+    /// Binds to `addr`, deferring any error until the stream is polled.
+    fn bind(addr: &SocketAddr, nodelay: bool) -> Self {
+        match TcpListener::bind(addr) {
+            Ok(listener) => TcpIncoming {
+                local_addr: listener.local_addr().ok(),
+                inner: Some(listener.incoming()),
+                bind_error: None,
+                nodelay,
+            },
+            Err(e) => TcpIncoming {
+                inner: None,
+                bind_error: Some(e),
+                local_addr: None,
+                nodelay,
+            },
+        }
+    }
+}
+
+impl Stream for TcpIncoming {
+    type Item = TcpStream;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        if let Some(e) = self.bind_error.take() {
+            return Err(e);
+        }
+
+        let inner = match self.inner {
+            Some(ref mut inner) => inner,
+            None => return Ok(Async::Ready(None)),
+        };
+
+        let socket = match try_ready!(inner.poll()) {
+            Some(socket) => socket,
+            None => return Ok(Async::Ready(None)),
+        };
+
+        if let Err(e) = socket.set_nodelay(self.nodelay) {
+            debug!("failed to set TCP_NODELAY: {}", e);
+        }
+
+        Ok(Async::Ready(Some(socket)))
+    }
+}
+
+// ===== impl UnixIncoming =====
+
+#[cfg(unix)]
+impl UnixIncoming {
+    /// Binds to `path`, deferring any error until the stream is polled.
+    fn bind(path: &Path) -> Self {
+        match UnixListener::bind(path) {
+            Ok(listener) => UnixIncoming {
+                inner: Some(listener.incoming()),
+                bind_error: None,
+                path: path.to_owned(),
+            },
+            Err(e) => UnixIncoming {
+                inner: None,
+                bind_error: Some(e),
+                path: path.to_owned(),
+            },
+        }
+    }
+}
+
+#[cfg(unix)]
+impl Drop for UnixIncoming {
+    fn drop(&mut self) {
+        // Only remove the socket file if it was bound here, so that the path
+        // can be bound again.
+        if self.inner.take().is_some() {
+            if let Err(e) = fs::remove_file(&self.path) {
+                debug!("failed to remove {}: {}", self.path.display(), e);
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+impl Stream for UnixIncoming {
+    type Item = UnixStream;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        if let Some(e) = self.bind_error.take() {
+            return Err(e);
+        }
+
+        match self.inner {
+            Some(ref mut inner) => inner.poll(),
+            None => Ok(Async::Ready(None)),
+        }
+    }
+}
+
+/// Returns whether an accept error is caused by the process running out of
+/// file descriptors or memory.
+fn is_resource_error(e: &io::Error) -> bool {
+    #[cfg(unix)]
+    {
+        match e.raw_os_error() {
+            Some(libc::EMFILE) | Some(libc::ENFILE) | Some(libc::ENOBUFS) | Some(libc::ENOMEM) => {
+                true
+            }
+            _ => false,
+        }
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = e;
+        false
+    }
+}
+
+/// Returns whether an accept error only affects the accepted connection,
+/// rather than the listener.
+fn is_connection_error(e: &io::Error) -> bool {
+    match e.kind() {
+        io::ErrorKind::ConnectionRefused
+        | io::ErrorKind::ConnectionAborted
+        | io::ErrorKind::ConnectionReset => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Code;
+
+    use futures::stream;
+    use tokio::runtime::Runtime;
+
+    #[cfg(unix)]
+    #[test]
+    fn backs_off_when_out_of_file_descriptors() {
+        let mut rt = Runtime::new().unwrap();
+
+        let listener = TcpListener::bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let emfile = io::Error::from_raw_os_error(libc::EMFILE);
+        let incoming = stream::once(Err(emfile)).chain(listener.incoming());
+
+        let serve = Server::builder()
+            .serve_incoming(incoming)
+            .map_err(|e| panic!("server error: {}", e));
+        rt.spawn(serve);
+
+        let response = TcpStream::connect(&addr)
+            .map_err(|e| panic!("connect error: {}", e))
+            .and_then(|io| h2::client::handshake(io).map_err(|e| panic!("handshake error: {}", e)))
+            .and_then(|(mut send_request, conn)| {
+                tokio::spawn(conn.map_err(|_| ()));
+
+                let request = http::Request::post("http://test/test.Missing/Call")
+                    .header("content-type", "application/grpc")
+                    .body(())
+                    .unwrap();
+                let (response, _) = send_request.send_request(request, true).unwrap();
+                response.map_err(|e| panic!("request error: {}", e))
+            });
+
+        let response = rt.block_on(response).unwrap();
+        let status = Status::from_header_map(response.headers()).expect("grpc-status");
+        assert_eq!(status.code(), Code::Unimplemented);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unix_socket_removed_on_shutdown() {
+        let path = std::env::temp_dir().join(format!("tower-grpc-rm-{}.sock", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        let mut rt = Runtime::new().unwrap();
+
+        for _ in 0..2 {
+            let (shutdown, signal) = futures::sync::oneshot::channel::<()>();
+            let serve = Server::builder().serve_unix_with_shutdown(&path, signal);
+
+            drop(shutdown);
+            rt.block_on(serve).unwrap();
+            assert!(!path.exists());
+        }
+    }
+}
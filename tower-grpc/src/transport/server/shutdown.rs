@@ -0,0 +1,199 @@
+use crate::error::Never;
+
+use futures::future::Shared;
+use futures::sync::{mpsc, oneshot};
+use futures::{Async, Future, Poll, Stream};
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// Returns a `Signal` used to shut down a server, and a `Watch` that
+/// observes it.
+///
+/// Each connection being served holds a clone of the `Watch`, as should the
+/// loop accepting new connections. Once the signal is triggered, the server
+/// stops accepting connections, and connections send a `GOAWAY` and drain
+/// their in-flight requests.
+pub fn shutdown() -> (Signal, Watch) {
+    let (deadline_tx, deadline_rx) = oneshot::channel();
+    let (drained_tx, drained_rx) = mpsc::channel(0);
+
+    let signal = Signal {
+        deadline: deadline_tx,
+        drained: drained_rx,
+    };
+
+    let watch = Watch {
+        deadline: deadline_rx.shared(),
+        _drained: drained_tx,
+    };
+
+    (signal, watch)
+}
+
+/// Triggers a graceful shutdown.
+pub struct Signal {
+    deadline: oneshot::Sender<Instant>,
+    drained: mpsc::Receiver<Never>,
+}
+
+/// Observes a shutdown `Signal`.
+///
+/// The signal is considered drained once every clone of its `Watch` has been
+/// dropped.
+#[derive(Clone)]
+pub struct Watch {
+    deadline: Shared<oneshot::Receiver<Instant>>,
+    _drained: mpsc::Sender<Never>,
+}
+
+/// A future that completes once shutdown has been signaled.
+pub struct Signaled {
+    deadline: Shared<oneshot::Receiver<Instant>>,
+}
+
+/// A future that completes once every `Watch` has been dropped.
+pub struct Drained {
+    drained: mpsc::Receiver<Never>,
+}
+
+// ===== impl Signal =====
+
+impl Signal {
+    /// Signal shutdown, allowing in-flight requests up to `grace` to
+    /// complete.
+    ///
+    /// Requests still in flight once the grace period has elapsed are
+    /// cancelled with `Code::Unavailable`.
+    pub fn drain(self, grace: Duration) -> Drained {
+        // If every `Watch` is already gone, there is nothing to notify.
+        let _ = self.deadline.send(Instant::now() + grace);
+
+        Drained {
+            drained: self.drained,
+        }
+    }
+}
+
+impl fmt::Debug for Signal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Signal").finish()
+    }
+}
+
+// ===== impl Watch =====
+
+impl Watch {
+    /// Returns a future that completes once shutdown has been signaled.
+    pub fn signaled(&self) -> Signaled {
+        Signaled {
+            deadline: self.deadline.clone(),
+        }
+    }
+
+    /// Polls for the deadline by which in-flight requests must complete.
+    pub(crate) fn poll_deadline(&mut self) -> Async<Instant> {
+        poll_deadline(&mut self.deadline)
+    }
+}
+
+impl fmt::Debug for Watch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Watch").finish()
+    }
+}
+
+// ===== impl Signaled =====
+
+impl Future for Signaled {
+    type Item = ();
+    type Error = Never;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        Ok(poll_deadline(&mut self.deadline).map(|_| ()))
+    }
+}
+
+impl fmt::Debug for Signaled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Signaled").finish()
+    }
+}
+
+// ===== impl Drained =====
+
+impl Future for Drained {
+    type Item = ();
+    type Error = Never;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        match self.drained.poll() {
+            Ok(Async::Ready(Some(never))) => match never {},
+            Ok(Async::Ready(None)) => Ok(Async::Ready(())),
+            Ok(Async::NotReady) => Ok(Async::NotReady),
+            Err(()) => unreachable!("mpsc::Receiver never errors"),
+        }
+    }
+}
+
+impl fmt::Debug for Drained {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Drained").finish()
+    }
+}
+
+fn poll_deadline(deadline: &mut Shared<oneshot::Receiver<Instant>>) -> Async<Instant> {
+    match deadline.poll() {
+        Ok(Async::Ready(deadline)) => Async::Ready(*deadline),
+        Ok(Async::NotReady) => Async::NotReady,
+        // The `Signal` was dropped without being triggered, so shutdown
+        // will never be signaled.
+        Err(_) => Async::NotReady,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::future;
+
+    #[test]
+    fn drained_once_watches_dropped() {
+        future::lazy(|| {
+            let (signal, watch) = shutdown();
+            let mut signaled = watch.signaled();
+            let mut watch2 = watch.clone();
+
+            assert!(signaled.poll().unwrap().is_not_ready());
+
+            let mut drained = signal.drain(Duration::from_secs(1));
+            assert!(signaled.poll().unwrap().is_ready());
+            assert!(watch2.poll_deadline().is_ready());
+            assert!(drained.poll().unwrap().is_not_ready());
+
+            drop(watch);
+            assert!(drained.poll().unwrap().is_not_ready());
+
+            drop(watch2);
+            assert!(drained.poll().unwrap().is_ready());
+
+            Ok::<_, ()>(())
+        })
+        .wait()
+        .unwrap();
+    }
+
+    #[test]
+    fn dropped_signal_never_fires() {
+        future::lazy(|| {
+            let (signal, watch) = shutdown();
+            let mut signaled = watch.signaled();
+
+            drop(signal);
+            assert!(signaled.poll().unwrap().is_not_ready());
+
+            Ok::<_, ()>(())
+        })
+        .wait()
+        .unwrap();
+    }
+}
@@ -0,0 +1,670 @@
+//! TLS configuration, backed by `rustls`.
+
+use crate::transport::channelz::TlsInfo;
+use crate::transport::{Error, PeerCertificates};
+
+use futures::Future;
+use log::debug;
+use rustls::internal::pemfile;
+use rustls::{
+    AllowAnyAuthenticatedClient, ClientConfig, NoClientAuth, PrivateKey, RootCertStore,
+    ServerConfig, Session,
+};
+use std::fmt;
+use std::io::{self, Cursor};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::timer::Timeout;
+use tokio_rustls::{client, server};
+use webpki::{DNSName, DNSNameRef};
+
+/// The ALPN protocol gRPC is served over.
+const ALPN_H2: &[u8] = b"h2";
+
+/// The default time a client is given to complete the TLS handshake.
+const DEFAULT_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A PEM encoded X.509 certificate.
+#[derive(Clone)]
+pub struct Certificate {
+    pem: Vec<u8>,
+}
+
+/// A PEM encoded certificate chain, and its PEM encoded private key.
+#[derive(Clone)]
+pub struct Identity {
+    cert: Certificate,
+    key: Vec<u8>,
+}
+
+/// Configures TLS for a `Server`.
+///
+/// ```ignore
+/// let tls = ServerTlsConfig::new()
+///     .identity(Identity::from_pem(cert, key))
+///     .client_ca_root(Certificate::from_pem(ca));
+/// ```
+#[derive(Clone, Debug)]
+pub struct ServerTlsConfig {
+    identity: Option<Identity>,
+    client_ca_root: Option<Certificate>,
+    handshake_timeout: Duration,
+}
+
+/// Configures TLS for an `Endpoint`.
+#[derive(Clone, Debug, Default)]
+pub struct ClientTlsConfig {
+    domain_name: Option<String>,
+    ca_certificate: Option<Certificate>,
+    identity: Option<Identity>,
+}
+
+/// Performs the server side of TLS handshakes.
+#[derive(Clone)]
+pub(crate) struct TlsAcceptor {
+    inner: tokio_rustls::TlsAcceptor,
+    handshake_timeout: Duration,
+    /// The DER encoding of the server's certificate, for channelz.
+    certificate: Option<Vec<u8>>,
+}
+
+/// Performs the client side of TLS handshakes with a server.
+#[derive(Clone)]
+pub(crate) struct TlsConnector {
+    inner: tokio_rustls::TlsConnector,
+    domain: DNSName,
+    /// The DER encoding of the client's certificate, for channelz.
+    certificate: Option<Vec<u8>>,
+}
+
+// ===== impl Certificate =====
+
+impl Certificate {
+    /// Returns a `Certificate` from its PEM encoding.
+    ///
+    /// The PEM is parsed when the configuration it is part of is used.
+    pub fn from_pem(pem: impl AsRef<[u8]>) -> Self {
+        Certificate {
+            pem: pem.as_ref().to_vec(),
+        }
+    }
+
+    fn load(&self) -> Result<Vec<rustls::Certificate>, Error> {
+        match pemfile::certs(&mut Cursor::new(&self.pem[..])) {
+            Ok(ref certs) if certs.is_empty() => Err(Error::tls("no certificates found in PEM")),
+            Ok(certs) => Ok(certs),
+            Err(()) => Err(Error::tls("invalid certificate PEM")),
+        }
+    }
+
+    fn load_roots(&self) -> Result<RootCertStore, Error> {
+        let mut roots = RootCertStore::empty();
+
+        for cert in self.load()? {
+            roots.add(&cert).map_err(Error::tls)?;
+        }
+
+        Ok(roots)
+    }
+}
+
+impl fmt::Debug for Certificate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Certificate").finish()
+    }
+}
+
+// ===== impl Identity =====
+
+impl Identity {
+    /// Returns an `Identity` from a PEM encoded certificate chain, and a PEM
+    /// encoded PKCS #8 or RSA private key.
+    pub fn from_pem(cert: impl AsRef<[u8]>, key: impl AsRef<[u8]>) -> Self {
+        Identity {
+            cert: Certificate::from_pem(cert),
+            key: key.as_ref().to_vec(),
+        }
+    }
+
+    fn load(&self) -> Result<(Vec<rustls::Certificate>, PrivateKey), Error> {
+        let certs = self.cert.load()?;
+
+        let key = match pemfile::pkcs8_private_keys(&mut Cursor::new(&self.key[..])) {
+            Ok(ref keys) if keys.is_empty() => {
+                pemfile::rsa_private_keys(&mut Cursor::new(&self.key[..]))
+            }
+            keys => keys,
+        };
+
+        match key {
+            Ok(mut keys) if !keys.is_empty() => Ok((certs, keys.remove(0))),
+            Ok(_) => Err(Error::tls("no private key found in PEM")),
+            Err(()) => Err(Error::tls("invalid private key PEM")),
+        }
+    }
+}
+
+impl fmt::Debug for Identity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Never print the private key.
+        f.debug_struct("Identity").finish()
+    }
+}
+
+// ===== impl ServerTlsConfig =====
+
+impl ServerTlsConfig {
+    /// Returns a new `ServerTlsConfig` with no identity.
+    pub fn new() -> Self {
+        ServerTlsConfig {
+            identity: None,
+            client_ca_root: None,
+            handshake_timeout: DEFAULT_HANDSHAKE_TIMEOUT,
+        }
+    }
+
+    /// Set the certificate chain and private key the server presents.
+    pub fn identity(mut self, identity: Identity) -> Self {
+        self.identity = Some(identity);
+        self
+    }
+
+    /// Require clients to present a certificate signed by `ca`.
+    pub fn client_ca_root(mut self, ca: Certificate) -> Self {
+        self.client_ca_root = Some(ca);
+        self
+    }
+
+    /// Set how long clients are given to complete the TLS handshake before
+    /// their connection is closed.
+    ///
+    /// Defaults to 10 seconds.
+    pub fn handshake_timeout(mut self, timeout: Duration) -> Self {
+        self.handshake_timeout = timeout;
+        self
+    }
+
+    /// Returns an acceptor for the configured identity.
+    pub(crate) fn acceptor(&self) -> Result<TlsAcceptor, Error> {
+        let verifier = match self.client_ca_root {
+            Some(ref ca) => AllowAnyAuthenticatedClient::new(ca.load_roots()?),
+            None => NoClientAuth::new(),
+        };
+
+        let (certs, key) = match self.identity {
+            Some(ref identity) => identity.load()?,
+            None => return Err(Error::tls("server TLS requires an identity")),
+        };
+
+        let certificate = certs.first().map(|cert| cert.0.clone());
+
+        let mut config = ServerConfig::new(verifier);
+        config.set_single_cert(certs, key).map_err(Error::tls)?;
+        config.set_protocols(&[ALPN_H2.to_vec()]);
+
+        Ok(TlsAcceptor {
+            inner: Arc::new(config).into(),
+            handshake_timeout: self.handshake_timeout,
+            certificate,
+        })
+    }
+}
+
+impl Default for ServerTlsConfig {
+    fn default() -> Self {
+        ServerTlsConfig::new()
+    }
+}
+
+// ===== impl ClientTlsConfig =====
+
+impl ClientTlsConfig {
+    /// Returns a new `ClientTlsConfig`, which trusts the Mozilla root
+    /// certificates bundled with `webpki-roots` until a CA certificate is set.
+    pub fn new() -> Self {
+        ClientTlsConfig::default()
+    }
+
+    /// Set the name the server's certificate is verified against, and sent
+    /// with SNI.
+    ///
+    /// Defaults to the host of the endpoint's URI, which must then be a DNS
+    /// name rather than an IP address.
+    pub fn domain_name(mut self, domain_name: impl Into<String>) -> Self {
+        self.domain_name = Some(domain_name.into());
+        self
+    }
+
+    /// Trust server certificates signed by `ca`, rather than by the bundled
+    /// root certificates.
+    pub fn ca_certificate(mut self, ca: Certificate) -> Self {
+        self.ca_certificate = Some(ca);
+        self
+    }
+
+    /// Set the certificate chain and private key presented to servers that
+    /// request client authentication.
+    pub fn identity(mut self, identity: Identity) -> Self {
+        self.identity = Some(identity);
+        self
+    }
+
+    /// Returns a connector for the endpoint at `host`.
+    pub(crate) fn connector(&self, host: &str) -> Result<TlsConnector, Error> {
+        let domain = self
+            .domain_name
+            .as_ref()
+            .map(String::as_str)
+            .unwrap_or(host);
+        let domain = DNSNameRef::try_from_ascii_str(domain)
+            .map_err(|()| Error::tls(format!("invalid domain name: {}", domain)))?
+            .to_owned();
+
+        let mut config = ClientConfig::new();
+
+        match self.ca_certificate {
+            Some(ref ca) => config.root_store = ca.load_roots()?,
+            None => config
+                .root_store
+                .add_server_trust_anchors(&webpki_roots::TLS_SERVER_ROOTS),
+        }
+
+        let mut certificate = None;
+        if let Some(ref identity) = self.identity {
+            let (certs, key) = identity.load()?;
+            certificate = certs.first().map(|cert| cert.0.clone());
+            config.set_single_client_cert(certs, key);
+        }
+
+        config.set_protocols(&[ALPN_H2.to_vec()]);
+
+        Ok(TlsConnector {
+            inner: Arc::new(config).into(),
+            domain,
+            certificate,
+        })
+    }
+}
+
+// ===== impl TlsAcceptor =====
+
+impl TlsAcceptor {
+    /// Accepts a TLS connection on `io`, returning it along with the
+    /// client's certificates, if it authenticated with any, and the session
+    /// to report to channelz.
+    ///
+    /// Fails if the client does not complete the handshake in time.
+    pub(crate) fn accept<I>(
+        &self,
+        io: I,
+    ) -> impl Future<Item = (server::TlsStream<I>, Option<PeerCertificates>, TlsInfo), Error = io::Error>
+    where
+        I: AsyncRead + AsyncWrite,
+    {
+        let certificate = self.certificate.clone();
+        let accept = self.inner.accept(io).and_then(move |io| {
+            let peer = match io.get_ref().1.get_peer_certificates() {
+                Some(ref certs) if certs.is_empty() => None,
+                Some(certs) => {
+                    let chain = certs.into_iter().map(|cert| cert.0).collect();
+                    let peer = PeerCertificates::new(chain)
+                        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                    Some(peer)
+                }
+                None => None,
+            };
+
+            let info = tls_info(io.get_ref().1, certificate);
+            Ok((io, peer, info))
+        });
+
+        Timeout::new(accept, self.handshake_timeout).map_err(|e| {
+            if e.is_elapsed() {
+                io::Error::new(io::ErrorKind::TimedOut, "TLS handshake timed out")
+            } else if e.is_inner() {
+                e.into_inner().expect("inner error")
+            } else {
+                io::Error::new(io::ErrorKind::Other, e.into_timer().expect("timer error"))
+            }
+        })
+    }
+}
+
+impl fmt::Debug for TlsAcceptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TlsAcceptor")
+            .field("handshake_timeout", &self.handshake_timeout)
+            .finish()
+    }
+}
+
+// ===== impl TlsConnector =====
+
+impl TlsConnector {
+    /// Connects over TLS on `io`, failing unless the server agreed to speak
+    /// HTTP/2, and returns it along with the session to report to channelz.
+    pub(crate) fn connect<I>(
+        &self,
+        io: I,
+    ) -> impl Future<Item = (client::TlsStream<I>, TlsInfo), Error = io::Error>
+    where
+        I: AsyncRead + AsyncWrite,
+    {
+        let certificate = self.certificate.clone();
+        self.inner.connect(self.domain.as_ref(), io).and_then(|io| {
+            match io.get_ref().1.get_alpn_protocol() {
+                Some(ALPN_H2) => {
+                    let info = tls_info(io.get_ref().1, certificate);
+                    Ok((io, info))
+                }
+                protocol => {
+                    debug!("server negotiated ALPN protocol {:?}", protocol);
+                    Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "server did not negotiate HTTP/2 with ALPN",
+                    ))
+                }
+            }
+        })
+    }
+}
+
+impl fmt::Debug for TlsConnector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TlsConnector")
+            .field("domain", &self.domain)
+            .finish()
+    }
+}
+
+/// Describes an established TLS `session`, in which this side presented
+/// `certificate`.
+fn tls_info<S: Session>(session: &S, certificate: Option<Vec<u8>>) -> TlsInfo {
+    let cipher_suite = match session.get_negotiated_ciphersuite() {
+        // IANA names TLS 1.3 suites without the version.
+        Some(suite) => format!("{:?}", suite.suite).replacen("TLS13_", "TLS_", 1),
+        None => String::new(),
+    };
+    let remote = session
+        .get_peer_certificates()
+        .and_then(|certs| certs.into_iter().next())
+        .map(|cert| cert.0);
+
+    TlsInfo::new(cipher_suite, certificate, remote)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::Empty;
+    use crate::transport::server::NamedService;
+    use crate::transport::{Channelz, Endpoint, Server};
+    use crate::{BoxBody, Code, Status};
+
+    use futures::{future, Async, Future, Poll, Stream};
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio::runtime::Runtime;
+    use tower_service::Service;
+
+    #[derive(Clone, Debug)]
+    struct Ping;
+
+    impl NamedService for Ping {
+        const NAME: &'static str = "test.Ping";
+    }
+
+    impl Service<http::Request<BoxBody>> for Ping {
+        type Response = http::Response<BoxBody>;
+        type Error = Status;
+        type Future = future::FutureResult<Self::Response, Self::Error>;
+
+        fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+            Ok(Async::Ready(()))
+        }
+
+        fn call(&mut self, request: http::Request<BoxBody>) -> Self::Future {
+            // Answers with a status identifying the client, if authenticated.
+            let code = match request.extensions().get::<PeerCertificates>() {
+                Some(peer) if peer.dns_names() == &["client.test".to_owned()] => {
+                    Code::PermissionDenied
+                }
+                Some(_) => Code::Internal,
+                None => Code::NotFound,
+            };
+            future::err(Status::new(code, "pong"))
+        }
+    }
+
+    /// A self-signed certificate and key for `localhost`.
+    const LOCALHOST: (&str, &str) = (
+        include_str!("testdata/localhost.pem"),
+        include_str!("testdata/localhost.key"),
+    );
+
+    /// A self-signed certificate and key for `client.test`.
+    const CLIENT: (&str, &str) = (
+        include_str!("testdata/client.pem"),
+        include_str!("testdata/client.key"),
+    );
+
+    /// Serves `Ping` with `tls`, returning the port it listens on.
+    fn serve(rt: &mut Runtime, tls: ServerTlsConfig) -> u16 {
+        let listener = TcpListener::bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let serve = Server::builder()
+            .tls_config(tls)
+            .unwrap()
+            .add_service(Ping)
+            .serve_incoming(listener.incoming())
+            .map_err(|e| panic!("server error: {}", e));
+        rt.spawn(serve);
+
+        port
+    }
+
+    /// Connects with `tls`, returning the `grpc-status` of a ping.
+    fn ping(rt: &mut Runtime, port: u16, tls: ClientTlsConfig) -> Result<String, Error> {
+        let uri = format!("https://127.0.0.1:{}", port).parse().unwrap();
+        let endpoint = Endpoint::from_uri(uri).tls_config(tls)?;
+
+        let ping = endpoint.connect().and_then(|mut channel| {
+            future::poll_fn(move || {
+                futures::try_ready!(channel.poll_ready());
+                Ok(Async::Ready(channel.clone()))
+            })
+            .and_then(|mut channel| {
+                let request = http::Request::builder()
+                    .uri("/test.Ping/Ping")
+                    .body(BoxBody::new(Box::new(Empty)))
+                    .unwrap();
+                channel.call(request)
+            })
+        });
+
+        let response = rt.block_on(ping)?;
+        let status = response.headers()["grpc-status"].to_str().unwrap();
+        Ok(status.to_owned())
+    }
+
+    #[test]
+    fn invalid_pem() {
+        let config = ServerTlsConfig::new().identity(Identity::from_pem("cert", "key"));
+        assert!(config.acceptor().is_err());
+
+        let (cert, _) = LOCALHOST;
+        let config = ServerTlsConfig::new().identity(Identity::from_pem(cert, "key"));
+        assert!(config.acceptor().is_err());
+    }
+
+    #[test]
+    fn ip_host_requires_domain_name() {
+        let config = ClientTlsConfig::new();
+        assert!(config.connector("127.0.0.1").is_err());
+        assert!(config
+            .domain_name("localhost")
+            .connector("127.0.0.1")
+            .is_ok());
+    }
+
+    #[test]
+    fn tls() {
+        let mut rt = Runtime::new().unwrap();
+        let (cert, key) = LOCALHOST;
+
+        let server = ServerTlsConfig::new().identity(Identity::from_pem(&cert, &key));
+        let port = serve(&mut rt, server);
+
+        let client = ClientTlsConfig::new()
+            .ca_certificate(Certificate::from_pem(&cert))
+            .domain_name("localhost");
+        assert_eq!(ping(&mut rt, port, client).unwrap(), "5");
+
+        // The server's certificate is not valid for another name.
+        let client = ClientTlsConfig::new()
+            .ca_certificate(Certificate::from_pem(&cert))
+            .domain_name("example.com");
+        assert!(ping(&mut rt, port, client).is_err());
+
+        // Nor is it trusted without its CA.
+        let client = ClientTlsConfig::new().domain_name("localhost");
+        assert!(ping(&mut rt, port, client).is_err());
+    }
+
+    #[test]
+    fn mutual_tls() {
+        let mut rt = Runtime::new().unwrap();
+        let (server_cert, server_key) = LOCALHOST;
+        let (client_cert, client_key) = CLIENT;
+
+        let server = ServerTlsConfig::new()
+            .identity(Identity::from_pem(&server_cert, &server_key))
+            .client_ca_root(Certificate::from_pem(&client_cert));
+        let port = serve(&mut rt, server);
+
+        let client = ClientTlsConfig::new()
+            .ca_certificate(Certificate::from_pem(&server_cert))
+            .domain_name("localhost");
+        assert!(ping(&mut rt, port, client.clone()).is_err());
+
+        let client = client.identity(Identity::from_pem(&client_cert, &client_key));
+        assert_eq!(ping(&mut rt, port, client).unwrap(), "7");
+    }
+
+    #[test]
+    fn requires_h2_alpn() {
+        let mut rt = Runtime::new().unwrap();
+        let (cert, key) = LOCALHOST;
+
+        // A TLS server that does not offer HTTP/2.
+        let (certs, key) = Identity::from_pem(&cert, &key).load().unwrap();
+        let mut config = ServerConfig::new(NoClientAuth::new());
+        config.set_single_cert(certs, key).unwrap();
+        let acceptor = tokio_rustls::TlsAcceptor::from(Arc::new(config));
+
+        let listener = TcpListener::bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let serve = listener
+            .incoming()
+            .map_err(|e| panic!("accept error: {}", e))
+            .for_each(move |io| {
+                tokio::spawn(acceptor.accept(io).map(drop).map_err(drop));
+                Ok(())
+            });
+        rt.spawn(serve);
+
+        let client = ClientTlsConfig::new()
+            .ca_certificate(Certificate::from_pem(&cert))
+            .domain_name("localhost");
+        assert!(ping(&mut rt, port, client).is_err());
+    }
+
+    #[test]
+    fn channelz_reports_tls() {
+        let mut rt = Runtime::new().unwrap();
+        let (cert, key) = LOCALHOST;
+        let (der, _) = Identity::from_pem(&cert, &key).load().unwrap();
+        let der = &der[0].0;
+
+        let channelz = Channelz::new();
+        let listener = TcpListener::bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let serve = Server::builder()
+            .tls_config(ServerTlsConfig::new().identity(Identity::from_pem(&cert, &key)))
+            .unwrap()
+            .channelz(channelz.clone())
+            .add_service(Ping)
+            .serve_incoming(listener.incoming())
+            .map_err(|e| panic!("server error: {}", e));
+        rt.spawn(serve);
+
+        let client = ClientTlsConfig::new()
+            .ca_certificate(Certificate::from_pem(&cert))
+            .domain_name("localhost");
+        let uri = format!("https://127.0.0.1:{}", port).parse().unwrap();
+        let connect = Endpoint::from_uri(uri)
+            .tls_config(client)
+            .unwrap()
+            .channelz(channelz.clone())
+            .connect();
+        // The channel's socket is reported until it is dropped.
+        let channel = rt.block_on(connect).unwrap();
+
+        let mut handle = channel.clone();
+        let ping = future::poll_fn(move || {
+            futures::try_ready!(handle.poll_ready());
+            Ok(Async::Ready(handle.clone()))
+        })
+        .and_then(|mut handle| {
+            let request = http::Request::builder()
+                .uri("/test.Ping/Ping")
+                .body(BoxBody::new(Box::new(Empty)))
+                .unwrap();
+            handle.call(request)
+        });
+        rt.block_on(ping).unwrap();
+
+        let servers = channelz.servers(0, 0);
+        let server = &servers.items()[0];
+        let sockets = channelz.server_sockets(server.id(), 0, 0).unwrap();
+        let socket = channelz.socket(sockets.items()[0].id()).unwrap();
+        let tls = socket.tls().expect("server socket TLS");
+        assert!(
+            tls.cipher_suite().starts_with("TLS_"),
+            "{}",
+            tls.cipher_suite()
+        );
+        assert_eq!(tls.local_certificate(), Some(&der[..]));
+        assert_eq!(tls.remote_certificate(), None);
+
+        let channels = channelz.top_channels(0, 0);
+        let top = &channels.items()[0];
+        let subchannel = channelz.subchannel(top.subchannels()[0]).unwrap();
+        let socket = channelz.socket(subchannel.sockets()[0].id()).unwrap();
+        let tls = socket.tls().expect("client socket TLS");
+        assert_eq!(tls.local_certificate(), None);
+        assert_eq!(tls.remote_certificate(), Some(&der[..]));
+
+        drop(channel);
+    }
+
+    #[test]
+    fn handshake_timeout() {
+        let mut rt = Runtime::new().unwrap();
+        let (cert, key) = LOCALHOST;
+
+        let server = ServerTlsConfig::new()
+            .identity(Identity::from_pem(&cert, &key))
+            .handshake_timeout(Duration::from_millis(100));
+        let port = serve(&mut rt, server);
+
+        // A client that never starts the handshake is disconnected.
+        let addr = ([127, 0, 0, 1], port).into();
+        let read = TcpStream::connect(&addr).and_then(|io| tokio::io::read_to_end(io, Vec::new()));
+        let (_, read) = rt
+            .block_on(Timeout::new(read, Duration::from_secs(5)))
+            .expect("connection closed by the server");
+        assert!(read.is_empty());
+    }
+}